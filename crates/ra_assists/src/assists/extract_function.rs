@@ -0,0 +1,1500 @@
+use format_buf::format;
+use hir::{
+    db::HirDatabase, ApplicationTy, AssocItem, Either, HasSource, HirDisplay, Local, Mutability,
+    PathResolution, ScopeDef, Ty, TypeCtor, TypeWalk,
+};
+use itertools::Itertools;
+use ra_fmt::{leading_indent, reindent_edits};
+use ra_syntax::{
+    algo::splice,
+    ast::{self, AstNode, NameOwner, TypeParamsOwner},
+    NodeOrToken,
+    SyntaxKind::{
+        FN_DEF, FOR_EXPR, IDENT, ITEM_LIST, LAMBDA_EXPR, LIFETIME, LOOP_EXPR, MODULE, SOURCE_FILE,
+        WHILE_EXPR,
+    },
+    SyntaxNode, TextRange, T,
+};
+
+use crate::{Assist, AssistCtx, AssistId};
+
+// Assist: extract_function
+//
+// Extracts the selected statements or expression into a new function.
+//
+// ```
+// fn main() {
+//     let n = 1;
+//     <|>let m = n + 2;
+//     let k = m * n;<|>
+//     let g = k + 3;
+// }
+// ```
+// ->
+// ```
+// fn main() {
+//     let n = 1;
+//     let k = fun_name(n);
+//     let g = k + 3;
+// }
+//
+// fn fun_name(n: i32) -> i32 {
+//     let m = n + 2;
+//     let k = m * n;
+//     k
+// }
+// ```
+pub(crate) fn extract_function(ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
+    if ctx.frange.range.is_empty() {
+        return None;
+    }
+    let body = FunctionBody::from_selection(&ctx)?;
+    let range = body.range();
+    let fn_def = body.parent().ancestors().find_map(ast::FnDef::cast)?;
+    if !range.is_subrange(&fn_def.body()?.syntax().text_range()) {
+        return None;
+    }
+
+    let db = ctx.db;
+    let params = collect_params(&ctx, &body, &fn_def);
+    let outputs = collect_outputs(&ctx, &body);
+    let (flow, exits) = control_flow(&body)?;
+    let ret = RetValue::new(&ctx, &body, &outputs)?;
+    // Types which can't be named would make the new function not compile.
+    if params.iter().any(|it| contains_unknown(&it.ty))
+        || ret.tys().into_iter().any(contains_unknown)
+    {
+        return None;
+    }
+    let self_param = params.iter().find(|it| it.is_self);
+    let container = fn_def.syntax().parent().and_then(|it| it.parent());
+    let impl_block = container.clone().and_then(ast::ImplBlock::cast);
+    // Methods go into the impl of `fn_def`, which must not be a trait or the
+    // impl of one, as the new method isn't part of the trait.
+    let is_inherent_impl = impl_block.as_ref().map_or(false, |it| it.target_trait().is_none());
+    if self_param.is_some() && !is_inherent_impl {
+        return None;
+    }
+    // A free function can't name the generic parameters of a trait.
+    let in_trait = container.map_or(false, |it| ast::TraitDef::can_cast(it.kind()));
+    let uses_generics = params.iter().map(|it| &it.ty).chain(ret.tys()).any(has_type_param);
+    if in_trait && uses_generics {
+        return None;
+    }
+    let fn_name = fresh_fn_name(&ctx, &fn_def, self_param);
+    let flow_ret = match flow {
+        ControlFlow::None => None,
+        ControlFlow::Try => Some(try_ret_type(&fn_def, &ret.ty_text(db))?),
+        // Early exits can't be combined with a value.
+        _ if !ret.is_unit() => return None,
+        ControlFlow::Return => Some(match non_unit_ret_type(&fn_def) {
+            Some(ty) => format!("Option<{}>", ty.syntax()),
+            None => "bool".to_string(),
+        }),
+        ControlFlow::Break | ControlFlow::Continue => Some("bool".to_string()),
+    };
+
+    ctx.add_assist(AssistId("extract_function"), "extract into function", |edit| {
+        edit.target(range);
+        let anchor =
+            if self_param.is_some() { fn_def.syntax().clone() } else { anchor_item(&fn_def) };
+        let anchor_indent = leading_indent(&anchor).map(|it| it.to_string()).unwrap_or_default();
+
+        // A free function needs the generic parameters of the impl too.
+        let generics_owners = match (&impl_block, self_param) {
+            (Some(impl_block), None) => {
+                vec![impl_block as &dyn TypeParamsOwner, &fn_def as &dyn TypeParamsOwner]
+            }
+            _ => vec![&fn_def as &dyn TypeParamsOwner],
+        };
+
+        let mut buf = String::new();
+        format!(buf, "\n\n{}fn {}", anchor_indent, fn_name);
+        if uses_generics {
+            format!(buf, "{}", type_params_text(&generics_owners));
+        }
+        let param_list = self_param
+            .map(|it| it.to_self_param())
+            .into_iter()
+            .chain(params.iter().filter(|it| !it.is_self).map(|it| it.to_param(db)));
+        format!(buf, "({})", param_list.format(", "));
+        match &flow_ret {
+            Some(ty) => format!(buf, " -> {}", ty),
+            None if !ret.is_unit() => format!(buf, " -> {}", ret.ty_text(db)),
+            None => (),
+        }
+        if uses_generics {
+            format!(buf, "{}", where_clause_text(&generics_owners));
+        }
+        buf.push_str(" {");
+        let body_indent = format!("{}    ", anchor_indent);
+        let text = body.text_with_edits(&params, &flow, &exits, &ret, &body_indent);
+        format!(buf, "\n{}{}", body_indent, text);
+        if let Some(tail) = ret.tail_text(&flow, &fn_def) {
+            format!(buf, "\n{}{}", body_indent, tail);
+        }
+        format!(buf, "\n{}}}", anchor_indent);
+        edit.insert(anchor.text_range().end(), buf);
+
+        let args = params.iter().filter(|it| !it.is_self).map(|it| it.to_arg());
+        let mut call = if self_param.is_some() {
+            format!("self.{}({})", fn_name, args.format(", "))
+        } else {
+            format!("{}({})", fn_name, args.format(", "))
+        };
+        if flow == ControlFlow::Try {
+            call.push('?');
+        }
+        edit.replace(range, ret.call_text(&body, &flow, &fn_def, call));
+    })
+}
+
+/// The part of a function which is extracted.
+enum FunctionBody {
+    Expr(ast::Expr),
+    /// Whole statements of `block`, the last of which may be its tail
+    /// expression.
+    Statements(ast::Block, Vec<SyntaxNode>),
+}
+
+impl FunctionBody {
+    fn from_selection(ctx: &AssistCtx<impl HirDatabase>) -> Option<FunctionBody> {
+        let selection = ctx.frange.range;
+        let node = match ctx.covering_element() {
+            NodeOrToken::Node(it) => it,
+            NodeOrToken::Token(it) => it.parent(),
+        };
+        if let Some(block) = ast::Block::cast(node.clone()) {
+            let nodes = block
+                .syntax()
+                .children()
+                .filter(|it| {
+                    let range = it.text_range();
+                    range.start() < selection.end() && selection.start() < range.end()
+                })
+                .collect::<Vec<_>>();
+            let is_valid = !nodes.is_empty()
+                && nodes.iter().all(|it| {
+                    (ast::Stmt::can_cast(it.kind()) || ast::Expr::can_cast(it.kind()))
+                        && it.text_range().is_subrange(&selection)
+                });
+            return if is_valid { Some(FunctionBody::Statements(block, nodes)) } else { None };
+        }
+        // The selection can only go past the statement if it ends with
+        // whitespace, otherwise the covering element would be the block.
+        if ast::Stmt::can_cast(node.kind())
+            && node.text_range().start() == selection.start()
+            && node.text_range().end() <= selection.end()
+        {
+            let block = node.parent().and_then(ast::Block::cast)?;
+            return Some(FunctionBody::Statements(block, vec![node]));
+        }
+        let expr = node
+            .ancestors()
+            .take_while(|it| selection.is_subrange(&it.text_range()))
+            .filter_map(ast::Expr::cast)
+            .find(|it| it.syntax().text_range() == selection)?;
+        let is_valid = match &expr {
+            ast::Expr::PathExpr(_) | ast::Expr::Literal(_) => false,
+            ast::Expr::BlockExpr(it) => it.is_standalone(),
+            _ => !is_assignee(&expr),
+        };
+        if is_valid {
+            Some(FunctionBody::Expr(expr))
+        } else {
+            None
+        }
+    }
+
+    fn nodes(&self) -> Vec<SyntaxNode> {
+        match self {
+            FunctionBody::Expr(expr) => vec![expr.syntax().clone()],
+            FunctionBody::Statements(_, nodes) => nodes.clone(),
+        }
+    }
+
+    /// The node which contains the body.
+    fn parent(&self) -> SyntaxNode {
+        match self {
+            FunctionBody::Expr(expr) => {
+                expr.syntax().parent().unwrap_or_else(|| expr.syntax().clone())
+            }
+            FunctionBody::Statements(block, _) => block.syntax().clone(),
+        }
+    }
+
+    fn range(&self) -> TextRange {
+        let nodes = self.nodes();
+        TextRange::from_to(nodes[0].text_range().start(), nodes[nodes.len() - 1].text_range().end())
+    }
+
+    /// The expression whose value is the value of the body, if any.
+    fn tail_expr(&self) -> Option<ast::Expr> {
+        match self {
+            FunctionBody::Expr(expr) => Some(expr.clone()),
+            FunctionBody::Statements(block, nodes) => {
+                block.expr().filter(|it| it.syntax() == &nodes[nodes.len() - 1])
+            }
+        }
+    }
+
+    /// The text of the body, adapted to the parameters and to the return type
+    /// of the new function, with its lines indented with `indent`.
+    fn text_with_edits(
+        &self,
+        params: &[Param],
+        flow: &ControlFlow,
+        exits: &[SyntaxNode],
+        ret: &RetValue,
+        indent: &str,
+    ) -> String {
+        let mut edits: Vec<(TextRange, String)> = Vec::new();
+        for param in params.iter().filter(|it| it.kind.is_ref()) {
+            for usage in &param.usages {
+                edits.push(deref_usage(usage, &param.name));
+            }
+        }
+        for exit in exits {
+            match ast::ReturnExpr::cast(exit.clone()).and_then(|it| it.expr()) {
+                Some(expr) => {
+                    let range = expr.syntax().text_range();
+                    edits.push((TextRange::offset_len(range.start(), 0.into()), "Some(".into()));
+                    edits.push((TextRange::offset_len(range.end(), 0.into()), ")".into()));
+                }
+                None => edits.push((exit.text_range(), "return true".into())),
+            }
+        }
+        match (flow, self.tail_expr()) {
+            (ControlFlow::Try, Some(tail)) => {
+                let range = tail.syntax().text_range();
+                let wrapper = format!("{}(", ret.try_wrapper.unwrap_or("Ok"));
+                edits.push((TextRange::offset_len(range.start(), 0.into()), wrapper));
+                edits.push((TextRange::offset_len(range.end(), 0.into()), ")".into()));
+            }
+            // The tail is followed by the value returned when there's no early
+            // exit.
+            (ControlFlow::Return, Some(tail))
+            | (ControlFlow::Break, Some(tail))
+            | (ControlFlow::Continue, Some(tail))
+                if !is_block_like(&tail) =>
+            {
+                let end = tail.syntax().text_range().end();
+                edits.push((TextRange::offset_len(end, 0.into()), ";".into()));
+            }
+            _ => (),
+        }
+
+        let parent = self.parent();
+        let range = self.range();
+        let old_indent =
+            leading_indent(&self.nodes()[0]).map(|it| it.to_string()).unwrap_or_default();
+        edits.extend(reindent_edits(&parent, range, &old_indent, indent));
+        edits.sort_by_key(|(range, _)| (range.start(), range.end()));
+        let text = splice(&parent, edits);
+        let start = (range.start() - parent.text_range().start()).to_usize();
+        let end = text.len() - (parent.text_range().end() - range.end()).to_usize();
+        text[start..end].to_string()
+    }
+}
+
+fn is_block_like(expr: &ast::Expr) -> bool {
+    match expr {
+        ast::Expr::BlockExpr(_)
+        | ast::Expr::IfExpr(_)
+        | ast::Expr::MatchExpr(_)
+        | ast::Expr::LoopExpr(_)
+        | ast::Expr::WhileExpr(_)
+        | ast::Expr::ForExpr(_) => true,
+        _ => false,
+    }
+}
+
+/// Whether `expr` is the left hand side of an assignment.
+fn is_assignee(expr: &ast::Expr) -> bool {
+    expr.syntax()
+        .parent()
+        .and_then(ast::BinExpr::cast)
+        .filter(is_assignment)
+        .and_then(|it| it.lhs())
+        .map_or(false, |lhs| lhs.syntax() == expr.syntax())
+}
+
+fn is_assignment(expr: &ast::BinExpr) -> bool {
+    match expr.op_token().map(|it| it.kind()) {
+        Some(T![=]) | Some(T![+=]) | Some(T![-=]) | Some(T![*=]) | Some(T![/=]) | Some(T![%=])
+        | Some(T![<<=]) | Some(T![>>=]) | Some(T![&=]) | Some(T![|=]) | Some(T![^=]) => true,
+        _ => false,
+    }
+}
+
+/// The item after which a free function is inserted.
+fn anchor_item(fn_def: &ast::FnDef) -> SyntaxNode {
+    fn_def
+        .syntax()
+        .ancestors()
+        .find(|it| match it.parent() {
+            Some(parent) if parent.kind() == SOURCE_FILE => true,
+            Some(parent) if parent.kind() == ITEM_LIST => {
+                parent.parent().map_or(false, |it| it.kind() == MODULE)
+            }
+            _ => false,
+        })
+        .unwrap_or_else(|| fn_def.syntax().clone())
+}
+
+/// `fun_name`, or `fun_name1`, `fun_name2`... if it is taken by an item in
+/// scope or, for a method, by an associated item of the type of `self`.
+fn fresh_fn_name(
+    ctx: &AssistCtx<impl HirDatabase>,
+    fn_def: &ast::FnDef,
+    self_param: Option<&Param>,
+) -> String {
+    let db = ctx.db;
+    let analyzer = ctx.source_analyzer(fn_def.syntax(), None);
+    let mut taken = Vec::new();
+    match self_param {
+        Some(param) => {
+            let ty = param.ty.as_reference().map_or(&param.ty, |(it, _)| it).clone();
+            analyzer.iterate_path_candidates(db, ty, None, |_, item| {
+                match item {
+                    AssocItem::Function(it) => taken.push(it.name(db).to_string()),
+                    AssocItem::Const(it) => taken.extend(it.name(db).map(|it| it.to_string())),
+                    AssocItem::TypeAlias(_) => (),
+                }
+                None::<()>
+            });
+        }
+        None => analyzer.process_all_names(db, &mut |name, _| taken.push(name.to_string())),
+    }
+    let mut name = "fun_name".to_string();
+    for idx in 1.. {
+        if !taken.contains(&name) {
+            break;
+        }
+        name = format!("fun_name{}", idx);
+    }
+    name
+}
+
+/// The generic parameters of `owners`, lifetimes first, like `<'a, T, U>`.
+fn type_params_text(owners: &[&dyn TypeParamsOwner]) -> String {
+    let lists = owners.iter().filter_map(|it| it.type_param_list()).collect::<Vec<_>>();
+    let lifetimes = lists.iter().flat_map(|it| it.lifetime_params()).map(|it| it.syntax().clone());
+    let types = lists.iter().flat_map(|it| it.type_params()).map(|it| it.syntax().clone());
+    let params = lifetimes.chain(types).collect::<Vec<_>>();
+    if params.is_empty() {
+        String::new()
+    } else {
+        format!("<{}>", params.iter().format(", "))
+    }
+}
+
+/// The where clauses of `owners` merged into one, with a leading space.
+fn where_clause_text(owners: &[&dyn TypeParamsOwner]) -> String {
+    let predicates = owners
+        .iter()
+        .filter_map(|it| it.where_clause())
+        .flat_map(|it| it.predicates())
+        .map(|it| it.syntax().clone())
+        .collect::<Vec<_>>();
+    if predicates.is_empty() {
+        String::new()
+    } else {
+        format!(" where {}", predicates.iter().format(", "))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParamKind {
+    Value,
+    MutValue,
+    SharedRef,
+    MutRef,
+}
+
+impl ParamKind {
+    fn is_ref(self) -> bool {
+        self == ParamKind::SharedRef || self == ParamKind::MutRef
+    }
+}
+
+/// A local which is defined before the body and used in it.
+struct Param {
+    local: Local,
+    name: String,
+    ty: Ty,
+    kind: ParamKind,
+    is_self: bool,
+    is_mutated: bool,
+    /// The path expressions of the body which refer to the local. Uses in
+    /// macro calls are not listed.
+    usages: Vec<ast::PathExpr>,
+}
+
+impl Param {
+    fn to_param(&self, db: &impl HirDatabase) -> String {
+        let ty = ty_text(db, &self.ty);
+        match self.kind {
+            ParamKind::Value => format!("{}: {}", self.name, ty),
+            ParamKind::MutValue => format!("mut {}: {}", self.name, ty),
+            ParamKind::SharedRef => format!("{}: &{}", self.name, ty),
+            ParamKind::MutRef => format!("{}: &mut {}", self.name, ty),
+        }
+    }
+
+    fn to_self_param(&self) -> String {
+        if self.ty.as_reference().is_some() {
+            return if self.is_mutated { "&mut self" } else { "&self" }.to_string();
+        }
+        match self.kind {
+            ParamKind::Value => "self",
+            ParamKind::MutValue => "mut self",
+            ParamKind::SharedRef => "&self",
+            ParamKind::MutRef => "&mut self",
+        }
+        .to_string()
+    }
+
+    fn to_arg(&self) -> String {
+        match self.kind {
+            ParamKind::Value | ParamKind::MutValue => self.name.clone(),
+            ParamKind::SharedRef => format!("&{}", self.name),
+            ParamKind::MutRef => format!("&mut {}", self.name),
+        }
+    }
+}
+
+fn collect_params(
+    ctx: &AssistCtx<impl HirDatabase>,
+    body: &FunctionBody,
+    fn_def: &ast::FnDef,
+) -> Vec<Param> {
+    let db = ctx.db;
+    let range = body.range();
+    let mut res: Vec<Param> = Vec::new();
+    let mut add_usage = |local: Local, usage: Option<ast::PathExpr>| {
+        let src = local.source(db);
+        if src.file_id != ctx.frange.file_id.into() {
+            return;
+        }
+        let def_range =
+            src.value.either(|it| it.syntax().text_range(), |it| it.syntax().text_range());
+        if def_range.is_subrange(&range) {
+            return;
+        }
+        let param = match res.iter().position(|it| it.local == local) {
+            Some(idx) => &mut res[idx],
+            None => {
+                res.push(Param {
+                    local,
+                    name: local.name(db).map_or_else(|| "_".to_string(), |it| it.to_string()),
+                    ty: local.ty(db),
+                    kind: ParamKind::Value,
+                    is_self: local.is_self(db),
+                    is_mutated: false,
+                    usages: Vec::new(),
+                });
+                res.last_mut().unwrap()
+            }
+        };
+        if let Some(usage) = usage {
+            param.is_mutated |= is_mutated(ctx, &usage);
+            param.usages.push(usage);
+        }
+    };
+
+    for node in body.nodes() {
+        for path_expr in node.descendants().filter_map(ast::PathExpr::cast) {
+            if let Some(local) = path_expr.path().and_then(|it| resolve_local(ctx, &it)) {
+                add_usage(local, Some(path_expr));
+            }
+        }
+        // Macro arguments are token trees, so their locals are found by name.
+        for macro_call in node.descendants().filter_map(ast::MacroCall::cast) {
+            let names = macro_arg_names(&macro_call);
+            let analyzer = ctx.source_analyzer(macro_call.syntax(), None);
+            let mut seen = Vec::new();
+            analyzer.process_all_names(db, &mut |name, def| {
+                let name = name.to_string();
+                if seen.contains(&name) {
+                    return;
+                }
+                if let ScopeDef::Local(local) = def {
+                    if names.contains(&name) {
+                        add_usage(local, None);
+                    }
+                }
+                seen.push(name);
+            });
+        }
+    }
+
+    for param in res.iter_mut() {
+        let is_used_after = is_used_after(ctx, param.local, &param.name, body, fn_def);
+        param.kind = match (param.is_mutated, is_used_after) {
+            // References are passed as is, or reborrowed if they are `&mut`.
+            _ if param.ty.as_reference().is_some() => ParamKind::Value,
+            (true, true) => ParamKind::MutRef,
+            (true, false) => ParamKind::MutValue,
+            (false, true) if !is_copy(&param.ty) => ParamKind::SharedRef,
+            (false, _) => ParamKind::Value,
+        };
+    }
+    res
+}
+
+fn resolve_local(ctx: &AssistCtx<impl HirDatabase>, path: &ast::Path) -> Option<Local> {
+    if path.qualifier().is_some() {
+        return None;
+    }
+    let analyzer = ctx.source_analyzer(path.syntax(), None);
+    match analyzer.resolve_path(ctx.db, path)? {
+        PathResolution::Local(local) => Some(local),
+        _ => None,
+    }
+}
+
+fn macro_arg_names(macro_call: &ast::MacroCall) -> Vec<String> {
+    macro_call
+        .token_tree()
+        .into_iter()
+        .flat_map(|it| it.syntax().descendants_with_tokens())
+        .filter_map(|it| it.into_token())
+        .filter(|it| it.kind() == IDENT || it.kind() == T![self])
+        .map(|it| it.text().to_string())
+        .collect()
+}
+
+/// Whether the place which `usage` is the base of is assigned, mutably
+/// borrowed, or the receiver of a `&mut self` method.
+fn is_mutated(ctx: &AssistCtx<impl HirDatabase>, usage: &ast::PathExpr) -> bool {
+    let mut place = usage.syntax().clone();
+    loop {
+        let parent = match place.parent() {
+            Some(it) => it,
+            None => return false,
+        };
+        let base = match ast::Expr::cast(parent.clone()) {
+            Some(ast::Expr::FieldExpr(it)) => it.expr(),
+            Some(ast::Expr::IndexExpr(it)) => it.base(),
+            _ => None,
+        };
+        if base.map_or(false, |it| it.syntax() == &place) {
+            place = parent;
+        } else {
+            break;
+        }
+    }
+    let place = match ast::Expr::cast(place) {
+        Some(it) => it,
+        None => return false,
+    };
+    let parent = match place.syntax().parent().and_then(ast::Expr::cast) {
+        Some(it) => it,
+        None => return false,
+    };
+    match parent {
+        ast::Expr::BinExpr(_) => is_assignee(&place),
+        ast::Expr::RefExpr(it) => it.is_mut(),
+        ast::Expr::MethodCallExpr(call) => {
+            if call.expr().map_or(true, |it| it.syntax() != place.syntax()) {
+                return false;
+            }
+            let analyzer = ctx.source_analyzer(call.syntax(), None);
+            analyzer
+                .resolve_method_call(&call)
+                .and_then(|it| it.source(ctx.db).value.param_list()?.self_param())
+                .map_or(false, |it| it.kind() == ast::SelfParamKind::MutRef)
+        }
+        _ => false,
+    }
+}
+
+/// Whether the value of `local` may be needed after the body runs, either
+/// because it is used later or because the body is in a loop.
+fn is_used_after(
+    ctx: &AssistCtx<impl HirDatabase>,
+    local: Local,
+    name: &str,
+    body: &FunctionBody,
+    fn_def: &ast::FnDef,
+) -> bool {
+    let range = body.range();
+    let bind_pat = match local.source(ctx.db).value {
+        Either::A(it) => it,
+        Either::B(_) => return true,
+    };
+    let in_loop = body.parent().ancestors().take_while(|it| it != fn_def.syntax()).any(|it| {
+        let is_loop = match it.kind() {
+            LOOP_EXPR | WHILE_EXPR | FOR_EXPR | LAMBDA_EXPR => true,
+            _ => false,
+        };
+        is_loop && !bind_pat.syntax().text_range().is_subrange(&it.text_range())
+    });
+    if in_loop {
+        return true;
+    }
+    let analyzer = ctx.source_analyzer(bind_pat.syntax(), None);
+    if analyzer.find_all_refs(&bind_pat).iter().any(|it| it.range.start() >= range.end()) {
+        return true;
+    }
+    fn_def
+        .syntax()
+        .descendants()
+        .filter_map(ast::MacroCall::cast)
+        .filter(|it| it.syntax().text_range().start() >= range.end())
+        .any(|it| macro_arg_names(&it).iter().any(|it| it == name))
+}
+
+fn is_copy(ty: &Ty) -> bool {
+    match ty {
+        Ty::Apply(ApplicationTy { ctor, parameters }) => match ctor {
+            TypeCtor::Bool
+            | TypeCtor::Char
+            | TypeCtor::Int(_)
+            | TypeCtor::Float(_)
+            | TypeCtor::RawPtr(_)
+            | TypeCtor::Ref(Mutability::Shared)
+            | TypeCtor::FnDef(_)
+            | TypeCtor::FnPtr { .. }
+            | TypeCtor::Never => true,
+            TypeCtor::Tuple { .. } => parameters.iter().all(is_copy),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn has_type_param(ty: &Ty) -> bool {
+    let mut res = false;
+    ty.walk(&mut |it| {
+        if let Ty::Param { .. } = it {
+            res = true;
+        }
+    });
+    res
+}
+
+fn ty_text(db: &impl HirDatabase, ty: &Ty) -> String {
+    ty.display(db).to_string()
+}
+
+fn contains_unknown(ty: &Ty) -> bool {
+    let mut res = false;
+    ty.walk(&mut |it| {
+        if let Ty::Unknown = it {
+            res = true;
+        }
+    });
+    res
+}
+
+/// Replaces a use of a local which is now passed by reference.
+fn deref_usage(usage: &ast::PathExpr, name: &str) -> (TextRange, String) {
+    let parent = usage.syntax().parent().and_then(ast::Expr::cast);
+    let is_auto_deref = match &parent {
+        Some(ast::Expr::FieldExpr(it)) => it.expr().is_some(),
+        Some(ast::Expr::MethodCallExpr(it)) => {
+            it.expr().map_or(false, |it| it.syntax() == usage.syntax())
+        }
+        Some(ast::Expr::IndexExpr(it)) => {
+            it.base().map_or(false, |it| it.syntax() == usage.syntax())
+        }
+        _ => false,
+    };
+    match parent {
+        _ if is_auto_deref => (usage.syntax().text_range(), name.to_string()),
+        Some(ast::Expr::RefExpr(it)) => (it.syntax().text_range(), name.to_string()),
+        _ => (usage.syntax().text_range(), format!("*{}", name)),
+    }
+}
+
+/// A local which is defined in the body and used after it.
+struct Output {
+    name: String,
+    ty: Ty,
+    is_mut: bool,
+}
+
+fn collect_outputs(ctx: &AssistCtx<impl HirDatabase>, body: &FunctionBody) -> Vec<Output> {
+    let range = body.range();
+    body.nodes()
+        .iter()
+        .flat_map(|it| it.descendants())
+        .filter_map(ast::BindPat::cast)
+        .filter_map(|bind_pat| {
+            let analyzer = ctx.source_analyzer(bind_pat.syntax(), None);
+            let refs = analyzer.find_all_refs(&bind_pat);
+            if !refs.iter().any(|it| it.range.start() >= range.end()) {
+                return None;
+            }
+            Some(Output {
+                name: bind_pat.name()?.text().to_string(),
+                ty: analyzer.type_of_pat(ctx.db, &bind_pat.clone().into())?,
+                is_mut: bind_pat.is_mutable(),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ControlFlow {
+    None,
+    Try,
+    Return,
+    Break,
+    Continue,
+}
+
+/// Finds the `?`, `return`, `break` and `continue` of the body which leave it,
+/// along with the expressions to rewrite. Different kinds of exits can't be
+/// combined.
+fn control_flow(body: &FunctionBody) -> Option<(ControlFlow, Vec<SyntaxNode>)> {
+    let mut flow = ControlFlow::None;
+    let mut exits = Vec::new();
+    for node in body.nodes() {
+        for it in node.descendants() {
+            let kind = match ast::Expr::cast(it.clone()) {
+                Some(ast::Expr::TryExpr(_)) => ControlFlow::Try,
+                Some(ast::Expr::ReturnExpr(_)) => ControlFlow::Return,
+                Some(ast::Expr::BreakExpr(_)) => ControlFlow::Break,
+                Some(ast::Expr::ContinueExpr(_)) => ControlFlow::Continue,
+                _ => continue,
+            };
+            let node_range = node.text_range();
+            let mut ancestors =
+                it.ancestors().skip(1).take_while(|it| it.text_range().is_subrange(&node_range));
+            let leaves_body = match kind {
+                ControlFlow::Try | ControlFlow::Return => {
+                    !ancestors.any(|it| it.kind() == LAMBDA_EXPR || it.kind() == FN_DEF)
+                }
+                _ => !ancestors.any(|it| match it.kind() {
+                    LOOP_EXPR | WHILE_EXPR | FOR_EXPR | LAMBDA_EXPR | FN_DEF => true,
+                    _ => false,
+                }),
+            };
+            if !leaves_body {
+                continue;
+            }
+            let has_label_or_value = match ast::Expr::cast(it.clone()) {
+                Some(ast::Expr::BreakExpr(brk)) => {
+                    brk.expr().is_some()
+                        || it.children_with_tokens().any(|it| it.kind() == LIFETIME)
+                }
+                Some(ast::Expr::ContinueExpr(_)) => {
+                    it.children_with_tokens().any(|it| it.kind() == LIFETIME)
+                }
+                _ => false,
+            };
+            if has_label_or_value || (flow != ControlFlow::None && flow != kind) {
+                return None;
+            }
+            flow = kind;
+            if kind != ControlFlow::Try {
+                exits.push(it);
+            }
+        }
+    }
+    Some((flow, exits))
+}
+
+/// The type of the enclosing function's return value, unless it is `()`.
+fn non_unit_ret_type(fn_def: &ast::FnDef) -> Option<ast::TypeRef> {
+    let ty = fn_def.ret_type()?.type_ref()?;
+    if let ast::TypeRef::TupleType(it) = &ty {
+        if it.fields().next().is_none() {
+            return None;
+        }
+    }
+    Some(ty)
+}
+
+/// Replaces the value type of the enclosing function's `Result` or `Option`,
+/// so that `?` keeps working in the new function.
+fn try_ret_type(fn_def: &ast::FnDef, value_ty: &str) -> Option<String> {
+    let path = match non_unit_ret_type(fn_def)? {
+        ast::TypeRef::PathType(it) => it.path()?,
+        _ => return None,
+    };
+    let first_arg = path.segment()?.type_arg_list()?.type_args().next()?;
+    let ty = path.syntax().text().to_string();
+    let offset = path.syntax().text_range().start();
+    let arg_range = first_arg.syntax().text_range();
+    let start = (arg_range.start() - offset).to_usize();
+    let end = (arg_range.end() - offset).to_usize();
+    Some(format!("{}{}{}", &ty[..start], value_ty, &ty[end..]))
+}
+
+/// What the new function returns when the body completes normally: either
+/// the value of its tail expression or the outputs.
+struct RetValue {
+    tail_ty: Option<Ty>,
+    outputs: Vec<Output>,
+    /// `Some` or `Ok`, for bodies which use `?`.
+    try_wrapper: Option<&'static str>,
+}
+
+impl RetValue {
+    fn new(
+        ctx: &AssistCtx<impl HirDatabase>,
+        body: &FunctionBody,
+        outputs: &[Output],
+    ) -> Option<RetValue> {
+        let fn_def = body.parent().ancestors().find_map(ast::FnDef::cast)?;
+        let try_wrapper = match non_unit_ret_type(&fn_def) {
+            Some(ast::TypeRef::PathType(it)) => {
+                let segment = it.path()?.segment()?;
+                if segment.name_ref().map_or(false, |it| it.text() == "Option") {
+                    Some("Some")
+                } else {
+                    Some("Ok")
+                }
+            }
+            _ => None,
+        };
+        let outputs: Vec<Output> = outputs
+            .iter()
+            .map(|it| Output { name: it.name.clone(), ty: it.ty.clone(), is_mut: it.is_mut })
+            .collect();
+        let tail_ty = body.tail_expr().map(|expr| {
+            let analyzer = ctx.source_analyzer(expr.syntax(), None);
+            analyzer.type_of(ctx.db, &expr).unwrap_or(Ty::Unknown)
+        });
+        Some(RetValue { tail_ty, outputs, try_wrapper })
+    }
+
+    fn tys(&self) -> Vec<&Ty> {
+        match &self.tail_ty {
+            Some(ty) => vec![ty],
+            None => self.outputs.iter().map(|it| &it.ty).collect(),
+        }
+    }
+
+    fn is_unit(&self) -> bool {
+        match &self.tail_ty {
+            Some(ty) => ty.as_tuple().map_or(false, |it| it.is_empty()),
+            None => self.outputs.is_empty(),
+        }
+    }
+
+    fn ty_text(&self, db: &impl HirDatabase) -> String {
+        match self.tys().as_slice() {
+            [ty] => ty_text(db, ty),
+            tys => format!("({})", tys.iter().map(|it| ty_text(db, it)).format(", ")),
+        }
+    }
+
+    /// The last line of the new function, if the body doesn't end with its
+    /// value.
+    fn tail_text(&self, flow: &ControlFlow, fn_def: &ast::FnDef) -> Option<String> {
+        match flow {
+            ControlFlow::None | ControlFlow::Try if self.tail_ty.is_some() => return None,
+            _ => (),
+        }
+        let value = match self.outputs.as_slice() {
+            [] => "()".to_string(),
+            [output] => output.name.clone(),
+            outputs => format!("({})", outputs.iter().map(|it| &it.name).format(", ")),
+        };
+        match flow {
+            ControlFlow::None if self.outputs.is_empty() => None,
+            ControlFlow::None => Some(value),
+            ControlFlow::Try => Some(format!("{}({})", self.try_wrapper.unwrap_or("Ok"), value)),
+            ControlFlow::Return if non_unit_ret_type(fn_def).is_some() => Some("None".to_string()),
+            ControlFlow::Return | ControlFlow::Break | ControlFlow::Continue => {
+                Some("false".to_string())
+            }
+        }
+    }
+
+    /// The code which replaces the body.
+    fn call_text(
+        &self,
+        body: &FunctionBody,
+        flow: &ControlFlow,
+        fn_def: &ast::FnDef,
+        call: String,
+    ) -> String {
+        match flow {
+            ControlFlow::Return if non_unit_ret_type(fn_def).is_some() => {
+                return format!("if let Some(value) = {} {{ return value; }}", call);
+            }
+            ControlFlow::Return => return format!("if {} {{ return; }}", call),
+            ControlFlow::Break => return format!("if {} {{ break; }}", call),
+            ControlFlow::Continue => return format!("if {} {{ continue; }}", call),
+            ControlFlow::None | ControlFlow::Try => (),
+        }
+        if let FunctionBody::Expr(_) = body {
+            return call;
+        }
+        let pats = self.outputs.iter().map(|it| {
+            if it.is_mut {
+                format!("mut {}", it.name)
+            } else {
+                it.name.clone()
+            }
+        });
+        match self.outputs.len() {
+            _ if self.tail_ty.is_some() => call,
+            0 => format!("{};", call),
+            1 => format!("let {} = {};", pats.format(""), call),
+            _ => format!("let ({}) = {};", pats.format(", "), call),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::{check_assist_range, check_assist_range_not_applicable};
+
+    use super::*;
+
+    #[test]
+    fn extract_expression() {
+        check_assist_range(
+            extract_function,
+            r#"
+fn foo() {
+    let a = 1;
+    let b = <|>a * 2 + 1<|>;
+}
+"#,
+            r#"
+fn foo() {
+    let a = 1;
+    let b = fun_name(a);
+}
+
+fn fun_name(a: i32) -> i32 {
+    a * 2 + 1
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_statements_with_outputs() {
+        check_assist_range(
+            extract_function,
+            r#"
+fn foo() {
+    <|>let a = 1;
+    let mut b = 2;<|>
+    b += a;
+}
+"#,
+            r#"
+fn foo() {
+    let (a, mut b) = fun_name();
+    b += a;
+}
+
+fn fun_name() -> (i32, i32) {
+    let a = 1;
+    let mut b = 2;
+    (a, b)
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_with_shared_reference() {
+        check_assist_range(
+            extract_function,
+            r#"
+struct Point { x: i32, y: i32 }
+
+fn foo() {
+    let p = Point { x: 1, y: 2 };
+    <|>let sum = p.x + p.y;<|>
+    let _ = (p, sum);
+}
+"#,
+            r#"
+struct Point { x: i32, y: i32 }
+
+fn foo() {
+    let p = Point { x: 1, y: 2 };
+    let sum = fun_name(&p);
+    let _ = (p, sum);
+}
+
+fn fun_name(p: &Point) -> i32 {
+    let sum = p.x + p.y;
+    sum
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_with_mutable_reference() {
+        check_assist_range(
+            extract_function,
+            r#"
+struct Counter { n: i32 }
+
+fn foo() {
+    let mut c = Counter { n: 0 };
+    let mut n = 1;
+    <|>c.n += 1;
+    n += c.n;<|>
+    let _ = (c, n);
+}
+"#,
+            r#"
+struct Counter { n: i32 }
+
+fn foo() {
+    let mut c = Counter { n: 0 };
+    let mut n = 1;
+    fun_name(&mut c, &mut n);
+    let _ = (c, n);
+}
+
+fn fun_name(c: &mut Counter, n: &mut i32) {
+    c.n += 1;
+    *n += c.n;
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_mutated_value() {
+        check_assist_range(
+            extract_function,
+            r#"
+fn foo() {
+    let mut n = 1;
+    <|>n += 1;
+    let _ = n;<|>
+}
+"#,
+            r#"
+fn foo() {
+    let mut n = 1;
+    fun_name(n);
+}
+
+fn fun_name(mut n: i32) {
+    n += 1;
+    let _ = n;
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_keeps_multi_line_string_literals() {
+        check_assist_range(
+            extract_function,
+            r#"
+fn foo() {
+    if true {
+        <|>let s = "a
+  b";
+        let _ = s;<|>
+    }
+}
+"#,
+            r#"
+fn foo() {
+    if true {
+        fun_name();
+    }
+}
+
+fn fun_name() {
+    let s = "a
+  b";
+    let _ = s;
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_method() {
+        check_assist_range(
+            extract_function,
+            r#"
+struct S { n: i32 }
+
+impl S {
+    fn foo(&mut self) {
+        <|>self.n += 1;<|>
+    }
+}
+"#,
+            r#"
+struct S { n: i32 }
+
+impl S {
+    fn foo(&mut self) {
+        self.fun_name();
+    }
+
+    fn fun_name(&mut self) {
+        self.n += 1;
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_free_function_from_method() {
+        check_assist_range(
+            extract_function,
+            r#"
+struct S;
+
+impl S {
+    fn foo(&self, n: i32) -> i32 {
+        <|>n * 2<|>
+    }
+}
+"#,
+            r#"
+struct S;
+
+impl S {
+    fn foo(&self, n: i32) -> i32 {
+        fun_name(n)
+    }
+}
+
+fn fun_name(n: i32) -> i32 {
+    n * 2
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_with_generics() {
+        check_assist_range(
+            extract_function,
+            r#"
+trait Clone { fn clone(&self) -> Self; }
+
+fn foo<T: Clone>(t: T) -> T {
+    <|>t.clone()<|>
+}
+"#,
+            r#"
+trait Clone { fn clone(&self) -> Self; }
+
+fn foo<T: Clone>(t: T) -> T {
+    fun_name(t)
+}
+
+fn fun_name<T: Clone>(t: T) -> T {
+    t.clone()
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_with_try() {
+        check_assist_range(
+            extract_function,
+            r#"
+fn foo() -> Result<i32, Error> {
+    <|>let x = 1;
+    bar()?;<|>
+    Ok(x)
+}
+"#,
+            r#"
+fn foo() -> Result<i32, Error> {
+    let x = fun_name()?;
+    Ok(x)
+}
+
+fn fun_name() -> Result<i32, Error> {
+    let x = 1;
+    bar()?;
+    Ok(x)
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_with_return() {
+        check_assist_range(
+            extract_function,
+            r#"
+fn foo(n: i32) -> i32 {
+    <|>if n > 0 {
+        return 1;
+    }<|>
+    0
+}
+"#,
+            r#"
+fn foo(n: i32) -> i32 {
+    if let Some(value) = fun_name(n) { return value; }
+    0
+}
+
+fn fun_name(n: i32) -> Option<i32> {
+    if n > 0 {
+        return Some(1);
+    }
+    None
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_with_break() {
+        check_assist_range(
+            extract_function,
+            r#"
+fn foo() {
+    let mut i = 0;
+    loop {
+        <|>i += 1;
+        if i > 10 {
+            break;
+        }<|>
+    }
+}
+"#,
+            r#"
+fn foo() {
+    let mut i = 0;
+    loop {
+        if fun_name(&mut i) { break; }
+    }
+}
+
+fn fun_name(i: &mut i32) -> bool {
+    *i += 1;
+    if *i > 10 {
+        return true;
+    }
+    false
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_not_applicable_to_partial_statements() {
+        check_assist_range_not_applicable(
+            extract_function,
+            r#"
+fn foo() {
+    let a = <|>1;
+    let b = 2<|>;
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_not_applicable_to_start_of_statement() {
+        check_assist_range_not_applicable(
+            extract_function,
+            r#"
+fn foo() {
+    <|>let a = 1 +<|> 2;
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_not_applicable_to_unknown_types() {
+        check_assist_range_not_applicable(
+            extract_function,
+            r#"
+fn foo() {
+    let a = unknown();
+    <|>let b = a;<|>
+    let c = b;
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_not_applicable_to_mixed_exits() {
+        check_assist_range_not_applicable(
+            extract_function,
+            r#"
+fn foo(n: i32) {
+    loop {
+        <|>if n > 0 {
+            return;
+        }
+        if n < 0 {
+            break;
+        }<|>
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_method_taking_self_by_value() {
+        check_assist_range(
+            extract_function,
+            r#"
+struct S { n: i32 }
+
+impl S {
+    fn into_n(self) -> i32 {
+        <|>self.n + 1<|>
+    }
+}
+"#,
+            r#"
+struct S { n: i32 }
+
+impl S {
+    fn into_n(self) -> i32 {
+        self.fun_name()
+    }
+
+    fn fun_name(self) -> i32 {
+        self.n + 1
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_method_not_applicable_in_trait_impl() {
+        check_assist_range_not_applicable(
+            extract_function,
+            r#"
+trait Tr { fn foo(&mut self); }
+struct S { n: i32 }
+
+impl Tr for S {
+    fn foo(&mut self) {
+        <|>self.n += 1;<|>
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_method_not_applicable_in_trait() {
+        check_assist_range_not_applicable(
+            extract_function,
+            r#"
+trait Tr {
+    fn n(&self) -> i32;
+    fn foo(&self) -> i32 {
+        <|>self.n() + 1<|>
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_free_function_with_impl_generics() {
+        check_assist_range(
+            extract_function,
+            r#"
+trait Clone { fn clone(&self) -> Self; }
+struct S<T>(T);
+
+impl<T> S<T> where T: Clone {
+    fn foo(t: T) -> T {
+        <|>t.clone()<|>
+    }
+}
+"#,
+            r#"
+trait Clone { fn clone(&self) -> Self; }
+struct S<T>(T);
+
+impl<T> S<T> where T: Clone {
+    fn foo(t: T) -> T {
+        fun_name(t)
+    }
+}
+
+fn fun_name<T>(t: T) -> T where T: Clone {
+    t.clone()
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_function_with_taken_name() {
+        check_assist_range(
+            extract_function,
+            r#"
+fn fun_name() {}
+
+fn foo(n: i32) -> i32 {
+    <|>n * 2<|>
+}
+"#,
+            r#"
+fn fun_name() {}
+
+fn foo(n: i32) -> i32 {
+    fun_name1(n)
+}
+
+fn fun_name1(n: i32) -> i32 {
+    n * 2
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn extract_method_with_taken_name() {
+        check_assist_range(
+            extract_function,
+            r#"
+struct S { n: i32 }
+
+impl S {
+    fn fun_name() {}
+}
+
+impl S {
+    fn foo(&mut self) {
+        <|>self.n += 1;<|>
+    }
+}
+"#,
+            r#"
+struct S { n: i32 }
+
+impl S {
+    fn fun_name() {}
+}
+
+impl S {
+    fn foo(&mut self) {
+        self.fun_name1();
+    }
+
+    fn fun_name1(&mut self) {
+        self.n += 1;
+    }
+}
+"#,
+        );
+    }
+}
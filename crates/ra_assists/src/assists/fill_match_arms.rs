@@ -3,7 +3,14 @@
 use std::iter;
 
 use hir::{db::HirDatabase, Adt, HasSource};
-use ra_syntax::ast::{self, edit::IndentLevel, make, AstNode, NameOwner};
+use ra_syntax::{
+    algo::{self, non_trivia_sibling},
+    ast::{self, edit::IndentLevel, make, AstNode, NameOwner},
+    Direction,
+    SyntaxKind::WHITESPACE,
+    SyntaxNode, SyntaxToken, T,
+};
+use ra_text_edit::TextEditBuilder;
 
 use crate::{Assist, AssistCtx, AssistId};
 
@@ -53,15 +60,7 @@ pub(crate) fn fill_match_arms(ctx: AssistCtx<impl HirDatabase>) -> Option<Assist
     let variant_list = enum_def.variant_list()?;
 
     ctx.add_assist(AssistId("fill_match_arms"), "fill match arms", |edit| {
-        let indent_level = IndentLevel::from_node(match_arm_list.syntax());
-
-        let new_arm_list = {
-            let variants = variant_list.variants();
-            let arms = variants
-                .filter_map(build_pat)
-                .map(|pat| make::match_arm(iter::once(pat), make::expr_unit()));
-            indent_level.increase_indent(make::match_arm_list(arms))
-        };
+        let new_arm_list = new_match_arm_list(&match_arm_list, variant_list.variants());
 
         edit.target(match_expr.syntax().text_range());
         edit.set_cursor(expr.syntax().text_range().start());
@@ -69,6 +68,98 @@ pub(crate) fn fill_match_arms(ctx: AssistCtx<impl HirDatabase>) -> Option<Assist
     })
 }
 
+/// Appends an arm with a `()` body for each of the `variants` to the end of
+/// `match_arm_list`, keeping the existing arms. The new arms are indented like
+/// the last existing one; an empty list is filled like the `fill_match_arms`
+/// assist does it.
+pub fn fill_match_arms_text_edit(
+    match_arm_list: &ast::MatchArmList,
+    variants: impl IntoIterator<Item = ast::EnumVariant>,
+    edit: &mut TextEditBuilder,
+) {
+    let last_arm = match match_arm_list.arms().last() {
+        Some(it) => it,
+        None => {
+            let new_arm_list = new_match_arm_list(match_arm_list, variants);
+            algo::diff(match_arm_list.syntax(), new_arm_list.syntax()).into_text_edit(edit);
+            return;
+        }
+    };
+
+    let mut buf = String::new();
+    // The new arms go after the last arm and its comma.
+    let anchor = match non_trivia_sibling(last_arm.syntax().clone().into(), Direction::Next) {
+        Some(comma) if comma.kind() == T![,] => comma.into_token(),
+        _ => {
+            let has_block_body = match last_arm.expr() {
+                Some(ast::Expr::BlockExpr(_)) => true,
+                _ => false,
+            };
+            if !has_block_body {
+                buf.push(',');
+            }
+            last_arm.syntax().last_token()
+        }
+    };
+    let anchor = match anchor {
+        Some(it) => it,
+        None => return,
+    };
+
+    let indent_level = IndentLevel::from_node(match_arm_list.syntax());
+    let arm_indent = match line_indent(last_arm.syntax()) {
+        Some(it) => it,
+        None => format!("{:width$}", "", width = (indent_level.0 as usize + 1) * 4),
+    };
+    for arm in new_arms(variants) {
+        buf.push_str(&format!("\n{}{},", arm_indent, arm.syntax()));
+    }
+    if line_indent_after(&anchor).is_none() {
+        buf.push_str(&format!("\n{:width$}", "", width = indent_level.0 as usize * 4));
+    }
+    edit.insert(anchor.text_range().end(), buf);
+}
+
+/// Builds the arm list the `fill_match_arms` assist puts in place of
+/// `match_arm_list`.
+fn new_match_arm_list(
+    match_arm_list: &ast::MatchArmList,
+    variants: impl IntoIterator<Item = ast::EnumVariant>,
+) -> ast::MatchArmList {
+    let indent_level = IndentLevel::from_node(match_arm_list.syntax());
+    indent_level.increase_indent(make::match_arm_list(new_arms(variants)))
+}
+
+fn new_arms(
+    variants: impl IntoIterator<Item = ast::EnumVariant>,
+) -> impl Iterator<Item = ast::MatchArm> {
+    variants
+        .into_iter()
+        .filter_map(build_pat)
+        .map(|pat| make::match_arm(iter::once(pat), make::expr_unit()))
+}
+
+/// Returns the indentation of `node` if it starts its own line.
+fn line_indent(node: &SyntaxNode) -> Option<String> {
+    let ws = node.first_token()?.prev_token()?;
+    line_indent_of(&ws)
+}
+
+/// Returns the indentation of the line following `token`, if `token` ends its
+/// line.
+fn line_indent_after(token: &SyntaxToken) -> Option<String> {
+    line_indent_of(&token.next_token()?)
+}
+
+fn line_indent_of(ws: &SyntaxToken) -> Option<String> {
+    if ws.kind() != WHITESPACE {
+        return None;
+    }
+    let text = ws.text();
+    let pos = text.rfind('\n')?;
+    Some(text[pos + 1..].to_string())
+}
+
 fn is_trivial(arm: &ast::MatchArm) -> bool {
     arm.pats().any(|pat| match pat {
         ast::Pat::PlaceholderPat(..) => true,
@@ -0,0 +1,1170 @@
+use std::collections::HashSet;
+
+use format_buf::format;
+use hir::{
+    db::HirDatabase, ApplicationTy, Either, Function, HasSource, Local, ModuleDef, PathResolution,
+    ScopeDef, Ty, TypeCtor,
+};
+use ra_fmt::{leading_indent, reindent_edits};
+use ra_syntax::{
+    algo::splice,
+    ast::{self, ArgListOwner, AstNode, NameOwner, TypeAscriptionOwner, TypeParamsOwner},
+    NodeOrToken, SyntaxKind,
+    SyntaxKind::{
+        ARRAY_EXPR, FIELD_EXPR, FN_DEF, FOR_EXPR, IDENT, IMPL_TRAIT_TYPE, LAMBDA_EXPR, LIFETIME,
+        LOOP_EXPR, METHOD_CALL_EXPR, PAREN_EXPR, RECORD_FIELD_PAT_LIST, RETURN_EXPR, TOKEN_TREE,
+        TRY_EXPR, TUPLE_EXPR, WHILE_EXPR, WHITESPACE,
+    },
+    SyntaxNode, SyntaxToken, TextRange, TextUnit, T,
+};
+
+use crate::{Assist, AssistCtx, AssistId};
+
+// Assist: inline_call
+//
+// Replaces a call to a local function with the function's body.
+//
+// ```
+// fn add(a: u32, b: u32) -> u32 {
+//     a + b
+// }
+// fn main() {
+//     let x = add<|>(1, 2);
+// }
+// ```
+// ->
+// ```
+// fn add(a: u32, b: u32) -> u32 {
+//     a + b
+// }
+// fn main() {
+//     let x = 1 + 2;
+// }
+// ```
+pub(crate) fn inline_call(ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
+    let name_ref = ctx.find_node_at_offset::<ast::NameRef>()?;
+    let call = CallSite::from_name_ref(&ctx, &name_ref)?;
+    let fn_def = local_fn_def(&ctx, &call)?;
+    let body = fn_def.body()?.block()?;
+    if !can_inline(&ctx, &call, &fn_def, &body) {
+        return None;
+    }
+    let mut edits = tail_exit_edits(&body)?;
+
+    let param_list = fn_def.param_list()?;
+    let mut args = call.args.clone();
+    if param_list.self_param().is_some() && call.receiver.is_none() {
+        // `Foo::method(foo, ..)` passes `self` as the first argument.
+        if args.is_empty() {
+            return None;
+        }
+    }
+    let self_arg = match (param_list.self_param(), &call.receiver) {
+        (Some(param), Some(receiver)) => Some(SelfArg::from_receiver(&ctx, param, receiver)),
+        (Some(param), None) => Some(SelfArg::from_arg(param, args.remove(0))),
+        (None, Some(_)) => return None,
+        (None, None) => None,
+    };
+    if args.len() != param_list.params().count() {
+        return None;
+    }
+
+    let mut visible = HashSet::new();
+    ctx.source_analyzer(call.expr.syntax(), None).process_all_names(ctx.db, &mut |name, def| {
+        if let ScopeDef::Local(_) = def {
+            visible.insert(name.to_string());
+        }
+    });
+    let mut taken: HashSet<String> = fn_def
+        .syntax()
+        .descendants()
+        .filter_map(ast::BindPat::cast)
+        .filter_map(|it| it.name())
+        .map(|it| it.text().to_string())
+        .chain(visible.iter().cloned())
+        .collect();
+
+    let mut bindings = Vec::new();
+    if let Some(self_arg) = self_arg {
+        let usages = self_usages(&ctx, &body);
+        if is_pure(&self_arg.expr)
+            && args.iter().all(is_pure)
+            && is_evaluated_once(&ctx, &usages, &body)
+        {
+            for usage in usages {
+                let (text, is_atomic) = if usage.is_receiver {
+                    (&self_arg.receiver_text, self_arg.receiver_is_atomic)
+                } else {
+                    (&self_arg.value_text, self_arg.value_is_atomic)
+                };
+                edits.push((usage.range, usage.replacement("self", text, is_atomic)));
+            }
+        } else {
+            let name = if taken.contains("this") {
+                fresh_name("this", &taken)
+            } else {
+                "this".to_string()
+            };
+            taken.insert(name.clone());
+            let mut_kw = if self_arg.is_mut { "mut " } else { "" };
+            bindings.push(format!("let {}{} = {};", mut_kw, name, self_arg.value_text));
+            for usage in usages {
+                edits.push((usage.range, usage.replacement("self", &name, true)));
+            }
+        }
+    }
+
+    let type_params = type_param_names(&fn_def);
+    let mut locals: Vec<ast::BindPat> = body
+        .syntax()
+        .descendants()
+        .filter(|it| !is_nested_in(it, &body, &[FN_DEF]))
+        .filter_map(ast::BindPat::cast)
+        .collect();
+    let mut param_bindings = Vec::new();
+    for (i, (param, arg)) in param_list.params().zip(args.iter()).enumerate() {
+        let pat = param.pat()?;
+        // Arguments are evaluated in order, so an argument is only inlined
+        // if none of the following ones can change its value.
+        let can_substitute = is_pure(arg) && args[i + 1..].iter().all(is_pure);
+        match &pat {
+            ast::Pat::BindPat(bind_pat)
+                if can_substitute && !bind_pat.is_mutable() && !bind_pat.is_ref() =>
+            {
+                let name = bind_pat.name()?.text().to_string();
+                let usages = local_usages(&ctx, bind_pat, &body);
+                if is_evaluated_once(&ctx, &usages, &body) {
+                    let text = arg.syntax().text().to_string();
+                    for usage in usages {
+                        edits.push((usage.range, usage.replacement(&name, &text, is_atomic(arg))));
+                    }
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        locals.extend(pat.syntax().descendants().filter_map(ast::BindPat::cast));
+        let ty = param
+            .ascribed_type()
+            .filter(|it| is_portable_type(it, &type_params))
+            .map(|it| format!(": {}", it.syntax()))
+            .unwrap_or_default();
+        param_bindings.push((pat, format!("{} = {};", ty, arg.syntax())));
+    }
+
+    for bind_pat in &locals {
+        let name = match bind_pat.name() {
+            Some(it) => it,
+            None => continue,
+        };
+        let text = name.text().to_string();
+        if !visible.contains(&text) {
+            continue;
+        }
+        let new_name = fresh_name(&text, &taken);
+        taken.insert(new_name.clone());
+        if bind_pat.syntax().parent().map_or(false, |it| it.kind() == RECORD_FIELD_PAT_LIST) {
+            // `Foo { x }` has to become `Foo { x: x1 }`.
+            let start = bind_pat.syntax().text_range().start();
+            edits.push((TextRange::offset_len(start, 0.into()), format!("{}: ", text)));
+        }
+        edits.push((name.syntax().text_range(), new_name.clone()));
+        for usage in local_usages(&ctx, bind_pat, &body) {
+            edits.push((usage.range, usage.replacement(&text, &new_name, true)));
+        }
+    }
+    for (pat, rest) in param_bindings {
+        let pat_text = apply_edits(pat.syntax(), &edits, Vec::new());
+        bindings.push(format!("let {}{}", pat_text, rest));
+    }
+
+    let indent = leading_indent(call.expr.syntax()).map(|it| it.to_string()).unwrap_or_default();
+    let body_indent = body
+        .statements()
+        .map(|it| it.syntax().clone())
+        .chain(body.expr().map(|it| it.syntax().clone()))
+        .next()
+        .and_then(|it| leading_indent(&it))
+        .map(|it| it.to_string())
+        .unwrap_or_default();
+    let replacement = match single_expr(&body).filter(|_| bindings.is_empty()) {
+        Some(expr) => {
+            let reindent =
+                reindent_edits(expr.syntax(), expr.syntax().text_range(), &body_indent, &indent);
+            let buf = apply_edits(expr.syntax(), &edits, reindent);
+            let value = match &expr {
+                ast::Expr::ReturnExpr(it) => it.expr().unwrap_or_else(|| expr.clone()),
+                _ => expr.clone(),
+            };
+            if is_operand(call.expr.syntax()) && !is_atomic(&value) {
+                format!("({})", buf)
+            } else {
+                buf
+            }
+        }
+        None => {
+            let (l_curly, r_curly) = (body.syntax().first_token()?, body.syntax().last_token()?);
+            if l_curly.kind() != T!['{'] || r_curly.kind() != T!['}'] {
+                return None;
+            }
+            let inner =
+                TextRange::from_to(l_curly.text_range().end(), r_curly.text_range().start());
+            let reindent =
+                reindent_edits(body.syntax(), inner, &body_indent, &format!("{}    ", indent));
+            let text = apply_edits(body.syntax(), &edits, reindent);
+            // Without the curly braces and the whitespace around the statements.
+            let text = text[1..text.len() - 1].trim();
+            if bindings.is_empty() && text.is_empty() {
+                "()".to_string()
+            } else {
+                let mut buf = String::from("{");
+                for binding in &bindings {
+                    format!(buf, "\n{}    {}", indent, binding);
+                }
+                if !text.is_empty() {
+                    format!(buf, "\n{}    {}", indent, text);
+                }
+                format!(buf, "\n{}}}", indent);
+                buf
+            }
+        }
+    };
+
+    ctx.add_assist(AssistId("inline_call"), "inline call", |edit| {
+        let range = call.expr.syntax().text_range();
+        edit.target(range);
+        edit.replace(range, replacement);
+        edit.set_cursor(range.start());
+    })
+}
+
+/// A call of the function which is being inlined.
+struct CallSite {
+    expr: ast::Expr,
+    function: Function,
+    /// The receiver of a method call, `None` for calls through a path.
+    receiver: Option<ast::Expr>,
+    args: Vec<ast::Expr>,
+}
+
+impl CallSite {
+    fn from_name_ref(
+        ctx: &AssistCtx<impl HirDatabase>,
+        name_ref: &ast::NameRef,
+    ) -> Option<CallSite> {
+        let parent = name_ref.syntax().parent()?;
+        if let Some(call) = ast::MethodCallExpr::cast(parent.clone()) {
+            let function = ctx.source_analyzer(call.syntax(), None).resolve_method_call(&call)?;
+            return Some(CallSite {
+                receiver: Some(call.expr()?),
+                args: call.arg_list()?.args().collect(),
+                function,
+                expr: call.into(),
+            });
+        }
+        let path = ast::PathSegment::cast(parent)?.parent_path();
+        let path_expr = path.syntax().parent().and_then(ast::PathExpr::cast)?;
+        let call = path_expr.syntax().parent().and_then(ast::CallExpr::cast)?;
+        let function = match ctx.source_analyzer(call.syntax(), None).resolve_path(ctx.db, &path)? {
+            PathResolution::Def(ModuleDef::Function(it)) => it,
+            _ => return None,
+        };
+        Some(CallSite {
+            receiver: None,
+            args: call.arg_list()?.args().collect(),
+            function,
+            expr: call.into(),
+        })
+    }
+}
+
+/// The value passed for the `self` parameter.
+struct SelfArg {
+    expr: ast::Expr,
+    /// The argument as it's passed to the parameter, with auto-referencing
+    /// made explicit.
+    value_text: String,
+    value_is_atomic: bool,
+    /// The argument as it's used by field accesses and method calls.
+    receiver_text: String,
+    receiver_is_atomic: bool,
+    is_mut: bool,
+}
+
+impl SelfArg {
+    fn from_receiver(
+        ctx: &AssistCtx<impl HirDatabase>,
+        param: ast::SelfParam,
+        receiver: &ast::Expr,
+    ) -> SelfArg {
+        let text = receiver.syntax().text().to_string();
+        let is_ref = match ctx.source_analyzer(receiver.syntax(), None).type_of(ctx.db, receiver) {
+            Some(Ty::Apply(ApplicationTy { ctor: TypeCtor::Ref(_), .. })) => true,
+            _ => false,
+        };
+        let value_text = match param.kind() {
+            ast::SelfParamKind::Ref if !is_ref => format!("&{}", text),
+            ast::SelfParamKind::MutRef if !is_ref => format!("&mut {}", text),
+            _ => text.clone(),
+        };
+        SelfArg {
+            expr: receiver.clone(),
+            value_is_atomic: value_text == text,
+            value_text,
+            receiver_text: text,
+            receiver_is_atomic: true,
+            is_mut: is_mut_self(&param),
+        }
+    }
+
+    fn from_arg(param: ast::SelfParam, arg: ast::Expr) -> SelfArg {
+        let receiver = match &arg {
+            ast::Expr::RefExpr(it) => it.expr().unwrap_or_else(|| arg.clone()),
+            _ => arg.clone(),
+        };
+        SelfArg {
+            value_text: arg.syntax().text().to_string(),
+            value_is_atomic: is_atomic(&arg),
+            receiver_text: receiver.syntax().text().to_string(),
+            receiver_is_atomic: is_atomic(&receiver),
+            is_mut: is_mut_self(&param),
+            expr: arg,
+        }
+    }
+}
+
+fn is_mut_self(param: &ast::SelfParam) -> bool {
+    param.kind() == ast::SelfParamKind::Owned
+        && param.syntax().children_with_tokens().any(|it| it.kind() == T![mut])
+}
+
+/// Finds the definition of the called function in the file being edited.
+fn local_fn_def(ctx: &AssistCtx<impl HirDatabase>, call: &CallSite) -> Option<ast::FnDef> {
+    let src = call.function.source(ctx.db);
+    if src.file_id != hir::HirFileId::from(ctx.frange.file_id) {
+        return None;
+    }
+    let range = src.value.syntax().text_range();
+    let fn_def = ctx.covering_node_for_range(range).ancestors().find_map(ast::FnDef::cast)?;
+    if fn_def.syntax().text_range() != range {
+        return None;
+    }
+    Some(fn_def)
+}
+
+fn can_inline(
+    ctx: &AssistCtx<impl HirDatabase>,
+    call: &CallSite,
+    fn_def: &ast::FnDef,
+    body: &ast::Block,
+) -> bool {
+    if call.expr.syntax().ancestors().any(|it| it == *fn_def.syntax()) {
+        return false;
+    }
+    if fn_def
+        .syntax()
+        .children_with_tokens()
+        .any(|it| it.kind() == T![unsafe] || it.kind() == T![async])
+    {
+        return false;
+    }
+    // Paths in the body have to resolve the same way at the call site.
+    let enclosing_module = |node: &SyntaxNode| node.ancestors().find_map(ast::Module::cast);
+    if enclosing_module(fn_def.syntax()) != enclosing_module(call.expr.syntax()) {
+        return false;
+    }
+    let mentions = |name: &str| {
+        body.syntax()
+            .descendants()
+            .filter_map(ast::NameRef::cast)
+            .any(|it| it.text().as_str() == name)
+    };
+    if type_param_names(fn_def).iter().any(|it| mentions(it)) {
+        return false;
+    }
+    let enclosing_impl = |node: &SyntaxNode| node.ancestors().find_map(ast::ImplBlock::cast);
+    if mentions("Self") && enclosing_impl(fn_def.syntax()) != enclosing_impl(call.expr.syntax()) {
+        return false;
+    }
+    // `?` would return from the caller.
+    if body
+        .syntax()
+        .descendants()
+        .any(|it| it.kind() == TRY_EXPR && !is_nested_in(&it, body, &[FN_DEF, LAMBDA_EXPR]))
+    {
+        return false;
+    }
+    !body.syntax().descendants().any(|it| calls_function(ctx, &it, call.function))
+}
+
+fn calls_function(
+    ctx: &AssistCtx<impl HirDatabase>,
+    node: &SyntaxNode,
+    function: Function,
+) -> bool {
+    let callee = if let Some(call) = ast::MethodCallExpr::cast(node.clone()) {
+        ctx.source_analyzer(node, None).resolve_method_call(&call)
+    } else if let Some(ast::Expr::PathExpr(callee)) =
+        ast::CallExpr::cast(node.clone()).and_then(|it| it.expr())
+    {
+        match callee
+            .path()
+            .and_then(|path| ctx.source_analyzer(node, None).resolve_path(ctx.db, &path))
+        {
+            Some(PathResolution::Def(ModuleDef::Function(it))) => Some(it),
+            _ => None,
+        }
+    } else {
+        None
+    };
+    callee == Some(function)
+}
+
+fn is_nested_in(node: &SyntaxNode, body: &ast::Block, kinds: &[SyntaxKind]) -> bool {
+    node.ancestors().take_while(|it| it != body.syntax()).any(|it| kinds.contains(&it.kind()))
+}
+
+fn type_param_names(fn_def: &ast::FnDef) -> Vec<String> {
+    fn_def
+        .type_param_list()
+        .into_iter()
+        .flat_map(|it| it.type_params())
+        .filter_map(|it| it.name())
+        .map(|it| it.text().to_string())
+        .collect()
+}
+
+/// Whether the parameter type can be spelled out at the call site.
+fn is_portable_type(ty: &ast::TypeRef, type_params: &[String]) -> bool {
+    !ty.syntax().descendants_with_tokens().any(|it| match it.kind() {
+        IMPL_TRAIT_TYPE | LIFETIME => true,
+        IDENT => {
+            let text = it.as_token().map(|it| it.text().to_string()).unwrap_or_default();
+            text == "Self" || type_params.contains(&text)
+        }
+        _ => false,
+    })
+}
+
+/// Rewrites `return`s in tail position of `body` into tail expressions.
+/// Returns `None` if there are other `return`s, which can't be expressed
+/// without leaving the caller.
+fn tail_exit_edits(body: &ast::Block) -> Option<Vec<(TextRange, String)>> {
+    let mut edits = Vec::new();
+    let mut exits = 0;
+    block_tail_exits(body, &mut edits, &mut exits);
+    let all_exits = body
+        .syntax()
+        .descendants()
+        .filter(|it| it.kind() == RETURN_EXPR && !is_nested_in(it, body, &[FN_DEF, LAMBDA_EXPR]))
+        .count();
+    if exits != all_exits {
+        return None;
+    }
+    Some(edits)
+}
+
+fn block_tail_exits(block: &ast::Block, edits: &mut Vec<(TextRange, String)>, exits: &mut usize) {
+    if let Some(expr) = block.expr() {
+        return expr_tail_exits(&expr, edits, exits);
+    }
+    let stmt = match block.statements().last() {
+        Some(ast::Stmt::ExprStmt(it)) => it,
+        _ => return,
+    };
+    match stmt.expr() {
+        Some(ast::Expr::ReturnExpr(ret)) => {
+            match ret.expr() {
+                Some(value) => {
+                    let keyword = TextRange::from_to(
+                        ret.syntax().text_range().start(),
+                        value.syntax().text_range().start(),
+                    );
+                    edits.push((keyword, String::new()));
+                    if let Some(semi) =
+                        stmt.syntax().last_child_or_token().filter(|it| it.kind() == T![;])
+                    {
+                        edits.push((semi.text_range(), String::new()));
+                    }
+                }
+                None => {
+                    let range = stmt.syntax().text_range();
+                    let start = match stmt.syntax().prev_sibling_or_token() {
+                        Some(ws) if ws.kind() == WHITESPACE => ws.text_range().start(),
+                        _ => range.start(),
+                    };
+                    edits.push((TextRange::from_to(start, range.end()), String::new()));
+                }
+            }
+            *exits += 1;
+        }
+        Some(expr) if !stmt.has_semi() => expr_tail_exits(&expr, edits, exits),
+        _ => (),
+    }
+}
+
+fn expr_tail_exits(expr: &ast::Expr, edits: &mut Vec<(TextRange, String)>, exits: &mut usize) {
+    match expr {
+        ast::Expr::ReturnExpr(ret) => {
+            match ret.expr() {
+                Some(value) => {
+                    let keyword = TextRange::from_to(
+                        ret.syntax().text_range().start(),
+                        value.syntax().text_range().start(),
+                    );
+                    edits.push((keyword, String::new()));
+                }
+                None => edits.push((ret.syntax().text_range(), "()".to_string())),
+            }
+            *exits += 1;
+        }
+        ast::Expr::BlockExpr(it) => {
+            if let Some(block) = it.block() {
+                block_tail_exits(&block, edits, exits);
+            }
+        }
+        ast::Expr::IfExpr(it) => {
+            if let Some(block) = it.then_branch().and_then(|it| it.block()) {
+                block_tail_exits(&block, edits, exits);
+            }
+            match it.else_branch() {
+                Some(ast::ElseBranch::Block(it)) => {
+                    if let Some(block) = it.block() {
+                        block_tail_exits(&block, edits, exits);
+                    }
+                }
+                Some(ast::ElseBranch::IfExpr(it)) => expr_tail_exits(&it.into(), edits, exits),
+                None => (),
+            }
+        }
+        ast::Expr::MatchExpr(it) => {
+            for arm in it.match_arm_list().into_iter().flat_map(|it| it.arms()) {
+                if let Some(expr) = arm.expr() {
+                    expr_tail_exits(&expr, edits, exits);
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+/// The body if it consists of a single expression, possibly a `return`.
+fn single_expr(body: &ast::Block) -> Option<ast::Expr> {
+    let stmts: Vec<ast::Stmt> = body.statements().collect();
+    match (stmts.as_slice(), body.expr()) {
+        ([], Some(expr)) => Some(expr),
+        ([ast::Stmt::ExprStmt(stmt)], None) => match stmt.expr()? {
+            expr @ ast::Expr::ReturnExpr(_) => Some(expr),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// An occurrence of a parameter or a local in the inlined function.
+struct Usage {
+    range: TextRange,
+    /// The usage is the `x` in `Foo { x }`.
+    is_field_shorthand: bool,
+    /// The usage is the receiver of a field access or a method call.
+    is_receiver: bool,
+    is_operand: bool,
+}
+
+impl Usage {
+    fn from_name_ref(name_ref: &ast::NameRef) -> Option<Usage> {
+        let parent = name_ref.syntax().parent()?;
+        if let Some(field) = ast::RecordField::cast(parent.clone()) {
+            if field.expr().is_some() {
+                return None;
+            }
+            return Some(Usage {
+                range: name_ref.syntax().text_range(),
+                is_field_shorthand: true,
+                is_receiver: false,
+                is_operand: false,
+            });
+        }
+        let path = ast::PathSegment::cast(parent)?.parent_path();
+        if path.qualifier().is_some() || path.parent_path().is_some() {
+            return None;
+        }
+        let path_expr = path.syntax().parent().and_then(ast::PathExpr::cast)?;
+        Some(Usage::from_path_expr(&path_expr))
+    }
+
+    fn from_path_expr(path_expr: &ast::PathExpr) -> Usage {
+        let parent_kind = path_expr.syntax().parent().map(|it| it.kind());
+        Usage {
+            range: path_expr.syntax().text_range(),
+            is_field_shorthand: false,
+            is_receiver: parent_kind == Some(FIELD_EXPR) || parent_kind == Some(METHOD_CALL_EXPR),
+            is_operand: is_operand(path_expr.syntax()),
+        }
+    }
+
+    fn replacement(&self, name: &str, text: &str, is_atomic: bool) -> String {
+        let text =
+            if self.is_operand && !is_atomic { format!("({})", text) } else { text.to_string() };
+        if self.is_field_shorthand {
+            format!("{}: {}", name, text)
+        } else {
+            text
+        }
+    }
+}
+
+fn local_usages(
+    ctx: &AssistCtx<impl HirDatabase>,
+    bind_pat: &ast::BindPat,
+    body: &ast::Block,
+) -> Vec<Usage> {
+    let mut res: Vec<Usage> = ctx
+        .source_analyzer(bind_pat.syntax(), None)
+        .find_all_refs(bind_pat)
+        .into_iter()
+        .filter_map(|it| {
+            let name_ref =
+                ctx.covering_node_for_range(it.range).ancestors().find_map(ast::NameRef::cast)?;
+            Usage::from_name_ref(&name_ref)
+        })
+        .collect();
+    if let Some(name) = bind_pat.name() {
+        let is_bind_pat = |local: Local| match local.source(ctx.db).value {
+            Either::A(it) => it.syntax().text_range() == bind_pat.syntax().text_range(),
+            Either::B(_) => false,
+        };
+        let after = bind_pat.syntax().text_range().end();
+        res.extend(macro_usages(ctx, body, name.text(), after, &is_bind_pat));
+    }
+    res
+}
+
+fn self_usages(ctx: &AssistCtx<impl HirDatabase>, body: &ast::Block) -> Vec<Usage> {
+    let mut res: Vec<Usage> = body
+        .syntax()
+        .descendants()
+        .filter(|it| !is_nested_in(it, body, &[FN_DEF]))
+        .filter_map(ast::PathExpr::cast)
+        .filter(|it| {
+            let path = match it.path() {
+                Some(it) => it,
+                None => return false,
+            };
+            match path.segment().and_then(|it| it.kind()) {
+                Some(ast::PathSegmentKind::SelfKw) => path.qualifier().is_none(),
+                _ => false,
+            }
+        })
+        .map(|it| Usage::from_path_expr(&it))
+        .collect();
+    let is_self = |local: Local| local.is_self(ctx.db);
+    res.extend(macro_usages(ctx, body, "self", body.syntax().text_range().start(), &is_self));
+    res
+}
+
+/// Finds the tokens of macro arguments which refer to the local once the
+/// macro is expanded. If a macro can't be expanded, any token with a matching
+/// name is considered to be a usage.
+fn macro_usages(
+    ctx: &AssistCtx<impl HirDatabase>,
+    body: &ast::Block,
+    name: &str,
+    after: TextUnit,
+    is_local: &dyn Fn(Local) -> bool,
+) -> Vec<Usage> {
+    body.syntax()
+        .descendants_with_tokens()
+        .filter_map(|it| it.into_token())
+        .filter(|it| {
+            (it.kind() == IDENT || it.kind() == T![self])
+                && it.text().as_str() == name
+                && it.text_range().start() >= after
+                && it.parent().kind() == TOKEN_TREE
+        })
+        .filter(|it| resolve_macro_arg(ctx, it).map_or(true, |local| local.map_or(false, is_local)))
+        .map(|it| Usage {
+            range: it.text_range(),
+            is_field_shorthand: false,
+            is_receiver: false,
+            is_operand: true,
+        })
+        .collect()
+}
+
+/// Resolves a token of a macro argument in the expansion of the macro, going
+/// through nested macro calls. Returns `None` if a macro can't be expanded,
+/// and `Some(None)` if the token doesn't refer to a local.
+fn resolve_macro_arg(
+    ctx: &AssistCtx<impl HirDatabase>,
+    token: &SyntaxToken,
+) -> Option<Option<Local>> {
+    let mut token = hir::Source::new(ctx.frange.file_id.into(), token.clone());
+    while token.value.parent().kind() == TOKEN_TREE {
+        let macro_call = token.value.parent().ancestors().find_map(ast::MacroCall::cast)?;
+        let analyzer =
+            hir::SourceAnalyzer::new(ctx.db, token.with_value(macro_call.syntax()), None);
+        let expansion = analyzer.expand(ctx.db, token.with_value(&macro_call))?;
+        token = expansion.map_token_down(ctx.db, token.as_ref())?;
+    }
+    let path = match token.value.parent().ancestors().find_map(ast::Path::cast) {
+        Some(it) => it,
+        None => return Some(None),
+    };
+    let analyzer = hir::SourceAnalyzer::new(ctx.db, token.with_value(path.syntax()), None);
+    match analyzer.resolve_path(ctx.db, &path) {
+        Some(PathResolution::Local(local)) => Some(Some(local)),
+        _ => Some(None),
+    }
+}
+
+/// Whether an argument with these `usages` can be substituted for its
+/// parameter: it has to be evaluated exactly once, so it can't be unused, or
+/// used in a loop or a closure.
+fn is_evaluated_once(
+    ctx: &AssistCtx<impl HirDatabase>,
+    usages: &[Usage],
+    body: &ast::Block,
+) -> bool {
+    let usage = match usages {
+        [usage] => usage,
+        _ => return false,
+    };
+    let node = match ctx.covering_node_for_range(usage.range) {
+        NodeOrToken::Node(it) => it,
+        NodeOrToken::Token(it) => it.parent(),
+    };
+    !is_nested_in(&node, body, &[LOOP_EXPR, WHILE_EXPR, FOR_EXPR, LAMBDA_EXPR])
+}
+
+/// Whether `expr` can be evaluated at a later point, any number of times,
+/// without changing its value.
+fn is_pure(expr: &ast::Expr) -> bool {
+    let inner = match expr {
+        ast::Expr::Literal(_) | ast::Expr::PathExpr(_) => return true,
+        ast::Expr::ParenExpr(it) => it.expr(),
+        ast::Expr::FieldExpr(it) => it.expr(),
+        ast::Expr::RefExpr(it) => it.expr(),
+        _ => return false,
+    };
+    inner.map_or(false, |it| is_pure(&it))
+}
+
+/// Whether `expr` can be used as an operand without parentheses.
+fn is_atomic(expr: &ast::Expr) -> bool {
+    match expr {
+        ast::Expr::Literal(_)
+        | ast::Expr::PathExpr(_)
+        | ast::Expr::FieldExpr(_)
+        | ast::Expr::ParenExpr(_)
+        | ast::Expr::CallExpr(_)
+        | ast::Expr::MethodCallExpr(_)
+        | ast::Expr::IndexExpr(_)
+        | ast::Expr::TupleExpr(_)
+        | ast::Expr::ArrayExpr(_)
+        | ast::Expr::MacroCall(_)
+        | ast::Expr::RecordLit(_) => true,
+        _ => false,
+    }
+}
+
+fn is_operand(node: &SyntaxNode) -> bool {
+    match node.parent() {
+        Some(parent) => {
+            ast::Expr::can_cast(parent.kind())
+                && ![PAREN_EXPR, TUPLE_EXPR, ARRAY_EXPR].contains(&parent.kind())
+        }
+        None => false,
+    }
+}
+
+fn fresh_name(name: &str, taken: &HashSet<String>) -> String {
+    (1..).map(|i| format!("{}{}", name, i)).find(|it| !taken.contains(it)).unwrap()
+}
+
+/// Applies the `edits` which are inside of `node`, and the `reindent` edits,
+/// to its text. Whitespace removed by an edit isn't reindented.
+fn apply_edits(
+    node: &SyntaxNode,
+    edits: &[(TextRange, String)],
+    reindent: Vec<(TextRange, String)>,
+) -> String {
+    let range = node.text_range();
+    let mut edits: Vec<(TextRange, String)> =
+        edits.iter().filter(|(it, _)| it.is_subrange(&range)).cloned().collect();
+    let reindent: Vec<(TextRange, String)> = reindent
+        .into_iter()
+        .filter(|(ws, _)| {
+            !edits.iter().any(|(it, _)| it.start() < ws.end() && ws.start() < it.end())
+        })
+        .collect();
+    edits.extend(reindent);
+    edits.sort_by_key(|(range, _)| (range.start(), range.end()));
+    splice(node, edits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::{check_assist, check_assist_not_applicable};
+
+    #[test]
+    fn inline_expression_body() {
+        check_assist(
+            inline_call,
+            "
+fn add(a: u32, b: u32) -> u32 {
+    a + b
+}
+fn main() {
+    let x = add<|>(1, 2) * 3;
+}",
+            "
+fn add(a: u32, b: u32) -> u32 {
+    a + b
+}
+fn main() {
+    let x = <|>(1 + 2) * 3;
+}",
+        );
+    }
+
+    #[test]
+    fn inline_binds_arguments_used_twice_or_with_side_effects() {
+        check_assist(
+            inline_call,
+            "
+fn make() -> u32 { 92 }
+fn square(x: u32, y: u32) -> u32 {
+    let z = x * x;
+    z + y
+}
+fn main() {
+    square<|>(2, make());
+}",
+            "
+fn make() -> u32 { 92 }
+fn square(x: u32, y: u32) -> u32 {
+    let z = x * x;
+    z + y
+}
+fn main() {
+    <|>{
+        let x: u32 = 2;
+        let y: u32 = make();
+        let z = x * x;
+        z + y
+    };
+}",
+        );
+    }
+
+    #[test]
+    fn inline_renames_clashing_locals() {
+        check_assist(
+            inline_call,
+            "
+fn sub(a: i32, b: i32) -> i32 {
+    let tmp = a;
+    tmp - b - b
+}
+fn main() {
+    let a = 1;
+    let b = 2;
+    let tmp = 3;
+    sub<|>(b, a + tmp);
+}",
+            "
+fn sub(a: i32, b: i32) -> i32 {
+    let tmp = a;
+    tmp - b - b
+}
+fn main() {
+    let a = 1;
+    let b = 2;
+    let tmp = 3;
+    <|>{
+        let a1: i32 = b;
+        let b1: i32 = a + tmp;
+        let tmp1 = a1;
+        tmp1 - b1 - b1
+    };
+}",
+        );
+    }
+
+    #[test]
+    fn inline_rewrites_tail_returns() {
+        check_assist(
+            inline_call,
+            "
+fn sign(x: i32) -> i32 {
+    if x < 0 {
+        return -1;
+    } else if x == 0 {
+        return 0;
+    } else {
+        return 1;
+    }
+}
+fn main() {
+    let s = sign<|>(5);
+}",
+            "
+fn sign(x: i32) -> i32 {
+    if x < 0 {
+        return -1;
+    } else if x == 0 {
+        return 0;
+    } else {
+        return 1;
+    }
+}
+fn main() {
+    let s = <|>{
+        let x: i32 = 5;
+        if x < 0 {
+            -1
+        } else if x == 0 {
+            0
+        } else {
+            1
+        }
+    };
+}",
+        );
+    }
+
+    #[test]
+    fn inline_method_call() {
+        check_assist(
+            inline_call,
+            "
+struct Point { x: i32, y: i32 }
+impl Point {
+    fn sum(&self) -> i32 {
+        return self.x + self.y;
+    }
+}
+fn main() {
+    let p = Point { x: 1, y: 2 };
+    let s = p.sum<|>();
+}",
+            "
+struct Point { x: i32, y: i32 }
+impl Point {
+    fn sum(&self) -> i32 {
+        return self.x + self.y;
+    }
+}
+fn main() {
+    let p = Point { x: 1, y: 2 };
+    let s = <|>{
+        let this = &p;
+        this.x + this.y
+    };
+}",
+        );
+    }
+
+    #[test]
+    fn inline_single_self_usage() {
+        check_assist(
+            inline_call,
+            "
+struct Counter { n: u32 }
+impl Counter {
+    fn get(&self) -> u32 {
+        self.n
+    }
+}
+fn main() {
+    let c = Counter { n: 0 };
+    let n = c.get<|>();
+}",
+            "
+struct Counter { n: u32 }
+impl Counter {
+    fn get(&self) -> u32 {
+        self.n
+    }
+}
+fn main() {
+    let c = Counter { n: 0 };
+    let n = <|>c.n;
+}",
+        );
+    }
+
+    #[test]
+    fn inline_expands_field_shorthand() {
+        check_assist(
+            inline_call,
+            "
+struct Foo { x: i32 }
+fn foo(x: i32) -> Foo {
+    Foo { x }
+}
+fn main() {
+    let f = foo<|>(92);
+}",
+            "
+struct Foo { x: i32 }
+fn foo(x: i32) -> Foo {
+    Foo { x }
+}
+fn main() {
+    let f = <|>Foo { x: 92 };
+}",
+        );
+    }
+
+    #[test]
+    fn inline_binds_arguments_unused_or_used_in_loops_and_closures() {
+        check_assist(
+            inline_call,
+            "
+fn apply(x: u32, y: u32, z: u32) -> u32 {
+    let f = || y;
+    let mut s = 0;
+    for _ in 0..2 {
+        s += z;
+    }
+    s + f()
+}
+fn main() {
+    apply<|>(1, 2, 3);
+}",
+            "
+fn apply(x: u32, y: u32, z: u32) -> u32 {
+    let f = || y;
+    let mut s = 0;
+    for _ in 0..2 {
+        s += z;
+    }
+    s + f()
+}
+fn main() {
+    <|>{
+        let x: u32 = 1;
+        let y: u32 = 2;
+        let z: u32 = 3;
+        let f = || y;
+        let mut s = 0;
+        for _ in 0..2 {
+            s += z;
+        }
+        s + f()
+    };
+}",
+        );
+    }
+
+    #[test]
+    fn inline_resolves_macro_arguments() {
+        check_assist(
+            inline_call,
+            "
+macro_rules! id {
+    ($e:expr) => { $e };
+}
+struct S { x: u32 }
+fn get(s: S, x: u32) -> u32 {
+    id!(s.x) + x
+}
+fn main() {
+    let t = S { x: 1 };
+    get<|>(t, 2);
+}",
+            "
+macro_rules! id {
+    ($e:expr) => { $e };
+}
+struct S { x: u32 }
+fn get(s: S, x: u32) -> u32 {
+    id!(s.x) + x
+}
+fn main() {
+    let t = S { x: 1 };
+    <|>id!(t.x) + 2;
+}",
+        );
+    }
+
+    #[test]
+    fn inline_keeps_multi_line_string_literals() {
+        check_assist(
+            inline_call,
+            "
+fn text() -> &'static str {
+    let s = \"a  \n  b\";
+    s
+}
+fn main() {
+    if true {
+        let t = text<|>();
+    }
+}",
+            "
+fn text() -> &'static str {
+    let s = \"a  \n  b\";
+    s
+}
+fn main() {
+    if true {
+        let t = <|>{
+            let s = \"a  \n  b\";
+            s
+        };
+    }
+}",
+        );
+    }
+
+    #[test]
+    fn inline_not_applicable_to_recursive_function() {
+        check_assist_not_applicable(
+            inline_call,
+            "
+fn fact(n: u32) -> u32 {
+    if n == 0 { 1 } else { n * fact(n - 1) }
+}
+fn main() {
+    fact<|>(5);
+}",
+        );
+    }
+
+    #[test]
+    fn inline_not_applicable_with_early_return() {
+        check_assist_not_applicable(
+            inline_call,
+            "
+fn foo(x: i32) -> i32 {
+    if x > 0 {
+        return x;
+    }
+    let y = x * 2;
+    y
+}
+fn main() {
+    foo<|>(5);
+}",
+        );
+    }
+
+    #[test]
+    fn inline_not_applicable_with_try() {
+        check_assist_not_applicable(
+            inline_call,
+            "
+fn foo(x: Option<i32>) -> Option<i32> {
+    let y = x?;
+    Some(y + 1)
+}
+fn main() {
+    foo<|>(None);
+}",
+        );
+    }
+}
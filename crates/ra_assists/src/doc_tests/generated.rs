@@ -232,6 +232,34 @@ fn main() {
     )
 }
 
+#[test]
+fn doctest_extract_function() {
+    check(
+        "extract_function",
+        r#####"
+fn main() {
+    let n = 1;
+    <|>let m = n + 2;
+    let k = m * n;<|>
+    let g = k + 3;
+}
+"#####,
+        r#####"
+fn main() {
+    let n = 1;
+    let k = fun_name(n);
+    let g = k + 3;
+}
+
+fn fun_name(n: i32) -> i32 {
+    let m = n + 2;
+    let k = m * n;
+    k
+}
+"#####,
+    )
+}
+
 #[test]
 fn doctest_fill_match_arms() {
     check(
@@ -305,6 +333,29 @@ fn foo<T: Copy + Clone>() { }
     )
 }
 
+#[test]
+fn doctest_inline_call() {
+    check(
+        "inline_call",
+        r#####"
+fn add(a: u32, b: u32) -> u32 {
+    a + b
+}
+fn main() {
+    let x = add<|>(1, 2);
+}
+"#####,
+        r#####"
+fn add(a: u32, b: u32) -> u32 {
+    a + b
+}
+fn main() {
+    let x = 1 + 2;
+}
+"#####,
+    )
+}
+
 #[test]
 fn doctest_inline_local_variable() {
     check(
@@ -18,7 +18,9 @@ use ra_syntax::{TextRange, TextUnit};
 use ra_text_edit::TextEdit;
 
 pub(crate) use crate::assist_ctx::{Assist, AssistCtx};
-pub use crate::assists::add_import::auto_import_text_edit;
+pub use crate::assists::{
+    add_import::auto_import_text_edit, fill_match_arms::fill_match_arms_text_edit,
+};
 
 /// Unique identifier of the assist, should not be shown to the user
 /// directly.
@@ -102,9 +104,11 @@ mod assists {
     mod flip_binexpr;
     mod flip_trait_bound;
     mod change_visibility;
-    mod fill_match_arms;
+    pub(crate) mod fill_match_arms;
     mod merge_match_arms;
     mod introduce_variable;
+    mod extract_function;
+    mod inline_call;
     mod inline_local_variable;
     mod raw_string;
     mod replace_if_let_with_match;
@@ -131,12 +135,14 @@ mod assists {
             flip_binexpr::flip_binexpr,
             flip_trait_bound::flip_trait_bound,
             introduce_variable::introduce_variable,
+            extract_function::extract_function,
             replace_if_let_with_match::replace_if_let_with_match,
             split_import::split_import,
             remove_dbg::remove_dbg,
             add_import::add_import,
             add_missing_impl_members::add_missing_impl_members,
             add_missing_impl_members::add_missing_default_members,
+            inline_call::inline_call,
             inline_local_variable::inline_local_varialbe,
             move_guard::move_guard_to_arm_body,
             move_guard::move_arm_cond_to_match_guard,
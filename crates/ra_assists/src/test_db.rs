@@ -2,7 +2,7 @@
 
 use std::sync::Arc;
 
-use ra_db::{salsa, CrateId, FileId, FileLoader, FileLoaderDelegate, RelativePath};
+use ra_db::{salsa, CrateId, FileId, FileLoader, FileLoaderDelegate, RelativePath, SourceRootId};
 
 #[salsa::database(
     ra_db::SourceDatabaseExtStorage,
@@ -36,6 +36,13 @@ impl FileLoader for TestDB {
     ) -> Option<FileId> {
         FileLoaderDelegate(self).resolve_relative_path(anchor, relative_path)
     }
+    fn resolve_extern_path(
+        &self,
+        extern_root: SourceRootId,
+        relative_path: &RelativePath,
+    ) -> Option<FileId> {
+        FileLoaderDelegate(self).resolve_extern_path(extern_root, relative_path)
+    }
     fn relevant_crates(&self, file_id: FileId) -> Arc<Vec<CrateId>> {
         FileLoaderDelegate(self).relevant_crates(file_id)
     }
@@ -7,7 +7,7 @@ use rustc_hash::FxHashMap;
 use crossbeam_channel::{unbounded, Receiver};
 use ra_db::{CrateGraph, FileId, SourceRootId};
 use ra_ide_api::{AnalysisChange, AnalysisHost, FeatureFlags};
-use ra_project_model::{get_rustc_cfg_options, PackageRoot, ProjectWorkspace};
+use ra_project_model::{get_rustc_cfg_options, PackageRoot, ProcMacroClient, ProjectWorkspace};
 use ra_vfs::{RootEntry, Vfs, VfsChange, VfsTask, Watch};
 use ra_vfs_glob::RustPackageFilterBuilder;
 
@@ -50,12 +50,22 @@ pub fn load_cargo(root: &Path) -> Result<(AnalysisHost, FxHashMap<SourceRootId,
         opts
     };
 
-    let (crate_graph, _crate_names) =
-        ws.to_crate_graph(&default_cfg_options, &mut |path: &Path| {
+    let extern_source_roots = roots
+        .iter()
+        .map(|&vfs_root| (vfs.root2path(vfs_root), vfs_root_to_id(vfs_root)))
+        .collect::<FxHashMap<_, _>>();
+    // FIXME: allow to pass a proc-macro server
+    let proc_macro_client = ProcMacroClient::dummy();
+    let (crate_graph, _crate_names) = ws.to_crate_graph(
+        &default_cfg_options,
+        &extern_source_roots,
+        &proc_macro_client,
+        &mut |path: &Path| {
             let vfs_file = vfs.load(path);
             log::debug!("vfs file {:?} -> {:?}", path, vfs_file);
             vfs_file.map(vfs_file_to_id)
-        });
+        },
+    );
     log::debug!("crate graph: {:?}", crate_graph);
 
     let source_roots = roots
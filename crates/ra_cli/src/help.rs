@@ -13,6 +13,7 @@ SUBCOMMANDS:
     analysis-stats
     highlight
     parse
+    ssr
     symbols";
 
 pub const ANALYSIS_BENCH_HELP: &str = "ra_cli-analysis-bench
@@ -73,3 +74,19 @@ USAGE:
 FLAGS:
     -h, --help       Prints help inforamtion
         --no-dump";
+
+pub const SSR_HELP: &str = "ra-cli-ssr
+
+USAGE:
+    ra_cli ssr [FLAGS] [OPTIONS] <QUERY>
+
+FLAGS:
+    -h, --help       Prints help information
+        --apply      Writes the replacements to the files
+
+OPTIONS:
+    --path <PATH>    Project to search, the current directory by default
+
+ARGS:
+    <QUERY>    A rule like `foo($a, $b) ==>> bar($b, $a)`, whose paths are resolved
+               in the root module of the project";
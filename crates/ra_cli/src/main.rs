@@ -3,6 +3,7 @@
 mod analysis_stats;
 mod analysis_bench;
 mod help;
+mod ssr;
 
 use std::{error::Error, fmt::Write, io::Read};
 
@@ -143,6 +144,23 @@ fn main() -> Result<()> {
             matches.finish().or_else(handle_extra_flags)?;
             analysis_bench::run(verbose, path.as_ref(), op)?;
         }
+        "ssr" => {
+            if matches.contains(["-h", "--help"]) {
+                eprintln!("{}", help::SSR_HELP);
+                return Ok(());
+            }
+            let apply = matches.contains("--apply");
+            let path: String = matches.opt_value_from_str("--path")?.unwrap_or_default();
+            let query = {
+                let mut trailing = matches.free()?;
+                if trailing.len() != 1 {
+                    eprintln!("{}", help::SSR_HELP);
+                    Err("Invalid flags")?;
+                }
+                trailing.pop().unwrap()
+            };
+            ssr::run(path.as_ref(), &query, apply)?;
+        }
         _ => eprintln!("{}", help::GLOBAL_HELP),
     }
     Ok(())
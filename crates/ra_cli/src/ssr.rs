@@ -0,0 +1,50 @@
+//! Runs structural search and replace over a project, printing the
+//! replacements or applying them to the files on disk.
+
+use std::{fs, path::Path};
+
+use ra_db::{SourceDatabase, SourceDatabaseExt};
+use ra_ide_api::{FileId, FilePosition};
+
+use crate::Result;
+
+pub(crate) fn run(path: &Path, query: &str, apply: bool) -> Result<()> {
+    let (host, roots) = ra_batch::load_cargo(path)?;
+    let db = host.raw_database();
+    let analysis = host.analysis();
+    // The paths of the rule are resolved in the root module of the first
+    // crate of the project.
+    let crate_graph = db.crate_graph();
+    let root_file = crate_graph
+        .iter()
+        .map(|it| crate_graph.crate_root(it))
+        .filter(|&file_id| roots[&db.file_source_root(file_id)].is_member())
+        .min()
+        .ok_or("No crate found in the project")?;
+    let position = FilePosition { file_id: root_file, offset: 0.into() };
+    let change = analysis.structural_search_replace(position, query, false)??;
+
+    let file_path = |file_id: FileId| {
+        let root = &roots[&db.file_source_root(file_id)];
+        db.file_relative_path(file_id).to_path(root.path())
+    };
+    for file_edit in change.source_file_edits {
+        let path = file_path(file_edit.file_id);
+        let line_index = analysis.file_line_index(file_edit.file_id)?;
+        for atom in file_edit.edit.as_atoms() {
+            let line_col = line_index.line_col(atom.delete.start());
+            println!(
+                "{}:{}:{}: {}",
+                path.display(),
+                line_col.line + 1,
+                line_col.col_utf16 + 1,
+                atom.insert
+            );
+        }
+        if apply {
+            let text = file_edit.edit.apply(&db.file_text(file_edit.file_id));
+            fs::write(&path, text)?;
+        }
+    }
+    Ok(())
+}
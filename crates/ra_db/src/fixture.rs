@@ -98,8 +98,7 @@ fn with_files(db: &mut dyn SourceDatabaseExt, fixture: &str) -> Option<FilePosit
         assert!(meta.path.starts_with(&source_root_prefix));
 
         if let Some(krate) = meta.krate {
-            let crate_id =
-                crate_graph.add_crate_root(file_id, meta.edition, meta.cfg, Env::default());
+            let crate_id = crate_graph.add_crate_root(file_id, meta.edition, meta.cfg, meta.env);
             let prev = crates.insert(krate.clone(), crate_id);
             assert!(prev.is_none());
             for dep in meta.deps {
@@ -160,9 +159,10 @@ struct FileMeta {
     deps: Vec<String>,
     cfg: CfgOptions,
     edition: Edition,
+    env: Env,
 }
 
-//- /lib.rs crate:foo deps:bar,baz
+//- /lib.rs crate:foo deps:bar,baz cfg:foo,bar=baz env:OUT_DIR=/out
 fn parse_meta(meta: &str) -> ParsedMeta {
     let components = meta.split_ascii_whitespace().collect::<Vec<_>>();
 
@@ -179,6 +179,7 @@ fn parse_meta(meta: &str) -> ParsedMeta {
     let mut deps = Vec::new();
     let mut edition = Edition::Edition2018;
     let mut cfg = CfgOptions::default();
+    let mut env = Env::default();
     for component in components[1..].iter() {
         let (key, value) = split1(component, ':').unwrap();
         match key {
@@ -193,11 +194,18 @@ fn parse_meta(meta: &str) -> ParsedMeta {
                     }
                 }
             }
+            "env" => {
+                for key in value.split(',') {
+                    if let Some((k, v)) = split1(key, '=') {
+                        env.set(k, v.into());
+                    }
+                }
+            }
             _ => panic!("bad component: {:?}", component),
         }
     }
 
-    ParsedMeta::File(FileMeta { path, krate, deps, edition, cfg })
+    ParsedMeta::File(FileMeta { path, krate, deps, edition, cfg, env })
 }
 
 fn split1(haystack: &str, delim: char) -> Option<(&str, &str)> {
@@ -6,7 +6,7 @@
 //! actual IO. See `vfs` and `project_model` in the `ra_lsp_server` crate for how
 //! actual IO is done and lowered to input.
 
-use std::{fmt, str::FromStr};
+use std::{fmt, path::Path, str::FromStr, sync::Arc};
 
 use ra_cfg::CfgOptions;
 use ra_syntax::SmolStr;
@@ -89,7 +89,9 @@ struct CrateData {
     edition: Edition,
     cfg_options: CfgOptions,
     env: Env,
+    extern_source: ExternSource,
     dependencies: Vec<Dependency>,
+    proc_macro: Vec<ProcMacro>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -103,6 +105,50 @@ pub struct Env {
     entries: FxHashMap<String, String>,
 }
 
+/// Source roots a crate can refer to by absolute path, like the `OUT_DIR` of
+/// its build script in `include!(concat!(env!("OUT_DIR"), "/generated.rs"))`.
+///
+/// This is the only way to cross source root boundaries by path: the paths of
+/// the roots are recorded as opaque strings, and absolute paths starting with
+/// them are resolved relative to the corresponding `SourceRootId`.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct ExternSource {
+    extern_paths: FxHashMap<String, SourceRootId>,
+}
+
+/// Index of a procedural macro in the list of macros exported by a
+/// `proc-macro` crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProcMacroId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProcMacroKind {
+    /// `#[proc_macro_derive]`, invoked by `#[derive(Foo)]`.
+    CustomDerive,
+    /// `#[proc_macro]`, invoked like `foo!(...)`.
+    FuncLike,
+    /// `#[proc_macro_attribute]`, invoked like `#[foo(...)]`.
+    Attr,
+}
+
+/// A procedural macro exported by a `proc-macro` crate. The actual expansion
+/// is delegated to `expander`, which usually talks to an external process.
+#[derive(Debug, Clone)]
+pub struct ProcMacro {
+    pub name: SmolStr,
+    pub kind: ProcMacroKind,
+    pub expander: Arc<dyn tt::TokenExpander>,
+}
+
+impl Eq for ProcMacro {}
+impl PartialEq for ProcMacro {
+    fn eq(&self, other: &ProcMacro) -> bool {
+        self.name == other.name
+            && self.kind == other.kind
+            && Arc::ptr_eq(&self.expander, &other.expander)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Dependency {
     pub crate_id: CrateId,
@@ -128,6 +174,26 @@ impl CrateGraph {
         &self.arena[&crate_id].cfg_options
     }
 
+    pub fn proc_macros(&self, crate_id: CrateId) -> &[ProcMacro] {
+        &self.arena[&crate_id].proc_macro
+    }
+
+    pub fn set_proc_macros(&mut self, crate_id: CrateId, proc_macros: Vec<ProcMacro>) {
+        self.arena.get_mut(&crate_id).unwrap().proc_macro = proc_macros;
+    }
+
+    pub fn env(&self, crate_id: CrateId) -> &Env {
+        &self.arena[&crate_id].env
+    }
+
+    pub fn extern_source(&self, crate_id: CrateId) -> &ExternSource {
+        &self.arena[&crate_id].extern_source
+    }
+
+    pub fn set_extern_source(&mut self, crate_id: CrateId, extern_source: ExternSource) {
+        self.arena.get_mut(&crate_id).unwrap().extern_source = extern_source;
+    }
+
     pub fn add_dep(
         &mut self,
         from: CrateId,
@@ -206,6 +272,32 @@ impl CrateGraph {
     }
 }
 
+impl Env {
+    pub fn set(&mut self, env: &str, value: String) {
+        self.entries.insert(env.to_owned(), value);
+    }
+
+    pub fn get(&self, env: &str) -> Option<String> {
+        self.entries.get(env).cloned()
+    }
+}
+
+impl ExternSource {
+    pub fn set_extern_path(&mut self, root_path: &str, root: SourceRootId) {
+        self.extern_paths.insert(root_path.to_owned(), root);
+    }
+
+    /// Splits an absolute `path` into the extern source root containing it
+    /// and the path relative to that root.
+    pub fn extern_path(&self, path: &str) -> Option<(SourceRootId, RelativePathBuf)> {
+        self.extern_paths.iter().find_map(|(root_path, &root)| {
+            let relative = Path::new(path).strip_prefix(root_path).ok()?;
+            let relative = RelativePathBuf::from_path(relative).ok()?;
+            Some((root, relative))
+        })
+    }
+}
+
 impl CrateId {
     pub fn shift(self, amount: u32) -> CrateId {
         CrateId(self.0 + amount)
@@ -214,7 +306,15 @@ impl CrateId {
 
 impl CrateData {
     fn new(file_id: FileId, edition: Edition, cfg_options: CfgOptions, env: Env) -> CrateData {
-        CrateData { file_id, edition, dependencies: Vec::new(), cfg_options, env }
+        CrateData {
+            file_id,
+            edition,
+            dependencies: Vec::new(),
+            cfg_options,
+            env,
+            extern_source: ExternSource::default(),
+            proc_macro: Vec::new(),
+        }
     }
 
     fn add_dep(&mut self, name: SmolStr, crate_id: CrateId) {
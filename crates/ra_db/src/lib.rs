@@ -10,7 +10,10 @@ use ra_syntax::{ast, Parse, SourceFile, TextRange, TextUnit};
 
 pub use crate::{
     cancellation::Canceled,
-    input::{CrateGraph, CrateId, Dependency, Edition, Env, FileId, SourceRoot, SourceRootId},
+    input::{
+        CrateGraph, CrateId, Dependency, Edition, Env, ExternSource, FileId, ProcMacro,
+        ProcMacroId, ProcMacroKind, SourceRoot, SourceRootId,
+    },
 };
 pub use relative_path::{RelativePath, RelativePathBuf};
 pub use salsa;
@@ -70,6 +73,13 @@ pub trait FileLoader {
     fn file_text(&self, file_id: FileId) -> Arc<String>;
     fn resolve_relative_path(&self, anchor: FileId, relative_path: &RelativePath)
         -> Option<FileId>;
+    /// Resolves a path inside of a source root which is referred to via
+    /// `ExternSource`.
+    fn resolve_extern_path(
+        &self,
+        extern_root: SourceRootId,
+        relative_path: &RelativePath,
+    ) -> Option<FileId>;
     fn relevant_crates(&self, file_id: FileId) -> Arc<Vec<CrateId>>;
 }
 
@@ -143,6 +153,14 @@ impl<T: SourceDatabaseExt> FileLoader for FileLoaderDelegate<&'_ T> {
         let source_root = self.0.source_root(source_root);
         source_root.file_by_relative_path(&path)
     }
+    fn resolve_extern_path(
+        &self,
+        extern_root: SourceRootId,
+        relative_path: &RelativePath,
+    ) -> Option<FileId> {
+        let source_root = self.0.source_root(extern_root);
+        source_root.file_by_relative_path(&relative_path.normalize())
+    }
 
     fn relevant_crates(&self, file_id: FileId) -> Arc<Vec<CrateId>> {
         let source_root = self.0.file_source_root(file_id);
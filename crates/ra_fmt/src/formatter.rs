@@ -0,0 +1,528 @@
+//! A simple formatter working on the syntax tree.
+//!
+//! It is used when `rustfmt` is not available, or can't format the file
+//! because of syntax errors. It never moves tokens between lines: it only
+//! reindents lines, normalizes blank lines and spacing around some tokens,
+//! and adds trailing commas to lists laid out vertically.
+
+use std::collections::HashMap;
+
+use ra_syntax::{
+    ast::{self, AstNode},
+    NodeOrToken, SyntaxElement, SyntaxKind,
+    SyntaxKind::*,
+    SyntaxNode, SyntaxToken, TextRange, TextUnit, T,
+};
+use ra_text_edit::{TextEdit, TextEditBuilder};
+
+/// How the formatted code is indented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// The number of spaces in an indentation level.
+    pub tab_size: usize,
+    /// Indent with spaces rather than with tabs.
+    pub insert_spaces: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions { tab_size: 4, insert_spaces: true }
+    }
+}
+
+impl FormatOptions {
+    fn indent(&self) -> String {
+        if self.insert_spaces {
+            " ".repeat(self.tab_size)
+        } else {
+            "\t".to_string()
+        }
+    }
+}
+
+/// Formats the whole file.
+pub fn format_file(file: &ast::SourceFile, options: FormatOptions) -> TextEdit {
+    format_range(file, file.syntax().text_range(), options)
+}
+
+/// Formats the file, keeping only the changes which touch `range`.
+pub fn format_range(file: &ast::SourceFile, range: TextRange, options: FormatOptions) -> TextEdit {
+    let mut formatter = Formatter {
+        trailing_commas: file.syntax().descendants().filter_map(|it| trailing_comma(&it)).collect(),
+        levels: HashMap::new(),
+        indent: options.indent(),
+        edit: TextEditBuilder::default(),
+        range,
+    };
+    formatter.format(file.syntax());
+    formatter.edit.finish()
+}
+
+struct Formatter {
+    /// Offsets at which a trailing comma is missing.
+    trailing_commas: Vec<TextUnit>,
+    /// Indentation levels of the tokens which start a line, by offset.
+    levels: HashMap<TextUnit, usize>,
+    /// The text of one indentation level.
+    indent: String,
+    edit: TextEditBuilder,
+    range: TextRange,
+}
+
+impl Formatter {
+    fn format(&mut self, root: &SyntaxNode) {
+        let mut prev: Option<SyntaxToken> = None;
+        let mut whitespace: Option<SyntaxToken> = None;
+        let tokens = root.descendants_with_tokens().filter_map(|it| it.into_token());
+        for token in tokens {
+            if token.kind() == WHITESPACE {
+                whitespace = Some(token);
+                continue;
+            }
+            let gap = match &whitespace {
+                Some(ws) => ws.text_range(),
+                None => TextRange::offset_len(token.text_range().start(), 0.into()),
+            };
+            let old_text = whitespace.as_ref().map_or("", |ws| ws.text().as_str());
+
+            let new_text = match &prev {
+                None => {
+                    self.levels.insert(token.text_range().start(), 0);
+                    String::new()
+                }
+                Some(prev) if old_text.contains('\n') => {
+                    let level = self.indent_level(&token);
+                    self.levels.insert(token.text_range().start(), level);
+                    let mut res = String::new();
+                    if self.trailing_commas.contains(&prev.text_range().end()) {
+                        res.push(',');
+                    }
+                    let max_newlines = if is_opening(prev) || is_closing(&token) { 1 } else { 2 };
+                    let newlines = old_text.matches('\n').count().min(max_newlines);
+                    res.extend(std::iter::repeat('\n').take(newlines));
+                    res.push_str(&self.indent.repeat(level));
+                    res
+                }
+                Some(prev) => {
+                    let mut res = String::new();
+                    if self.trailing_commas.contains(&prev.text_range().end()) {
+                        res.push(',');
+                    }
+                    res.push_str(spacing(prev, &token).unwrap_or(old_text));
+                    res
+                }
+            };
+            self.replace(gap, old_text, new_text);
+
+            prev = Some(token);
+            whitespace = None;
+        }
+
+        // Files end with exactly one newline.
+        if prev.is_some() {
+            let end = root.text_range().end();
+            let (gap, old_text) = match &whitespace {
+                Some(ws) => (ws.text_range(), ws.text().as_str()),
+                None => (TextRange::offset_len(end, 0.into()), ""),
+            };
+            self.replace(gap, old_text, "\n".to_string());
+        }
+    }
+
+    fn replace(&mut self, range: TextRange, old_text: &str, new_text: String) {
+        if old_text != new_text && self.range.intersection(&range).is_some() {
+            self.edit.replace(range, new_text);
+        }
+    }
+
+    /// Computes the indentation of a token at the start of a line: one level
+    /// deeper than the line which opens the innermost enclosing block or list,
+    /// plus one for the lines continuing an expression or an item.
+    fn indent_level(&self, token: &SyntaxToken) -> usize {
+        let mut starts_node = token.kind() == COMMENT;
+        let mut child: SyntaxElement = token.clone().into();
+        for node in token.parent().ancestors() {
+            if node.kind() == ATTR && node.first_token().as_ref() == Some(token) {
+                starts_node = true;
+            }
+            if is_indenting(node.kind()) {
+                let first = node.first_token();
+                let last = node.last_token();
+                if first.as_ref() == Some(token) {
+                    starts_node = true;
+                } else if last.as_ref() == Some(token) && is_closing(token) {
+                    return first.map_or(0, |it| self.line_level(&it));
+                } else {
+                    let level = first.map_or(0, |it| self.line_level(&it) + 1);
+                    return level + is_continuation(&child, token, starts_node) as usize;
+                }
+            }
+            if node.parent().is_none() {
+                return is_continuation(&child, token, starts_node) as usize;
+            }
+            child = node.into();
+        }
+        0
+    }
+
+    /// Returns the (new) indentation level of the line containing `token`.
+    fn line_level(&self, token: &SyntaxToken) -> usize {
+        let mut line_start = token.clone();
+        while let Some(prev) = line_start.prev_token() {
+            if prev.kind() == WHITESPACE && prev.text().contains('\n') {
+                break;
+            }
+            line_start = prev;
+        }
+        self.levels.get(&line_start.text_range().start()).copied().unwrap_or(0)
+    }
+}
+
+fn is_continuation(child: &SyntaxElement, token: &SyntaxToken, starts_node: bool) -> bool {
+    if starts_node || token.kind() == WHERE_KW {
+        return false;
+    }
+    let child = match child {
+        NodeOrToken::Node(it) => it,
+        NodeOrToken::Token(_) => return false,
+    };
+    // Attributes and comments attached to an item don't start it.
+    let first = child
+        .children_with_tokens()
+        .find(|it| match it.kind() {
+            ATTR | COMMENT | WHITESPACE => false,
+            _ => true,
+        })
+        .and_then(|it| match it {
+            NodeOrToken::Node(it) => it.first_token(),
+            NodeOrToken::Token(it) => Some(it),
+        });
+    first.as_ref() != Some(token)
+}
+
+fn is_indenting(kind: SyntaxKind) -> bool {
+    match kind {
+        BLOCK
+        | ITEM_LIST
+        | EXTERN_ITEM_LIST
+        | MATCH_ARM_LIST
+        | RECORD_FIELD_DEF_LIST
+        | TUPLE_FIELD_DEF_LIST
+        | ENUM_VARIANT_LIST
+        | RECORD_FIELD_LIST
+        | RECORD_FIELD_PAT_LIST
+        | USE_TREE_LIST
+        | ARG_LIST
+        | PARAM_LIST
+        | TUPLE_EXPR
+        | ARRAY_EXPR
+        | PAREN_EXPR
+        | TUPLE_PAT
+        | TUPLE_TYPE
+        | TOKEN_TREE
+        | WHERE_CLAUSE => true,
+        _ => false,
+    }
+}
+
+fn is_opening(token: &SyntaxToken) -> bool {
+    match token.kind() {
+        T!['{'] | T!['('] | T!['['] => true,
+        _ => false,
+    }
+}
+
+fn is_closing(token: &SyntaxToken) -> bool {
+    match token.kind() {
+        T!['}'] | T![')'] | T![']'] => true,
+        _ => false,
+    }
+}
+
+/// Returns the whitespace between two tokens on the same line, or `None` to
+/// keep the existing one.
+fn spacing(left: &SyntaxToken, right: &SyntaxToken) -> Option<&'static str> {
+    if left.kind() == COMMENT || right.kind() == COMMENT {
+        return None;
+    }
+    // Macro arguments and broken code can't be understood token by token.
+    let is_opaque = |token: &SyntaxToken| {
+        token.parent().ancestors().any(|it| it.kind() == TOKEN_TREE || it.kind() == ERROR)
+    };
+    if is_opaque(left) || is_opaque(right) {
+        return None;
+    }
+
+    let parent_kind = |token: &SyntaxToken| token.parent().kind();
+    let res = match (left.kind(), right.kind()) {
+        (_, T![,]) | (_, T![;]) => "",
+        (T!['('], _) | (T!['['], _) | (_, T![')']) | (_, T![']']) => "",
+        (T![,], _) | (T![;], _) => " ",
+        (T![.], _) if is_field_access(left) => "",
+        (_, T![.]) if is_field_access(right) => "",
+        (_, T!['(']) if parent_kind(right) == ARG_LIST || parent_kind(right) == PARAM_LIST => "",
+        (_, T!['{']) if opens_block(right) => " ",
+        (T![:], _) if is_type_ascription(left) => " ",
+        (_, T![:]) if is_type_ascription(right) => "",
+        _ if is_spaced_operator(left) || is_spaced_operator(right) => " ",
+        _ => return None,
+    };
+    Some(res)
+}
+
+fn is_field_access(dot: &SyntaxToken) -> bool {
+    match dot.parent().kind() {
+        FIELD_EXPR | METHOD_CALL_EXPR => true,
+        _ => false,
+    }
+}
+
+fn opens_block(token: &SyntaxToken) -> bool {
+    match token.parent().kind() {
+        BLOCK
+        | ITEM_LIST
+        | MATCH_ARM_LIST
+        | RECORD_FIELD_DEF_LIST
+        | ENUM_VARIANT_LIST
+        | RECORD_FIELD_LIST
+        | RECORD_FIELD_PAT_LIST => true,
+        _ => false,
+    }
+}
+
+fn is_type_ascription(colon: &SyntaxToken) -> bool {
+    match colon.parent().kind() {
+        PARAM | SELF_PARAM | RECORD_FIELD_DEF | LET_STMT | CONST_DEF | STATIC_DEF
+        | RECORD_FIELD | RECORD_FIELD_PAT | TYPE_PARAM => true,
+        _ => false,
+    }
+}
+
+fn is_spaced_operator(token: &SyntaxToken) -> bool {
+    match (token.parent().kind(), token.kind()) {
+        (BIN_EXPR, _) => true,
+        (LET_STMT, T![=]) | (CONST_DEF, T![=]) | (STATIC_DEF, T![=]) | (TYPE_ALIAS_DEF, T![=]) => {
+            true
+        }
+        (RET_TYPE, T![->]) | (MATCH_ARM, T![=>]) => true,
+        _ => false,
+    }
+}
+
+/// If `list` is laid out vertically, and its last element is not followed by
+/// a comma, returns the offset at which the comma should be inserted.
+fn trailing_comma(list: &SyntaxNode) -> Option<TextUnit> {
+    match list.kind() {
+        RECORD_FIELD_DEF_LIST
+        | TUPLE_FIELD_DEF_LIST
+        | ENUM_VARIANT_LIST
+        | RECORD_FIELD_LIST
+        | MATCH_ARM_LIST
+        | ARG_LIST
+        | PARAM_LIST => (),
+        _ => return None,
+    }
+    let closing = list.last_token().filter(is_closing)?;
+    match closing.prev_token() {
+        Some(ws) if ws.kind() == WHITESPACE && ws.text().contains('\n') => (),
+        _ => return None,
+    }
+    if list.children().any(|it| it.kind() == ERROR) {
+        return None;
+    }
+
+    let last = list.children().filter(|it| it.kind() != ATTR).last()?;
+    let has_comma =
+        std::iter::successors(last.next_sibling_or_token(), |it| it.next_sibling_or_token())
+            .any(|it| it.kind() == T![,]);
+    if has_comma {
+        return None;
+    }
+    match list.kind() {
+        // `..base` can't be followed by a comma.
+        RECORD_FIELD_LIST if last.kind() != RECORD_FIELD => return None,
+        // Arms with a block body don't need one.
+        MATCH_ARM_LIST => {
+            let arm = ast::MatchArm::cast(last.clone())?;
+            if arm.expr()?.syntax().kind() == BLOCK_EXPR {
+                return None;
+            }
+        }
+        _ => (),
+    }
+    Some(last.text_range().end())
+}
+
+#[cfg(test)]
+mod tests {
+    use ra_syntax::SourceFile;
+    use test_utils::{assert_eq_text, extract_range};
+
+    use super::*;
+
+    fn check_format(before: &str, after: &str) {
+        let file = SourceFile::parse(before).tree();
+        let actual = format_file(&file, FormatOptions::default()).apply(before);
+        assert_eq_text!(after, &actual);
+    }
+
+    fn check_format_range(before: &str, after: &str) {
+        let (range, before) = extract_range(before);
+        let file = SourceFile::parse(&before).tree();
+        let actual = format_range(&file, range, FormatOptions::default()).apply(&before);
+        assert_eq_text!(after, &actual);
+    }
+
+    #[test]
+    fn reindents_blocks() {
+        check_format(
+            r#"
+fn main() {
+let x = foo
+.bar(|| {
+baz
+});
+  if x {
+        1
+  } else {
+2
+  }
+}
+"#,
+            r#"fn main() {
+    let x = foo
+        .bar(|| {
+            baz
+        });
+    if x {
+        1
+    } else {
+        2
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn normalizes_spacing_and_blank_lines() {
+        check_format(
+            r#"
+
+
+#[derive(Debug)]
+struct Foo{a:u32 , b : String}
+
+
+
+fn foo (x:u32)->u32{
+
+    let y=x+1 ;
+    y . to_string ( )
+
+
+}"#,
+            r#"#[derive(Debug)]
+struct Foo {a: u32, b: String}
+
+fn foo(x: u32) -> u32 {
+    let y = x + 1;
+    y.to_string()
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn adds_trailing_commas() {
+        check_format(
+            r#"
+enum E {
+    A,
+    B
+}
+fn foo(
+    a: u32,
+    b: u32
+) {
+    match a {
+        0 => (),
+        _ => b
+    }
+    match b {
+        _ => {}
+    }
+}
+"#,
+            r#"enum E {
+    A,
+    B,
+}
+fn foo(
+    a: u32,
+    b: u32,
+) {
+    match a {
+        0 => (),
+        _ => b,
+    }
+    match b {
+        _ => {}
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn formats_file_with_syntax_errors() {
+        check_format(
+            r#"fn foo() {
+let x = 1
+      bar(x)
+}
+"#,
+            r#"fn foo() {
+    let x = 1
+    bar(x)
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn formats_only_the_range() {
+        check_format_range(
+            r#"fn foo() {
+let x=1;
+<|>let y=2;
+let z=3;<|>
+}
+"#,
+            r#"fn foo() {
+let x=1;
+    let y = 2;
+    let z = 3;
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn indents_with_tabs() {
+        let before = "fn foo() {\nif x {\n        1\n}\n}\n";
+        let file = SourceFile::parse(before).tree();
+        let options = FormatOptions { tab_size: 8, insert_spaces: false };
+        let actual = format_file(&file, options).apply(before);
+        assert_eq_text!("fn foo() {\n\tif x {\n\t\t1\n\t}\n}\n", &actual);
+    }
+
+    #[test]
+    fn indents_with_tab_size() {
+        let before = "fn foo() {\nif x {\n1\n}\n}\n";
+        let file = SourceFile::parse(before).tree();
+        let options = FormatOptions { tab_size: 2, insert_spaces: true };
+        let actual = format_file(&file, options).apply(before);
+        assert_eq_text!("fn foo() {\n  if x {\n    1\n  }\n}\n", &actual);
+    }
+}
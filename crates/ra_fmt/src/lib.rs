@@ -1,4 +1,7 @@
-//! This crate provides some utilities for indenting rust code.
+//! This crate provides some utilities for indenting rust code, and a simple
+//! formatter for when `rustfmt` can't be used.
+
+mod formatter;
 
 use std::iter::successors;
 
@@ -7,14 +10,45 @@ use ra_syntax::{
     ast::{self, AstNode, AstToken},
     SmolStr, SyntaxKind,
     SyntaxKind::*,
-    SyntaxNode, SyntaxToken, T,
+    SyntaxNode, SyntaxToken, TextRange, T,
 };
 
+pub use crate::formatter::{format_file, format_range, FormatOptions};
+
 pub fn reindent(text: &str, indent: &str) -> String {
     let indent = format!("\n{}", indent);
     text.lines().intersperse(&indent).collect()
 }
 
+/// Returns the edits which replace the `old_indent` of the lines in `range` of
+/// `node` with `new_indent`. Lines which aren't indented with `old_indent` lose
+/// their indentation. Only whitespace tokens are changed, so the contents of
+/// multi-line string literals and comments are kept as they are.
+pub fn reindent_edits(
+    node: &SyntaxNode,
+    range: TextRange,
+    old_indent: &str,
+    new_indent: &str,
+) -> Vec<(TextRange, String)> {
+    node.descendants_with_tokens()
+        .filter_map(|it| it.into_token())
+        .filter(|it| it.kind() == WHITESPACE && it.text_range().is_subrange(&range))
+        .filter_map(|ws| {
+            let text = ws.text();
+            let first_newline = text.find('\n')?;
+            let last_newline = text.rfind('\n')?;
+            let indent = &text[last_newline + 1..];
+            let indent =
+                if indent.starts_with(old_indent) { &indent[old_indent.len()..] } else { "" };
+            // Blank lines lose their trailing whitespace.
+            let newlines = "\n".repeat(text.matches('\n').count());
+            let new_text =
+                format!("{}{}{}{}", &text[..first_newline], newlines, new_indent, indent);
+            Some((ws.text_range(), new_text))
+        })
+        .collect()
+}
+
 /// If the node is on the beginning of the line, calculate indent.
 pub fn leading_indent(node: &SyntaxNode) -> Option<SmolStr> {
     for token in prev_tokens(node.first_token()?) {
@@ -1,8 +1,9 @@
 //! FIXME: write short doc here
 
 pub(crate) mod src;
+mod unused_imports;
 
-use std::sync::Arc;
+use std::{iter, sync::Arc};
 
 use hir_def::{
     adt::VariantData,
@@ -12,13 +13,13 @@ use hir_def::{
     per_ns::PerNs,
     resolver::{HasResolver, TypeNs},
     type_ref::TypeRef,
-    ContainerId, HasModule, ImplId, LocalEnumVariantId, LocalImportId, LocalModuleId,
+    AttrDefId, ContainerId, HasModule, ImplId, LocalEnumVariantId, LocalImportId, LocalModuleId,
     LocalStructFieldId, Lookup, ModuleId, UnionId,
 };
 use hir_expand::{
     diagnostics::DiagnosticSink,
     name::{self, AsName},
-    AstId,
+    AstId, MacroDefKind,
 };
 use ra_db::{CrateId, Edition, FileId, FilePosition};
 use ra_syntax::{ast, AstNode, SyntaxNode};
@@ -271,6 +272,39 @@ impl Module {
         }
     }
 
+    /// Reports the unused imports, variables and `mut` qualifiers of this module.
+    /// These are lints rather than errors, so they are kept apart from
+    /// `diagnostics`.
+    pub fn unused_diagnostics(self, db: &impl HirDatabase, sink: &mut DiagnosticSink) {
+        unused_imports::add_diagnostics(db, self, sink);
+        for decl in self.declarations(db) {
+            match decl {
+                crate::ModuleDef::Function(f) => f.unused_diagnostics(db, sink),
+                crate::ModuleDef::Module(m) => {
+                    // Only add diagnostics from inline modules
+                    if let ModuleSource::Module(_) = m.definition_source(db).value {
+                        m.unused_diagnostics(db, sink)
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        for impl_block in self.impl_blocks(db) {
+            for item in impl_block.items(db) {
+                if let AssocItem::Function(f) = item {
+                    f.unused_diagnostics(db, sink);
+                }
+            }
+        }
+    }
+
+    /// Whether `lint` is allowed by an attribute of this module or of one of its
+    /// parents.
+    pub(crate) fn allows_lint(self, db: &impl DefDatabase, lint: &str) -> bool {
+        iter::successors(Some(self), |it| it.parent(db)).any(|it| it.attrs(db).allows(lint))
+    }
+
     pub fn declarations(self, db: &impl DefDatabase) -> Vec<ModuleDef> {
         let def_map = db.crate_def_map(self.id.krate);
         def_map[self.id.module_id].scope.declarations().map(ModuleDef::from).collect()
@@ -675,12 +709,32 @@ impl Function {
         }
     }
 
+    /// Whether `lint` is allowed by an attribute of this function or of an
+    /// enclosing item.
+    pub(crate) fn allows_lint(self, db: &impl DefDatabase, lint: &str) -> bool {
+        if self.attrs(db).allows(lint) {
+            return true;
+        }
+        let container_allows = match self.container(db) {
+            Some(Container::ImplBlock(it)) => db.attrs(AttrDefId::ImplId(it.id)).allows(lint),
+            Some(Container::Trait(it)) => it.attrs(db).allows(lint),
+            None => false,
+        };
+        container_allows || self.module(db).allows_lint(db, lint)
+    }
+
     pub fn diagnostics(self, db: &impl HirDatabase, sink: &mut DiagnosticSink) {
         let infer = self.infer(db);
         infer.add_diagnostics(db, self, sink);
         let mut validator = ExprValidator::new(self, infer, sink);
         validator.validate_body(db);
     }
+
+    pub fn unused_diagnostics(self, db: &impl HirDatabase, sink: &mut DiagnosticSink) {
+        let infer = self.infer(db);
+        let mut validator = ExprValidator::new(self, infer, sink);
+        validator.validate_unused_bindings(db);
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -889,7 +943,22 @@ pub struct MacroDef {
     pub(crate) id: MacroDefId,
 }
 
-impl MacroDef {}
+impl MacroDef {
+    /// Procedural macros are loaded from compiled libraries, so they have no
+    /// source to navigate to.
+    pub fn is_proc_macro(self) -> bool {
+        match self.id.kind {
+            MacroDefKind::ProcMacro(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the root module of the crate this macro is defined in.
+    pub fn crate_root(self, db: &impl DefDatabase) -> Module {
+        let krate = Crate { crate_id: self.id.krate };
+        Module::new(krate, db.crate_def_map(self.id.krate).root())
+    }
+}
 
 pub enum Container {
     Trait(Trait),
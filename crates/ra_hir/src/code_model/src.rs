@@ -104,10 +104,14 @@ impl HasSource for TypeAlias {
         self.id.lookup(db).source(db)
     }
 }
-impl HasSource for MacroDef {
-    type Ast = ast::MacroCall;
-    fn source(self, db: &impl DefDatabase) -> Source<ast::MacroCall> {
-        Source { file_id: self.id.ast_id.file_id(), value: self.id.ast_id.to_node(db) }
+/// NB: MacroDef is !HasSource, because procedural macros are loaded from
+/// compiled libraries and have no source.
+impl MacroDef {
+    /// Returns the `macro_rules!` call defining this macro. `None` for
+    /// procedural macros.
+    pub fn source(self, db: &impl DefDatabase) -> Option<Source<ast::MacroCall>> {
+        let ast_id = self.id.ast_id?;
+        Some(Source { file_id: ast_id.file_id(), value: ast_id.to_node(db) })
     }
 }
 impl HasSource for Import {
@@ -0,0 +1,372 @@
+//! Reports the imports of a module which are never referred to.
+//!
+//! Name resolution doesn't record which import a path went through, so the
+//! paths of the module and of its descendants, which are the only ones able to
+//! see a private import, are resolved again. A path uses an import if it is a
+//! single name resolving through the scope entry the import added, or if its
+//! qualifier resolves to the module of the import, like `crate::m::Foo`. Glob
+//! imports are followed, so `use super::*` in a child module can use the
+//! imports of its parent. Re-exports and globs are never reported, and neither
+//! are traits, which are used by method resolution without being named.
+
+use hir_def::{attr::Attr, LocalImportId};
+use hir_expand::{
+    diagnostics::DiagnosticSink,
+    hygiene::Hygiene,
+    name::{AsName, Name},
+    HirFileId,
+};
+use ra_syntax::{
+    ast::{self, AstNode, VisibilityOwner},
+    AstPtr, SyntaxNode, TextRange,
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    db::HirDatabase, diagnostics::UnusedImport, Either, HasSource, Module, ModuleDef, ModuleSource,
+    PathResolution, ScopeDef, Source, SourceAnalyzer,
+};
+
+/// How deep macro expansions and chains of glob imports are followed.
+const MAX_DEPTH: usize = 8;
+
+pub(super) fn add_diagnostics(db: &impl HirDatabase, module: Module, sink: &mut DiagnosticSink) {
+    if module.allows_lint(db, "unused_imports") {
+        return;
+    }
+    let mut candidates = FxHashMap::default();
+    for (name, def, import) in module.scope(db) {
+        let import = match import {
+            Some(it) => it,
+            None => continue,
+        };
+        match def {
+            ScopeDef::Unknown | ScopeDef::ModuleDef(ModuleDef::Trait(_)) => continue,
+            _ => (),
+        }
+        let id = import.id;
+        let src = import.source(db);
+        let use_tree = match src.value {
+            Either::A(it) => it,
+            Either::B(_) => continue,
+        };
+        if is_reported(&use_tree) {
+            candidates.insert(name, Candidate { id, tree: src.with_value(use_tree) });
+        }
+    }
+    if candidates.is_empty() {
+        return;
+    }
+
+    let mut uses = ImportUses {
+        db,
+        module,
+        candidates,
+        used: FxHashSet::default(),
+        scopes: FxHashMap::default(),
+    };
+    uses.visit_module(module);
+
+    let ImportUses { candidates, used, .. } = uses;
+    let mut unused = candidates
+        .into_iter()
+        .filter(|(name, _)| !used.contains(name))
+        .map(|(_, it)| it.tree)
+        .collect::<Vec<_>>();
+    unused.sort_by_key(|it| it.value.syntax().text_range().start());
+    for tree in unused {
+        sink.push(UnusedImport { file: tree.file_id, decl: AstPtr::new(&tree.value) });
+    }
+}
+
+/// Whether the import of `use_tree` may be reported: it must be a single
+/// private name, and the `use` item must not allow the lint.
+fn is_reported(use_tree: &ast::UseTree) -> bool {
+    if use_tree.use_tree_list().is_some() || use_tree.has_star() {
+        return false;
+    }
+    let is_name =
+        use_tree.path().and_then(|it| it.segment()).and_then(|it| it.name_ref()).is_some();
+    let use_item = use_tree.syntax().ancestors().find_map(ast::UseItem::cast);
+    is_name
+        && use_item.map_or(false, |it| {
+            it.visibility().is_none()
+                && !Attr::from_attrs_owner(&it, &Hygiene::new_unhygienic()).allows("unused_imports")
+        })
+}
+
+struct Candidate {
+    id: LocalImportId,
+    tree: Source<ast::UseTree>,
+}
+
+struct ImportUses<'a, DB> {
+    db: &'a DB,
+    module: Module,
+    candidates: FxHashMap<Name, Candidate>,
+    used: FxHashSet<Name>,
+    /// The imports of the scope entries of the modules looked at so far.
+    scopes: FxHashMap<Module, FxHashMap<Name, Option<LocalImportId>>>,
+}
+
+impl<DB: HirDatabase> ImportUses<'_, DB> {
+    /// Looks for uses in `module`, which is the module of the imports or one of
+    /// its descendants.
+    fn visit_module(&mut self, module: Module) {
+        let src = module.definition_source(self.db);
+        let node = match &src.value {
+            ModuleSource::SourceFile(it) => it.syntax().clone(),
+            ModuleSource::Module(it) => match it.item_list() {
+                Some(it) => it.syntax().clone(),
+                None => return,
+            },
+        };
+        self.visit_node(module, src.file_id, &node, 0);
+        for child in module.children(self.db) {
+            self.visit_module(child);
+        }
+    }
+
+    /// Looks for uses in the paths of `node` and in the expansions of its macro
+    /// calls. Inline modules are skipped, they are visited on their own.
+    fn visit_node(&mut self, module: Module, file_id: HirFileId, node: &SyntaxNode, depth: usize) {
+        let in_module = |it: &SyntaxNode| {
+            !it.ancestors()
+                .take_while(|it| it != node)
+                .any(|it| ast::Module::cast(it).map_or(false, |it| it.item_list().is_some()))
+        };
+        for it in node.descendants() {
+            if let Some(path) = ast::Path::cast(it.clone()) {
+                let is_candidate = path
+                    .segment()
+                    .and_then(|it| it.name_ref())
+                    .map_or(false, |it| self.candidates.contains_key(&it.as_name()));
+                if is_candidate && in_module(&it) {
+                    self.visit_path(module, file_id, &path);
+                }
+            } else if let Some(macro_call) = ast::MacroCall::cast(it) {
+                if depth < MAX_DEPTH && in_module(macro_call.syntax()) {
+                    let analyzer = SourceAnalyzer::new(
+                        self.db,
+                        Source::new(file_id, macro_call.syntax()),
+                        None,
+                    );
+                    let expansion =
+                        match analyzer.expand(self.db, Source::new(file_id, &macro_call)) {
+                            Some(it) => it,
+                            None => continue,
+                        };
+                    let file_id = expansion.file_id();
+                    if let Some(node) = self.db.parse_or_expand(file_id) {
+                        self.visit_node(module, file_id, &node, depth + 1);
+                    }
+                }
+            }
+        }
+    }
+
+    fn visit_path(&mut self, module: Module, file_id: HirFileId, path: &ast::Path) {
+        let name = match path.segment().and_then(|it| it.name_ref()) {
+            Some(it) => it.as_name(),
+            None => return,
+        };
+        let range = Source::new(file_id, path.syntax().text_range());
+        let analyzer = SourceAnalyzer::new(self.db, Source::new(file_id, path.syntax()), None);
+        match path.qualifier() {
+            Some(qualifier) => {
+                if let Some(PathResolution::Def(ModuleDef::Module(it))) =
+                    analyzer.resolve_path(self.db, &qualifier)
+                {
+                    self.mark_scope_entry(it, &name, range, 0);
+                }
+            }
+            None => match analyzer.resolve_path(self.db, path) {
+                Some(PathResolution::Def(_)) | Some(PathResolution::Macro(_)) => {
+                    self.mark_scope_entry(module, &name, range, 0)
+                }
+                _ => (),
+            },
+        }
+    }
+
+    /// Marks the import the scope entry `name` of `module` comes from as used,
+    /// following glob imports. The path at `range` doesn't use the import it is
+    /// part of.
+    fn mark_scope_entry(
+        &mut self,
+        module: Module,
+        name: &Name,
+        range: Source<TextRange>,
+        depth: usize,
+    ) {
+        if module.id.krate != self.module.id.krate {
+            return;
+        }
+        let import = match self.scope(module).get(name) {
+            Some(Some(it)) => *it,
+            _ => return,
+        };
+        if module == self.module {
+            if let Some(candidate) = self.candidates.get(name) {
+                let is_own_path = candidate.tree.file_id == range.file_id
+                    && range.value.is_subrange(&candidate.tree.value.syntax().text_range());
+                if candidate.id == import && !is_own_path {
+                    self.used.insert(name.clone());
+                }
+            }
+            return;
+        }
+        if depth >= MAX_DEPTH {
+            return;
+        }
+        if let Some(target) = self.glob_target(module, import) {
+            if target != module {
+                self.mark_scope_entry(target, name, range, depth + 1);
+            }
+        }
+    }
+
+    fn scope(&mut self, module: Module) -> &FxHashMap<Name, Option<LocalImportId>> {
+        let db = self.db;
+        self.scopes.entry(module).or_insert_with(|| {
+            module
+                .scope(db)
+                .into_iter()
+                .map(|(name, _, import)| (name, import.map(|it| it.id)))
+                .collect()
+        })
+    }
+
+    /// The module `use foo::*` imports from, if `import` is such a glob.
+    fn glob_target(&self, module: Module, import: LocalImportId) -> Option<Module> {
+        let src = crate::Import { parent: module, id: import }.source(self.db);
+        let use_tree = src.value.a()?;
+        if !use_tree.has_star() {
+            return None;
+        }
+        let path = use_tree.path()?;
+        let analyzer = SourceAnalyzer::new(self.db, src.with_value(path.syntax()), None);
+        match analyzer.resolve_path(self.db, &path)? {
+            PathResolution::Def(ModuleDef::Module(it)) => Some(it),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+    use ra_db::fixture::WithFixture;
+
+    use crate::test_db::TestDB;
+
+    #[test]
+    fn locals_shadowing_imports_are_not_uses() {
+        let diagnostics = TestDB::with_files(
+            r"
+            //- /lib.rs
+            mod foo {
+                pub fn a() {}
+                pub fn b() {}
+                pub fn c() {}
+                pub fn d() {}
+            }
+            use foo::{a, b, c, d};
+            fn test(b: i32) -> i32 {
+                let a = 1;
+                let f = |c: i32| c;
+                match Some(d) { Some(d) => { d(); } None => () }
+                a + b + f(2)
+            }
+            ",
+        )
+        .unused_diagnostics();
+
+        assert_snapshot!(diagnostics, @r###"
+        "a": unused import
+        "b": unused import
+        "c": unused import
+        "###
+        );
+    }
+
+    #[test]
+    fn fields_and_methods_are_not_uses() {
+        let diagnostics = TestDB::with_files(
+            r"
+            //- /lib.rs
+            mod foo {
+                pub fn len() {}
+                pub struct x;
+            }
+            use foo::{len, x};
+            struct S { x: i32 }
+            impl S { fn len(&self) -> i32 { self.x } }
+            fn test(s: S) -> i32 {
+                s.len() + S { x: 1 }.x
+            }
+            ",
+        )
+        .unused_diagnostics();
+
+        assert_snapshot!(diagnostics, @r###"
+        "len": unused import
+        "x": unused import
+        "###
+        );
+    }
+
+    #[test]
+    fn imports_used_through_a_qualified_path() {
+        let diagnostics = TestDB::with_files(
+            r"
+            //- /lib.rs
+            mod foo {
+                pub struct Foo;
+                pub struct Bar;
+                pub struct Baz;
+            }
+            mod m {
+                use crate::foo::{Foo, Bar, Baz};
+                pub mod child;
+                fn f() -> self::Baz { self::Baz }
+            }
+
+            //- /m/child.rs
+            fn f() -> crate::m::Foo {
+                crate::m::Foo
+            }
+            ",
+        )
+        .unused_diagnostics();
+
+        assert_snapshot!(diagnostics, @r###"
+        "Bar": unused import
+        "###
+        );
+    }
+
+    #[test]
+    fn imports_used_in_macro_expansions() {
+        let diagnostics = TestDB::with_files(
+            r"
+            //- /lib.rs
+            mod foo {
+                pub struct Foo;
+                pub struct Bar;
+            }
+            use foo::{Foo, Bar};
+            macro_rules! make_foo { () => { Foo } }
+            fn f() {
+                let _foo = make_foo!();
+            }
+            ",
+        )
+        .unused_diagnostics();
+
+        assert_snapshot!(diagnostics, @r###"
+        "Bar": unused import
+        "###
+        );
+    }
+}
@@ -4,9 +4,9 @@ use std::any::Any;
 
 use ra_syntax::{ast, AstNode, AstPtr, SyntaxNodePtr};
 
-use crate::{db::AstDatabase, HirFileId, Name, Source};
+use crate::{db::AstDatabase, EnumVariant, HirFileId, Name, Source};
 
-pub use hir_def::diagnostics::UnresolvedModule;
+pub use hir_def::diagnostics::{UnresolvedImport, UnresolvedModule, UnresolvedPath};
 pub use hir_expand::diagnostics::{AstDiagnostic, Diagnostic, DiagnosticSink};
 
 #[derive(Debug)]
@@ -85,3 +85,144 @@ impl AstDiagnostic for MissingOkInTailExpr {
         ast::Expr::cast(node).unwrap()
     }
 }
+
+#[derive(Debug)]
+pub struct TypeMismatch {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl Diagnostic for TypeMismatch {
+    fn message(&self) -> String {
+        format!("mismatched types: expected {}, found {}", self.expected, self.actual)
+    }
+    fn source(&self) -> Source<SyntaxNodePtr> {
+        Source { file_id: self.file, value: self.expr.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+impl AstDiagnostic for TypeMismatch {
+    type AST = ast::Expr;
+
+    fn ast(&self, db: &impl AstDatabase) -> Self::AST {
+        let root = db.parse_or_expand(self.file).unwrap();
+        let node = self.source().value.to_node(&root);
+        ast::Expr::cast(node).unwrap()
+    }
+}
+
+#[derive(Debug)]
+pub struct MissingMatchArms {
+    pub file: HirFileId,
+    pub match_expr: AstPtr<ast::Expr>,
+    pub arms: AstPtr<ast::MatchArmList>,
+    pub missed_variants: Vec<EnumVariant>,
+}
+
+impl Diagnostic for MissingMatchArms {
+    fn message(&self) -> String {
+        "missing match arms".to_string()
+    }
+    fn source(&self) -> Source<SyntaxNodePtr> {
+        Source { file_id: self.file, value: self.match_expr.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+impl AstDiagnostic for MissingMatchArms {
+    type AST = ast::MatchArmList;
+
+    fn ast(&self, db: &impl AstDatabase) -> Self::AST {
+        let root = db.parse_or_expand(self.file).unwrap();
+        self.arms.to_node(&root)
+    }
+}
+
+#[derive(Debug)]
+pub struct UnusedVariable {
+    pub file: HirFileId,
+    pub pat: AstPtr<ast::BindPat>,
+    pub name: Name,
+}
+
+impl Diagnostic for UnusedVariable {
+    fn message(&self) -> String {
+        format!("unused variable: `{}`", self.name)
+    }
+    fn source(&self) -> Source<SyntaxNodePtr> {
+        Source { file_id: self.file, value: self.pat.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+impl AstDiagnostic for UnusedVariable {
+    type AST = ast::BindPat;
+
+    fn ast(&self, db: &impl AstDatabase) -> Self::AST {
+        let root = db.parse_or_expand(self.file).unwrap();
+        self.pat.to_node(&root)
+    }
+}
+
+#[derive(Debug)]
+pub struct UnusedMut {
+    pub file: HirFileId,
+    pub pat: AstPtr<ast::BindPat>,
+}
+
+impl Diagnostic for UnusedMut {
+    fn message(&self) -> String {
+        "variable does not need to be mutable".to_string()
+    }
+    fn source(&self) -> Source<SyntaxNodePtr> {
+        Source { file_id: self.file, value: self.pat.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+impl AstDiagnostic for UnusedMut {
+    type AST = ast::BindPat;
+
+    fn ast(&self, db: &impl AstDatabase) -> Self::AST {
+        let root = db.parse_or_expand(self.file).unwrap();
+        self.pat.to_node(&root)
+    }
+}
+
+#[derive(Debug)]
+pub struct UnusedImport {
+    pub file: HirFileId,
+    pub decl: AstPtr<ast::UseTree>,
+}
+
+impl Diagnostic for UnusedImport {
+    fn message(&self) -> String {
+        "unused import".to_string()
+    }
+    fn source(&self) -> Source<SyntaxNodePtr> {
+        Source { file_id: self.file, value: self.decl.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+impl AstDiagnostic for UnusedImport {
+    type AST = ast::UseTree;
+
+    fn ast(&self, db: &impl AstDatabase) -> Self::AST {
+        let root = db.parse_or_expand(self.file).unwrap();
+        self.decl.to_node(&root)
+    }
+}
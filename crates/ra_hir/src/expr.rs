@@ -1,18 +1,22 @@
 //! FIXME: write short doc here
 
+mod match_check;
+mod unused;
+
 use std::sync::Arc;
 
 use hir_def::{path::known, resolver::HasResolver};
 use hir_expand::diagnostics::DiagnosticSink;
-use ra_syntax::ast;
-use ra_syntax::AstPtr;
+use ra_syntax::{ast, AstNode, AstPtr, SyntaxKind::IDENT};
 use rustc_hash::FxHashSet;
 
 use crate::{
     db::HirDatabase,
-    diagnostics::{MissingFields, MissingOkInTailExpr},
+    diagnostics::{
+        MissingFields, MissingMatchArms, MissingOkInTailExpr, UnusedMut, UnusedVariable,
+    },
     ty::{ApplicationTy, InferenceResult, Ty, TypeCtor},
-    Adt, Function, Name, Path,
+    Adt, Function, HasBody, Name, Path,
 };
 
 pub use hir_def::{
@@ -45,8 +49,14 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
         let body = self.func.body(db);
 
         for e in body.exprs() {
-            if let (id, Expr::RecordLit { path, fields, spread }) = e {
-                self.validate_record_literal(id, path, fields, *spread, db);
+            match e {
+                (id, Expr::RecordLit { path, fields, spread }) => {
+                    self.validate_record_literal(id, path, fields, *spread, db);
+                }
+                (id, Expr::Match { expr, arms }) => {
+                    self.validate_match(id, *expr, arms, &body, db);
+                }
+                _ => {}
             }
         }
 
@@ -107,6 +117,100 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
         }
     }
 
+    fn validate_match(
+        &mut self,
+        id: ExprId,
+        match_expr: ExprId,
+        arms: &[MatchArm],
+        body: &Body,
+        db: &impl HirDatabase,
+    ) {
+        let missed_variants =
+            match match_check::missing_variants(db, body, &self.infer, match_expr, arms) {
+                Some(it) => it,
+                None => return,
+            };
+        let source_map = self.func.body_source_map(db);
+
+        if let Some(source_ptr) = source_map.expr_syntax(id) {
+            if let Some(expr) = source_ptr.value.a() {
+                let root = source_ptr.file_syntax(db);
+                if let ast::Expr::MatchExpr(match_expr) = expr.to_node(&root) {
+                    if let Some(arm_list) = match_expr.match_arm_list() {
+                        self.sink.push(MissingMatchArms {
+                            file: source_ptr.file_id,
+                            match_expr: expr,
+                            arms: AstPtr::new(&arm_list),
+                            missed_variants,
+                        })
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn validate_unused_bindings(&mut self, db: &impl HirDatabase) {
+        let report_unused = !self.func.allows_lint(db, "unused_variables");
+        let report_unused_mut = !self.func.allows_lint(db, "unused_mut");
+        if !report_unused && !report_unused_mut {
+            return;
+        }
+        let body = self.func.body(db);
+        let source_map = self.func.body_source_map(db);
+
+        let mut opaque_names = FxHashSet::default();
+        for (id, expr) in body.exprs() {
+            if let Expr::Missing = expr {
+                let source_ptr = match source_map.expr_syntax(id) {
+                    Some(it) => it,
+                    None => continue,
+                };
+                let root = source_ptr.file_syntax(db);
+                let call = match source_ptr.value.a().map(|it| it.to_node(&root)) {
+                    Some(ast::Expr::MacroCall(it)) => it,
+                    _ => continue,
+                };
+                if let Some(tt) = call.token_tree() {
+                    let tokens =
+                        tt.syntax().descendants_with_tokens().filter_map(|it| it.into_token());
+                    opaque_names.extend(
+                        tokens.filter(|it| it.kind() == IDENT).map(|it| it.text().to_string()),
+                    );
+                }
+            }
+        }
+
+        let scopes = self.func.expr_scopes(db);
+        let unused = unused::unused_bindings(&body, &scopes, &opaque_names);
+        let bind_pat = |pat: PatId| {
+            let source_ptr = source_map.pat_syntax(pat)?;
+            // Bindings produced by macros are not reported, they can't be
+            // renamed by the user.
+            if source_ptr.file_id != source_ptr.file_id.original_file(db).into() {
+                return None;
+            }
+            let root = source_ptr.file_syntax(db);
+            match source_ptr.value.a()?.to_node(&root) {
+                ast::Pat::BindPat(it) => Some((source_ptr.file_id, AstPtr::new(&it))),
+                _ => None,
+            }
+        };
+        if report_unused {
+            for pat in unused.unused {
+                if let (Some((file, ptr)), Pat::Bind { name, .. }) = (bind_pat(pat), &body[pat]) {
+                    self.sink.push(UnusedVariable { file, pat: ptr, name: name.clone() });
+                }
+            }
+        }
+        if report_unused_mut {
+            for pat in unused.unused_mut {
+                if let Some((file, ptr)) = bind_pat(pat) {
+                    self.sink.push(UnusedMut { file, pat: ptr });
+                }
+            }
+        }
+    }
+
     fn validate_results_in_tail_expr(
         &mut self,
         body_id: ExprId,
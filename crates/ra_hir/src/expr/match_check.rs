@@ -0,0 +1,256 @@
+//! Exhaustiveness checking for `match` expressions.
+//!
+//! This is a simplified version of the usefulness algorithm used by rustc,
+//! described in "Warnings for pattern matching" by Luc Maranget. A pattern
+//! vector is *useful* with respect to a matrix of patterns if it matches some
+//! value which none of the rows of the matrix match. A match is exhaustive iff
+//! the wildcard pattern is not useful with respect to its arms.
+//!
+//! Only enums, structs, tuples, bools and references are understood. Other
+//! literals are assumed to never cover their type. If some pattern can't be
+//! understood at all, the match is not checked, to avoid false positives.
+
+use std::iter;
+
+use crate::{
+    db::HirDatabase,
+    expr::{Body, Expr, ExprId, Literal, MatchArm, Pat, PatId},
+    ty::{ApplicationTy, InferenceResult, Ty, TypeCtor},
+    Adt, EnumVariant, Struct, VariantDef,
+};
+
+/// Returns `None` if the match is exhaustive, or if we can't tell. Otherwise,
+/// returns the variants of the matched enum which are not fully covered by the
+/// arms. This list can be empty, for example when the missing values are
+/// inside a nested pattern of a type which is not an enum.
+pub(super) fn missing_variants(
+    db: &impl HirDatabase,
+    body: &Body,
+    infer: &InferenceResult,
+    expr: ExprId,
+    arms: &[MatchArm],
+) -> Option<Vec<EnumVariant>> {
+    let mut ty = &infer[expr];
+    while let Some((inner, _)) = ty.as_reference() {
+        ty = inner;
+    }
+    if let Ty::Unknown = ty {
+        return None;
+    }
+
+    let cx = MatchCheckCtx { db, body, infer };
+    let mut matrix = Vec::new();
+    for arm in arms {
+        // A guarded arm might not match, so it doesn't cover anything.
+        if arm.guard.is_some() {
+            continue;
+        }
+        for &pat in &arm.pats {
+            matrix.push(vec![cx.lower_pat(pat)?]);
+        }
+    }
+
+    if matrix.is_empty() && cx.is_uninhabited(ty) {
+        return None;
+    }
+    if !cx.is_useful(&matrix, &[SimplePat::Wild]) {
+        return None;
+    }
+
+    let variants: Vec<EnumVariant> = match ty.as_adt() {
+        Some((Adt::Enum(e), _)) => e
+            .variants(db)
+            .into_iter()
+            .filter(|&variant| {
+                let ctor = Constructor::Variant(variant);
+                let args = vec![SimplePat::Wild; cx.arity(ctor)];
+                cx.is_useful(&matrix, &[SimplePat::Ctor(ctor, args)])
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    Some(variants)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Constructor {
+    Variant(EnumVariant),
+    Struct(Struct),
+    Tuple(usize),
+    Bool(bool),
+    /// A value of a type with too many values to enumerate, like an integer
+    /// literal. These never cover the whole type.
+    Opaque,
+}
+
+#[derive(Debug, Clone)]
+enum SimplePat {
+    Wild,
+    Ctor(Constructor, Vec<SimplePat>),
+}
+
+type Row = Vec<SimplePat>;
+
+struct MatchCheckCtx<'a, DB> {
+    db: &'a DB,
+    body: &'a Body,
+    infer: &'a InferenceResult,
+}
+
+impl<'a, DB: HirDatabase> MatchCheckCtx<'a, DB> {
+    fn lower_pat(&self, pat: PatId) -> Option<SimplePat> {
+        let res = match &self.body[pat] {
+            Pat::Wild | Pat::Bind { subpat: None, .. } => SimplePat::Wild,
+            Pat::Bind { subpat: Some(subpat), .. } => self.lower_pat(*subpat)?,
+            Pat::Ref { pat, .. } => self.lower_pat(*pat)?,
+            Pat::Tuple(args) => {
+                SimplePat::Ctor(Constructor::Tuple(args.len()), self.lower_pats(args)?)
+            }
+            Pat::Lit(expr) => match &self.body[*expr] {
+                Expr::Literal(Literal::Bool(b)) => SimplePat::Ctor(Constructor::Bool(*b), vec![]),
+                _ => SimplePat::Ctor(Constructor::Opaque, vec![]),
+            },
+            Pat::Path(_) => self.lower_variant(pat, Vec::new())?,
+            Pat::TupleStruct { args, .. } => {
+                let args = self.lower_pats(args)?;
+                self.lower_variant(pat, args)?
+            }
+            Pat::Record { args, .. } => {
+                let variant = self.infer.variant_resolution_for_pat(pat)?;
+                let fields = variant.fields(self.db);
+                let mut subpats = vec![SimplePat::Wild; fields.len()];
+                for arg in args {
+                    let idx = fields.iter().position(|it| it.name(self.db) == arg.name)?;
+                    subpats[idx] = self.lower_pat(arg.pat)?;
+                }
+                SimplePat::Ctor(variant_constructor(variant), subpats)
+            }
+            Pat::Missing | Pat::Range { .. } | Pat::Slice { .. } => return None,
+        };
+        Some(res)
+    }
+
+    fn lower_pats(&self, pats: &[PatId]) -> Option<Vec<SimplePat>> {
+        pats.iter().map(|&pat| self.lower_pat(pat)).collect()
+    }
+
+    fn lower_variant(&self, pat: PatId, args: Vec<SimplePat>) -> Option<SimplePat> {
+        let variant = self.infer.variant_resolution_for_pat(pat)?;
+        let ctor = variant_constructor(variant);
+        if args.len() != self.arity(ctor) {
+            return None;
+        }
+        Some(SimplePat::Ctor(ctor, args))
+    }
+
+    fn arity(&self, ctor: Constructor) -> usize {
+        match ctor {
+            Constructor::Variant(it) => it.fields(self.db).len(),
+            Constructor::Struct(it) => it.fields(self.db).len(),
+            Constructor::Tuple(len) => len,
+            Constructor::Bool(_) | Constructor::Opaque => 0,
+        }
+    }
+
+    fn is_uninhabited(&self, ty: &Ty) -> bool {
+        match ty {
+            Ty::Apply(ApplicationTy { ctor: TypeCtor::Never, .. }) => true,
+            _ => match ty.as_adt() {
+                Some((Adt::Enum(e), _)) => e.variants(self.db).is_empty(),
+                _ => false,
+            },
+        }
+    }
+
+    /// Checks whether there's a value matched by `row`, but not by any of the
+    /// rows of `matrix`. All rows have the same length as `row`.
+    fn is_useful(&self, matrix: &[Row], row: &[SimplePat]) -> bool {
+        let (head, tail) = match row.split_first() {
+            Some(it) => it,
+            None => return matrix.is_empty(),
+        };
+        match head {
+            SimplePat::Ctor(ctor, args) => {
+                let matrix = self.specialize(matrix, *ctor);
+                let row: Row = args.iter().chain(tail).cloned().collect();
+                self.is_useful(&matrix, &row)
+            }
+            SimplePat::Wild => {
+                let used: Vec<Constructor> = matrix
+                    .iter()
+                    .filter_map(|row| match &row[0] {
+                        SimplePat::Ctor(ctor, _) => Some(*ctor),
+                        SimplePat::Wild => None,
+                    })
+                    .collect();
+                match self.complete_signature(&used) {
+                    Some(ctors) => ctors.into_iter().any(|ctor| {
+                        let matrix = self.specialize(matrix, ctor);
+                        let row: Row = iter::repeat(SimplePat::Wild)
+                            .take(self.arity(ctor))
+                            .chain(tail.iter().cloned())
+                            .collect();
+                        self.is_useful(&matrix, &row)
+                    }),
+                    None => {
+                        // Some constructor is not mentioned in the first
+                        // column, so only the rows starting with a wildcard
+                        // can match its values.
+                        let matrix: Vec<Row> = matrix
+                            .iter()
+                            .filter(|row| match row[0] {
+                                SimplePat::Wild => true,
+                                SimplePat::Ctor(..) => false,
+                            })
+                            .map(|row| row[1..].to_vec())
+                            .collect();
+                        self.is_useful(&matrix, tail)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Keeps the rows which match values built with `ctor`, replacing their
+    /// first column with the patterns for the constructor's fields.
+    fn specialize(&self, matrix: &[Row], ctor: Constructor) -> Vec<Row> {
+        matrix
+            .iter()
+            .filter_map(|row| {
+                let args = match &row[0] {
+                    SimplePat::Wild => vec![SimplePat::Wild; self.arity(ctor)],
+                    SimplePat::Ctor(it, args) if *it == ctor => args.clone(),
+                    SimplePat::Ctor(..) => return None,
+                };
+                Some(args.into_iter().chain(row[1..].iter().cloned()).collect())
+            })
+            .collect()
+    }
+
+    /// Returns all constructors of the type, if every one of them is in `used`.
+    fn complete_signature(&self, used: &[Constructor]) -> Option<Vec<Constructor>> {
+        let all: Vec<Constructor> = match used.first()? {
+            Constructor::Variant(it) => it
+                .parent_enum(self.db)
+                .variants(self.db)
+                .into_iter()
+                .map(Constructor::Variant)
+                .collect(),
+            Constructor::Bool(_) => vec![Constructor::Bool(true), Constructor::Bool(false)],
+            ctor @ Constructor::Struct(_) | ctor @ Constructor::Tuple(_) => vec![*ctor],
+            Constructor::Opaque => return None,
+        };
+        if all.iter().all(|ctor| used.contains(ctor)) {
+            Some(all)
+        } else {
+            None
+        }
+    }
+}
+
+fn variant_constructor(variant: VariantDef) -> Constructor {
+    match variant {
+        VariantDef::Struct(it) => Constructor::Struct(it),
+        VariantDef::EnumVariant(it) => Constructor::Variant(it),
+    }
+}
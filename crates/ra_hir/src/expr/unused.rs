@@ -0,0 +1,164 @@
+//! Finds the bindings of a body which are never used, and the `mut` bindings
+//! which are never mutated.
+//!
+//! A binding is used if a path expression resolves to it through the
+//! expression scopes. It is mutated if it is the base of an assigned place, of
+//! a `&mut` borrow, of a method call receiver or of a callee, or if it is
+//! matched by a pattern with `ref mut` bindings. Without types we can't tell
+//! whether a method takes `&mut self`, or whether a place goes through a `&mut`
+//! reference, so all of these are assumed to need the `mut`.
+//!
+//! Macro calls which failed to expand are opaque: the bindings named by an
+//! identifier in their arguments are assumed to be used and mutated.
+
+use rustc_hash::FxHashSet;
+
+use crate::{
+    expr::{
+        BinaryOp, BindingAnnotation, Body, Expr, ExprId, ExprScopes, Pat, PatId, ScopeId,
+        Statement, UnaryOp,
+    },
+    Mutability, Name,
+};
+
+#[derive(Debug, Default)]
+pub(super) struct UnusedBindings {
+    /// Bindings which are never referred to.
+    pub(super) unused: Vec<PatId>,
+    /// `mut` bindings which are referred to, but never mutated.
+    pub(super) unused_mut: Vec<PatId>,
+}
+
+pub(super) fn unused_bindings(
+    body: &Body,
+    scopes: &ExprScopes,
+    opaque_names: &FxHashSet<String>,
+) -> UnusedBindings {
+    let mut locals =
+        Locals { body, scopes, used: FxHashSet::default(), mutated: FxHashSet::default() };
+    for (id, expr) in body.exprs() {
+        match expr {
+            Expr::Path(_) => {
+                if let Some(local) = locals.resolve(id) {
+                    locals.used.insert(local);
+                }
+            }
+            Expr::BinaryOp { lhs, op: Some(BinaryOp::Assignment { .. }), .. } => {
+                locals.mutate_place(*lhs)
+            }
+            Expr::Ref { expr, mutability: Mutability::Mut } => locals.mutate_place(*expr),
+            Expr::MethodCall { receiver, .. } => locals.mutate_place(*receiver),
+            Expr::Call { callee, .. } => locals.mutate_place(*callee),
+            Expr::Match { expr, arms } => {
+                if arms.iter().flat_map(|arm| arm.pats.iter()).any(|&pat| has_ref_mut(body, pat)) {
+                    locals.mutate_place(*expr)
+                }
+            }
+            Expr::Block { statements, .. } => {
+                for statement in statements {
+                    if let Statement::Let { pat, initializer: Some(init), .. } = statement {
+                        if has_ref_mut(body, *pat) {
+                            locals.mutate_place(*init)
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut res = UnusedBindings::default();
+    for (id, pat) in body.pats() {
+        let (name, mode) = match pat {
+            Pat::Bind { name, mode, .. } => (name, *mode),
+            _ => continue,
+        };
+        let text = name.to_string();
+        if *name == Name::missing()
+            || text.starts_with('_')
+            || text == "self"
+            || opaque_names.contains(&text)
+        {
+            continue;
+        }
+        if !locals.used.iter().any(|it| locals.refers_to(it, id)) {
+            res.unused.push(id);
+        } else if mode == BindingAnnotation::Mutable
+            && !locals.mutated.iter().any(|it| locals.refers_to(it, id))
+        {
+            res.unused_mut.push(id);
+        }
+    }
+    res
+}
+
+/// What a path expression resolves to, if it may be a binding.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum Local {
+    /// The bindings called `name` in the scope which declares it. There are
+    /// several of them for `A(x) | B(x)`.
+    Scoped(ScopeId, Name),
+    /// Expressions without a scope, like match guards, may refer to any
+    /// binding with their name.
+    Unscoped(Name),
+}
+
+struct Locals<'a> {
+    body: &'a Body,
+    scopes: &'a ExprScopes,
+    used: FxHashSet<Local>,
+    mutated: FxHashSet<Local>,
+}
+
+impl Locals<'_> {
+    fn resolve(&self, expr: ExprId) -> Option<Local> {
+        let name = match &self.body[expr] {
+            Expr::Path(path) => path.as_ident()?,
+            _ => return None,
+        };
+        let scope = match self.scopes.scope_for(expr) {
+            Some(it) => it,
+            None => return Some(Local::Unscoped(name.clone())),
+        };
+        let scope = self
+            .scopes
+            .scope_chain(Some(scope))
+            .find(|&scope| self.scopes.entries(scope).iter().any(|it| it.name() == name))?;
+        Some(Local::Scoped(scope, name.clone()))
+    }
+
+    fn refers_to(&self, local: &Local, pat: PatId) -> bool {
+        match local {
+            Local::Scoped(scope, name) => {
+                self.scopes.entries(*scope).iter().any(|it| it.pat() == pat && it.name() == name)
+            }
+            Local::Unscoped(name) => match &self.body[pat] {
+                Pat::Bind { name: it, .. } => it == name,
+                _ => false,
+            },
+        }
+    }
+
+    fn mutate_place(&mut self, mut expr: ExprId) {
+        loop {
+            match &self.body[expr] {
+                Expr::Field { expr: base, .. }
+                | Expr::Index { base, .. }
+                | Expr::UnaryOp { expr: base, op: UnaryOp::Deref } => expr = *base,
+                _ => break,
+            }
+        }
+        if let Some(local) = self.resolve(expr) {
+            self.mutated.insert(local);
+        }
+    }
+}
+
+fn has_ref_mut(body: &Body, pat: PatId) -> bool {
+    let mut res = false;
+    if let Pat::Bind { mode: BindingAnnotation::RefMut, .. } = &body[pat] {
+        res = true;
+    }
+    body[pat].walk_child_pats(|child| res |= has_ref_mut(body, child));
+    res
+}
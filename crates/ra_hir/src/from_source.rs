@@ -147,7 +147,7 @@ impl FromSource for MacroDef {
 
         let ast_id = AstId::new(src.file_id, db.ast_id_map(src.file_id).ast_id(&src.value));
 
-        let id: MacroDefId = MacroDefId { krate, ast_id, kind };
+        let id: MacroDefId = MacroDefId { krate, ast_id: Some(ast_id), kind };
         Some(MacroDef { id })
     }
 }
@@ -5,7 +5,7 @@
 //!
 //! So, this modules should not be used during hir construction, it exists
 //! purely for "IDE needs".
-use std::sync::Arc;
+use std::{iter::successors, sync::Arc};
 
 use hir_def::{
     expr::{ExprId, PatId},
@@ -13,22 +13,23 @@ use hir_def::{
     resolver::{self, resolver_for_scope, HasResolver, Resolver, TypeNs, ValueNs},
     DefWithBodyId,
 };
-use hir_expand::{name::AsName, AstId, MacroCallId, MacroCallLoc, MacroFileKind, Source};
+use hir_expand::{
+    name::AsName, AstId, MacroCallId, MacroCallKind, MacroCallLoc, MacroFileKind, Source,
+};
 use ra_syntax::{
     ast::{self, AstNode},
     match_ast, AstPtr,
     SyntaxKind::*,
-    SyntaxNode, SyntaxNodePtr, SyntaxToken, TextRange, TextUnit,
+    SyntaxNode, SyntaxToken, TextRange, TextUnit,
 };
 
 use crate::{
     db::HirDatabase,
     expr::{BodySourceMap, ExprScopes, ScopeId},
-    ids::LocationCtx,
     ty::method_resolution::{self, implements_trait},
     Adt, AssocItem, Const, DefWithBody, Either, Enum, EnumVariant, FromSource, Function,
-    GenericParam, HasBody, HirFileId, Local, MacroDef, Module, Name, Path, ScopeDef, Static,
-    Struct, Trait, Ty, TypeAlias,
+    GenericParam, HasBody, HirFileId, Local, MacroDef, Name, Path, ScopeDef, Static, Struct, Trait,
+    Ty, TypeAlias,
 };
 
 fn try_get_resolver_for_node(db: &impl HirDatabase, node: Source<&SyntaxNode>) -> Option<Resolver> {
@@ -67,22 +68,31 @@ fn def_with_body_from_child_node(
     db: &impl HirDatabase,
     child: Source<&SyntaxNode>,
 ) -> Option<DefWithBody> {
-    let module_source = crate::ModuleSource::from_child_node(db, child);
-    let module = Module::from_definition(db, Source::new(child.file_id, module_source))?;
-    let ctx = LocationCtx::new(db, module.id, child.file_id);
-
-    child.value.ancestors().find_map(|node| {
+    ancestors_with_macros(db, child.map(|it| it.clone())).find_map(|node| {
         match_ast! {
-            match node {
-                ast::FnDef(def)  => { return Function::from_source(db, child.with_value(def)).map(DefWithBody::from); },
-                ast::ConstDef(def) => { return Const::from_source(db, child.with_value(def)).map(DefWithBody::from); },
-                ast::StaticDef(def) => { Some(Static { id: ctx.to_def(&def) }.into()) },
+            match (node.value) {
+                ast::FnDef(def)  => { Function::from_source(db, node.with_value(def)).map(DefWithBody::from) },
+                ast::ConstDef(def) => { Const::from_source(db, node.with_value(def)).map(DefWithBody::from) },
+                ast::StaticDef(def) => { Static::from_source(db, node.with_value(def)).map(DefWithBody::from) },
                 _ => { None },
             }
         }
     })
 }
 
+/// Returns the ancestors of `node`. Nodes in macro expansions are followed by
+/// the ancestors of the macro call, so that expressions of macro calls are
+/// found in the body containing the call.
+fn ancestors_with_macros<'a>(
+    db: &'a impl HirDatabase,
+    node: Source<SyntaxNode>,
+) -> impl Iterator<Item = Source<SyntaxNode>> + 'a {
+    successors(Some(node), move |node| match node.value.parent() {
+        Some(parent) => Some(node.with_value(parent)),
+        None => node.file_id.call_node(db),
+    })
+}
+
 /// `SourceAnalyzer` is a convenience wrapper which exposes HIR API in terms of
 /// original source files. It should not be used inside the HIR itself.
 #[derive(Debug)]
@@ -162,7 +172,7 @@ impl SourceAnalyzer {
             let scopes = def.expr_scopes(db);
             let scope = match offset {
                 None => scope_for(&scopes, &source_map, node),
-                Some(offset) => scope_for_offset(&scopes, &source_map, node.with_value(offset)),
+                Some(offset) => scope_for_offset(db, &scopes, &source_map, node.with_value(offset)),
             };
             let resolver = resolver_for_scope(db, def.into(), scope);
             SourceAnalyzer {
@@ -175,10 +185,8 @@ impl SourceAnalyzer {
             }
         } else {
             SourceAnalyzer {
-                resolver: node
-                    .value
-                    .ancestors()
-                    .find_map(|it| try_get_resolver_for_node(db, node.with_value(&it)))
+                resolver: ancestors_with_macros(db, node.map(|it| it.clone()))
+                    .find_map(|it| try_get_resolver_for_node(db, it.as_ref()))
                     .unwrap_or_default(),
                 body_owner: None,
                 body_source_map: None,
@@ -435,11 +443,12 @@ impl SourceAnalyzer {
         macro_call: Source<&ast::MacroCall>,
     ) -> Option<Expansion> {
         let def = self.resolve_macro_call(db, macro_call.value)?.id;
+        let krate = self.resolver.krate()?;
         let ast_id = AstId::new(
             macro_call.file_id,
             db.ast_id_map(macro_call.file_id).ast_id(macro_call.value),
         );
-        let macro_call_loc = MacroCallLoc { def, ast_id };
+        let macro_call_loc = MacroCallLoc { def, krate, kind: MacroCallKind::FnLike(ast_id) };
         Some(Expansion {
             macro_call_id: db.intern_macro(macro_call_loc),
             macro_file_kind: to_macro_file_kind(macro_call.value),
@@ -475,6 +484,7 @@ fn scope_for(
 }
 
 fn scope_for_offset(
+    db: &impl HirDatabase,
     scopes: &ExprScopes,
     source_map: &BodySourceMap,
     offset: Source<TextUnit>,
@@ -484,50 +494,39 @@ fn scope_for_offset(
         .iter()
         .filter_map(|(id, scope)| {
             let source = source_map.expr_syntax(*id)?;
-            // FIXME: correctly handle macro expansion
-            if source.file_id != offset.file_id {
-                return None;
-            }
-            let syntax_node_ptr =
-                source.value.either(|it| it.syntax_node_ptr(), |it| it.syntax_node_ptr());
-            Some((syntax_node_ptr, scope))
+            let ptr = source.value.either(|it| it.syntax_node_ptr(), |it| it.syntax_node_ptr());
+            let range =
+                range_in_file(db, Source::new(source.file_id, ptr.range()), offset.file_id)?;
+            Some((range, scope))
         })
         // find containing scope
-        .min_by_key(|(ptr, _scope)| {
-            (
-                !(ptr.range().start() <= offset.value && offset.value <= ptr.range().end()),
-                ptr.range().len(),
-            )
+        .min_by_key(|(range, _scope)| {
+            (!(range.start() <= offset.value && offset.value <= range.end()), range.len())
         })
-        .map(|(ptr, scope)| {
-            adjust(scopes, source_map, ptr, offset.file_id, offset.value).unwrap_or(*scope)
+        .map(|(range, scope)| {
+            adjust(db, scopes, source_map, range, offset.file_id, offset.value).unwrap_or(*scope)
         })
 }
 
 // XXX: during completion, cursor might be outside of any particular
 // expression. Try to figure out the correct scope...
 fn adjust(
+    db: &impl HirDatabase,
     scopes: &ExprScopes,
     source_map: &BodySourceMap,
-    ptr: SyntaxNodePtr,
+    r: TextRange,
     file_id: HirFileId,
     offset: TextUnit,
 ) -> Option<ScopeId> {
-    let r = ptr.range();
     let child_scopes = scopes
         .scope_by_expr()
         .iter()
         .filter_map(|(id, scope)| {
             let source = source_map.expr_syntax(*id)?;
-            // FIXME: correctly handle macro expansion
-            if source.file_id != file_id {
-                return None;
-            }
-            let syntax_node_ptr =
-                source.value.either(|it| it.syntax_node_ptr(), |it| it.syntax_node_ptr());
-            Some((syntax_node_ptr, scope))
+            let ptr = source.value.either(|it| it.syntax_node_ptr(), |it| it.syntax_node_ptr());
+            let range = range_in_file(db, Source::new(source.file_id, ptr.range()), file_id)?;
+            Some((range, scope))
         })
-        .map(|(ptr, scope)| (ptr.range(), scope))
         .filter(|(range, _)| range.start() <= offset && range.is_subrange(&r) && *range != r);
 
     child_scopes
@@ -543,6 +542,21 @@ fn adjust(
         .map(|(_ptr, scope)| *scope)
 }
 
+/// Maps `range` to `file_id`. Ranges in macro expansions are mapped to the
+/// range of the macro call in `file_id`.
+fn range_in_file(
+    db: &impl HirDatabase,
+    range: Source<TextRange>,
+    file_id: HirFileId,
+) -> Option<TextRange> {
+    let mut range = range;
+    while range.file_id != file_id {
+        let call = range.file_id.call_node(db)?;
+        range = call.with_value(call.value.text_range());
+    }
+    Some(range.value)
+}
+
 /// Given a `ast::MacroCall`, return what `MacroKindFile` it belongs to.
 /// FIXME: Not completed  
 fn to_macro_file_kind(macro_call: &ast::MacroCall) -> MacroFileKind {
@@ -5,9 +5,12 @@ use std::{panic, sync::Arc};
 use hir_def::{db::DefDatabase, ModuleId};
 use hir_expand::diagnostics::DiagnosticSink;
 use parking_lot::Mutex;
-use ra_db::{salsa, CrateId, FileId, FileLoader, FileLoaderDelegate, RelativePath, SourceDatabase};
+use ra_db::{
+    salsa, CrateId, FileId, FileLoader, FileLoaderDelegate, RelativePath, SourceDatabase,
+    SourceRootId,
+};
 
-use crate::{db, debug::HirDebugHelper};
+use crate::{db, debug::HirDebugHelper, ModuleSource};
 
 #[salsa::database(
     ra_db::SourceDatabaseExtStorage,
@@ -58,6 +61,13 @@ impl FileLoader for TestDB {
     ) -> Option<FileId> {
         FileLoaderDelegate(self).resolve_relative_path(anchor, relative_path)
     }
+    fn resolve_extern_path(
+        &self,
+        extern_root: SourceRootId,
+        relative_path: &RelativePath,
+    ) -> Option<FileId> {
+        FileLoaderDelegate(self).resolve_extern_path(extern_root, relative_path)
+    }
     fn relevant_crates(&self, file_id: FileId) -> Arc<Vec<CrateId>> {
         FileLoaderDelegate(self).relevant_crates(file_id)
     }
@@ -93,6 +103,30 @@ impl TestDB {
         }
         buf
     }
+
+    /// Renders the lints about unused code of the first crate.
+    pub fn unused_diagnostics(&self) -> String {
+        let mut buf = String::new();
+        let crate_graph = self.crate_graph();
+        for krate in crate_graph.iter().next() {
+            let crate_def_map = self.crate_def_map(krate);
+            for module_id in crate_def_map.modules() {
+                let module_id = ModuleId { krate, module_id };
+                let module = crate::Module::from(module_id);
+                // Inline modules are reported by their parent.
+                if let ModuleSource::Module(_) = module.definition_source(self).value {
+                    continue;
+                }
+                module.unused_diagnostics(
+                    self,
+                    &mut DiagnosticSink::new(|d| {
+                        buf += &format!("{:?}: {}\n", d.syntax_node(self).text(), d.message());
+                    }),
+                )
+            }
+        }
+        buf
+    }
 }
 
 impl TestDB {
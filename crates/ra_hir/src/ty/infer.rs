@@ -14,6 +14,7 @@
 //! the `ena` crate, which is extracted from rustc.
 
 use std::borrow::Cow;
+use std::iter;
 use std::mem;
 use std::ops::Index;
 use std::sync::Arc;
@@ -23,6 +24,7 @@ use rustc_hash::FxHashMap;
 
 use hir_def::{
     data::{ConstData, FunctionData},
+    lang_item::LangItemTarget,
     path::known,
     resolver::{HasResolver, Resolver, TypeNs},
     type_ref::{Mutability, TypeRef},
@@ -42,7 +44,7 @@ use super::{
 use crate::{
     code_model::TypeAlias,
     db::HirDatabase,
-    expr::{BindingAnnotation, Body, ExprId, PatId},
+    expr::{BindingAnnotation, Body, Expr, ExprId, PatId},
     ty::infer::diagnostics::InferenceDiagnostic,
     Adt, AssocItem, DefWithBody, FloatTy, Function, HasBody, IntTy, Path, StructField, Trait,
     VariantDef,
@@ -164,7 +166,7 @@ impl InferenceResult {
         owner: Function,
         sink: &mut DiagnosticSink,
     ) {
-        self.diagnostics.iter().for_each(|it| it.add_to(db, owner, sink))
+        self.diagnostics.iter().for_each(|it| it.add_to(db, owner, self, sink))
     }
 }
 
@@ -233,6 +235,16 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             let resolved = self.resolve_ty_completely(&mut tv_stack, mem::replace(ty, Ty::Unknown));
             *ty = resolved;
         }
+        for mismatch in result.type_mismatches.values_mut() {
+            mismatch.expected = self.resolve_ty_completely(
+                &mut tv_stack,
+                mem::replace(&mut mismatch.expected, Ty::Unknown),
+            );
+            mismatch.actual = self.resolve_ty_completely(
+                &mut tv_stack,
+                mem::replace(&mut mismatch.actual, Ty::Unknown),
+            );
+        }
         result
     }
 
@@ -264,6 +276,16 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         self.result.diagnostics.push(diagnostic);
     }
 
+    /// Reports the type mismatch recorded for `expr`, if there is one.
+    ///
+    /// Mismatches are recorded for every expression, but only the ones at
+    /// coercion sites the user wrote a type for are reported.
+    fn report_type_mismatch(&mut self, expr: ExprId) {
+        if self.result.type_mismatches.get(expr).is_some() {
+            self.push_diagnostic(InferenceDiagnostic::TypeMismatch { expr });
+        }
+    }
+
     fn make_ty(&mut self, type_ref: &TypeRef) -> Ty {
         let ty = Ty::from_hir(
             self.db,
@@ -574,7 +596,62 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
     }
 
     fn infer_body(&mut self) {
-        self.infer_expr(self.body.body_expr(), &Expectation::has_type(self.return_ty.clone()));
+        let body_expr = self.body.body_expr();
+        self.infer_expr(body_expr, &Expectation::has_type(self.return_ty.clone()));
+        let body = Arc::clone(&self.body);
+        // Only the tail is checked against the return type: a block without a
+        // tail can still diverge through `return`.
+        match &body[body_expr] {
+            Expr::Block { tail: Some(tail), .. } => self.report_type_mismatch(*tail),
+            Expr::Block { .. } => {}
+            _ => self.report_type_mismatch(body_expr),
+        }
+    }
+
+    /// Returns the type of the associated type `assoc_ty` for `inner_ty` and the
+    /// other trait parameters `params`, registering a projection obligation.
+    fn resolve_associated_type_with_params(
+        &mut self,
+        inner_ty: Ty,
+        assoc_ty: Option<TypeAlias>,
+        params: &[Ty],
+    ) -> Ty {
+        match assoc_ty {
+            Some(res_assoc_ty) => {
+                let ty = self.new_type_var();
+                let parameters = Substs::build_for_def(self.db, res_assoc_ty)
+                    .push(inner_ty)
+                    .fill(params.iter().cloned().chain(iter::repeat(Ty::Unknown)))
+                    .build();
+                let projection = ProjectionPredicate {
+                    ty: ty.clone(),
+                    projection_ty: ProjectionTy { associated_ty: res_assoc_ty, parameters },
+                };
+                self.obligations.push(Obligation::Projection(projection));
+                self.resolve_ty_as_possible(&mut vec![], ty)
+            }
+            None => Ty::Unknown,
+        }
+    }
+
+    fn resolve_associated_type(&mut self, inner_ty: Ty, assoc_ty: Option<TypeAlias>) -> Ty {
+        self.resolve_associated_type_with_params(inner_ty, assoc_ty, &[])
+    }
+
+    /// Resolves a trait by its `#[lang = "..."]` name, like `add` for `std::ops::Add`.
+    fn resolve_lang_trait(&self, lang_item: &str) -> Option<Trait> {
+        let krate = self.resolver.krate()?;
+        match self.db.lang_item(krate.into(), lang_item.into())? {
+            LangItemTarget::TraitId(t) => Some(t.into()),
+            _ => None,
+        }
+    }
+
+    /// Resolves the `Output` associated type of an operator trait, like
+    /// `std::ops::Add::Output` for the `add` lang item.
+    fn resolve_ops_output(&self, lang_item: &str) -> Option<TypeAlias> {
+        let trait_ = self.resolve_lang_trait(lang_item)?;
+        trait_.associated_type_by_name(self.db, &name::OUTPUT_TYPE)
     }
 
     fn resolve_into_iter_item(&self) -> Option<TypeAlias> {
@@ -718,11 +795,26 @@ impl Expectation {
 mod diagnostics {
     use hir_expand::diagnostics::DiagnosticSink;
 
-    use crate::{db::HirDatabase, diagnostics::NoSuchField, expr::ExprId, Function, HasSource};
+    use ra_syntax::{
+        ast::{self, TypeAscriptionOwner},
+        AstNode, AstPtr, SyntaxNode,
+    };
+
+    use super::{ExprOrPatId, InferenceResult};
+    use crate::{
+        db::HirDatabase,
+        diagnostics::{NoSuchField, TypeMismatch, UnresolvedPath},
+        expr::ExprId,
+        ty::{display::HirDisplay, Ty, TypeWalk},
+        Function, HasSource, Path, Source,
+    };
 
     #[derive(Debug, PartialEq, Eq, Clone)]
     pub(super) enum InferenceDiagnostic {
         NoSuchField { expr: ExprId, field: usize },
+        TypeMismatch { expr: ExprId },
+        UnresolvedPath { expr: ExprId },
+        UnresolvedTypePath { id: ExprOrPatId, path: Path },
     }
 
     impl InferenceDiagnostic {
@@ -730,6 +822,7 @@ mod diagnostics {
             &self,
             db: &impl HirDatabase,
             owner: Function,
+            infer: &InferenceResult,
             sink: &mut DiagnosticSink,
         ) {
             match self {
@@ -738,7 +831,133 @@ mod diagnostics {
                     let field = owner.body_source_map(db).field_syntax(*expr, *field);
                     sink.push(NoSuchField { file, field })
                 }
+                InferenceDiagnostic::TypeMismatch { expr } => {
+                    let mismatch = match infer.type_mismatch_for_expr(*expr) {
+                        Some(it) => it,
+                        None => return,
+                    };
+                    // Unknown types usually come from things we don't support
+                    // yet, reporting them would mostly produce false positives.
+                    if contains_unknown(&mismatch.expected) || contains_unknown(&mismatch.actual) {
+                        return;
+                    }
+                    let source = match owner.body_source_map(db).expr_syntax(*expr) {
+                        Some(it) => it,
+                        None => return,
+                    };
+                    if let Some(ptr) = source.value.a() {
+                        sink.push(TypeMismatch {
+                            file: source.file_id,
+                            expr: ptr,
+                            expected: mismatch.expected.display(db).to_string(),
+                            actual: mismatch.actual.display(db).to_string(),
+                        })
+                    }
+                }
+                InferenceDiagnostic::UnresolvedPath { expr } => {
+                    let source = match owner.body_source_map(db).expr_syntax(*expr) {
+                        Some(it) => it,
+                        None => return,
+                    };
+                    // Paths produced by macros are not reported, there's
+                    // nothing the user could fix there.
+                    if source.file_id != source.file_id.original_file(db).into() {
+                        return;
+                    }
+                    let ptr = match source.value.a() {
+                        Some(it) => it,
+                        None => return,
+                    };
+                    let root = db.parse_or_expand(source.file_id).unwrap();
+                    let expr = ptr.to_node(&root);
+                    // The base of a struct update isn't reported, like the
+                    // fields it fills in.
+                    if expr.syntax().parent().and_then(ast::RecordFieldList::cast).is_some() {
+                        return;
+                    }
+                    if is_in_block_with_items(expr.syntax()) {
+                        return;
+                    }
+                    let path = match expr {
+                        ast::Expr::PathExpr(it) => it.path(),
+                        ast::Expr::RecordLit(it) => it.path(),
+                        _ => None,
+                    };
+                    if let Some(path) = path {
+                        sink.push(UnresolvedPath { file: source.file_id, path: AstPtr::new(&path) })
+                    }
+                }
+                InferenceDiagnostic::UnresolvedTypePath { id, path } => {
+                    let type_ref = match type_ref_syntax(db, owner, *id) {
+                        Some(it) => it,
+                        None => return,
+                    };
+                    if type_ref.file_id != type_ref.file_id.original_file(db).into() {
+                        return;
+                    }
+                    let ast_path = type_ref
+                        .value
+                        .syntax()
+                        .descendants()
+                        .filter_map(ast::Path::cast)
+                        .find(|it| Path::from_ast(it.clone()).as_ref() == Some(path));
+                    if let Some(ast_path) = ast_path {
+                        if is_in_block_with_items(ast_path.syntax()) {
+                            return;
+                        }
+                        sink.push(UnresolvedPath {
+                            file: type_ref.file_id,
+                            path: AstPtr::new(&ast_path),
+                        })
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the type of the cast expression or of the let statement `id`
+    /// refers to.
+    fn type_ref_syntax(
+        db: &impl HirDatabase,
+        owner: Function,
+        id: ExprOrPatId,
+    ) -> Option<Source<ast::TypeRef>> {
+        let source_map = owner.body_source_map(db);
+        match id {
+            ExprOrPatId::ExprId(expr) => {
+                let source = source_map.expr_syntax(expr)?;
+                let root = db.parse_or_expand(source.file_id)?;
+                match source.value.a()?.to_node(&root) {
+                    ast::Expr::CastExpr(it) => Some(source.with_value(it.type_ref()?)),
+                    _ => None,
+                }
+            }
+            ExprOrPatId::PatId(pat) => {
+                let source = source_map.pat_syntax(pat)?;
+                let root = db.parse_or_expand(source.file_id)?;
+                let pat = source.value.a()?.to_node(&root);
+                let let_stmt = pat.syntax().parent().and_then(ast::LetStmt::cast)?;
+                Some(source.with_value(let_stmt.ascribed_type()?))
             }
         }
     }
+
+    /// Whether `node` is inside a block which declares items. Block-local
+    /// items are not lowered into the body or the resolver yet, so paths in
+    /// such blocks may fail to resolve even though they are fine.
+    fn is_in_block_with_items(node: &SyntaxNode) -> bool {
+        node.ancestors()
+            .filter_map(ast::Block::cast)
+            .any(|block| block.syntax().children().any(|it| ast::ModuleItem::can_cast(it.kind())))
+    }
+
+    fn contains_unknown(ty: &Ty) -> bool {
+        let mut res = false;
+        ty.walk(&mut |ty| {
+            if let Ty::Unknown = ty {
+                res = true;
+            }
+        });
+        res
+    }
 }
@@ -1,6 +1,6 @@
 //! Type inference for expressions.
 
-use std::iter::{repeat, repeat_with};
+use std::iter::{once, repeat, repeat_with};
 use std::sync::Arc;
 
 use hir_def::{
@@ -19,7 +19,7 @@ use crate::{
         Obligation, ProjectionPredicate, ProjectionTy, Substs, TraitRef, Ty, TypeCtor, TypeWalk,
         Uncertain,
     },
-    Adt, Name,
+    Adt, Crate, Name,
 };
 
 use super::{BindingMode, Expectation, InferenceContext, InferenceDiagnostic, TypeMismatch};
@@ -201,14 +201,20 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             }
             Expr::Return { expr } => {
                 if let Some(expr) = expr {
-                    self.infer_expr(*expr, &Expectation::has_type(self.return_ty.clone()));
+                    self.infer_expr_coerce(*expr, &Expectation::has_type(self.return_ty.clone()));
+                    self.report_type_mismatch(*expr);
                 }
                 Ty::simple(TypeCtor::Never)
             }
             Expr::RecordLit { path, fields, spread } => {
                 let (ty, def_id) = self.resolve_variant(path.as_ref());
-                if let Some(variant) = def_id {
-                    self.write_variant_resolution(tgt_expr.into(), variant);
+                match (def_id, path) {
+                    (Some(variant), _) => self.write_variant_resolution(tgt_expr.into(), variant),
+                    (None, Some(path)) => {
+                        let resolver = self.resolver.clone();
+                        self.report_unresolved_path(&resolver, path, tgt_expr.into())
+                    }
+                    (None, None) => {}
                 }
 
                 self.unify(&ty, &expected.ty);
@@ -229,6 +235,7 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                         .map_or(Ty::Unknown, |field| field.ty(self.db))
                         .subst(&substs);
                     self.infer_expr_coerce(field.expr, &Expectation::has_type(field_ty));
+                    self.report_type_mismatch(field.expr);
                 }
                 if let Some(expr) = spread {
                     self.infer_expr(*expr, &Expectation::has_type(ty.clone()));
@@ -262,44 +269,17 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             }
             Expr::Await { expr } => {
                 let inner_ty = self.infer_expr(*expr, &Expectation::none());
-                let ty = match self.resolve_future_future_output() {
-                    Some(future_future_output_alias) => {
-                        let ty = self.new_type_var();
-                        let projection = ProjectionPredicate {
-                            ty: ty.clone(),
-                            projection_ty: ProjectionTy {
-                                associated_ty: future_future_output_alias,
-                                parameters: Substs::single(inner_ty),
-                            },
-                        };
-                        self.obligations.push(Obligation::Projection(projection));
-                        self.resolve_ty_as_possible(&mut vec![], ty)
-                    }
-                    None => Ty::Unknown,
-                };
-                ty
+                let future_future_output = self.resolve_future_future_output();
+                self.resolve_associated_type(inner_ty, future_future_output)
             }
             Expr::Try { expr } => {
                 let inner_ty = self.infer_expr(*expr, &Expectation::none());
-                let ty = match self.resolve_ops_try_ok() {
-                    Some(ops_try_ok_alias) => {
-                        let ty = self.new_type_var();
-                        let projection = ProjectionPredicate {
-                            ty: ty.clone(),
-                            projection_ty: ProjectionTy {
-                                associated_ty: ops_try_ok_alias,
-                                parameters: Substs::single(inner_ty),
-                            },
-                        };
-                        self.obligations.push(Obligation::Projection(projection));
-                        self.resolve_ty_as_possible(&mut vec![], ty)
-                    }
-                    None => Ty::Unknown,
-                };
-                ty
+                let ops_try_ok = self.resolve_ops_try_ok();
+                self.resolve_associated_type(inner_ty, ops_try_ok)
             }
             Expr::Cast { expr, type_ref } => {
                 let _inner_ty = self.infer_expr(*expr, &Expectation::none());
+                self.report_unresolved_type_paths(type_ref, tgt_expr.into());
                 let cast_ty = self.make_ty(type_ref);
                 // FIXME check the cast...
                 cast_ty
@@ -340,35 +320,29 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                             Ty::Unknown
                         }
                     }
-                    UnaryOp::Neg => {
-                        match &inner_ty {
-                            Ty::Apply(a_ty) => match a_ty.ctor {
-                                TypeCtor::Int(Uncertain::Unknown)
-                                | TypeCtor::Int(Uncertain::Known(IntTy {
-                                    signedness: Signedness::Signed,
-                                    ..
-                                }))
-                                | TypeCtor::Float(..) => inner_ty,
-                                _ => Ty::Unknown,
-                            },
-                            Ty::Infer(InferTy::IntVar(..)) | Ty::Infer(InferTy::FloatVar(..)) => {
-                                inner_ty
-                            }
-                            // FIXME: resolve ops::Neg trait
-                            _ => Ty::Unknown,
-                        }
-                    }
-                    UnaryOp::Not => {
-                        match &inner_ty {
-                            Ty::Apply(a_ty) => match a_ty.ctor {
-                                TypeCtor::Bool | TypeCtor::Int(_) => inner_ty,
-                                _ => Ty::Unknown,
-                            },
-                            Ty::Infer(InferTy::IntVar(..)) => inner_ty,
-                            // FIXME: resolve ops::Not trait for inner_ty
-                            _ => Ty::Unknown,
+                    UnaryOp::Neg => match &inner_ty {
+                        Ty::Apply(a_ty) => match a_ty.ctor {
+                            TypeCtor::Int(Uncertain::Unknown)
+                            | TypeCtor::Int(Uncertain::Known(IntTy {
+                                signedness: Signedness::Signed,
+                                ..
+                            }))
+                            | TypeCtor::Float(..) => inner_ty,
+                            _ => self.infer_overloaded_unop("neg", inner_ty.clone()),
+                        },
+                        Ty::Infer(InferTy::IntVar(..)) | Ty::Infer(InferTy::FloatVar(..)) => {
+                            inner_ty
                         }
-                    }
+                        _ => self.infer_overloaded_unop("neg", inner_ty.clone()),
+                    },
+                    UnaryOp::Not => match &inner_ty {
+                        Ty::Apply(a_ty) => match a_ty.ctor {
+                            TypeCtor::Bool | TypeCtor::Int(_) => inner_ty,
+                            _ => self.infer_overloaded_unop("not", inner_ty.clone()),
+                        },
+                        Ty::Infer(InferTy::IntVar(..)) => inner_ty,
+                        _ => self.infer_overloaded_unop("not", inner_ty.clone()),
+                    },
                 }
             }
             Expr::BinaryOp { lhs, rhs, op } => match op {
@@ -378,21 +352,52 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                         _ => Expectation::none(),
                     };
                     let lhs_ty = self.infer_expr(*lhs, &lhs_expectation);
-                    // FIXME: find implementation of trait corresponding to operation
-                    // symbol and resolve associated `Output` type
-                    let rhs_expectation = op::binary_op_rhs_expectation(*op, lhs_ty);
+                    // Operators on primitive types are builtin, everything else goes
+                    // through the `std::ops` traits.
+                    let is_builtin = op::is_builtin_binop_lhs(&self.resolve_ty_shallow(&lhs_ty));
+                    let rhs_expectation = op::binary_op_rhs_expectation(*op, lhs_ty.clone());
                     let rhs_ty = self.infer_expr(*rhs, &Expectation::has_type(rhs_expectation));
 
-                    // FIXME: similar as above, return ty is often associated trait type
-                    op::binary_op_return_ty(*op, rhs_ty)
+                    match op::binary_op_lang_item(*op) {
+                        Some(lang_item) if !is_builtin => {
+                            self.infer_overloaded_binop(*op, lang_item, lhs_ty, rhs_ty)
+                        }
+                        _ => op::binary_op_return_ty(*op, rhs_ty),
+                    }
                 }
                 _ => Ty::Unknown,
             },
             Expr::Index { base, index } => {
-                let _base_ty = self.infer_expr(*base, &Expectation::none());
-                let _index_ty = self.infer_expr(*index, &Expectation::none());
-                // FIXME: use `std::ops::Index::Output` to figure out the real return type
-                Ty::Unknown
+                let base_ty = self.infer_expr(*base, &Expectation::none());
+                let index_ty = self.infer_expr(*index, &Expectation::none());
+                match self.resolve_lang_trait("index") {
+                    Some(index_trait) => {
+                        // The base is auto-derefed until a type implementing `Index` is found.
+                        let canonicalized = self.canonicalizer().canonicalize_ty(base_ty.clone());
+                        let krate = self.resolver.krate().map(Crate::from);
+                        let self_ty = autoderef::autoderef(
+                            self.db,
+                            &self.resolver.clone(),
+                            canonicalized.value.clone(),
+                        )
+                        .find(|derefed_ty| match krate {
+                            Some(krate) => method_resolution::implements_trait(
+                                derefed_ty,
+                                self.db,
+                                &self.resolver,
+                                krate,
+                                index_trait,
+                            ),
+                            None => false,
+                        })
+                        .map(|derefed_ty| canonicalized.decanonicalize_ty(derefed_ty.value))
+                        .unwrap_or(base_ty);
+                        let output =
+                            index_trait.associated_type_by_name(self.db, &name::OUTPUT_TYPE);
+                        self.resolve_associated_type_with_params(self_ty, output, &[index_ty])
+                    }
+                    None => Ty::Unknown,
+                }
             }
             Expr::Tuple { exprs } => {
                 let mut tys = match &expected.ty {
@@ -473,6 +478,9 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         for stmt in statements {
             match stmt {
                 Statement::Let { pat, type_ref, initializer } => {
+                    if let Some(type_ref) = type_ref {
+                        self.report_unresolved_type_paths(type_ref, (*pat).into());
+                    }
                     let decl_ty =
                         type_ref.as_ref().map(|tr| self.make_ty(tr)).unwrap_or(Ty::Unknown);
 
@@ -482,6 +490,7 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                     if let Some(expr) = initializer {
                         let actual_ty =
                             self.infer_expr_coerce(*expr, &Expectation::has_type(decl_ty.clone()));
+                        self.report_type_mismatch(*expr);
                         if decl_ty == Ty::Unknown {
                             ty = actual_ty;
                         }
@@ -511,6 +520,41 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         }
     }
 
+    fn infer_overloaded_unop(&mut self, lang_item: &str, inner_ty: Ty) -> Ty {
+        let output = self.resolve_ops_output(lang_item);
+        self.resolve_associated_type(inner_ty, output)
+    }
+
+    fn infer_overloaded_binop(
+        &mut self,
+        op: BinaryOp,
+        lang_item: &str,
+        lhs_ty: Ty,
+        rhs_ty: Ty,
+    ) -> Ty {
+        match op {
+            // Compound assignment traits, like `AddAssign`, don't have an
+            // `Output`, but we still want the rhs to be inferred from the impl.
+            BinaryOp::Assignment { .. } => {
+                if let Some(trait_) = self.resolve_lang_trait(lang_item) {
+                    let substs = Substs::build_for_def(self.db, trait_)
+                        .push(lhs_ty)
+                        .fill(once(rhs_ty).chain(repeat(Ty::Unknown)))
+                        .build();
+                    self.obligations.push(Obligation::Trait(TraitRef { trait_, substs }));
+                }
+                Ty::unit()
+            }
+            _ => match self.resolve_ops_output(lang_item) {
+                Some(output) => {
+                    self.resolve_associated_type_with_params(lhs_ty, Some(output), &[rhs_ty])
+                }
+                // Without the `std::ops` traits, fall back to guessing from the operands.
+                None => op::binary_op_return_ty(op, rhs_ty),
+            },
+        }
+    }
+
     fn infer_method_call(
         &mut self,
         tgt_expr: ExprId,
@@ -586,6 +630,7 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
 
                 let param_ty = self.normalize_associated_types_in(param_ty);
                 self.infer_expr_coerce(arg, &Expectation::has_type(param_ty.clone()));
+                self.report_type_mismatch(arg);
             }
         }
     }
@@ -3,14 +3,15 @@
 use std::iter::repeat;
 use std::sync::Arc;
 
+use hir_def::resolver::ValueNs;
 use test_utils::tested_by;
 
-use super::{BindingMode, InferenceContext};
+use super::{BindingMode, Expectation, InferenceContext};
 use crate::{
     db::HirDatabase,
-    expr::{BindingAnnotation, Pat, PatId, RecordFieldPat},
+    expr::{BindingAnnotation, Expr, Literal, Pat, PatId, RecordFieldPat},
     ty::{Mutability, Substs, Ty, TypeCtor, TypeWalk},
-    Name, Path,
+    EnumVariant, Name, Path, Struct,
 };
 
 impl<'a, D: HirDatabase> InferenceContext<'a, D> {
@@ -20,8 +21,12 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         subpats: &[PatId],
         expected: &Ty,
         default_bm: BindingMode,
+        id: PatId,
     ) -> Ty {
         let (ty, def) = self.resolve_variant(path);
+        if let Some(variant) = def {
+            self.write_variant_resolution(id.into(), variant);
+        }
 
         self.unify(&ty, expected);
 
@@ -81,8 +86,13 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             | Pat::Record { .. }
             | Pat::Range { .. }
             | Pat::Slice { .. } => true,
-            // FIXME: Path/Lit might actually evaluate to ref, but inference is unimplemented.
-            Pat::Path(..) | Pat::Lit(..) => true,
+            // A string literal pattern matches a `&str`, so it is a reference pattern.
+            Pat::Lit(expr) => match &body[*expr] {
+                Expr::Literal(Literal::String(..)) => false,
+                _ => true,
+            },
+            // FIXME: Path might actually evaluate to ref, but inference is unimplemented.
+            Pat::Path(..) => true,
             Pat::Wild | Pat::Bind { .. } | Pat::Ref { .. } | Pat::Missing => false,
         };
         if is_non_ref_pat {
@@ -135,7 +145,7 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 Ty::apply_one(TypeCtor::Ref(*mutability), subty)
             }
             Pat::TupleStruct { path: p, args: subpats } => {
-                self.infer_tuple_struct_pat(p.as_ref(), subpats, expected, default_bm)
+                self.infer_tuple_struct_pat(p.as_ref(), subpats, expected, default_bm, pat)
             }
             Pat::Record { path: p, args: fields } => {
                 self.infer_record_pat(p.as_ref(), fields, expected, default_bm, pat)
@@ -143,6 +153,15 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             Pat::Path(path) => {
                 // FIXME use correct resolver for the surrounding expression
                 let resolver = self.resolver.clone();
+                match resolver.resolve_path_in_value_ns_fully(self.db, path) {
+                    Some(ValueNs::EnumVariantId(it)) => {
+                        self.write_variant_resolution(pat.into(), EnumVariant::from(it).into())
+                    }
+                    Some(ValueNs::StructId(it)) => {
+                        self.write_variant_resolution(pat.into(), Struct::from(it).into())
+                    }
+                    _ => {}
+                }
                 self.infer_path(&resolver, &path, pat.into()).unwrap_or(Ty::Unknown)
             }
             Pat::Bind { mode, name: _, subpat } => {
@@ -168,6 +187,7 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 self.write_pat_ty(pat, bound_ty);
                 return inner_ty;
             }
+            Pat::Lit(expr) => self.infer_expr(*expr, &Expectation::has_type(expected.clone())),
             _ => Ty::Unknown,
         };
         // use a new type variable if we got Ty::Unknown here
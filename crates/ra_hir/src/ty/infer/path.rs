@@ -1,8 +1,9 @@
 //! Path expression resolution.
 
 use hir_def::{
-    path::PathSegment,
+    path::{GenericArg, PathSegment},
     resolver::{ResolveValueResult, Resolver, TypeNs, ValueNs},
+    type_ref::{TypeBound, TypeRef},
 };
 
 use crate::{
@@ -11,7 +12,7 @@ use crate::{
     AssocItem, Container, Function, Name, Path,
 };
 
-use super::{ExprOrPatId, InferenceContext, TraitRef};
+use super::{ExprOrPatId, InferenceContext, InferenceDiagnostic, TraitRef};
 
 impl<'a, D: HirDatabase> InferenceContext<'a, D> {
     pub(super) fn infer_path(
@@ -26,6 +27,48 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         Some(ty)
     }
 
+    /// Reports `path` if it doesn't resolve to anything, in any namespace.
+    ///
+    /// Paths which resolve to something of the wrong kind (like a type used as
+    /// a value) are left to other diagnostics.
+    pub(super) fn report_unresolved_path(
+        &mut self,
+        resolver: &Resolver,
+        path: &Path,
+        id: ExprOrPatId,
+    ) {
+        let expr = match id {
+            ExprOrPatId::ExprId(it) => it,
+            ExprOrPatId::PatId(_) => return,
+        };
+        if path.is_type_relative()
+            || resolver.resolve_path_in_value_ns(self.db, path).is_some()
+            || resolver.resolve_path_in_type_ns(self.db, path).is_some()
+        {
+            return;
+        }
+        self.push_diagnostic(InferenceDiagnostic::UnresolvedPath { expr });
+    }
+
+    /// Reports the paths in `type_ref` which don't resolve to a type or a
+    /// trait. `id` is the cast expression or the let pattern `type_ref` is
+    /// written for.
+    pub(super) fn report_unresolved_type_paths(&mut self, type_ref: &TypeRef, id: ExprOrPatId) {
+        let mut paths = Vec::new();
+        collect_type_ref_paths(type_ref, &mut paths);
+        for path in paths {
+            if path.is_type_relative()
+                || self.resolver.resolve_path_in_type_ns(self.db, path).is_some()
+            {
+                continue;
+            }
+            self.push_diagnostic(InferenceDiagnostic::UnresolvedTypePath {
+                id,
+                path: path.clone(),
+            });
+        }
+    }
+
     fn resolve_value_path(
         &mut self,
         resolver: &Resolver,
@@ -46,7 +89,13 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 id,
             )?
         } else {
-            let value_or_partial = resolver.resolve_path_in_value_ns(self.db, &path)?;
+            let value_or_partial = match resolver.resolve_path_in_value_ns(self.db, &path) {
+                Some(it) => it,
+                None => {
+                    self.report_unresolved_path(resolver, path, id);
+                    return None;
+                }
+            };
 
             match value_or_partial {
                 ResolveValueResult::ValueNs(it) => (it, None),
@@ -256,3 +305,36 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         }
     }
 }
+
+/// Collects the paths in `type_ref`, including the ones in generic arguments.
+fn collect_type_ref_paths<'a>(type_ref: &'a TypeRef, paths: &mut Vec<&'a Path>) {
+    match type_ref {
+        TypeRef::Path(path) => {
+            paths.push(path);
+            let args = path.segments.iter().filter_map(|it| it.args_and_bindings.as_ref());
+            for args in args {
+                for GenericArg::Type(type_ref) in &args.args {
+                    collect_type_ref_paths(type_ref, paths);
+                }
+                for (_, type_ref) in &args.bindings {
+                    collect_type_ref_paths(type_ref, paths);
+                }
+            }
+        }
+        TypeRef::Tuple(type_refs) | TypeRef::Fn(type_refs) => {
+            type_refs.iter().for_each(|it| collect_type_ref_paths(it, paths))
+        }
+        TypeRef::RawPtr(inner, _)
+        | TypeRef::Reference(inner, _)
+        | TypeRef::Array(inner)
+        | TypeRef::Slice(inner) => collect_type_ref_paths(inner, paths),
+        TypeRef::ImplTrait(bounds) | TypeRef::DynTrait(bounds) => {
+            for bound in bounds {
+                if let TypeBound::Path(path) = bound {
+                    paths.push(path);
+                }
+            }
+        }
+        TypeRef::Never | TypeRef::Placeholder | TypeRef::Error => {}
+    }
+}
@@ -2,7 +2,7 @@
 
 use super::{InferTy, Ty, TypeCtor};
 use crate::{
-    expr::{BinaryOp, CmpOp},
+    expr::{ArithOp, BinaryOp, CmpOp},
     ty::ApplicationTy,
 };
 
@@ -21,6 +21,57 @@ pub(super) fn binary_op_return_ty(op: BinaryOp, rhs_ty: Ty) -> Ty {
     }
 }
 
+/// Returns the `#[lang = "..."]` name of the `std::ops` trait used to overload
+/// `op`. Comparison and logic operators always return `bool`, so they are not
+/// considered here.
+pub(super) fn binary_op_lang_item(op: BinaryOp) -> Option<&'static str> {
+    let res = match op {
+        BinaryOp::ArithOp(op) => match op {
+            ArithOp::Add => "add",
+            ArithOp::Mul => "mul",
+            ArithOp::Sub => "sub",
+            ArithOp::Div => "div",
+            ArithOp::Rem => "rem",
+            ArithOp::Shl => "shl",
+            ArithOp::Shr => "shr",
+            ArithOp::BitXor => "bitxor",
+            ArithOp::BitOr => "bitor",
+            ArithOp::BitAnd => "bitand",
+        },
+        BinaryOp::Assignment { op: Some(op) } => match op {
+            ArithOp::Add => "add_assign",
+            ArithOp::Mul => "mul_assign",
+            ArithOp::Sub => "sub_assign",
+            ArithOp::Div => "div_assign",
+            ArithOp::Rem => "rem_assign",
+            ArithOp::Shl => "shl_assign",
+            ArithOp::Shr => "shr_assign",
+            ArithOp::BitXor => "bitxor_assign",
+            ArithOp::BitOr => "bitor_assign",
+            ArithOp::BitAnd => "bitand_assign",
+        },
+        BinaryOp::Assignment { op: None } | BinaryOp::LogicOp(_) | BinaryOp::CmpOp(_) => {
+            return None
+        }
+    };
+    Some(res)
+}
+
+/// Whether a binary operator applied to a lhs of type `lhs_ty` is builtin,
+/// rather than overloaded through a `std::ops` trait. Unknown types count as
+/// builtin, so that the result is still guessed from the rhs.
+pub(super) fn is_builtin_binop_lhs(lhs_ty: &Ty) -> bool {
+    match lhs_ty {
+        Ty::Apply(ApplicationTy { ctor, .. }) => match ctor {
+            TypeCtor::Bool | TypeCtor::Char | TypeCtor::Int(..) | TypeCtor::Float(..) => true,
+            _ => false,
+        },
+        Ty::Infer(InferTy::IntVar(..)) | Ty::Infer(InferTy::FloatVar(..)) => true,
+        Ty::Unknown => true,
+        _ => false,
+    }
+}
+
 pub(super) fn binary_op_rhs_expectation(op: BinaryOp, lhs_ty: Ty) -> Ty {
     match op {
         BinaryOp::LogicOp(..) => Ty::simple(TypeCtor::Bool),
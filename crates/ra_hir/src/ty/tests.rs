@@ -121,6 +121,202 @@ mod boxed {
     assert_eq!("(Box<i32>, Box<Box<i32>>, Box<&i32>, Box<[i32;_]>)", type_at_pos(&db, pos));
 }
 
+#[test]
+fn infer_ops_neg() {
+    let (db, pos) = TestDB::with_position(
+        r#"
+//- /main.rs crate:main deps:std
+
+struct Bar;
+struct Foo;
+
+impl std::ops::Neg for Bar {
+    type Output = Foo;
+}
+
+fn test() {
+    let a = Bar;
+    let b = -a;
+    b<|>;
+}
+
+//- /std.rs crate:std
+
+#[prelude_import] use ops::*;
+pub mod ops {
+    #[lang = "neg"]
+    pub trait Neg {
+        type Output;
+    }
+}
+"#,
+    );
+    assert_eq!("Foo", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_ops_not() {
+    let (db, pos) = TestDB::with_position(
+        r#"
+//- /main.rs crate:main deps:std
+
+struct Bar;
+struct Foo;
+
+impl std::ops::Not for Bar {
+    type Output = Foo;
+}
+
+fn test() {
+    let a = Bar;
+    let b = !a;
+    b<|>;
+}
+
+//- /std.rs crate:std
+
+#[prelude_import] use ops::*;
+pub mod ops {
+    #[lang = "not"]
+    pub trait Not {
+        type Output;
+    }
+}
+"#,
+    );
+    assert_eq!("Foo", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_ops_add() {
+    let (db, pos) = TestDB::with_position(
+        r#"
+//- /main.rs crate:main deps:std
+
+struct Vector3;
+struct Scaled;
+
+impl std::ops::Add<Vector3> for Vector3 {
+    type Output = Vector3;
+}
+
+impl std::ops::Mul<f32> for Vector3 {
+    type Output = Scaled;
+}
+
+fn test() {
+    let a = Vector3;
+    let b = a + Vector3;
+    let c = b * 2.0f32;
+    (b, c)<|>;
+}
+
+//- /std.rs crate:std
+
+#[prelude_import] use ops::*;
+pub mod ops {
+    #[lang = "add"]
+    pub trait Add<Rhs = Self> {
+        type Output;
+    }
+
+    #[lang = "mul"]
+    pub trait Mul<Rhs = Self> {
+        type Output;
+    }
+}
+"#,
+    );
+    assert_eq!("(Vector3, Scaled)", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_ops_add_with_unknown_lhs() {
+    let (db, pos) = TestDB::with_position(
+        r#"
+//- /main.rs crate:main deps:std
+
+fn test() {
+    let a = unknown_fn() + 1;
+    a<|>;
+}
+
+//- /std.rs crate:std
+
+#[prelude_import] use ops::*;
+pub mod ops {
+    #[lang = "add"]
+    pub trait Add<Rhs = Self> {
+        type Output;
+    }
+}
+"#,
+    );
+    assert_eq!("i32", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_ops_add_assign() {
+    let (db, pos) = TestDB::with_position(
+        r#"
+//- /main.rs crate:main deps:std
+
+struct Vector3;
+
+impl std::ops::AddAssign<u64> for Vector3 {}
+
+fn test() {
+    let mut a = Vector3;
+    let b = 1;
+    let c = a += b;
+    (b, c)<|>;
+}
+
+//- /std.rs crate:std
+
+#[prelude_import] use ops::*;
+pub mod ops {
+    #[lang = "add_assign"]
+    pub trait AddAssign<Rhs = Self> {}
+}
+"#,
+    );
+    assert_eq!("(u64, ())", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_ops_index() {
+    let (db, pos) = TestDB::with_position(
+        r#"
+//- /main.rs crate:main deps:std
+
+struct Matrix;
+struct Row;
+
+impl std::ops::Index<u32> for Matrix {
+    type Output = Row;
+}
+
+fn test() {
+    let matrix = Matrix;
+    let by_ref = &matrix;
+    (matrix[1u32], by_ref[2u32])<|>;
+}
+
+//- /std.rs crate:std
+
+#[prelude_import] use ops::*;
+pub mod ops {
+    #[lang = "index"]
+    pub trait Index<Idx> {
+        type Output;
+    }
+}
+"#,
+    );
+    assert_eq!("(Row, Row)", type_at_pos(&db, pos));
+}
+
 #[test]
 fn infer_adt_self() {
     let (db, pos) = TestDB::with_position(
@@ -1397,6 +1593,72 @@ fn test() {
     );
 }
 
+#[test]
+fn infer_literal_pattern() {
+    let t = type_at(
+        r#"
+//- /main.rs
+fn test(x: u8) {
+    match x {
+        1<|> => {}
+        _ => {}
+    }
+}
+"#,
+    );
+    assert_eq!(t, "u8");
+}
+
+#[test]
+fn infer_string_literal_pattern() {
+    let t = type_at(
+        r#"
+//- /main.rs
+fn test(s: &str) {
+    match s {
+        "foo"<|> => {}
+        _ => {}
+    }
+}
+"#,
+    );
+    assert_eq!(t, "&str");
+}
+
+#[test]
+fn infer_ident_pattern_naming_a_function_is_a_binding() {
+    let t = type_at(
+        r#"
+//- /main.rs
+fn f() {}
+fn test(x: u32) {
+    match x {
+        f => { f<|>; }
+    }
+}
+"#,
+    );
+    assert_eq!(t, "u32");
+}
+
+#[test]
+fn infer_ident_pattern_naming_a_unit_variant_is_a_path() {
+    let t = type_at(
+        r#"
+//- /main.rs
+enum Option<T> { None, Some(T) }
+use Option::{None, Some};
+fn test(o: Option<u32>) {
+    match o {
+        None => { None<|>; }
+        Some(_) => {}
+    }
+}
+"#,
+    );
+    assert_eq!(t, "Option<{unknown}>");
+}
+
 #[test]
 fn infer_adt_pattern() {
     assert_snapshot!(
@@ -4839,6 +5101,341 @@ fn no_such_field_diagnostics() {
     );
 }
 
+#[test]
+fn type_mismatch_diagnostics() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        struct S { foo: i32 }
+        fn takes_u32(x: u32) {}
+        fn test(flag: bool) -> u32 {
+            let x: u32 = S { foo: 92 };
+            let s = S { foo: false };
+            takes_u32(unknown_fn());
+            takes_u32(1u8);
+            if flag {
+                return S { foo: 1 };
+            }
+            true
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "S { foo: 92 }": mismatched types: expected u32, found S
+    "false": mismatched types: expected i32, found bool
+    "unknown_fn": unresolved path
+    "1u8": mismatched types: expected u32, found u8
+    "S { foo: 1 }": mismatched types: expected u32, found S
+    "true": mismatched types: expected u32, found bool
+    "###
+    );
+}
+
+#[test]
+fn unresolved_path_diagnostics() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        struct S { foo: i32 }
+        impl S {
+            fn new(foo: i32) -> S {
+                let s = Self { foo };
+                let t = Missing { foo };
+                S::new(missing_fn(foo, Self::new))
+            }
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "Missing": unresolved path
+    "missing_fn": unresolved path
+    "###
+    );
+}
+
+#[test]
+fn unresolved_type_path_diagnostics() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        struct S { foo: i32 }
+        struct Wrapper<T>(T);
+        trait Tr {}
+        fn test<T: Tr>(t: T) {
+            let a: Wrapper<Missing>;
+            let b: &dyn Tr = &t;
+            let c = 1 as missing::Int;
+            let d: Wrapper<S> = Wrapper(S { ..missing_base });
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "Missing": unresolved path
+    "missing::Int": unresolved path
+    "###
+    );
+}
+
+#[test]
+fn no_unresolved_path_diagnostics_in_blocks_with_items() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        mod m { pub fn imported() {} }
+        fn local_fn() {
+            fn inner() -> i32 { 92 }
+            let _x = inner();
+        }
+        fn local_struct() {
+            struct Local { x: i32 }
+            let s = Local { x: 92 };
+            let _t: Local = s;
+        }
+        fn local_const() {
+            const LOCAL: i32 = 92;
+            let _x = LOCAL;
+        }
+        fn local_use() {
+            use m::imported;
+            { imported() };
+        }
+        fn no_items() {
+            let _x = { missing_fn() };
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "missing_fn": unresolved path
+    "###
+    );
+}
+
+#[test]
+fn missing_match_arms_diagnostics() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        enum E { A, B(bool), C { x: (bool, bool) } }
+        fn test(e: E, r: &E, t: (bool, E), b: bool) {
+            match e { E::A => (), E::B(true) => (), E::C { x } => () }
+            match r { E::A | E::B(_) => (), E::C { x: (true, _) } => (), E::C { x: (_, false) } => () }
+            match t { (true, _) => (), (false, E::A) => (), (false, _) => () }
+            match b { true => () }
+            match e { E::A if b => (), _ => () }
+            match e { E::A if b => (), E::B(_) | E::C { x: _ } => () }
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "match e { E::A => (), E::B(true) => (), E::C { x } => () }": missing match arms
+    "match r { E::A | E::B(_) => (), E::C { x: (true, _) } => (), E::C { x: (_, false) } => () }": missing match arms
+    "match b { true => () }": missing match arms
+    "match e { E::A if b => (), E::B(_) | E::C { x: _ } => () }": missing match arms
+    "###
+    );
+}
+
+#[test]
+fn missing_match_arms_diagnostics_for_imported_items() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        enum E { A, B }
+        use E::{A, B};
+        fn test(e: E, b: bool) {
+            match e { A => (), B => () }
+            match e { A => () }
+            match b { true => (), false => () }
+            match b { false => () }
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "match e { A => () }": missing match arms
+    "match b { false => () }": missing match arms
+    "###
+    );
+}
+
+#[test]
+fn unused_variable_diagnostics_skip_unit_items_in_patterns() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        enum E { A, B }
+        use E::{A, B};
+        struct Unit;
+        const ZERO: i32 = 0;
+        fn helper() {}
+        fn test(e: E, u: Unit, n: i32) {
+            match e { A => (), B => () }
+            let Unit = u;
+            match n { ZERO => (), helper => () }
+        }
+        ",
+    )
+    .unused_diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "helper": unused variable: `helper`
+    "###
+    );
+}
+
+#[test]
+fn unused_variable_diagnostics() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        struct S { foo: i32 }
+        enum E { A(i32), B(i32) }
+        fn test(unused: i32, _ignored: i32, used: i32, e: E) {
+            let x = used;
+            let (a, b) = (1, 2);
+            let _c = a;
+            let S { foo } = S { foo: 92 };
+            let s = 1;
+            let s = s;
+            let f = |z| 1;
+            f(0);
+            match e { E::A(y) | E::B(y) if y > 0 => (), _ => () }
+            let m = 1;
+            let _n = unknown_macro!(m);
+        }
+        ",
+    )
+    .unused_diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "unused": unused variable: `unused`
+    "x": unused variable: `x`
+    "b": unused variable: `b`
+    "foo": unused variable: `foo`
+    "s": unused variable: `s`
+    "z": unused variable: `z`
+    "###
+    );
+}
+
+#[test]
+fn unused_mut_diagnostics() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        fn test() {
+            let mut a = 1;
+            let _a = a;
+            let mut b = 1;
+            b += 1;
+            let mut c = 0;
+            c.push(1);
+            let mut d = (1, 2);
+            let _r = &mut d.0;
+            let mut f = || ();
+            f();
+            let mut g = (1,);
+            let (ref mut h,) = g;
+            *h += 1;
+            let mut unused = 1;
+        }
+        ",
+    )
+    .unused_diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "mut unused": unused variable: `unused`
+    "mut a": variable does not need to be mutable
+    "###
+    );
+}
+
+#[test]
+fn unused_diagnostics_respect_allow_attributes() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        #[allow(unused_variables)]
+        fn allowed(x: i32) {
+            let mut y = 1;
+            let _z = y;
+        }
+        #[allow(unused)]
+        mod m {
+            use super::S;
+            fn f(x: i32) {
+                let mut y = 1;
+                let _z = y;
+            }
+        }
+        struct S;
+        #[allow(unused_mut)]
+        impl S {
+            fn f(&self) {
+                let mut y = 1;
+                let _z = y;
+            }
+        }
+        ",
+    )
+    .unused_diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "mut y": variable does not need to be mutable
+    "###
+    );
+}
+
+#[test]
+fn unused_import_diagnostics() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        mod foo {
+            pub struct Used;
+            pub struct Unused;
+            pub struct Helper;
+            pub trait Tr {}
+            pub fn func() {}
+        }
+        mod child;
+        use foo::{Used, Unused, Tr};
+        use foo::func as renamed;
+        use foo::Helper;
+        pub use foo::Used as Reexported;
+        #[allow(unused_imports)]
+        use foo::func;
+        fn test() -> Used {
+            renamed();
+            Used
+        }
+
+        //- /child.rs
+        use super::*;
+        fn f() -> Helper {
+            Helper
+        }
+        ",
+    )
+    .unused_diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "Unused": unused import
+    "###
+    );
+}
+
 #[test]
 fn infer_builtin_macros_line() {
     assert_snapshot!(
@@ -4895,3 +5492,73 @@ fn main() {
     "###
     );
 }
+
+#[test]
+fn infer_builtin_macros_concat() {
+    assert_snapshot!(
+        infer(r#"
+#[rustc_builtin_macro]
+macro_rules! concat {() => {}}
+
+fn main() {
+    let x = concat!("foo", 92, 'c', true);
+}
+"#),
+        @r###"
+    ![0; 12) '"foo92ctrue"': &str
+    [66; 112) '{     ...ue); }': ()
+    [76; 77) 'x': &str
+    "###
+    );
+}
+
+#[test]
+fn infer_derive_clone_simple() {
+    let (db, pos) = TestDB::with_position(
+        r#"
+//- /main.rs crate:main deps:core
+#[derive(Clone)]
+struct S;
+fn test() {
+    S.clone()<|>;
+}
+
+//- /lib.rs crate:core
+#[prelude_import]
+use clone::*;
+mod clone {
+    trait Clone {
+        fn clone(&self) -> Self;
+    }
+}
+"#,
+    );
+    assert_eq!("S", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_derive_clone_with_params() {
+    let (db, pos) = TestDB::with_position(
+        r#"
+//- /main.rs crate:main deps:core
+#[derive(Clone)]
+struct S;
+#[derive(Clone)]
+struct Wrapper<T>(T);
+struct NonClone;
+fn test() {
+    (Wrapper(S).clone(), Wrapper(NonClone).clone())<|>;
+}
+
+//- /lib.rs crate:core
+#[prelude_import]
+use clone::*;
+mod clone {
+    trait Clone {
+        fn clone(&self) -> Self;
+    }
+}
+"#,
+    );
+    assert_eq!("(Wrapper<S>, {unknown})", type_at_pos(&db, pos));
+}
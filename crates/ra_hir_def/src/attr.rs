@@ -2,17 +2,19 @@
 
 use std::{ops, sync::Arc};
 
-use hir_expand::{either::Either, hygiene::Hygiene, AstId};
+use hir_expand::{either::Either, hygiene::Hygiene, name::AsName, AstId};
 use mbe::ast_to_token_tree;
 use ra_cfg::CfgOptions;
 use ra_syntax::{
     ast::{self, AstNode, AttrsOwner},
     SmolStr,
 };
-use tt::Subtree;
+use tt::{Leaf, Subtree, TokenTree};
 
 use crate::{
-    db::DefDatabase, path::Path, AdtId, AstItemDef, AttrDefId, HasChildSource, HasSource, Lookup,
+    db::DefDatabase,
+    path::{Path, PathKind},
+    AdtId, AstItemDef, AttrDefId, HasChildSource, HasSource, Lookup,
 };
 
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
@@ -33,6 +35,12 @@ impl ops::Deref for Attrs {
 
 impl Attrs {
     pub(crate) fn attrs_query(db: &impl DefDatabase, def: AttrDefId) -> Attrs {
+        let attrs = Attrs::unexpanded(db, def);
+        let crate_graph = db.crate_graph();
+        attrs.expand_cfg_attr(crate_graph.cfg_options(def.krate(db)))
+    }
+
+    fn unexpanded(db: &impl DefDatabase, def: AttrDefId) -> Attrs {
         match def {
             AttrDefId::ModuleId(module) => {
                 let def_map = db.crate_def_map(module.krate);
@@ -65,7 +73,10 @@ impl Attrs {
             },
             AttrDefId::StaticId(it) => attrs_from_ast(it.lookup_intern(db).ast_id, db),
             AttrDefId::TraitId(it) => attrs_from_ast(it.lookup_intern(db).ast_id, db),
-            AttrDefId::MacroDefId(it) => attrs_from_ast(it.ast_id, db),
+            AttrDefId::MacroDefId(it) => match it.ast_id {
+                Some(ast_id) => attrs_from_ast(ast_id, db),
+                None => Attrs::default(),
+            },
             AttrDefId::ImplId(it) => attrs_from_ast(it.lookup_intern(db).ast_id, db),
             AttrDefId::ConstId(it) => attrs_from_loc(it.lookup(db), db),
             AttrDefId::FunctionId(it) => attrs_from_loc(it.lookup(db), db),
@@ -73,10 +84,63 @@ impl Attrs {
         }
     }
 
+    /// Replaces each `#[cfg_attr(predicate, attrs...)]` with `attrs...` if the
+    /// predicate holds for `cfg_options`, and drops it otherwise. Nested
+    /// `cfg_attr`s are expanded recursively.
+    pub fn expand_cfg_attr(&self, cfg_options: &CfgOptions) -> Attrs {
+        if !self.iter().any(|attr| attr.is_simple_atom("cfg_attr")) {
+            return self.clone();
+        }
+        let mut acc = Vec::new();
+        for attr in self.iter() {
+            attr.expand_cfg_attr_into(cfg_options, &mut acc);
+        }
+        let entries = if acc.is_empty() { None } else { Some(acc.into()) };
+        Attrs { entries }
+    }
+
     pub fn has_atom(&self, atom: &str) -> bool {
         self.iter().any(|it| it.is_simple_atom(atom))
     }
 
+    /// Whether an `#[allow(..)]` silences `lint`, either by name or through the
+    /// `unused` group for the `unused_*` lints.
+    pub fn allows(&self, lint: &str) -> bool {
+        self.iter()
+            .filter(|attr| attr.is_simple_atom("allow"))
+            .filter_map(|attr| match &attr.input {
+                Some(AttrInput::TokenTree(it)) => Some(it),
+                _ => None,
+            })
+            .flat_map(|subtree| subtree.token_trees.iter())
+            .any(|tt| match tt {
+                TokenTree::Leaf(Leaf::Ident(ident)) => {
+                    ident.text == lint || (ident.text == "unused" && lint.starts_with("unused_"))
+                }
+                _ => false,
+            })
+    }
+
+    /// Returns the paths of the derived traits, like `Clone` and `fmt::Debug`
+    /// for `#[derive(Clone, fmt::Debug)]`.
+    pub(crate) fn derives(&self) -> impl Iterator<Item = Path> + '_ {
+        self.iter()
+            .filter(|attr| attr.is_simple_atom("derive"))
+            .filter_map(|attr| match &attr.input {
+                Some(AttrInput::TokenTree(it)) => Some(it),
+                _ => None,
+            })
+            .flat_map(|subtree| {
+                subtree
+                    .token_trees
+                    .split(|tt| match tt {
+                        TokenTree::Leaf(Leaf::Punct(punct)) => punct.char == ',',
+                        _ => false,
+                    })
+                    .filter_map(path_from_tokens)
+            })
+    }
+
     pub fn find_string_value(&self, key: &str) -> Option<SmolStr> {
         self.iter().filter(|attr| attr.is_simple_atom(key)).find_map(|attr| {
             match attr.input.as_ref()? {
@@ -87,6 +151,32 @@ impl Attrs {
     }
 }
 
+/// Parses a path like `::core::fmt::Debug` out of the tokens of an attribute.
+fn path_from_tokens(tokens: &[TokenTree]) -> Option<Path> {
+    let mut segments = Vec::new();
+    let mut kind = PathKind::Plain;
+    for (idx, tt) in tokens.iter().enumerate() {
+        match tt {
+            TokenTree::Leaf(Leaf::Punct(punct)) if punct.char == ':' => {
+                if idx == 0 {
+                    kind = PathKind::Abs;
+                }
+            }
+            TokenTree::Leaf(Leaf::Ident(ident)) => match ident.text.as_str() {
+                "crate" if idx == 0 => kind = PathKind::Crate,
+                "self" if idx == 0 => kind = PathKind::Self_,
+                "super" if idx == 0 => kind = PathKind::Super,
+                _ => segments.push(ident.as_name()),
+            },
+            _ => return None,
+        }
+    }
+    if segments.is_empty() {
+        return None;
+    }
+    Some(Path::from_simple_segments(kind, segments))
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Attr {
     pub(crate) path: Path,
@@ -133,7 +223,8 @@ impl Attr {
         self.path.as_ident().map_or(false, |s| s.to_string() == name)
     }
 
-    // FIXME: handle cfg_attr :-)
+    /// Note that `cfg`s nested in `cfg_attr` are not visible here until
+    /// `Attrs::expand_cfg_attr` is applied.
     pub fn as_cfg(&self) -> Option<&Subtree> {
         if !self.is_simple_atom("cfg") {
             return None;
@@ -157,6 +248,84 @@ impl Attr {
     pub fn is_cfg_enabled(&self, cfg_options: &CfgOptions) -> Option<bool> {
         cfg_options.is_cfg_enabled(self.as_cfg()?)
     }
+
+    /// Splits `cfg_attr(predicate, attr1, attr2, ...)` into the predicate and
+    /// the attributes it guards.
+    fn as_cfg_attr(&self) -> Option<(Subtree, Vec<Attr>)> {
+        if !self.is_simple_atom("cfg_attr") {
+            return None;
+        }
+        let subtree = match &self.input {
+            Some(AttrInput::TokenTree(it)) => it,
+            _ => return None,
+        };
+        let mut parts = subtree.token_trees.split(|tt| match tt {
+            TokenTree::Leaf(Leaf::Punct(punct)) => punct.char == ',',
+            _ => false,
+        });
+        let predicate =
+            Subtree { delimiter: tt::Delimiter::None, token_trees: parts.next()?.to_vec() };
+        let attrs = parts.filter_map(Attr::from_tt).collect();
+        Some((predicate, attrs))
+    }
+
+    fn expand_cfg_attr_into(&self, cfg_options: &CfgOptions, acc: &mut Vec<Attr>) {
+        let (predicate, attrs) = match self.as_cfg_attr() {
+            Some(it) => it,
+            None => {
+                acc.push(self.clone());
+                return;
+            }
+        };
+        // Unlike plain `cfg`, a malformed predicate doesn't enable anything.
+        if cfg_options.is_cfg_enabled(&predicate) != Some(true) {
+            return;
+        }
+        for attr in attrs {
+            attr.expand_cfg_attr_into(cfg_options, acc);
+        }
+    }
+
+    /// Parses an attribute from the tokens of a `cfg_attr` argument, like
+    /// `path = "foo.rs"` or `derive(Debug)`.
+    fn from_tt(tokens: &[TokenTree]) -> Option<Attr> {
+        let is_punct = |idx: usize, c: char| match tokens.get(idx) {
+            Some(TokenTree::Leaf(Leaf::Punct(punct))) => punct.char == c,
+            _ => false,
+        };
+        let mut idx = 0;
+        let kind = if is_punct(0, ':') && is_punct(1, ':') {
+            idx = 2;
+            PathKind::Abs
+        } else {
+            PathKind::Plain
+        };
+        let mut segments = Vec::new();
+        loop {
+            match tokens.get(idx) {
+                Some(TokenTree::Leaf(Leaf::Ident(ident))) => segments.push(ident.as_name()),
+                _ => return None,
+            }
+            idx += 1;
+            if !(is_punct(idx, ':') && is_punct(idx + 1, ':')) {
+                break;
+            }
+            idx += 2;
+        }
+        let input = match &tokens[idx..] {
+            [] => None,
+            [TokenTree::Leaf(Leaf::Punct(punct)), TokenTree::Leaf(Leaf::Literal(lit))]
+                if punct.char == '=' =>
+            {
+                // FIXME: escape? raw string?
+                Some(AttrInput::Literal(lit.text.trim_matches('"').into()))
+            }
+            [TokenTree::Subtree(subtree)] => Some(AttrInput::TokenTree(subtree.clone())),
+            _ => return None,
+        };
+        let path = Path::from_simple_segments(kind, segments);
+        Some(Attr { path, input })
+    }
 }
 
 fn attrs_from_ast<D, N>(src: AstId<N>, db: &D) -> Attrs
@@ -5,8 +5,8 @@ pub mod scope;
 use std::{ops::Index, sync::Arc};
 
 use hir_expand::{
-    either::Either, hygiene::Hygiene, AstId, HirFileId, MacroCallLoc, MacroDefId, MacroFileKind,
-    Source,
+    either::Either, hygiene::Hygiene, AstId, HirFileId, MacroCallKind, MacroCallLoc, MacroDefId,
+    MacroFileKind, Source,
 };
 use ra_arena::{map::ArenaMap, Arena};
 use ra_syntax::{ast, AstNode, AstPtr};
@@ -17,7 +17,7 @@ use crate::{
     expr::{Expr, ExprId, Pat, PatId},
     nameres::CrateDefMap,
     path::Path,
-    AstItemDef, DefWithBodyId, HasModule, HasSource, Lookup, ModuleId,
+    AstItemDef, DefWithBodyId, HasModule, HasSource, Lookup, ModuleDefId, ModuleId,
 };
 
 pub struct Expander {
@@ -46,7 +46,11 @@ impl Expander {
 
         if let Some(path) = macro_call.path().and_then(|path| self.parse_path(path)) {
             if let Some(def) = self.resolve_path_as_macro(db, &path) {
-                let call_id = db.intern_macro(MacroCallLoc { def, ast_id });
+                let call_id = db.intern_macro(MacroCallLoc {
+                    def,
+                    krate: self.module.krate,
+                    kind: MacroCallKind::FnLike(ast_id),
+                });
                 let file_id = call_id.as_file(MacroFileKind::Expr);
                 if let Some(node) = db.parse_or_expand(file_id) {
                     if let Some(expr) = ast::Expr::cast(node) {
@@ -84,6 +88,10 @@ impl Expander {
     fn resolve_path_as_macro(&self, db: &impl DefDatabase, path: &Path) -> Option<MacroDefId> {
         self.crate_def_map.resolve_path(db, self.module.module_id, path).0.get_macros()
     }
+
+    fn resolve_path_as_value(&self, db: &impl DefDatabase, path: &Path) -> Option<ModuleDefId> {
+        self.crate_def_map.resolve_path(db, self.module.module_id, path).0.take_values()
+    }
 }
 
 struct Mark {
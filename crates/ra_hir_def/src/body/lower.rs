@@ -10,7 +10,7 @@ use ra_syntax::{
         self, ArgListOwner, ArrayExprKind, LiteralKind, LoopBodyOwner, NameOwner,
         TypeAscriptionOwner,
     },
-    AstNode, AstPtr,
+    AstNode, AstPtr, T,
 };
 use test_utils::tested_by;
 
@@ -25,6 +25,7 @@ use crate::{
     path::GenericArgs,
     path::Path,
     type_ref::{Mutability, TypeRef},
+    AdtId, ModuleDefId,
 };
 
 pub(super) fn lower(
@@ -418,7 +419,7 @@ where
                     LiteralKind::ByteString => Literal::ByteString(Default::default()),
                     LiteralKind::String => Literal::String(Default::default()),
                     LiteralKind::Byte => Literal::Int(Default::default(), Some(BuiltinInt::U8)),
-                    LiteralKind::Bool => Literal::Bool(Default::default()),
+                    LiteralKind::Bool => Literal::Bool(e.token().kind() == T![true]),
                     LiteralKind::Char => Literal::Char(Default::default()),
                 };
                 self.alloc_expr(Expr::Literal(lit), syntax_ptr)
@@ -487,7 +488,21 @@ where
                 let name = bp.name().map(|nr| nr.as_name()).unwrap_or_else(Name::missing);
                 let annotation = BindingAnnotation::new(bp.is_mutable(), bp.is_ref());
                 let subpat = bp.pat().map(|subpat| self.collect_pat(subpat));
-                Pat::Bind { name, mode: annotation, subpat }
+                if annotation == BindingAnnotation::Unannotated && subpat.is_none() {
+                    // `None` in `match opt { None => () }` is a path, not a
+                    // binding: a plain identifier refers to a unit variant,
+                    // a unit struct or a const if one with this name is in
+                    // scope.
+                    let path = Path::from(name.clone());
+                    match self.expander.resolve_path_as_value(self.db, &path) {
+                        Some(ModuleDefId::EnumVariantId(_))
+                        | Some(ModuleDefId::AdtId(AdtId::StructId(_)))
+                        | Some(ModuleDefId::ConstId(_)) => Pat::Path(path),
+                        _ => Pat::Bind { name, mode: annotation, subpat },
+                    }
+                } else {
+                    Pat::Bind { name, mode: annotation, subpat }
+                }
             }
             ast::Pat::TupleStructPat(p) => {
                 let path = p.path().and_then(|path| self.expander.parse_path(path));
@@ -532,11 +547,14 @@ where
 
                 Pat::Record { path, args: fields }
             }
+            ast::Pat::LiteralPat(p) => match p.literal() {
+                Some(lit) => Pat::Lit(self.collect_expr(ast::Expr::Literal(lit))),
+                None => Pat::Missing,
+            },
 
             // FIXME: implement
             ast::Pat::DotDotPat(_) => Pat::Missing,
             ast::Pat::BoxPat(_) => Pat::Missing,
-            ast::Pat::LiteralPat(_) => Pat::Missing,
             ast::Pat::SlicePat(_) | ast::Pat::RangePat(_) => Pat::Missing,
         };
         let ptr = AstPtr::new(&pat);
@@ -2,11 +2,11 @@
 
 use std::any::Any;
 
-use hir_expand::diagnostics::Diagnostic;
+use hir_expand::diagnostics::{AstDiagnostic, Diagnostic};
 use ra_db::RelativePathBuf;
-use ra_syntax::{ast, AstPtr, SyntaxNodePtr};
+use ra_syntax::{ast, AstNode, AstPtr, SyntaxNodePtr};
 
-use hir_expand::{HirFileId, Source};
+use hir_expand::{db::AstDatabase, HirFileId, Source};
 
 #[derive(Debug)]
 pub struct UnresolvedModule {
@@ -26,3 +26,59 @@ impl Diagnostic for UnresolvedModule {
         self
     }
 }
+
+#[derive(Debug)]
+pub struct UnresolvedImport {
+    pub file: HirFileId,
+    pub decl: AstPtr<ast::UseTree>,
+}
+
+impl Diagnostic for UnresolvedImport {
+    fn message(&self) -> String {
+        "unresolved import".to_string()
+    }
+    fn source(&self) -> Source<SyntaxNodePtr> {
+        Source { file_id: self.file, value: self.decl.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+impl AstDiagnostic for UnresolvedImport {
+    type AST = ast::UseTree;
+
+    fn ast(&self, db: &impl AstDatabase) -> Self::AST {
+        let root = db.parse_or_expand(self.file).unwrap();
+        let node = self.source().value.to_node(&root);
+        ast::UseTree::cast(node).unwrap()
+    }
+}
+
+#[derive(Debug)]
+pub struct UnresolvedPath {
+    pub file: HirFileId,
+    pub path: AstPtr<ast::Path>,
+}
+
+impl Diagnostic for UnresolvedPath {
+    fn message(&self) -> String {
+        "unresolved path".to_string()
+    }
+    fn source(&self) -> Source<SyntaxNodePtr> {
+        Source { file_id: self.file, value: self.path.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+impl AstDiagnostic for UnresolvedPath {
+    type AST = ast::Path;
+
+    fn ast(&self, db: &impl AstDatabase) -> Self::AST {
+        let root = db.parse_or_expand(self.file).unwrap();
+        let node = self.source().value.to_node(&root);
+        ast::Path::cast(node).unwrap()
+    }
+}
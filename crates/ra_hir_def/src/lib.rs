@@ -494,6 +494,31 @@ impl HasModule for ConstLoc {
     }
 }
 
+impl AttrDefId {
+    pub fn krate(self, db: &impl db::DefDatabase) -> CrateId {
+        match self {
+            AttrDefId::ModuleId(it) => it.krate,
+            AttrDefId::StructFieldId(it) => match it.parent {
+                VariantId::EnumVariantId(it) => it.parent.module(db).krate,
+                VariantId::StructId(it) => it.0.module(db).krate,
+            },
+            AttrDefId::AdtId(it) => match it {
+                AdtId::StructId(it) => it.0.module(db).krate,
+                AdtId::UnionId(it) => it.0.module(db).krate,
+                AdtId::EnumId(it) => it.module(db).krate,
+            },
+            AttrDefId::FunctionId(it) => it.lookup(db).module(db).krate,
+            AttrDefId::EnumVariantId(it) => it.parent.module(db).krate,
+            AttrDefId::StaticId(it) => it.module(db).krate,
+            AttrDefId::ConstId(it) => it.lookup(db).module(db).krate,
+            AttrDefId::TraitId(it) => it.module(db).krate,
+            AttrDefId::TypeAliasId(it) => it.lookup(db).module(db).krate,
+            AttrDefId::MacroDefId(it) => it.krate,
+            AttrDefId::ImplId(it) => it.module(db).krate,
+        }
+    }
+}
+
 pub trait HasSource {
     type Value;
     fn source(&self, db: &impl db::DefDatabase) -> Source<Self::Value>;
@@ -313,11 +313,16 @@ impl ModuleData {
 }
 
 mod diagnostics {
-    use hir_expand::diagnostics::DiagnosticSink;
+    use hir_expand::{diagnostics::DiagnosticSink, HirFileId};
     use ra_db::RelativePathBuf;
     use ra_syntax::{ast, AstPtr};
 
-    use crate::{db::DefDatabase, diagnostics::UnresolvedModule, nameres::LocalModuleId, AstId};
+    use crate::{
+        db::DefDatabase,
+        diagnostics::{UnresolvedImport, UnresolvedModule},
+        nameres::LocalModuleId,
+        AstId, LocalImportId,
+    };
 
     #[derive(Debug, PartialEq, Eq)]
     pub(super) enum DefDiagnostic {
@@ -326,6 +331,11 @@ mod diagnostics {
             declaration: AstId<ast::Module>,
             candidate: RelativePathBuf,
         },
+        UnresolvedImport {
+            module: LocalModuleId,
+            file_id: HirFileId,
+            import: LocalImportId,
+        },
     }
 
     impl DefDiagnostic {
@@ -347,6 +357,16 @@ mod diagnostics {
                         candidate: candidate.clone(),
                     })
                 }
+                DefDiagnostic::UnresolvedImport { module, file_id, import } => {
+                    if *module != target_module {
+                        return;
+                    }
+                    // `extern crate` items are not reported.
+                    let (_, source_map) = db.raw_items_with_source_map(*file_id);
+                    if let Some(decl) = source_map.get(*import).a() {
+                        sink.push(UnresolvedImport { file: *file_id, decl })
+                    }
+                }
             }
         }
     }
@@ -1,14 +1,16 @@
 //! FIXME: write short doc here
 
 use hir_expand::{
-    builtin_macro::find_builtin_macro,
+    builtin_derive::find_builtin_derive,
+    builtin_macro::{find_builtin_macro, included_file},
     name::{self, AsName, Name},
-    HirFileId, MacroCallId, MacroCallLoc, MacroDefId, MacroDefKind, MacroFileKind,
+    proc_macro::ProcMacroExpander,
+    HirFileId, MacroCallId, MacroCallKind, MacroCallLoc, MacroDefId, MacroDefKind, MacroFileKind,
 };
 use ra_cfg::CfgOptions;
-use ra_db::{CrateId, FileId};
+use ra_db::{CrateId, FileId, ProcMacroId, ProcMacroKind};
 use ra_syntax::{ast, SmolStr};
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use test_utils::tested_by;
 
 use crate::{
@@ -20,9 +22,9 @@ use crate::{
     },
     path::{Path, PathKind},
     per_ns::PerNs,
-    AdtId, AstId, AstItemDef, ConstLoc, ContainerId, EnumId, EnumVariantId, FunctionLoc, ImplId,
-    Intern, LocalImportId, LocalModuleId, LocationCtx, ModuleDefId, ModuleId, StaticId, StructId,
-    StructOrUnionId, TraitId, TypeAliasLoc, UnionId,
+    AdtId, AstId, AstItemDef, ConstLoc, ContainerId, EnumId, EnumVariantId, FileAstId, FunctionLoc,
+    ImplId, Intern, LocalImportId, LocalModuleId, LocationCtx, ModuleDefId, ModuleId, StaticId,
+    StructId, StructOrUnionId, TraitId, TypeAliasLoc, UnionId,
 };
 
 pub(super) fn collect_defs(db: &impl DefDatabase, mut def_map: CrateDefMap) -> CrateDefMap {
@@ -49,12 +51,31 @@ pub(super) fn collect_defs(db: &impl DefDatabase, mut def_map: CrateDefMap) -> C
 
     let cfg_options = crate_graph.cfg_options(def_map.krate);
 
+    // Attribute macros are defined by the proc-macro crates among the
+    // dependencies, and may be re-exported by the other ones.
+    let mut attribute_macro_names = FxHashSet::default();
+    let mut deps =
+        crate_graph.dependencies(def_map.krate).map(|it| it.crate_id).collect::<Vec<_>>();
+    let mut seen = FxHashSet::default();
+    while let Some(krate) = deps.pop() {
+        if !seen.insert(krate) {
+            continue;
+        }
+        let proc_macros = crate_graph.proc_macros(krate).iter();
+        attribute_macro_names
+            .extend(proc_macros.filter(|it| it.kind == ProcMacroKind::Attr).map(|it| it.as_name()));
+        deps.extend(crate_graph.dependencies(krate).map(|it| it.crate_id));
+    }
+
     let mut collector = DefCollector {
         db,
         def_map,
         glob_imports: FxHashMap::default(),
         unresolved_imports: Vec::new(),
         unexpanded_macros: Vec::new(),
+        unexpanded_derives: Vec::new(),
+        unexpanded_attribute_items: Vec::new(),
+        attribute_macro_names,
         mod_dirs: FxHashMap::default(),
         macro_stack_monitor: MacroStackMonitor::default(),
         cfg_options,
@@ -96,8 +117,15 @@ struct DefCollector<'a, DB> {
     db: &'a DB,
     def_map: CrateDefMap,
     glob_imports: FxHashMap<LocalModuleId, Vec<(LocalModuleId, LocalImportId)>>,
-    unresolved_imports: Vec<(LocalModuleId, LocalImportId, raw::ImportData)>,
+    unresolved_imports: Vec<(LocalModuleId, HirFileId, LocalImportId, raw::ImportData)>,
     unexpanded_macros: Vec<(LocalModuleId, AstId<ast::MacroCall>, Path)>,
+    /// Derives applied to items, of kind `MacroCallKind::Derive`.
+    unexpanded_derives: Vec<(LocalModuleId, Path, MacroCallKind)>,
+    /// Items which are only defined if none of their attributes turns out to
+    /// be an attribute macro.
+    unexpanded_attribute_items: Vec<AttributeMacroItem>,
+    /// The names of the attribute macros of the dependencies.
+    attribute_macro_names: FxHashSet<Name>,
     mod_dirs: FxHashMap<LocalModuleId, ModDir>,
 
     /// Some macro use `$tt:tt which mean we have to handle the macro perfectly
@@ -117,6 +145,7 @@ where
         let raw_items = self.db.raw_items(file_id.into());
         let module_id = self.def_map.root;
         self.def_map.modules[module_id].definition = Some(file_id);
+        self.define_proc_macros();
         ModCollector {
             def_collector: &mut *self,
             module_id,
@@ -130,10 +159,16 @@ where
         let mut i = 0;
         loop {
             self.db.check_canceled();
-            match (self.resolve_imports(), self.resolve_macros()) {
-                (ReachedFixedPoint::Yes, ReachedFixedPoint::Yes) => break,
-                _ => i += 1,
+            // Attributes are only resolved once imports and macros are, as
+            // most of them are inert and never resolve to anything.
+            if let (ReachedFixedPoint::Yes, ReachedFixedPoint::Yes) =
+                (self.resolve_imports(), self.resolve_macros())
+            {
+                if self.resolve_attribute_macros() == ReachedFixedPoint::Yes {
+                    break;
+                }
             }
+            i += 1;
             if i == 1000 {
                 log::error!("name resolution is stuck");
                 break;
@@ -142,9 +177,31 @@ where
 
         let unresolved_imports = std::mem::replace(&mut self.unresolved_imports, Vec::new());
         // show unresolved imports in completion, etc
-        for (module_id, import, import_data) in unresolved_imports {
+        for (module_id, file_id, import, import_data) in unresolved_imports {
+            self.def_map.diagnostics.push(DefDiagnostic::UnresolvedImport {
+                module: module_id,
+                file_id,
+                import,
+            });
             self.record_resolved_import(module_id, PerNs::none(), import, &import_data)
         }
+
+        // Derives which are not custom derives in scope name builtin ones.
+        let derives = std::mem::replace(&mut self.unexpanded_derives, Vec::new());
+        for (module_id, path, kind) in derives {
+            let krate = self.def_map.krate;
+            let macro_def = match path.segments.last() {
+                Some(segment) if is_builtin_derive_path(&path) => {
+                    find_builtin_derive(&segment.name, krate)
+                }
+                _ => None,
+            };
+            if let Some(macro_def) = macro_def {
+                let macro_call_id =
+                    self.db.intern_macro(MacroCallLoc { def: macro_def, krate, kind });
+                self.collect_macro_expansion(module_id, macro_call_id, macro_def);
+            }
+        }
     }
 
     /// Define a macro with `macro_rules`.
@@ -194,6 +251,25 @@ where
         }
     }
 
+    /// Define the procedural macros exported by a `proc-macro` crate.
+    ///
+    /// They are not backed by any item in the crate's source, so they go straight
+    /// into the root module scope, where dependent crates can import them.
+    fn define_proc_macros(&mut self) {
+        let krate = self.def_map.krate;
+        let crate_graph = self.db.crate_graph();
+        for (idx, proc_macro) in crate_graph.proc_macros(krate).iter().enumerate() {
+            let expander = ProcMacroExpander::new(krate, ProcMacroId(idx as u32), proc_macro.kind);
+            let macro_ =
+                MacroDefId { krate, ast_id: None, kind: MacroDefKind::ProcMacro(expander) };
+            self.update(
+                self.def_map.root,
+                None,
+                &[(proc_macro.as_name(), Resolution { def: PerNs::macros(macro_), import: None })],
+            );
+        }
+    }
+
     /// Define a legacy textual scoped macro in module
     ///
     /// We use a map `legacy_macros` to store all legacy textual scoped macros visable per module.
@@ -247,12 +323,19 @@ where
     fn resolve_imports(&mut self) -> ReachedFixedPoint {
         let mut imports = std::mem::replace(&mut self.unresolved_imports, Vec::new());
         let mut resolved = Vec::new();
-        imports.retain(|(module_id, import, import_data)| {
+        imports.retain(|(module_id, _file_id, import, import_data)| {
             let (def, fp) = self.resolve_import(*module_id, import_data);
-            if fp == ReachedFixedPoint::Yes {
+            // An import which resolves to nothing is kept and retried as
+            // well: the name may still be added by a macro expansion or by a
+            // glob import, and it is only reported once the fixed point is
+            // reached. This doesn't affect termination, as the loop only
+            // continues while some import gets resolved and removed from
+            // the list, or some macro gets expanded.
+            let is_resolved = fp == ReachedFixedPoint::Yes && !def.is_none();
+            if is_resolved {
                 resolved.push((*module_id, def, *import, import_data.clone()))
             }
-            fp == ReachedFixedPoint::No
+            !is_resolved
         });
         self.unresolved_imports = imports;
         // Resolves imports, filling-in module scopes
@@ -465,7 +548,11 @@ where
             );
 
             if let Some(def) = resolved_res.resolved_def.get_macros() {
-                let call_id = self.db.intern_macro(MacroCallLoc { def, ast_id: *ast_id });
+                let call_id = self.db.intern_macro(MacroCallLoc {
+                    def,
+                    krate: self.def_map.krate,
+                    kind: MacroCallKind::FnLike(*ast_id),
+                });
                 resolved.push((*module_id, call_id, def));
                 res = ReachedFixedPoint::No;
                 return false;
@@ -483,6 +570,98 @@ where
         res
     }
 
+    /// Expands the custom derives, and the first attribute macro of the items
+    /// which have one. The items whose attributes are all left unresolved are
+    /// defined as written once nothing else gets expanded.
+    fn resolve_attribute_macros(&mut self) -> ReachedFixedPoint {
+        let mut resolved = Vec::new();
+
+        let mut derives = std::mem::replace(&mut self.unexpanded_derives, Vec::new());
+        derives.retain(|(module_id, path, kind)| {
+            match self.resolve_proc_macro(*module_id, path, ProcMacroKind::CustomDerive) {
+                Some(def) => {
+                    resolved.push((*module_id, kind.clone(), def));
+                    false
+                }
+                None => true,
+            }
+        });
+        self.unexpanded_derives = derives;
+
+        let items = std::mem::replace(&mut self.unexpanded_attribute_items, Vec::new());
+        let mut unexpanded = Vec::new();
+        for item in items {
+            let attribute_macro = item.attrs.iter().find_map(|attr| {
+                if !self.is_attribute_macro_candidate(&attr.path) {
+                    return None;
+                }
+                let def =
+                    self.resolve_proc_macro(item.module_id, &attr.path, ProcMacroKind::Attr)?;
+                let name = attr.path.segments.last()?.name.clone();
+                Some((MacroCallKind::Attr(item.ast_id, name), def))
+            });
+            match attribute_macro {
+                Some((kind, def)) => resolved.push((item.module_id, kind, def)),
+                None => unexpanded.push(item),
+            }
+        }
+
+        if resolved.is_empty() {
+            if unexpanded.is_empty() {
+                return ReachedFixedPoint::Yes;
+            }
+            for item in unexpanded {
+                self.define_attribute_item(item);
+            }
+            return ReachedFixedPoint::No;
+        }
+        self.unexpanded_attribute_items = unexpanded;
+        for (module_id, kind, def) in resolved {
+            let call_id =
+                self.db.intern_macro(MacroCallLoc { def, krate: self.def_map.krate, kind });
+            self.collect_macro_expansion(module_id, call_id, def);
+        }
+        ReachedFixedPoint::No
+    }
+
+    /// Resolves `path` to a procedural macro of the given kind.
+    fn resolve_proc_macro(
+        &self,
+        module_id: LocalModuleId,
+        path: &Path,
+        kind: ProcMacroKind,
+    ) -> Option<MacroDefId> {
+        let resolved_res =
+            self.def_map.resolve_path_fp_with_macro(self.db, ResolveMode::Other, module_id, path);
+        let def = resolved_res.resolved_def.get_macros()?;
+        match def.kind {
+            MacroDefKind::ProcMacro(expander) if expander.kind() == kind => Some(def),
+            // Something else named like the attribute, like a function-like macro.
+            _ => None,
+        }
+    }
+
+    /// Whether the attribute `path` may name an attribute macro, that is if its
+    /// last segment is the name of one. Attribute macros imported under
+    /// another name are not expanded.
+    fn is_attribute_macro_candidate(&self, path: &Path) -> bool {
+        path.segments.last().map_or(false, |it| self.attribute_macro_names.contains(&it.name))
+    }
+
+    fn define_attribute_item(&mut self, item: AttributeMacroItem) {
+        let raw_items = self.db.raw_items(item.file_id);
+        let mod_dir = self.mod_dirs[&item.module_id].clone();
+        let mut collector = ModCollector {
+            def_collector: &mut *self,
+            module_id: item.module_id,
+            file_id: item.file_id,
+            raw_items: &raw_items,
+            mod_dir,
+        };
+        collector.define_def(&raw_items[item.def]);
+        collector.collect_derives(&item.attrs, &raw_items[item.def]);
+    }
+
     fn collect_macro_expansion(
         &mut self,
         module_id: LocalModuleId,
@@ -496,7 +675,11 @@ where
         self.macro_stack_monitor.increase(macro_def_id);
 
         if !self.macro_stack_monitor.is_poison(macro_def_id) {
-            let file_id: HirFileId = macro_call_id.as_file(MacroFileKind::Items);
+            // Items from `include!`d files are collected from the file itself
+            let file_id: HirFileId = match included_file(self.db, macro_call_id) {
+                Some(file_id) => file_id.into(),
+                None => macro_call_id.as_file(MacroFileKind::Items),
+            };
             let raw_items = self.db.raw_items(file_id);
             let mod_dir = self.mod_dirs[&module_id].clone();
             ModCollector {
@@ -550,10 +733,11 @@ where
         // `#[macro_use] extern crate` is hoisted to imports macros before collecting
         // any other items.
         for item in items {
-            if self.is_cfg_enabled(&item.attrs) {
+            let attrs = self.expand_cfg_attr(&item.attrs);
+            if self.is_cfg_enabled(&attrs) {
                 if let raw::RawItemKind::Import(import_id) = item.kind {
                     let import = self.raw_items[import_id].clone();
-                    if import.is_extern_crate && import.is_macro_use {
+                    if import.is_extern_crate && attrs.has_atom("macro_use") {
                         self.def_collector.import_macros_from_extern_crate(self.module_id, &import);
                     }
                 }
@@ -561,17 +745,25 @@ where
         }
 
         for item in items {
-            if self.is_cfg_enabled(&item.attrs) {
+            let attrs = self.expand_cfg_attr(&item.attrs);
+            if self.is_cfg_enabled(&attrs) {
                 match item.kind {
-                    raw::RawItemKind::Module(m) => {
-                        self.collect_module(&self.raw_items[m], &item.attrs)
+                    raw::RawItemKind::Module(m) => self.collect_module(&self.raw_items[m], &attrs),
+                    raw::RawItemKind::Import(import_id) => {
+                        let mut import = self.raw_items[import_id].clone();
+                        import.is_prelude =
+                            !import.is_extern_crate && attrs.has_atom("prelude_import");
+                        self.def_collector.unresolved_imports.push((
+                            self.module_id,
+                            self.file_id,
+                            import_id,
+                            import,
+                        ))
+                    }
+                    raw::RawItemKind::Def(def) => self.collect_def(def, attrs),
+                    raw::RawItemKind::Macro(mac) => {
+                        self.collect_macro(&self.raw_items[mac], &attrs)
                     }
-                    raw::RawItemKind::Import(import_id) => self
-                        .def_collector
-                        .unresolved_imports
-                        .push((self.module_id, import_id, self.raw_items[import_id].clone())),
-                    raw::RawItemKind::Def(def) => self.define_def(&self.raw_items[def]),
-                    raw::RawItemKind::Macro(mac) => self.collect_macro(&self.raw_items[mac]),
                     raw::RawItemKind::Impl(imp) => {
                         let module = ModuleId {
                             krate: self.def_collector.def_map.krate,
@@ -720,20 +912,46 @@ where
         self.def_collector.update(self.module_id, None, &[(name, resolution)])
     }
 
-    fn collect_macro(&mut self, mac: &raw::MacroData) {
+    /// Defines an item, unless one of its attributes may be an attribute macro
+    /// which replaces it.
+    fn collect_def(&mut self, def: raw::Def, attrs: Attrs) {
+        if attrs.iter().any(|attr| self.def_collector.is_attribute_macro_candidate(&attr.path)) {
+            let ast_id = item_ast_id(self.file_id, &self.raw_items[def]);
+            self.def_collector.unexpanded_attribute_items.push(AttributeMacroItem {
+                module_id: self.module_id,
+                file_id: self.file_id,
+                def,
+                ast_id,
+                attrs,
+            });
+            return;
+        }
+        self.define_def(&self.raw_items[def]);
+        self.collect_derives(&attrs, &self.raw_items[def]);
+    }
+
+    /// Records the derives applied to an item, to be resolved together with
+    /// the attribute macros.
+    fn collect_derives(&mut self, attrs: &Attrs, def: &raw::DefData) {
+        if let raw::DefKind::Struct(_) | raw::DefKind::Union(_) | raw::DefKind::Enum(_) = def.kind {
+            let ast_id = item_ast_id(self.file_id, def);
+            for path in attrs.derives() {
+                let kind = MacroCallKind::Derive(ast_id);
+                self.def_collector.unexpanded_derives.push((self.module_id, path, kind));
+            }
+        }
+    }
+
+    fn collect_macro(&mut self, mac: &raw::MacroData, attrs: &Attrs) {
         let ast_id = AstId::new(self.file_id, mac.ast_id);
+        let export = attrs.has_atom("macro_export");
 
         // Case 0: builtin macros
-        if mac.builtin {
+        if attrs.has_atom("rustc_builtin_macro") {
             if let Some(name) = &mac.name {
                 let krate = self.def_collector.def_map.krate;
                 if let Some(macro_id) = find_builtin_macro(name, krate, ast_id) {
-                    self.def_collector.define_macro(
-                        self.module_id,
-                        name.clone(),
-                        macro_id,
-                        mac.export,
-                    );
+                    self.def_collector.define_macro(self.module_id, name.clone(), macro_id, export);
                     return;
                 }
             }
@@ -743,11 +961,11 @@ where
         if is_macro_rules(&mac.path) {
             if let Some(name) = &mac.name {
                 let macro_id = MacroDefId {
-                    ast_id,
+                    ast_id: Some(ast_id),
                     krate: self.def_collector.def_map.krate,
                     kind: MacroDefKind::Declarative,
                 };
-                self.def_collector.define_macro(self.module_id, name.clone(), macro_id, mac.export);
+                self.def_collector.define_macro(self.module_id, name.clone(), macro_id, export);
             }
             return;
         }
@@ -757,8 +975,11 @@ where
         if let Some(macro_def) = mac.path.as_ident().and_then(|name| {
             self.def_collector.def_map[self.module_id].scope.get_legacy_macro(&name)
         }) {
-            let macro_call_id =
-                self.def_collector.db.intern_macro(MacroCallLoc { def: macro_def, ast_id });
+            let macro_call_id = self.def_collector.db.intern_macro(MacroCallLoc {
+                def: macro_def,
+                krate: self.def_collector.def_map.krate,
+                kind: MacroCallKind::FnLike(ast_id),
+            });
 
             self.def_collector.collect_macro_expansion(self.module_id, macro_call_id, macro_def);
             return;
@@ -780,6 +1001,10 @@ where
         }
     }
 
+    fn expand_cfg_attr(&self, attrs: &Attrs) -> Attrs {
+        attrs.expand_cfg_attr(&self.def_collector.cfg_options)
+    }
+
     fn is_cfg_enabled(&self, attrs: &Attrs) -> bool {
         attrs.iter().all(|attr| attr.is_cfg_enabled(&self.def_collector.cfg_options) != Some(false))
     }
@@ -789,6 +1014,41 @@ where
     }
 }
 
+/// An item with attributes which may be attribute macros.
+struct AttributeMacroItem {
+    module_id: LocalModuleId,
+    file_id: HirFileId,
+    def: raw::Def,
+    ast_id: AstId<ast::ModuleItem>,
+    attrs: Attrs,
+}
+
+fn item_ast_id(file_id: HirFileId, def: &raw::DefData) -> AstId<ast::ModuleItem> {
+    let ast_id: FileAstId<ast::ModuleItem> = match def.kind {
+        raw::DefKind::Function(ast_id) => ast_id.upcast(),
+        raw::DefKind::Struct(ast_id) | raw::DefKind::Union(ast_id) => ast_id.upcast(),
+        raw::DefKind::Enum(ast_id) => ast_id.upcast(),
+        raw::DefKind::Const(ast_id) => ast_id.upcast(),
+        raw::DefKind::Static(ast_id) => ast_id.upcast(),
+        raw::DefKind::Trait(ast_id) => ast_id.upcast(),
+        raw::DefKind::TypeAlias(ast_id) => ast_id.upcast(),
+    };
+    AstId::new(file_id, ast_id)
+}
+
+/// Whether `path` can name a builtin derive: `Clone`, `std::clone::Clone` or
+/// `::core::clone::Clone` do, but `my::Clone` doesn't.
+fn is_builtin_derive_path(path: &Path) -> bool {
+    match path.kind {
+        PathKind::Plain if path.segments.len() == 1 => true,
+        PathKind::Plain | PathKind::Abs => match path.segments.first() {
+            Some(segment) => segment.name == name::STD || segment.name == name::CORE,
+            None => false,
+        },
+        _ => false,
+    }
+}
+
 fn is_macro_rules(path: &Path) -> bool {
     path.as_ident() == Some(&name::MACRO_RULES)
 }
@@ -814,6 +1074,9 @@ mod tests {
             glob_imports: FxHashMap::default(),
             unresolved_imports: Vec::new(),
             unexpanded_macros: Vec::new(),
+            unexpanded_derives: Vec::new(),
+            unexpanded_attribute_items: Vec::new(),
+            attribute_macro_names: FxHashSet::default(),
             mod_dirs: FxHashMap::default(),
             macro_stack_monitor: monitor,
             cfg_options: &CfgOptions::default(),
@@ -11,7 +11,7 @@ use hir_expand::{
 };
 use ra_arena::{impl_arena_id, map::ArenaMap, Arena, RawId};
 use ra_syntax::{
-    ast::{self, AttrsOwner, NameOwner},
+    ast::{self, NameOwner},
     AstNode, AstPtr,
 };
 use test_utils::tested_by;
@@ -154,9 +154,9 @@ pub struct ImportData {
     pub(super) path: Path,
     pub(super) alias: Option<Name>,
     pub(super) is_glob: bool,
+    /// Set by the collector, as `prelude_import` may come from a `cfg_attr`.
     pub(super) is_prelude: bool,
     pub(super) is_extern_crate: bool,
-    pub(super) is_macro_use: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -190,8 +190,6 @@ pub(super) struct MacroData {
     pub(super) ast_id: FileAstId<ast::MacroCall>,
     pub(super) path: Path,
     pub(super) name: Option<Name>,
-    pub(super) export: bool,
-    pub(super) builtin: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -303,8 +301,6 @@ impl RawItemsCollector {
     }
 
     fn add_use_item(&mut self, current_module: Option<Module>, use_item: ast::UseItem) {
-        // FIXME: cfg_attr
-        let is_prelude = use_item.has_atom_attr("prelude_import");
         let attrs = self.parse_attrs(&use_item);
 
         let mut buf = Vec::new();
@@ -312,14 +308,8 @@ impl RawItemsCollector {
             Source { value: use_item, file_id: self.file_id },
             &self.hygiene,
             |path, use_tree, is_glob, alias| {
-                let import_data = ImportData {
-                    path,
-                    alias,
-                    is_glob,
-                    is_prelude,
-                    is_extern_crate: false,
-                    is_macro_use: false,
-                };
+                let import_data =
+                    ImportData { path, alias, is_glob, is_prelude: false, is_extern_crate: false };
                 buf.push((import_data, Either::A(AstPtr::new(use_tree))));
             },
         );
@@ -337,15 +327,12 @@ impl RawItemsCollector {
             let path = Path::from_name_ref(&name_ref);
             let alias = extern_crate.alias().and_then(|a| a.name()).map(|it| it.as_name());
             let attrs = self.parse_attrs(&extern_crate);
-            // FIXME: cfg_attr
-            let is_macro_use = extern_crate.has_atom_attr("macro_use");
             let import_data = ImportData {
                 path,
                 alias,
                 is_glob: false,
                 is_prelude: false,
                 is_extern_crate: true,
-                is_macro_use,
             };
             self.push_import(
                 current_module,
@@ -365,14 +352,8 @@ impl RawItemsCollector {
 
         let name = m.name().map(|it| it.as_name());
         let ast_id = self.source_ast_id_map.ast_id(&m);
-        // FIXME: cfg_attr
-        let export = m.attrs().filter_map(|x| x.simple_name()).any(|name| name == "macro_export");
 
-        // FIXME: cfg_attr
-        let builtin =
-            m.attrs().filter_map(|x| x.simple_name()).any(|name| name == "rustc_builtin_macro");
-
-        let m = self.raw_items.macros.alloc(MacroData { ast_id, path, name, export, builtin });
+        let m = self.raw_items.macros.alloc(MacroData { ast_id, path, name });
         self.push_item(current_module, attrs, RawItemKind::Macro(m));
     }
 
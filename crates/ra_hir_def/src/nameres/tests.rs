@@ -241,6 +241,35 @@ fn std_prelude() {
     "###);
 }
 
+#[test]
+fn std_prelude_from_cfg_attr() {
+    let map = def_map(
+        r#"
+        //- /main.rs crate:main deps:test_crate
+        use Foo::*;
+
+        //- /lib.rs crate:test_crate cfg:feature=std
+        mod prelude;
+        mod unused;
+        #[cfg_attr(feature = "std", prelude_import)]
+        use prelude::*;
+        #[cfg_attr(feature = "unset", prelude_import)]
+        use unused::*;
+
+        //- /prelude.rs
+        pub enum Foo { Bar, Baz };
+
+        //- /unused.rs
+        pub struct Foo;
+        "#,
+    );
+    assert_snapshot!(map, @r###"
+        ⋮crate
+        ⋮Bar: t v
+        ⋮Baz: t v
+    "###);
+}
+
 #[test]
 fn can_import_enum_variant() {
     covers!(can_import_enum_variant);
@@ -551,3 +580,136 @@ fn cfg_test() {
         ⋮Foo: t v
     "###);
 }
+
+#[test]
+fn cfg_attr_is_expanded() {
+    let map = def_map(
+        r#"
+        //- /main.rs crate:main cfg:test,feature=foo
+        #[cfg_attr(feature = "foo", cfg(test))]
+        struct Enabled;
+        #[cfg_attr(feature = "foo", cfg(not(test)))]
+        struct Disabled;
+        #[cfg_attr(feature = "bar", cfg(not(test)))]
+        struct PredicateIsFalse;
+        #[cfg_attr(feature = "foo", cfg_attr(test, cfg(unknown)))]
+        struct NestedDisabled;
+        #[cfg_attr(all(feature = "foo", test), derive(Debug), cfg(any()))]
+        struct MultipleDisabled;
+        "#,
+    );
+
+    assert_snapshot!(map, @r###"
+        ⋮crate
+        ⋮Enabled: t v
+        ⋮PredicateIsFalse: t v
+    "###);
+}
+
+#[test]
+fn unresolved_import_diagnostics() {
+    let map = compute_crate_def_map(
+        r"
+        //- /lib.rs
+        mod foo { pub struct Bar; }
+        use foo::Bar;
+        use foo::Baz;
+        use missing::Thing;
+        ",
+    );
+
+    insta::assert_debug_snapshot!(map.diagnostics, @r###"
+    [
+        UnresolvedImport {
+            module: LocalModuleId(
+                0,
+            ),
+            file_id: HirFileId(
+                FileId(
+                    FileId(
+                        0,
+                    ),
+                ),
+            ),
+            import: LocalImportId(
+                1,
+            ),
+        },
+        UnresolvedImport {
+            module: LocalModuleId(
+                0,
+            ),
+            file_id: HirFileId(
+                FileId(
+                    FileId(
+                        0,
+                    ),
+                ),
+            ),
+            import: LocalImportId(
+                2,
+            ),
+        },
+    ]
+    "###);
+}
+
+#[test]
+fn import_of_item_added_by_macro_expansion() {
+    let map = compute_crate_def_map(
+        r"
+        //- /lib.rs
+        macro_rules! make { () => { pub struct Made; } }
+        use m::Made;
+        mod m { make!(); }
+        ",
+    );
+
+    assert!(map.diagnostics.is_empty(), "{:?}", map.diagnostics);
+    assert_snapshot!(render_crate_def_map(&map), @r###"
+        ⋮crate
+        ⋮Made: t v
+        ⋮m: t
+        ⋮
+        ⋮crate::m
+        ⋮Made: t v
+    "###);
+}
+
+#[test]
+fn unresolved_imports_in_cyclic_globs() {
+    let map = compute_crate_def_map(
+        r"
+        //- /lib.rs
+        mod a {
+            pub use super::b::*;
+            pub use super::b::Missing;
+            pub struct A;
+        }
+        mod b {
+            pub use super::a::*;
+            pub struct B;
+        }
+        use a::B;
+        use b::Nope;
+        ",
+    );
+
+    assert_eq!(map.diagnostics.len(), 2);
+    assert_snapshot!(render_crate_def_map(&map), @r###"
+        ⋮crate
+        ⋮B: t v
+        ⋮Nope: _
+        ⋮a: t
+        ⋮b: t
+        ⋮
+        ⋮crate::a
+        ⋮A: t v
+        ⋮B: t v
+        ⋮Missing: _
+        ⋮
+        ⋮crate::b
+        ⋮A: t v
+        ⋮B: t v
+    "###);
+}
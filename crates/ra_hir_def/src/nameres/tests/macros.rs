@@ -223,6 +223,36 @@ fn macro_rules_from_other_crates_are_visible_with_macro_use() {
     "###);
 }
 
+#[test]
+fn macro_use_and_macro_export_from_cfg_attr() {
+    let map = def_map(
+        r#"
+        //- /main.rs crate:main deps:foo cfg:feature=foo
+        structs!(Foo);
+        structs_not_exported!(MacroNotResolved);
+
+        #[cfg_attr(feature = "foo", macro_use)]
+        extern crate foo;
+
+        //- /lib.rs crate:foo cfg:feature=export
+        #[cfg_attr(feature = "export", macro_export)]
+        macro_rules! structs {
+            ($i:ident) => { struct $i; }
+        }
+
+        #[cfg_attr(feature = "unset", macro_export)]
+        macro_rules! structs_not_exported {
+            ($i:ident) => { struct $i; }
+        }
+        "#,
+    );
+    assert_snapshot!(map, @r###"
+   ⋮crate
+   ⋮Foo: t v
+   ⋮foo: t
+    "###);
+}
+
 #[test]
 fn prelude_is_macro_use() {
     covers!(prelude_is_macro_use);
@@ -600,3 +630,245 @@ fn macro_dollar_crate_is_correct_in_indirect_deps() {
         ⋮bar: t v
     "###);
 }
+
+#[test]
+fn items_from_included_file_are_collected() {
+    let map = def_map(
+        r#"
+        //- /main.rs
+        #[rustc_builtin_macro]
+        macro_rules! concat {() => {}}
+        #[rustc_builtin_macro]
+        macro_rules! include {() => {}}
+
+        include!(concat!("gen/", "foo.rs"));
+
+        //- /gen/foo.rs
+        pub struct Foo;
+        pub fn bar() {}
+        "#,
+    );
+    assert_snapshot!(map, @r###"
+   ⋮crate
+   ⋮Foo: t v
+   ⋮bar: v
+    "###);
+}
+
+#[test]
+fn builtin_derives_are_collected_as_impls() {
+    let map = compute_crate_def_map(
+        r#"
+        //- /main.rs
+        #[derive(Clone, Copy, std::fmt::Debug, NotABuiltinDerive)]
+        struct Foo<T: Clone>(T);
+
+        mod m {
+            #[cfg_attr(not(test), derive(PartialEq))]
+            enum Bar { A }
+        }
+        "#,
+    );
+    let root = &map.modules[map.root()];
+    assert_eq!(root.impls.len(), 3);
+    let m = *root.children.values().next().unwrap();
+    assert_eq!(map.modules[m].impls.len(), 1);
+}
+
+#[test]
+fn builtin_derives_need_a_std_path() {
+    let map = compute_crate_def_map(
+        r#"
+        //- /main.rs
+        #[derive(my::Clone, ::core::marker::Copy, crate::Debug)]
+        struct Foo;
+        "#,
+    );
+    assert_eq!(map.modules[map.root()].impls.len(), 1);
+}
+
+#[test]
+fn proc_macros_are_resolved_and_expanded() {
+    use ra_db::{ProcMacro, ProcMacroKind};
+
+    #[derive(Debug)]
+    struct IdentityExpander;
+
+    impl tt::TokenExpander for IdentityExpander {
+        fn expand(
+            &self,
+            subtree: &tt::Subtree,
+            _attrs: Option<&tt::Subtree>,
+        ) -> Result<tt::Subtree, tt::ExpansionError> {
+            Ok(subtree.clone())
+        }
+    }
+
+    let mut db = TestDB::with_files(
+        r#"
+        //- /main.rs crate:main deps:proc_macros
+        use proc_macros::identity;
+
+        identity!(struct Foo;);
+        proc_macros::identity!(struct Bar;);
+        not_a_proc_macro!(struct Baz;);
+
+        //- /proc_macros.rs crate:proc_macros
+        pub fn identity() {}
+        "#,
+    );
+    let mut crate_graph = (*db.crate_graph()).clone();
+    let main =
+        crate_graph.iter().find(|&it| crate_graph.dependencies(it).next().is_some()).unwrap();
+    let proc_macros = crate_graph.dependencies(main).next().unwrap().crate_id();
+    crate_graph.set_proc_macros(
+        proc_macros,
+        vec![ProcMacro {
+            name: "identity".into(),
+            kind: ProcMacroKind::FuncLike,
+            expander: Arc::new(IdentityExpander),
+        }],
+    );
+    db.set_crate_graph(Arc::new(crate_graph));
+
+    let map = db.crate_def_map(main);
+    assert_snapshot!(render_crate_def_map(&map), @r###"
+   ⋮crate
+   ⋮Bar: t v
+   ⋮Foo: t v
+   ⋮identity: m
+    "###);
+}
+
+#[test]
+fn proc_macro_derives_and_attributes_are_expanded() {
+    use ra_db::{ProcMacro, ProcMacroKind};
+
+    fn ident(text: &str) -> tt::TokenTree {
+        tt::Leaf::from(tt::Ident { text: text.into(), id: tt::TokenId::unspecified() }).into()
+    }
+
+    fn unit_struct(name: &str) -> tt::Subtree {
+        let semi = tt::Leaf::from(tt::Punct { char: ';', spacing: tt::Spacing::Alone }).into();
+        tt::Subtree {
+            delimiter: tt::Delimiter::None,
+            token_trees: vec![ident("struct"), ident(name), semi],
+        }
+    }
+
+    /// Defines `struct {Name}Helper;` for the derived `{Name}`.
+    #[derive(Debug)]
+    struct HelperDerive;
+
+    impl tt::TokenExpander for HelperDerive {
+        fn expand(
+            &self,
+            subtree: &tt::Subtree,
+            _attrs: Option<&tt::Subtree>,
+        ) -> Result<tt::Subtree, tt::ExpansionError> {
+            let name = subtree
+                .token_trees
+                .iter()
+                .skip_while(|tt| **tt != ident("struct") && **tt != ident("enum"))
+                .nth(1)
+                .ok_or_else(|| tt::ExpansionError::Unknown("not an adt".to_string()))?;
+            Ok(unit_struct(&format!("{}Helper", name)))
+        }
+    }
+
+    /// Defines a unit struct named by the attribute argument.
+    #[derive(Debug)]
+    struct GenerateAttr;
+
+    impl tt::TokenExpander for GenerateAttr {
+        fn expand(
+            &self,
+            subtree: &tt::Subtree,
+            attrs: Option<&tt::Subtree>,
+        ) -> Result<tt::Subtree, tt::ExpansionError> {
+            if subtree.to_string().contains("generate") {
+                return Err(tt::ExpansionError::Unknown("attribute not removed".to_string()));
+            }
+            let name = attrs.and_then(|it| it.token_trees.first());
+            let name =
+                name.ok_or_else(|| tt::ExpansionError::Unknown("no arguments".to_string()))?;
+            Ok(unit_struct(&name.to_string()))
+        }
+    }
+
+    let mut db = TestDB::with_files(
+        r#"
+        //- /main.rs crate:main deps:proc_macros
+        use proc_macros::{generate, Helper};
+
+        #[derive(Clone, Helper)]
+        struct Foo;
+
+        #[derive(proc_macros::Helper)]
+        enum Bar {}
+
+        #[proc_macros::generate(Baz)]
+        fn foo() {}
+
+        #[generate(Qux)]
+        #[inline]
+        fn bar() {}
+
+        #[derive(generate)]
+        struct NotADerive;
+
+        #[inline]
+        fn kept() {}
+
+        use m::not_expanded;
+        mod m {
+            #[generate(Nope)]
+            pub fn not_expanded() {}
+        }
+
+        //- /proc_macros.rs crate:proc_macros
+        pub fn helper() {}
+        "#,
+    );
+    let mut crate_graph = (*db.crate_graph()).clone();
+    let main =
+        crate_graph.iter().find(|&it| crate_graph.dependencies(it).next().is_some()).unwrap();
+    let proc_macros = crate_graph.dependencies(main).next().unwrap().crate_id();
+    crate_graph.set_proc_macros(
+        proc_macros,
+        vec![
+            ProcMacro {
+                name: "Helper".into(),
+                kind: ProcMacroKind::CustomDerive,
+                expander: Arc::new(HelperDerive),
+            },
+            ProcMacro {
+                name: "generate".into(),
+                kind: ProcMacroKind::Attr,
+                expander: Arc::new(GenerateAttr),
+            },
+        ],
+    );
+    db.set_crate_graph(Arc::new(crate_graph));
+
+    let map = db.crate_def_map(main);
+    assert_snapshot!(render_crate_def_map(&map), @r###"
+   ⋮crate
+   ⋮Bar: t
+   ⋮BarHelper: t v
+   ⋮Baz: t v
+   ⋮Foo: t v
+   ⋮FooHelper: t v
+   ⋮Helper: m
+   ⋮NotADerive: t v
+   ⋮Qux: t v
+   ⋮generate: m
+   ⋮kept: v
+   ⋮m: t
+   ⋮not_expanded: v
+   ⋮
+   ⋮crate::m
+   ⋮not_expanded: v
+    "###);
+    assert_eq!(map.modules[map.root()].impls.len(), 1);
+}
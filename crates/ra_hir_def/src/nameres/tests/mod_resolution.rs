@@ -780,3 +780,38 @@ fn nested_out_of_line_module_with_path() {
     X: t v
     "###);
 }
+
+#[test]
+fn module_resolution_with_cfg_attr_path() {
+    let map = def_map(
+        r###"
+        //- /main.rs crate:main cfg:feature=foo
+        #[cfg_attr(feature = "foo", path = "bar.rs")]
+        #[cfg_attr(not(feature = "foo"), path = "baz.rs")]
+        mod foo;
+        #[cfg_attr(feature = "foo", cfg_attr(not(test), path = "quux.rs"))]
+        mod qux;
+
+        //- /bar.rs
+        pub struct Bar;
+
+        //- /baz.rs
+        pub struct Baz;
+
+        //- /quux.rs
+        pub struct Quux;
+        "###,
+    );
+
+    assert_snapshot!(map, @r###"
+    crate
+    foo: t
+    qux: t
+    
+    crate::foo
+    Bar: t v
+    
+    crate::qux
+    Quux: t v
+    "###);
+}
@@ -0,0 +1,330 @@
+//! Builtin derives.
+//!
+//! A derive is expanded from the item it is attached to, and produces an empty
+//! impl of the derived trait. Trait items are not generated: we only need the
+//! impl to exist for method resolution and goto implementation.
+
+use ra_parser::FragmentKind;
+use ra_syntax::{
+    ast::{self, AstNode, NameOwner, TypeBoundsOwner, TypeParamsOwner},
+    SyntaxNode,
+};
+
+use crate::db::AstDatabase;
+use crate::{name, quote, CrateId, MacroCallId, MacroDefId, MacroDefKind};
+
+macro_rules! register_builtin {
+    ( $(($name:ident, $kind: ident) => $expand:ident),* ) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum BuiltinDeriveExpander {
+            $($kind),*
+        }
+
+        impl BuiltinDeriveExpander {
+            pub fn expand(
+                &self,
+                db: &dyn AstDatabase,
+                id: MacroCallId,
+                tt: &tt::Subtree,
+            ) -> Result<tt::Subtree, mbe::ExpandError> {
+                let expander = match *self {
+                    $( BuiltinDeriveExpander::$kind => $expand, )*
+                };
+                expander(db, id, tt)
+            }
+        }
+
+        pub fn find_builtin_derive(ident: &name::Name, krate: CrateId) -> Option<MacroDefId> {
+            let kind = match ident {
+                 $( id if id == &name::$name => BuiltinDeriveExpander::$kind, )*
+                 _ => return None,
+            };
+
+            Some(MacroDefId { krate, ast_id: None, kind: MacroDefKind::BuiltInDerive(kind) })
+        }
+    };
+}
+
+register_builtin! {
+    (COPY_TRAIT, Copy) => copy_expand,
+    (CLONE_TRAIT, Clone) => clone_expand,
+    (DEFAULT_TRAIT, Default) => default_expand,
+    (DEBUG_TRAIT, Debug) => debug_expand,
+    (HASH_TRAIT, Hash) => hash_expand,
+    (ORD_TRAIT, Ord) => ord_expand,
+    (PARTIAL_ORD_TRAIT, PartialOrd) => partial_ord_expand,
+    (EQ_TRAIT, Eq) => eq_expand,
+    (PARTIAL_EQ_TRAIT, PartialEq) => partial_eq_expand
+}
+
+struct BasicAdtInfo {
+    name: tt::Ident,
+    /// Lifetime parameters, with their bounds.
+    lifetime_params: Vec<tt::Subtree>,
+    /// Type parameter names, with their bounds (defaults are dropped).
+    type_params: Vec<(tt::Ident, Option<tt::Subtree>)>,
+    where_clause: Option<tt::Subtree>,
+}
+
+fn parse_adt(tt: &tt::Subtree) -> Result<BasicAdtInfo, mbe::ExpandError> {
+    let (parsed, token_map) = mbe::token_tree_to_syntax_node(tt, FragmentKind::Items)?;
+    let node = parsed.syntax_node();
+    let item = node
+        .children()
+        .find_map(ast::NominalDef::cast)
+        .ok_or_else(|| mbe::ExpandError::ConversionError)?;
+
+    // Keep the ids of the input tokens, so that the expansion maps back to the item.
+    let to_ident = |name: ast::Name| tt::Ident {
+        text: name.text().clone(),
+        id: token_map
+            .token_by_range(name.syntax().text_range())
+            .unwrap_or_else(tt::TokenId::unspecified),
+    };
+    let name = item.name().map(to_ident).ok_or_else(|| mbe::ExpandError::ConversionError)?;
+
+    let mut lifetime_params = Vec::new();
+    let mut type_params = Vec::new();
+    if let Some(param_list) = item.type_param_list() {
+        for param in param_list.lifetime_params() {
+            lifetime_params.push(to_tt(param.syntax())?);
+        }
+        for param in param_list.type_params() {
+            let name =
+                param.name().map(to_ident).ok_or_else(|| mbe::ExpandError::ConversionError)?;
+            let bounds = match param.type_bound_list() {
+                Some(bounds) => Some(to_tt(bounds.syntax())?),
+                None => None,
+            };
+            type_params.push((name, bounds));
+        }
+    }
+    let where_clause = match item.where_clause() {
+        Some(where_clause) => Some(to_tt(where_clause.syntax())?),
+        None => None,
+    };
+
+    Ok(BasicAdtInfo { name, lifetime_params, type_params, where_clause })
+}
+
+fn to_tt(node: &SyntaxNode) -> Result<tt::Subtree, mbe::ExpandError> {
+    mbe::syntax_node_to_token_tree(node).map(|(tt, _)| tt).ok_or(mbe::ExpandError::ConversionError)
+}
+
+/// Produces `impl<'a, T: Bound + Trait> Trait for Name<'a, T> where ... {}`.
+///
+/// Every type parameter is required to implement the derived trait, like rustc
+/// does.
+fn expand_simple_derive(
+    tt: &tt::Subtree,
+    trait_path: tt::Subtree,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    let info = parse_adt(tt)?;
+
+    let mut params = Vec::new();
+    let mut args = Vec::new();
+    for lifetime_param in info.lifetime_params {
+        // The lifetime itself is the first token, it's followed by the bounds.
+        if let Some(lifetime) = lifetime_param.token_trees.first() {
+            args.push(vec![lifetime.clone()]);
+        }
+        params.push(lifetime_param.token_trees);
+    }
+    for (name, bounds) in &info.type_params {
+        let mut param = quote!(#name :).token_trees;
+        if let Some(bounds) = bounds {
+            param.extend(bounds.token_trees.clone());
+            param.extend(quote!(+).token_trees);
+        }
+        param.extend(trait_path.token_trees.clone());
+        params.push(param);
+        args.push(quote!(#name).token_trees);
+    }
+
+    let name = info.name;
+    let mut token_trees = quote!(impl).token_trees;
+    token_trees.extend(generic_list(params));
+    token_trees.extend(trait_path.token_trees);
+    token_trees.extend(quote!(for #name).token_trees);
+    token_trees.extend(generic_list(args));
+    if let Some(where_clause) = info.where_clause {
+        token_trees.extend(where_clause.token_trees);
+    }
+    token_trees.extend(quote!({}).token_trees);
+
+    Ok(tt::Subtree { delimiter: tt::Delimiter::None, token_trees })
+}
+
+/// Produces `<a, b>` from the tokens of `a` and `b`, or nothing at all if there
+/// are no items.
+fn generic_list(items: Vec<Vec<tt::TokenTree>>) -> Vec<tt::TokenTree> {
+    if items.is_empty() {
+        return Vec::new();
+    }
+    let mut res = quote!(<).token_trees;
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            res.extend(quote!(,).token_trees);
+        }
+        res.extend(item);
+    }
+    res.extend(quote!(>).token_trees);
+    res
+}
+
+fn copy_expand(
+    _db: &dyn AstDatabase,
+    _id: MacroCallId,
+    tt: &tt::Subtree,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    expand_simple_derive(tt, quote! { core::marker::Copy })
+}
+
+fn clone_expand(
+    _db: &dyn AstDatabase,
+    _id: MacroCallId,
+    tt: &tt::Subtree,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    expand_simple_derive(tt, quote! { core::clone::Clone })
+}
+
+fn default_expand(
+    _db: &dyn AstDatabase,
+    _id: MacroCallId,
+    tt: &tt::Subtree,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    expand_simple_derive(tt, quote! { core::default::Default })
+}
+
+fn debug_expand(
+    _db: &dyn AstDatabase,
+    _id: MacroCallId,
+    tt: &tt::Subtree,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    expand_simple_derive(tt, quote! { core::fmt::Debug })
+}
+
+fn hash_expand(
+    _db: &dyn AstDatabase,
+    _id: MacroCallId,
+    tt: &tt::Subtree,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    expand_simple_derive(tt, quote! { core::hash::Hash })
+}
+
+fn eq_expand(
+    _db: &dyn AstDatabase,
+    _id: MacroCallId,
+    tt: &tt::Subtree,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    expand_simple_derive(tt, quote! { core::cmp::Eq })
+}
+
+fn partial_eq_expand(
+    _db: &dyn AstDatabase,
+    _id: MacroCallId,
+    tt: &tt::Subtree,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    expand_simple_derive(tt, quote! { core::cmp::PartialEq })
+}
+
+fn ord_expand(
+    _db: &dyn AstDatabase,
+    _id: MacroCallId,
+    tt: &tt::Subtree,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    expand_simple_derive(tt, quote! { core::cmp::Ord })
+}
+
+fn partial_ord_expand(
+    _db: &dyn AstDatabase,
+    _id: MacroCallId,
+    tt: &tt::Subtree,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    expand_simple_derive(tt, quote! { core::cmp::PartialOrd })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_db::TestDB, AstId, MacroCallKind, MacroCallLoc, MacroFileKind};
+    use ra_db::{fixture::WithFixture, SourceDatabase};
+
+    fn expand_builtin_derive(s: &str, expander: BuiltinDeriveExpander) -> String {
+        let (db, file_id) = TestDB::with_single_file(&s);
+        let parsed = db.parse(file_id);
+        let items: Vec<_> =
+            parsed.syntax_node().descendants().filter_map(|it| ast::ModuleItem::cast(it)).collect();
+
+        let ast_id_map = db.ast_id_map(file_id.into());
+
+        // the first one should be the item the derive is attached to
+        let def = MacroDefId {
+            krate: CrateId(0),
+            ast_id: None,
+            kind: MacroDefKind::BuiltInDerive(expander),
+        };
+
+        let loc = MacroCallLoc {
+            def,
+            krate: CrateId(0),
+            kind: MacroCallKind::Derive(AstId::new(file_id.into(), ast_id_map.ast_id(&items[0]))),
+        };
+
+        let id = db.intern_macro(loc);
+        let parsed = db.parse_or_expand(id.as_file(MacroFileKind::Items)).unwrap();
+
+        // The tree parsed from the tokens has no whitespace
+        let tokens: Vec<_> = parsed
+            .descendants_with_tokens()
+            .filter_map(|it| it.into_token())
+            .map(|it| it.text().to_string())
+            .collect();
+        tokens.join(" ")
+    }
+
+    #[test]
+    fn test_copy_expand_simple() {
+        let expanded = expand_builtin_derive(
+            r#"
+        #[derive(Copy)]
+        struct Foo;
+"#,
+            BuiltinDeriveExpander::Copy,
+        );
+
+        assert_eq!(expanded, "impl core :: marker :: Copy for Foo { }");
+    }
+
+    #[test]
+    fn test_clone_expand_with_type_params() {
+        let expanded = expand_builtin_derive(
+            r#"
+        #[derive(Clone)]
+        struct Foo<'a, A: Tr, B = ()> where A: Other { a: &'a A, b: B }
+"#,
+            BuiltinDeriveExpander::Clone,
+        );
+
+        assert_eq!(
+            expanded,
+            "impl < 'a , A : Tr + core :: clone :: Clone , B : core :: clone :: Clone > core :: clone :: Clone for Foo < 'a , A , B > where A : Other { }"
+        );
+    }
+
+    #[test]
+    fn test_partial_eq_expand_enum() {
+        let expanded = expand_builtin_derive(
+            r#"
+        #[derive(PartialEq)]
+        enum Foo<T> { A(T), B }
+"#,
+            BuiltinDeriveExpander::PartialEq,
+        );
+
+        assert_eq!(
+            expanded,
+            "impl < T : core :: cmp :: PartialEq > core :: cmp :: PartialEq for Foo < T > { }"
+        );
+    }
+}
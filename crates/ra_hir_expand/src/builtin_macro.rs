@@ -1,20 +1,29 @@
 //! Builtin macro
+use std::path::Path;
+
+use ra_db::{FileId, RelativePath};
+
 use crate::db::AstDatabase;
 use crate::{
     ast::{self, AstNode},
-    name, AstId, CrateId, HirFileId, MacroCallId, MacroDefId, MacroDefKind, MacroFileKind,
-    TextUnit,
+    name::{self, AsName},
+    AstId, CrateId, HirFileId, MacroCallId, MacroDefId, MacroDefKind, MacroFileKind, TextUnit,
 };
 
 use crate::quote;
 
 macro_rules! register_builtin {
-    ( $(($name:ident, $kind: ident) => $expand:ident),* ) => {
+    ( LAZY: $(($name:ident, $kind: ident) => $expand:ident),* , EAGER: $(($e_name:ident, $e_kind: ident) => $e_expand:ident),* ) => {
         #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
         pub enum BuiltinFnLikeExpander {
             $($kind),*
         }
 
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum EagerExpander {
+            $($e_kind),*
+        }
+
         impl BuiltinFnLikeExpander {
             pub fn expand(
                 &self,
@@ -29,26 +38,184 @@ macro_rules! register_builtin {
             }
         }
 
+        impl EagerExpander {
+            /// Expands the macro after expanding eager macro calls in its
+            /// arguments.
+            pub fn expand(
+                &self,
+                db: &dyn AstDatabase,
+                id: MacroCallId,
+                tt: &tt::Subtree,
+            ) -> Result<tt::Subtree, mbe::ExpandError> {
+                let tt = expand_eager_args(db, id, tt)?;
+                let expander = match *self {
+                    $( EagerExpander::$e_kind => $e_expand, )*
+                };
+                expander(db, id, &tt)
+            }
+
+            fn find_by_name(ident: &name::Name) -> Option<EagerExpander> {
+                match ident {
+                    $( id if id == &name::$e_name => Some(EagerExpander::$e_kind), )*
+                    _ => None,
+                }
+            }
+        }
+
         pub fn find_builtin_macro(
             ident: &name::Name,
             krate: CrateId,
             ast_id: AstId<ast::MacroCall>,
         ) -> Option<MacroDefId> {
             let kind = match ident {
-                 $( id if id == &name::$name => BuiltinFnLikeExpander::$kind, )*
+                 $( id if id == &name::$name => MacroDefKind::BuiltIn(BuiltinFnLikeExpander::$kind), )*
+                 $( id if id == &name::$e_name => MacroDefKind::BuiltInEager(EagerExpander::$e_kind), )*
                  _ => return None,
             };
 
-            Some(MacroDefId { krate, ast_id, kind: MacroDefKind::BuiltIn(kind) })
+            Some(MacroDefId { krate, ast_id: Some(ast_id), kind })
         }
     };
 }
 
 register_builtin! {
+    LAZY:
     (COLUMN_MACRO, Column) => column_expand,
+    (COMPILE_ERROR_MACRO, CompileError) => compile_error_expand,
     (FILE_MACRO, File) => file_expand,
     (LINE_MACRO, Line) => line_expand,
-    (STRINGIFY_MACRO, Stringify) => stringify_expand
+    (STRINGIFY_MACRO, Stringify) => stringify_expand,
+
+    EAGER:
+    (CONCAT_MACRO, Concat) => concat_expand,
+    (ENV_MACRO, Env) => env_expand,
+    (INCLUDE_MACRO, Include) => include_expand,
+    (OPTION_ENV_MACRO, OptionEnv) => option_env_expand
+}
+
+/// Returns the file included by an `include!` call.
+///
+/// Items of the included file are collected directly from that file, so that
+/// they have a real `HirFileId` and navigation leads into the included file.
+pub fn included_file(db: &dyn AstDatabase, id: MacroCallId) -> Option<FileId> {
+    let loc = db.lookup_intern_macro(id);
+    match loc.def.kind {
+        MacroDefKind::BuiltInEager(EagerExpander::Include) => (),
+        _ => return None,
+    }
+    let macro_arg = db.macro_arg(id)?;
+    let arg = expand_eager_args(db, id, &macro_arg.0).ok()?;
+    resolve_include(db, id, &arg)
+}
+
+/// Expands eager builtin macros nested in the arguments of an eager macro, like
+/// `env!` in `include!(concat!(env!("OUT_DIR"), "/generated.rs"))`.
+// FIXME: nested macro calls are recognized by their name instead of being
+// resolved, so a user-defined `concat!` is mistaken for the builtin one.
+fn expand_eager_args(
+    db: &dyn AstDatabase,
+    id: MacroCallId,
+    tt: &tt::Subtree,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    let mut token_trees = Vec::with_capacity(tt.token_trees.len());
+    let mut idx = 0;
+    while idx < tt.token_trees.len() {
+        if let Some((expander, args)) = as_eager_call(&tt.token_trees[idx..]) {
+            let expanded = expander.expand(db, id, args)?;
+            token_trees.extend(expanded.token_trees);
+            idx += 3;
+            continue;
+        }
+        let token = match &tt.token_trees[idx] {
+            tt::TokenTree::Subtree(subtree) => expand_eager_args(db, id, subtree)?.into(),
+            it => it.clone(),
+        };
+        token_trees.push(token);
+        idx += 1;
+    }
+    Ok(tt::Subtree { delimiter: tt.delimiter, token_trees })
+}
+
+/// Matches `name!(args)` at the start of `tokens`.
+fn as_eager_call(tokens: &[tt::TokenTree]) -> Option<(EagerExpander, &tt::Subtree)> {
+    let name = match tokens.get(0)? {
+        tt::TokenTree::Leaf(tt::Leaf::Ident(it)) => it,
+        _ => return None,
+    };
+    match tokens.get(1)? {
+        tt::TokenTree::Leaf(tt::Leaf::Punct(tt::Punct { char: '!', .. })) => (),
+        _ => return None,
+    }
+    let args = match tokens.get(2)? {
+        tt::TokenTree::Subtree(it) => it,
+        _ => return None,
+    };
+    Some((EagerExpander::find_by_name(&name.as_name())?, args))
+}
+
+/// Returns the value of a string literal, or the content of a char literal.
+fn unquote_str(lit: &tt::Literal) -> Option<String> {
+    let text = lit.text.as_str();
+    if text.starts_with('r') {
+        let text = text[1..].trim_matches('#');
+        if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+            return Some(text[1..text.len() - 1].to_string());
+        }
+        return None;
+    }
+    let quote = text.chars().next()?;
+    if text.len() < 2 || (quote != '"' && quote != '\'') || !text.ends_with(quote) {
+        return None;
+    }
+    Some(unescape(&text[1..text.len() - 1]))
+}
+
+// FIXME: only the common escapes are handled
+fn unescape(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => res.push('\n'),
+            Some('r') => res.push('\r'),
+            Some('t') => res.push('\t'),
+            Some('0') => res.push('\0'),
+            Some(c) => res.push(c),
+            None => (),
+        }
+    }
+    res
+}
+
+fn str_literal(value: &str) -> tt::Literal {
+    tt::Literal { text: format!("{:?}", value).into() }
+}
+
+/// Parses the first argument of a macro, which should be a string literal.
+fn parse_string(tt: &tt::Subtree) -> Result<String, mbe::ExpandError> {
+    match tt.token_trees.get(0) {
+        Some(tt::TokenTree::Leaf(tt::Leaf::Literal(it))) if it.text.contains('"') => {
+            unquote_str(it).ok_or(mbe::ExpandError::ConversionError)
+        }
+        _ => Err(mbe::ExpandError::ConversionError),
+    }
+}
+
+fn resolve_include(db: &dyn AstDatabase, id: MacroCallId, tt: &tt::Subtree) -> Option<FileId> {
+    let path = parse_string(tt).ok()?;
+    let loc = db.lookup_intern_macro(id);
+    if Path::new(&path).is_absolute() {
+        let (extern_root, relative_path) =
+            db.crate_graph().extern_source(loc.krate).extern_path(&path)?;
+        db.resolve_extern_path(extern_root, &relative_path)
+    } else {
+        let anchor = loc.kind.file_id().original_file(db);
+        db.resolve_relative_path(anchor, RelativePath::new(&path))
+    }
 }
 
 fn to_line_number(db: &dyn AstDatabase, file: HirFileId, pos: TextUnit) -> usize {
@@ -76,10 +243,8 @@ fn line_expand(
     _tt: &tt::Subtree,
 ) -> Result<tt::Subtree, mbe::ExpandError> {
     let loc = db.lookup_intern_macro(id);
-    let macro_call = loc.ast_id.to_node(db);
-
-    let arg = macro_call.token_tree().ok_or_else(|| mbe::ExpandError::UnexpectedToken)?;
-    let arg_start = arg.syntax().text_range().start();
+    let arg = loc.kind.arg(db).ok_or_else(|| mbe::ExpandError::UnexpectedToken)?;
+    let arg_start = arg.text_range().start();
 
     let file = id.as_file(MacroFileKind::Expr);
     let line_num = to_line_number(db, file, arg_start);
@@ -97,11 +262,8 @@ fn stringify_expand(
     _tt: &tt::Subtree,
 ) -> Result<tt::Subtree, mbe::ExpandError> {
     let loc = db.lookup_intern_macro(id);
-    let macro_call = loc.ast_id.to_node(db);
-
     let macro_content = {
-        let arg = macro_call.token_tree().ok_or_else(|| mbe::ExpandError::UnexpectedToken)?;
-        let macro_args = arg.syntax().clone();
+        let macro_args = loc.kind.arg(db).ok_or_else(|| mbe::ExpandError::UnexpectedToken)?;
         let text = macro_args.text();
         let without_parens = TextUnit::of_char('(')..text.len() - TextUnit::of_char(')');
         text.slice(without_parens).to_string()
@@ -136,10 +298,8 @@ fn column_expand(
     _tt: &tt::Subtree,
 ) -> Result<tt::Subtree, mbe::ExpandError> {
     let loc = db.lookup_intern_macro(id);
-    let macro_call = loc.ast_id.to_node(db);
-
-    let _arg = macro_call.token_tree().ok_or_else(|| mbe::ExpandError::UnexpectedToken)?;
-    let col_start = macro_call.syntax().text_range().start();
+    let _arg = loc.kind.arg(db).ok_or_else(|| mbe::ExpandError::UnexpectedToken)?;
+    let col_start = loc.kind.node(db).value.text_range().start();
 
     let file = id.as_file(MacroFileKind::Expr);
     let col_num = to_col_number(db, file, col_start);
@@ -157,9 +317,7 @@ fn file_expand(
     _tt: &tt::Subtree,
 ) -> Result<tt::Subtree, mbe::ExpandError> {
     let loc = db.lookup_intern_macro(id);
-    let macro_call = loc.ast_id.to_node(db);
-
-    let _ = macro_call.token_tree().ok_or_else(|| mbe::ExpandError::UnexpectedToken)?;
+    let _ = loc.kind.arg(db).ok_or_else(|| mbe::ExpandError::UnexpectedToken)?;
 
     // FIXME: RA purposefully lacks knowledge of absolute file names
     // so just return "".
@@ -172,14 +330,147 @@ fn file_expand(
     Ok(expanded)
 }
 
+fn compile_error_expand(
+    _db: &dyn AstDatabase,
+    _id: MacroCallId,
+    tt: &tt::Subtree,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    // Expand to a diverging expression, so that the surrounding code can still
+    // be type checked.
+    if let [tt::TokenTree::Leaf(tt::Leaf::Literal(it))] = tt.token_trees.as_slice() {
+        if it.text.contains('"') {
+            let it = it.clone();
+            return Ok(quote! { loop { #it } });
+        }
+    }
+
+    Err(mbe::ExpandError::BindingError("`compile_error!` takes 1 string literal".into()))
+}
+
+fn concat_expand(
+    _db: &dyn AstDatabase,
+    _id: MacroCallId,
+    tt: &tt::Subtree,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    let mut text = String::new();
+    let mut tokens = tt.token_trees.iter().peekable();
+    loop {
+        let is_negative = match tokens.peek() {
+            Some(tt::TokenTree::Leaf(tt::Leaf::Punct(punct))) if punct.char == '-' => {
+                tokens.next();
+                true
+            }
+            _ => false,
+        };
+        match tokens.next() {
+            Some(tt::TokenTree::Leaf(tt::Leaf::Literal(it))) => {
+                if it.text.starts_with(|c: char| c.is_ascii_digit()) {
+                    if is_negative {
+                        text.push('-');
+                    }
+                    text.push_str(&it.text);
+                } else if is_negative || it.text.starts_with('b') {
+                    // Only numbers can be negated, and byte strings and
+                    // bytes can't be concatenated.
+                    return Err(mbe::ExpandError::UnexpectedToken);
+                } else {
+                    // String, raw string or char literal.
+                    let value = unquote_str(it).ok_or(mbe::ExpandError::ConversionError)?;
+                    text.push_str(&value);
+                }
+            }
+            Some(tt::TokenTree::Leaf(tt::Leaf::Ident(it)))
+                if !is_negative && (it.text == "true" || it.text == "false") =>
+            {
+                text.push_str(&it.text)
+            }
+            // No arguments, or a trailing comma.
+            None if !is_negative => break,
+            _ => return Err(mbe::ExpandError::UnexpectedToken),
+        }
+        match tokens.next() {
+            Some(tt::TokenTree::Leaf(tt::Leaf::Punct(punct))) if punct.char == ',' => (),
+            None => break,
+            _ => return Err(mbe::ExpandError::UnexpectedToken),
+        }
+    }
+    let text = str_literal(&text);
+
+    Ok(quote! { #text })
+}
+
+fn env_expand(
+    db: &dyn AstDatabase,
+    id: MacroCallId,
+    tt: &tt::Subtree,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    let key = parse_string(tt)?;
+    let loc = db.lookup_intern_macro(id);
+    // Variables which are not known to us (for example, the ones set by cargo
+    // itself) are expanded to an empty string instead of an error, so that the
+    // expression still has the type `&str`.
+    let value = db.crate_graph().env(loc.krate).get(&key).unwrap_or_default();
+    let value = str_literal(&value);
+
+    Ok(quote! { #value })
+}
+
+fn option_env_expand(
+    db: &dyn AstDatabase,
+    id: MacroCallId,
+    tt: &tt::Subtree,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    let key = parse_string(tt)?;
+    let loc = db.lookup_intern_macro(id);
+    let expanded = match db.crate_graph().env(loc.krate).get(&key) {
+        Some(value) => {
+            let value = str_literal(&value);
+            quote! { std::option::Option::Some(#value) }
+        }
+        None => quote! { std::option::Option::None::<&str> },
+    };
+
+    Ok(expanded)
+}
+
+fn include_expand(
+    db: &dyn AstDatabase,
+    id: MacroCallId,
+    tt: &tt::Subtree,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    let file_id = resolve_include(db, id, tt).ok_or(mbe::ExpandError::ConversionError)?;
+    let file = db.parse(file_id).tree();
+    let (subtree, _token_map) =
+        mbe::syntax_node_to_token_tree(file.syntax()).ok_or(mbe::ExpandError::ConversionError)?;
+
+    Ok(subtree)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{test_db::TestDB, MacroCallLoc};
+    use crate::{test_db::TestDB, MacroCallKind, MacroCallLoc};
     use ra_db::{fixture::WithFixture, SourceDatabase};
 
     fn expand_builtin_macro(s: &str, expander: BuiltinFnLikeExpander) -> String {
         let (db, file_id) = TestDB::with_single_file(&s);
+        expand_macro(&db, file_id, MacroDefKind::BuiltIn(expander))
+    }
+
+    fn expand_eager_macro(ra_fixture: &str, expander: EagerExpander) -> String {
+        try_expand_eager_macro(ra_fixture, expander).unwrap()
+    }
+
+    fn try_expand_eager_macro(ra_fixture: &str, expander: EagerExpander) -> Option<String> {
+        let db = TestDB::with_files(ra_fixture);
+        try_expand_macro(&db, FileId(0), MacroDefKind::BuiltInEager(expander))
+    }
+
+    fn expand_macro(db: &TestDB, file_id: FileId, kind: MacroDefKind) -> String {
+        try_expand_macro(db, file_id, kind).unwrap()
+    }
+
+    fn try_expand_macro(db: &TestDB, file_id: FileId, kind: MacroDefKind) -> Option<String> {
         let parsed = db.parse(file_id);
         let macro_calls: Vec<_> =
             parsed.syntax_node().descendants().filter_map(|it| ast::MacroCall::cast(it)).collect();
@@ -189,19 +480,23 @@ mod tests {
         // the first one should be a macro_rules
         let def = MacroDefId {
             krate: CrateId(0),
-            ast_id: AstId::new(file_id.into(), ast_id_map.ast_id(&macro_calls[0])),
-            kind: MacroDefKind::BuiltIn(expander),
+            ast_id: Some(AstId::new(file_id.into(), ast_id_map.ast_id(&macro_calls[0]))),
+            kind,
         };
 
         let loc = MacroCallLoc {
             def,
-            ast_id: AstId::new(file_id.into(), ast_id_map.ast_id(&macro_calls[1])),
+            krate: CrateId(0),
+            kind: MacroCallKind::FnLike(AstId::new(
+                file_id.into(),
+                ast_id_map.ast_id(&macro_calls[1]),
+            )),
         };
 
         let id = db.intern_macro(loc);
-        let parsed = db.parse_or_expand(id.as_file(MacroFileKind::Expr)).unwrap();
+        let parsed = db.parse_or_expand(id.as_file(MacroFileKind::Expr))?;
 
-        parsed.text().to_string()
+        Some(parsed.text().to_string())
     }
 
     #[test]
@@ -259,4 +554,111 @@ mod tests {
 
         assert_eq!(expanded, "\"\"");
     }
+
+    #[test]
+    fn test_compile_error_expand() {
+        let expanded = expand_builtin_macro(
+            r#"
+        #[rustc_builtin_macro]
+        macro_rules! compile_error {() => {}}
+        compile_error!("error!")
+"#,
+            BuiltinFnLikeExpander::CompileError,
+        );
+
+        assert_eq!(expanded, r#"loop{"error!"}"#);
+    }
+
+    #[test]
+    fn test_concat_expand() {
+        let expanded = expand_eager_macro(
+            r#"
+        //- /main.rs crate:main env:NAME=ra
+        #[rustc_builtin_macro]
+        macro_rules! concat {() => {}}
+        concat!("hello ", env!("NAME"), ", ", 92, 'c', true)
+"#,
+            EagerExpander::Concat,
+        );
+
+        assert_eq!(expanded, r#""hello ra, 92ctrue""#);
+    }
+
+    #[test]
+    fn test_concat_expand_negative_numbers_and_chars() {
+        let expanded = expand_eager_macro(
+            r#"
+        //- /main.rs crate:main
+        #[rustc_builtin_macro]
+        macro_rules! concat {() => {}}
+        concat!(-1, "x", 'a', -2.5,)
+"#,
+            EagerExpander::Concat,
+        );
+
+        assert_eq!(expanded, r#""-1xa-2.5""#);
+    }
+
+    #[test]
+    fn test_concat_expand_rejects_byte_strings() {
+        let expanded = try_expand_eager_macro(
+            r#"
+        //- /main.rs crate:main
+        #[rustc_builtin_macro]
+        macro_rules! concat {() => {}}
+        concat!("x", b"y")
+"#,
+            EagerExpander::Concat,
+        );
+
+        assert_eq!(expanded, None);
+    }
+
+    #[test]
+    fn test_env_expand() {
+        let expanded = expand_eager_macro(
+            r#"
+        //- /main.rs crate:main env:OUT_DIR=/out
+        #[rustc_builtin_macro]
+        macro_rules! env {() => {}}
+        env!("OUT_DIR")
+"#,
+            EagerExpander::Env,
+        );
+
+        assert_eq!(expanded, r#""/out""#);
+    }
+
+    #[test]
+    fn test_option_env_expand() {
+        let expanded = expand_eager_macro(
+            r#"
+        //- /main.rs crate:main env:PROFILE=debug
+        #[rustc_builtin_macro]
+        macro_rules! option_env {() => {}}
+        option_env!("PROFILE")
+"#,
+            EagerExpander::OptionEnv,
+        );
+
+        assert_eq!(expanded, r#"std::option::Option::Some("debug")"#);
+    }
+
+    #[test]
+    fn test_include_expand() {
+        let expanded = expand_eager_macro(
+            r#"
+        //- /main.rs
+        #[rustc_builtin_macro]
+        macro_rules! include {() => {}}
+        include!(concat!("gen", "/value.rs"))
+
+        //- /gen/value.rs
+        "included"
+"#,
+            EagerExpander::Include,
+        );
+
+        assert_eq!(expanded, r#""included""#);
+    }
 }
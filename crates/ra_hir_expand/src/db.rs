@@ -6,17 +6,21 @@ use mbe::MacroRules;
 use ra_db::{salsa, SourceDatabase};
 use ra_parser::FragmentKind;
 use ra_prof::profile;
-use ra_syntax::{AstNode, Parse, SyntaxNode};
+use ra_syntax::{ast, AstNode, Parse, SyntaxNode};
 
 use crate::{
-    ast_id_map::AstIdMap, BuiltinFnLikeExpander, HirFileId, HirFileIdRepr, MacroCallId,
-    MacroCallLoc, MacroDefId, MacroDefKind, MacroFile, MacroFileKind,
+    ast_id_map::AstIdMap, builtin_derive::BuiltinDeriveExpander, builtin_macro::EagerExpander,
+    proc_macro::ProcMacroExpander, BuiltinFnLikeExpander, HirFileId, HirFileIdRepr, MacroCallId,
+    MacroCallKind, MacroCallLoc, MacroDefId, MacroDefKind, MacroFile, MacroFileKind,
 };
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum TokenExpander {
     MacroRules(mbe::MacroRules),
     Builtin(BuiltinFnLikeExpander),
+    BuiltinEager(EagerExpander),
+    BuiltinDerive(BuiltinDeriveExpander),
+    ProcMacro(ProcMacroExpander),
 }
 
 impl TokenExpander {
@@ -29,20 +33,29 @@ impl TokenExpander {
         match self {
             TokenExpander::MacroRules(it) => it.expand(tt),
             TokenExpander::Builtin(it) => it.expand(db, id, tt),
+            TokenExpander::BuiltinEager(it) => it.expand(db, id, tt),
+            TokenExpander::BuiltinDerive(it) => it.expand(db, id, tt),
+            TokenExpander::ProcMacro(it) => it.expand(db, id, tt),
         }
     }
 
     pub fn map_id_down(&self, id: tt::TokenId) -> tt::TokenId {
         match self {
             TokenExpander::MacroRules(it) => it.map_id_down(id),
-            TokenExpander::Builtin(..) => id,
+            TokenExpander::Builtin(..)
+            | TokenExpander::BuiltinEager(..)
+            | TokenExpander::BuiltinDerive(..)
+            | TokenExpander::ProcMacro(..) => id,
         }
     }
 
     pub fn map_id_up(&self, id: tt::TokenId) -> (tt::TokenId, mbe::Origin) {
         match self {
             TokenExpander::MacroRules(it) => it.map_id_up(id),
-            TokenExpander::Builtin(..) => (id, mbe::Origin::Def),
+            TokenExpander::Builtin(..) | TokenExpander::BuiltinEager(..) => (id, mbe::Origin::Def),
+            TokenExpander::BuiltinDerive(..) | TokenExpander::ProcMacro(..) => {
+                (id, mbe::Origin::Call)
+            }
         }
     }
 }
@@ -76,7 +89,7 @@ pub(crate) fn macro_def(
 ) -> Option<Arc<(TokenExpander, mbe::TokenMap)>> {
     match id.kind {
         MacroDefKind::Declarative => {
-            let macro_call = id.ast_id.to_node(db);
+            let macro_call = id.ast_id?.to_node(db);
             let arg = macro_call.token_tree()?;
             let (tt, tmap) = mbe::ast_to_token_tree(&arg).or_else(|| {
                 log::warn!("fail on macro_def to token tree: {:#?}", arg);
@@ -91,6 +104,15 @@ pub(crate) fn macro_def(
         MacroDefKind::BuiltIn(expander) => {
             Some(Arc::new((TokenExpander::Builtin(expander.clone()), mbe::TokenMap::default())))
         }
+        MacroDefKind::BuiltInEager(expander) => {
+            Some(Arc::new((TokenExpander::BuiltinEager(expander), mbe::TokenMap::default())))
+        }
+        MacroDefKind::BuiltInDerive(expander) => {
+            Some(Arc::new((TokenExpander::BuiltinDerive(expander), mbe::TokenMap::default())))
+        }
+        MacroDefKind::ProcMacro(expander) => {
+            Some(Arc::new((TokenExpander::ProcMacro(expander), mbe::TokenMap::default())))
+        }
     }
 }
 
@@ -99,9 +121,13 @@ pub(crate) fn macro_arg(
     id: MacroCallId,
 ) -> Option<Arc<(tt::Subtree, mbe::TokenMap)>> {
     let loc = db.lookup_intern_macro(id);
-    let macro_call = loc.ast_id.to_node(db);
-    let arg = macro_call.token_tree()?;
-    let (tt, tmap) = mbe::ast_to_token_tree(&arg)?;
+    let arg = loc.kind.arg(db)?;
+    // Only the items attribute-like macros are applied to need the conversion
+    // of arbitrary syntax.
+    let (tt, tmap) = match &loc.kind {
+        MacroCallKind::FnLike(_) => mbe::ast_to_token_tree(&ast::TokenTree::cast(arg)?)?,
+        MacroCallKind::Derive(_) | MacroCallKind::Attr(..) => mbe::syntax_node_to_token_tree(&arg)?,
+    };
     Some(Arc::new((tt, tmap)))
 }
 
@@ -26,7 +26,10 @@ impl Hygiene {
                 let loc = db.lookup_intern_macro(macro_file.macro_call_id);
                 match loc.def.kind {
                     MacroDefKind::Declarative => Some(loc.def.krate),
-                    MacroDefKind::BuiltIn(_) => None,
+                    MacroDefKind::BuiltIn(_)
+                    | MacroDefKind::BuiltInEager(_)
+                    | MacroDefKind::BuiltInDerive(_)
+                    | MacroDefKind::ProcMacro(_) => None,
                 }
             }
         };
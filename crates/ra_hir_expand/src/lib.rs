@@ -11,6 +11,8 @@ pub mod name;
 pub mod hygiene;
 pub mod diagnostics;
 pub mod builtin_macro;
+pub mod builtin_derive;
+pub mod proc_macro;
 pub mod quote;
 
 use std::hash::{Hash, Hasher};
@@ -24,7 +26,10 @@ use ra_syntax::{
 };
 
 use crate::ast_id_map::FileAstId;
-use crate::builtin_macro::BuiltinFnLikeExpander;
+use crate::builtin_derive::BuiltinDeriveExpander;
+use crate::builtin_macro::{BuiltinFnLikeExpander, EagerExpander};
+use crate::name::Name;
+use crate::proc_macro::ProcMacroExpander;
 
 #[cfg(test)]
 mod test_db;
@@ -70,7 +75,19 @@ impl HirFileId {
             HirFileIdRepr::FileId(file_id) => file_id,
             HirFileIdRepr::MacroFile(macro_file) => {
                 let loc = db.lookup_intern_macro(macro_file.macro_call_id);
-                loc.ast_id.file_id().original_file(db)
+                loc.kind.file_id().original_file(db)
+            }
+        }
+    }
+
+    /// For macro-expansion files, returns the macro call the file is an
+    /// expansion of.
+    pub fn call_node(self, db: &dyn db::AstDatabase) -> Option<Source<SyntaxNode>> {
+        match self.0 {
+            HirFileIdRepr::FileId(_) => None,
+            HirFileIdRepr::MacroFile(macro_file) => {
+                let loc = db.lookup_intern_macro(macro_file.macro_call_id);
+                Some(loc.kind.node(db))
             }
         }
     }
@@ -82,8 +99,11 @@ impl HirFileId {
             HirFileIdRepr::MacroFile(macro_file) => {
                 let loc: MacroCallLoc = db.lookup_intern_macro(macro_file.macro_call_id);
 
-                let arg_tt = loc.ast_id.to_node(db).token_tree()?;
-                let def_tt = loc.def.ast_id.to_node(db).token_tree()?;
+                let arg_tt = loc.kind.arg(db)?;
+                let def = loc.def.ast_id.and_then(|id| {
+                    let def_tt = id.to_node(db).token_tree()?;
+                    Some(Source::new(id.file_id, def_tt))
+                });
 
                 let macro_def = db.macro_def(loc.def)?;
                 let (parse, exp_map) = db.parse_macro(macro_file)?;
@@ -91,8 +111,8 @@ impl HirFileId {
 
                 Some(ExpansionInfo {
                     expanded: Source::new(self, parse.syntax_node()),
-                    arg: Source::new(loc.ast_id.file_id, arg_tt),
-                    def: Source::new(loc.ast_id.file_id, def_tt),
+                    arg: Source::new(loc.kind.file_id(), arg_tt),
+                    def,
                     macro_arg,
                     macro_def,
                     exp_map,
@@ -131,7 +151,8 @@ impl salsa::InternKey for MacroCallId {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct MacroDefId {
     pub krate: CrateId,
-    pub ast_id: AstId<ast::MacroCall>,
+    /// `None` for procedural macros, which are not defined by a macro call.
+    pub ast_id: Option<AstId<ast::MacroCall>>,
     pub kind: MacroDefKind,
 }
 
@@ -139,12 +160,64 @@ pub struct MacroDefId {
 pub enum MacroDefKind {
     Declarative,
     BuiltIn(BuiltinFnLikeExpander),
+    /// Builtin macros like `concat!` and `include!`, whose arguments are
+    /// expanded before the macro itself.
+    BuiltInEager(EagerExpander),
+    BuiltInDerive(BuiltinDeriveExpander),
+    ProcMacro(ProcMacroExpander),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MacroCallLoc {
     pub def: MacroDefId,
-    pub ast_id: AstId<ast::MacroCall>,
+    /// The crate containing the call. Note that it differs from `def.krate`
+    /// for macros defined in other crates.
+    pub krate: CrateId,
+    pub kind: MacroCallKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MacroCallKind {
+    /// `foo!(...)`
+    FnLike(AstId<ast::MacroCall>),
+    /// `#[derive(Foo)]` applied to an item, the id points to the item.
+    Derive(AstId<ast::ModuleItem>),
+    /// `#[foo]` attribute macro applied to an item, the id points to the
+    /// item. The name is the last segment of the attribute path.
+    Attr(AstId<ast::ModuleItem>, Name),
+}
+
+impl MacroCallKind {
+    pub fn file_id(&self) -> HirFileId {
+        match self {
+            MacroCallKind::FnLike(ast_id) => ast_id.file_id(),
+            MacroCallKind::Derive(ast_id) | MacroCallKind::Attr(ast_id, _) => ast_id.file_id(),
+        }
+    }
+
+    pub fn node(&self, db: &dyn db::AstDatabase) -> Source<SyntaxNode> {
+        match self {
+            MacroCallKind::FnLike(ast_id) => {
+                Source::new(ast_id.file_id(), ast_id.to_node(db).syntax().clone())
+            }
+            MacroCallKind::Derive(ast_id) | MacroCallKind::Attr(ast_id, _) => {
+                Source::new(ast_id.file_id(), ast_id.to_node(db).syntax().clone())
+            }
+        }
+    }
+
+    /// The syntax the macro is applied to: the token tree of a function-like
+    /// macro call, or the whole item for attribute-like macros.
+    pub fn arg(&self, db: &dyn db::AstDatabase) -> Option<SyntaxNode> {
+        match self {
+            MacroCallKind::FnLike(ast_id) => {
+                Some(ast_id.to_node(db).token_tree()?.syntax().clone())
+            }
+            MacroCallKind::Derive(ast_id) | MacroCallKind::Attr(ast_id, _) => {
+                Some(ast_id.to_node(db).syntax().clone())
+            }
+        }
+    }
 }
 
 impl MacroCallId {
@@ -158,8 +231,8 @@ impl MacroCallId {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ExpansionInfo {
     expanded: Source<SyntaxNode>,
-    arg: Source<ast::TokenTree>,
-    def: Source<ast::TokenTree>,
+    arg: Source<SyntaxNode>,
+    def: Option<Source<ast::TokenTree>>,
 
     macro_def: Arc<(db::TokenExpander, mbe::TokenMap)>,
     macro_arg: Arc<(tt::Subtree, mbe::TokenMap)>,
@@ -169,8 +242,7 @@ pub struct ExpansionInfo {
 impl ExpansionInfo {
     pub fn map_token_down(&self, token: Source<&SyntaxToken>) -> Option<Source<SyntaxToken>> {
         assert_eq!(token.file_id, self.arg.file_id);
-        let range =
-            token.value.text_range().checked_sub(self.arg.value.syntax().text_range().start())?;
+        let range = token.value.text_range().checked_sub(self.arg.value.text_range().start())?;
         let token_id = self.macro_arg.1.token_by_range(range)?;
         let token_id = self.macro_def.0.map_id_down(token_id);
 
@@ -186,16 +258,15 @@ impl ExpansionInfo {
 
         let (token_id, origin) = self.macro_def.0.map_id_up(token_id);
         let (token_map, tt) = match origin {
-            mbe::Origin::Call => (&self.macro_arg.1, &self.arg),
-            mbe::Origin::Def => (&self.macro_def.1, &self.def),
+            mbe::Origin::Call => (&self.macro_arg.1, self.arg.clone()),
+            mbe::Origin::Def => {
+                (&self.macro_def.1, self.def.as_ref()?.as_ref().map(|tt| tt.syntax().clone()))
+            }
         };
 
         let range = token_map.range_by_token(token_id)?;
-        let token = algo::find_covering_element(
-            tt.value.syntax(),
-            range + tt.value.syntax().text_range().start(),
-        )
-        .into_token()?;
+        let token = algo::find_covering_element(&tt.value, range + tt.value.text_range().start())
+            .into_token()?;
         Some(tt.with_value(token))
     }
 }
@@ -83,6 +83,12 @@ impl AsName for ast::Name {
     }
 }
 
+impl AsName for tt::Ident {
+    fn as_name(&self) -> Name {
+        Name::resolve(&self.text)
+    }
+}
+
 impl AsName for ast::FieldKind {
     fn as_name(&self) -> Name {
         match self {
@@ -98,6 +104,12 @@ impl AsName for ra_db::Dependency {
     }
 }
 
+impl AsName for ra_db::ProcMacro {
+    fn as_name(&self) -> Name {
+        Name::new_text(self.name.clone())
+    }
+}
+
 // Primitives
 pub const ISIZE: Name = Name::new_inline_ascii(5, b"isize");
 pub const I8: Name = Name::new_inline_ascii(2, b"i8");
@@ -124,6 +136,7 @@ pub const MACRO_RULES: Name = Name::new_inline_ascii(11, b"macro_rules");
 
 // Components of known path (value or mod name)
 pub const STD: Name = Name::new_inline_ascii(3, b"std");
+pub const CORE: Name = Name::new_inline_ascii(4, b"core");
 pub const ITER: Name = Name::new_inline_ascii(4, b"iter");
 pub const OPS: Name = Name::new_inline_ascii(3, b"ops");
 pub const FUTURE: Name = Name::new_inline_ascii(6, b"future");
@@ -146,3 +159,19 @@ pub const FILE_MACRO: Name = Name::new_inline_ascii(4, b"file");
 pub const COLUMN_MACRO: Name = Name::new_inline_ascii(6, b"column");
 pub const LINE_MACRO: Name = Name::new_inline_ascii(4, b"line");
 pub const STRINGIFY_MACRO: Name = Name::new_inline_ascii(9, b"stringify");
+pub const COMPILE_ERROR_MACRO: Name = Name::new_inline_ascii(13, b"compile_error");
+pub const CONCAT_MACRO: Name = Name::new_inline_ascii(6, b"concat");
+pub const ENV_MACRO: Name = Name::new_inline_ascii(3, b"env");
+pub const OPTION_ENV_MACRO: Name = Name::new_inline_ascii(10, b"option_env");
+pub const INCLUDE_MACRO: Name = Name::new_inline_ascii(7, b"include");
+
+// Builtin derives
+pub const COPY_TRAIT: Name = Name::new_inline_ascii(4, b"Copy");
+pub const CLONE_TRAIT: Name = Name::new_inline_ascii(5, b"Clone");
+pub const DEFAULT_TRAIT: Name = Name::new_inline_ascii(7, b"Default");
+pub const DEBUG_TRAIT: Name = Name::new_inline_ascii(5, b"Debug");
+pub const HASH_TRAIT: Name = Name::new_inline_ascii(4, b"Hash");
+pub const EQ_TRAIT: Name = Name::new_inline_ascii(2, b"Eq");
+pub const PARTIAL_EQ_TRAIT: Name = Name::new_inline_ascii(9, b"PartialEq");
+pub const ORD_TRAIT: Name = Name::new_inline_ascii(3, b"Ord");
+pub const PARTIAL_ORD_TRAIT: Name = Name::new_inline_ascii(10, b"PartialOrd");
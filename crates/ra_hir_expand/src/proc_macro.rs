@@ -0,0 +1,203 @@
+//! Proc Macro Expander stub
+
+use crate::{db::AstDatabase, name::AsName, MacroCallId, MacroCallKind};
+use ra_db::{CrateId, ProcMacroId, ProcMacroKind};
+use tt::{Delimiter, Leaf, Subtree, TokenTree};
+
+/// Expands a procedural macro exported by the `proc-macro` crate `krate`. The
+/// expander itself is registered in the crate graph.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct ProcMacroExpander {
+    krate: CrateId,
+    proc_macro_id: ProcMacroId,
+    kind: ProcMacroKind,
+}
+
+impl ProcMacroExpander {
+    pub fn new(
+        krate: CrateId,
+        proc_macro_id: ProcMacroId,
+        kind: ProcMacroKind,
+    ) -> ProcMacroExpander {
+        ProcMacroExpander { krate, proc_macro_id, kind }
+    }
+
+    pub fn kind(&self) -> ProcMacroKind {
+        self.kind
+    }
+
+    pub fn expand(
+        &self,
+        db: &dyn AstDatabase,
+        id: MacroCallId,
+        tt: &tt::Subtree,
+    ) -> Result<tt::Subtree, mbe::ExpandError> {
+        let crate_graph = db.crate_graph();
+        let proc_macro = crate_graph
+            .proc_macros(self.krate)
+            .get(self.proc_macro_id.0 as usize)
+            .ok_or_else(|| mbe::ExpandError::ConversionError)?;
+
+        let loc = db.lookup_intern_macro(id);
+        let res = match (self.kind, &loc.kind) {
+            (ProcMacroKind::FuncLike, MacroCallKind::FnLike(_)) => {
+                // Function-like proc macros see the tokens inside the call delimiters only.
+                let input =
+                    Subtree { delimiter: Delimiter::None, token_trees: tt.token_trees.clone() };
+                proc_macro.expander.expand(&input, None)
+            }
+            (ProcMacroKind::CustomDerive, MacroCallKind::Derive(_)) => {
+                let (input, _) =
+                    remove_attrs(tt, |path| path.len() == 1 && path[0].text == "derive");
+                proc_macro.expander.expand(&input, None)
+            }
+            (ProcMacroKind::Attr, MacroCallKind::Attr(_, name)) => {
+                // The macro gets the item without the attribute, and the
+                // arguments of the attribute separately.
+                let mut found = false;
+                let (input, args) = remove_attrs(tt, |path| {
+                    let is_match =
+                        !found && path.last().map(|it| it.as_name()).as_ref() == Some(name);
+                    found |= is_match;
+                    is_match
+                });
+                let args =
+                    args.into_iter().next().ok_or_else(|| mbe::ExpandError::ConversionError)?;
+                proc_macro.expander.expand(&input, Some(&args))
+            }
+            _ => return Err(mbe::ExpandError::ConversionError),
+        };
+        res.map_err(mbe::ExpandError::from)
+    }
+}
+
+/// Removes the outer attributes for which `matches` returns true from the
+/// token trees of an item, returning the item and the arguments of the
+/// removed attributes. `matches` gets the identifiers of the attribute path.
+fn remove_attrs(
+    item: &Subtree,
+    mut matches: impl FnMut(&[&tt::Ident]) -> bool,
+) -> (Subtree, Vec<Subtree>) {
+    let mut token_trees = Vec::with_capacity(item.token_trees.len());
+    let mut args = Vec::new();
+    let mut iter = item.token_trees.iter().peekable();
+    while let Some(tt) = iter.next() {
+        if let TokenTree::Leaf(Leaf::Punct(punct)) = tt {
+            if punct.char == '#' {
+                if let Some(TokenTree::Subtree(attr)) = iter.peek() {
+                    if attr.delimiter == Delimiter::Bracket {
+                        let path = attr_path(attr);
+                        if matches(&path) {
+                            args.push(attr_args(attr, path.len()));
+                            iter.next();
+                            continue;
+                        }
+                    }
+                }
+            }
+        }
+        token_trees.push(tt.clone());
+    }
+    (Subtree { delimiter: item.delimiter, token_trees }, args)
+}
+
+/// Returns the identifiers of the path at the start of the tokens of an
+/// attribute, like `serde` and `rename` for `serde::rename = "x"`.
+fn attr_path(attr: &Subtree) -> Vec<&tt::Ident> {
+    attr.token_trees
+        .iter()
+        .take_while(|tt| match tt {
+            TokenTree::Leaf(Leaf::Ident(_)) => true,
+            TokenTree::Leaf(Leaf::Punct(punct)) => punct.char == ':',
+            _ => false,
+        })
+        .filter_map(|tt| match tt {
+            TokenTree::Leaf(Leaf::Ident(ident)) => Some(ident),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the tokens after the path of an attribute, without the delimiters
+/// of `#[foo(...)]`.
+fn attr_args(attr: &Subtree, path_len: usize) -> Subtree {
+    let mut idents = 0;
+    let rest: Vec<&TokenTree> = attr
+        .token_trees
+        .iter()
+        .skip_while(|tt| match tt {
+            TokenTree::Leaf(Leaf::Ident(_)) if idents < path_len => {
+                idents += 1;
+                true
+            }
+            TokenTree::Leaf(Leaf::Punct(punct)) => punct.char == ':',
+            _ => false,
+        })
+        .collect();
+    let token_trees = match rest.as_slice() {
+        [TokenTree::Subtree(args)] => args.token_trees.clone(),
+        _ => rest.into_iter().cloned().collect(),
+    };
+    Subtree { delimiter: Delimiter::None, token_trees }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ident(text: &str) -> TokenTree {
+        tt::Ident { text: text.into(), id: tt::TokenId::unspecified() }.into()
+    }
+
+    fn punct(char: char) -> TokenTree {
+        tt::Punct { char, spacing: tt::Spacing::Alone }.into()
+    }
+
+    fn subtree(delimiter: Delimiter, token_trees: Vec<TokenTree>) -> TokenTree {
+        Subtree { delimiter, token_trees }.into()
+    }
+
+    #[test]
+    fn removes_matching_attributes() {
+        // #[derive(Foo)] #[my::attr(a, b)] struct S;
+        let item = Subtree {
+            delimiter: Delimiter::None,
+            token_trees: vec![
+                punct('#'),
+                subtree(
+                    Delimiter::Bracket,
+                    vec![ident("derive"), subtree(Delimiter::Parenthesis, vec![ident("Foo")])],
+                ),
+                punct('#'),
+                subtree(
+                    Delimiter::Bracket,
+                    vec![
+                        ident("my"),
+                        punct(':'),
+                        punct(':'),
+                        ident("attr"),
+                        subtree(Delimiter::Parenthesis, vec![ident("a"), punct(','), ident("b")]),
+                    ],
+                ),
+                ident("struct"),
+                ident("S"),
+                punct(';'),
+            ],
+        };
+
+        let (input, args) =
+            remove_attrs(&item, |path| path.last().map(|it| it.text.as_str()) == Some("attr"));
+        assert_eq!(input.token_trees.len(), 5);
+        assert_eq!(input.token_trees[..2], item.token_trees[..2]);
+        assert_eq!(
+            args,
+            vec![Subtree {
+                delimiter: Delimiter::None,
+                token_trees: vec![ident("a"), punct(','), ident("b")]
+            }]
+        );
+
+        let (input, _) = remove_attrs(&item, |path| path.len() == 1 && path[0].text == "derive");
+        assert_eq!(input.token_trees[0], item.token_trees[2]);
+    }
+}
@@ -85,7 +85,11 @@ macro_rules! __quote {
     ( & ) => {$crate::__quote!(@PUNCT '&')};
     ( , ) => {$crate::__quote!(@PUNCT ',')};
     ( : ) => {$crate::__quote!(@PUNCT ':')};
+    ( :: ) => {$crate::__quote!(@PUNCT ':', ':')};
+    ( < ) => {$crate::__quote!(@PUNCT '<')};
+    ( > ) => {$crate::__quote!(@PUNCT '>')};
     ( . ) => {$crate::__quote!(@PUNCT '.')};
+    ( + ) => {$crate::__quote!(@PUNCT '+')};
 
     ( $first:tt $($tail:tt)+ ) => {
         {
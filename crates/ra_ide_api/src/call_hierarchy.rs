@@ -0,0 +1,340 @@
+//! Finds the callers and the callees of a function.
+//!
+//! Incoming calls are the references to the function which are calls, grouped
+//! by the function they are in. Outgoing calls are the calls in the body of
+//! the function, grouped by the function they resolve to. A call of a trait
+//! method on a value of a known type is dispatched to the method of the
+//! corresponding impl, if it has one.
+
+use hir::{
+    db::HirDatabase, AssocItem, Crate, FromSource, HasSource, ModuleDef, PathResolution, Source, Ty,
+};
+use ra_db::SourceDatabase;
+use ra_syntax::{
+    algo::find_node_at_offset,
+    ast::{self, NameOwner},
+    AstNode, SyntaxNode, TextRange,
+};
+
+use crate::{
+    db::RootDatabase,
+    display::ToNav,
+    references::{self, classify_name, classify_name_ref, NameKind},
+    FileId, FilePosition, NavigationTarget, RangeInfo,
+};
+
+#[derive(Debug, Clone)]
+pub struct CallItem {
+    /// The caller for incoming calls, the callee for outgoing calls.
+    pub target: NavigationTarget,
+    /// The names of the called function at the call sites, in the file of the
+    /// caller.
+    pub ranges: Vec<TextRange>,
+}
+
+pub(crate) fn call_hierarchy(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Option<RangeInfo<Vec<NavigationTarget>>> {
+    let RangeInfo { range, info: function } = function_at(db, position)?;
+    Some(RangeInfo::new(range, vec![function.to_nav(db)]))
+}
+
+pub(crate) fn incoming_calls(db: &RootDatabase, position: FilePosition) -> Option<Vec<CallItem>> {
+    let function = function_at(db, position)?.info;
+
+    // Calls of the trait method can be dispatched to `function`.
+    let mut searches = vec![(position, false)];
+    if let Some(trait_method) = implemented_trait_method(db, function) {
+        searches.push((name_position(db, trait_method)?, true));
+    }
+
+    let mut calls = CallLocations::default();
+    for (position, needs_dispatch) in searches {
+        let refs = match references::find_all_refs(db, position, None) {
+            Some(it) => it.info,
+            None => continue,
+        };
+        for reference in refs.references() {
+            let file = db.parse(reference.file_id).tree();
+            let name_ref =
+                match find_node_at_offset::<ast::NameRef>(file.syntax(), reference.range.start()) {
+                    Some(it) => it,
+                    None => continue,
+                };
+            let call = match call_of_name_ref(&name_ref) {
+                Some(it) => it,
+                None => continue,
+            };
+            let caller = match enclosing_function(db, reference.file_id, name_ref.syntax()) {
+                Some(it) => it,
+                None => continue,
+            };
+            if needs_dispatch {
+                let krate = caller.module(db).krate();
+                match call_target(db, reference.file_id, krate, &call) {
+                    Some((target, _)) if target == function => (),
+                    _ => continue,
+                }
+            }
+            calls.add(caller, name_ref.syntax().text_range());
+        }
+    }
+    Some(calls.into_items(db))
+}
+
+pub(crate) fn outgoing_calls(db: &RootDatabase, position: FilePosition) -> Option<Vec<CallItem>> {
+    let function = function_at(db, position)?.info;
+    let source = function.source(db);
+    let file_id = source.file_id.original_file(db);
+    let fn_def = source.value.syntax().clone();
+    let body = source.value.body()?;
+    let krate = function.module(db).krate();
+
+    let mut calls = CallLocations::default();
+    for node in body.syntax().descendants() {
+        // Calls in nested functions belong to those functions.
+        if node.ancestors().find(|it| ast::FnDef::can_cast(it.kind())) != Some(fn_def.clone()) {
+            continue;
+        }
+        if let Some((callee, name_ref)) = call_target(db, file_id, krate, &node) {
+            calls.add(callee, name_ref.syntax().text_range());
+        }
+    }
+    Some(calls.into_items(db))
+}
+
+#[derive(Default)]
+struct CallLocations {
+    funcs: Vec<(hir::Function, Vec<TextRange>)>,
+}
+
+impl CallLocations {
+    fn add(&mut self, function: hir::Function, range: TextRange) {
+        match self.funcs.iter_mut().find(|(it, _)| *it == function) {
+            Some((_, ranges)) => ranges.push(range),
+            None => self.funcs.push((function, vec![range])),
+        }
+    }
+
+    fn into_items(self, db: &RootDatabase) -> Vec<CallItem> {
+        self.funcs
+            .into_iter()
+            .map(|(function, ranges)| CallItem { target: function.to_nav(db), ranges })
+            .collect()
+    }
+}
+
+/// Finds the function defined or referred to at `position`.
+pub(crate) fn function_at(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Option<RangeInfo<hir::Function>> {
+    let file = db.parse(position.file_id).tree();
+    let (range, def) =
+        if let Some(name) = find_node_at_offset::<ast::Name>(file.syntax(), position.offset) {
+            let def = classify_name(db, Source::new(position.file_id.into(), &name))?;
+            (name.syntax().text_range(), def)
+        } else {
+            let name_ref = find_node_at_offset::<ast::NameRef>(file.syntax(), position.offset)?;
+            let def = classify_name_ref(db, Source::new(position.file_id.into(), &name_ref))?;
+            (name_ref.syntax().text_range(), def)
+        };
+    match def.kind {
+        NameKind::Def(ModuleDef::Function(it)) | NameKind::AssocItem(AssocItem::Function(it)) => {
+            Some(RangeInfo::new(range, it))
+        }
+        _ => None,
+    }
+}
+
+fn enclosing_function(
+    db: &RootDatabase,
+    file_id: FileId,
+    node: &SyntaxNode,
+) -> Option<hir::Function> {
+    let fn_def = node.ancestors().find_map(ast::FnDef::cast)?;
+    hir::Function::from_source(db, Source::new(file_id.into(), fn_def))
+}
+
+pub(crate) fn name_position(db: &RootDatabase, function: hir::Function) -> Option<FilePosition> {
+    let source = function.source(db);
+    let name = source.value.name()?;
+    let file_id = source.file_id.original_file(db);
+    Some(FilePosition { file_id, offset: name.syntax().text_range().start() })
+}
+
+/// Returns the call expression calling the function named by `name_ref`.
+pub(crate) fn call_of_name_ref(name_ref: &ast::NameRef) -> Option<SyntaxNode> {
+    let parent = name_ref.syntax().parent()?;
+    if let Some(call) = ast::MethodCallExpr::cast(parent.clone()) {
+        return Some(call.syntax().clone());
+    }
+    let path = ast::PathSegment::cast(parent)?.syntax().parent().and_then(ast::Path::cast)?;
+    let path_expr = path.syntax().parent().and_then(ast::PathExpr::cast)?;
+    let call = path_expr.syntax().parent().and_then(ast::CallExpr::cast)?;
+    if call.expr()?.syntax() != path_expr.syntax() {
+        return None;
+    }
+    Some(call.syntax().clone())
+}
+
+/// If `node` is a call of a function, returns the function and the name
+/// referring to it. Trait methods are dispatched using the impls visible from
+/// `krate`.
+fn call_target(
+    db: &RootDatabase,
+    file_id: FileId,
+    krate: Crate,
+    node: &SyntaxNode,
+) -> Option<(hir::Function, ast::NameRef)> {
+    if !ast::MethodCallExpr::can_cast(node.kind()) && !ast::CallExpr::can_cast(node.kind()) {
+        return None;
+    }
+    let analyzer = hir::SourceAnalyzer::new(db, Source::new(file_id.into(), node), None);
+    if let Some(call) = ast::MethodCallExpr::cast(node.clone()) {
+        let function = analyzer.resolve_method_call(&call)?;
+        let function = match analyzer.type_of(db, &call.expr()?) {
+            Some(receiver) => dispatch(db, krate, function, &receiver).unwrap_or(function),
+            None => function,
+        };
+        return Some((function, call.name_ref()?));
+    }
+
+    let call = ast::CallExpr::cast(node.clone())?;
+    let path = match call.expr()? {
+        ast::Expr::PathExpr(it) => it.path()?,
+        _ => return None,
+    };
+    let function = match analyzer.resolve_path(db, &path)? {
+        PathResolution::Def(ModuleDef::Function(it))
+        | PathResolution::AssocItem(AssocItem::Function(it)) => it,
+        _ => return None,
+    };
+    Some((function, path.segment()?.name_ref()?))
+}
+
+/// If `function` is implementing a trait method, returns that method.
+pub(crate) fn implemented_trait_method(
+    db: &RootDatabase,
+    function: hir::Function,
+) -> Option<hir::Function> {
+    let trait_ = function.impl_block(db)?.target_trait_ref(db)?.trait_;
+    let name = function.name(db);
+    trait_.items(db).into_iter().find_map(|item| match item {
+        AssocItem::Function(it) if it.name(db) == name => Some(it),
+        _ => None,
+    })
+}
+
+/// Finds the method of the impl of the trait of `function` for `receiver`.
+fn dispatch(
+    db: &RootDatabase,
+    krate: Crate,
+    function: hir::Function,
+    receiver: &Ty,
+) -> Option<hir::Function> {
+    let trait_ = function.parent_trait(db)?;
+    let name = function.name(db);
+    db.impls_for_trait(krate, trait_)
+        .iter()
+        .filter(|impl_block| same_type_ctor(&impl_block.target_ty(db), receiver))
+        .flat_map(|impl_block| impl_block.items(db))
+        .find_map(|item| match item {
+            AssocItem::Function(it) if it.name(db) == name => Some(it),
+            _ => None,
+        })
+}
+
+fn same_type_ctor(a: &Ty, b: &Ty) -> bool {
+    fn strip_refs(mut ty: &Ty) -> &Ty {
+        while let Some((inner, _)) = ty.as_reference() {
+            ty = inner;
+        }
+        ty
+    }
+    match (strip_refs(a), strip_refs(b)) {
+        (Ty::Apply(a), Ty::Apply(b)) => a.ctor == b.ctor,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock_analysis::single_file_with_position;
+
+    fn check_incoming(fixture: &str, expected: &[&str]) {
+        let (analysis, position) = single_file_with_position(fixture);
+        let calls = analysis.incoming_calls(position).unwrap().unwrap();
+        check_calls(calls, expected);
+    }
+
+    fn check_outgoing(fixture: &str, expected: &[&str]) {
+        let (analysis, position) = single_file_with_position(fixture);
+        let calls = analysis.outgoing_calls(position).unwrap().unwrap();
+        check_calls(calls, expected);
+    }
+
+    fn check_calls(calls: Vec<super::CallItem>, expected: &[&str]) {
+        let actual: Vec<String> = calls
+            .iter()
+            .map(|it| format!("{} {:?} {:?}", it.target.name(), it.target.full_range(), it.ranges))
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_call_hierarchy_on_ref() {
+        let (analysis, position) = single_file_with_position(
+            r#"
+fn foo() {}
+fn main() { fo<|>o(); }
+"#,
+        );
+        let navs = analysis.call_hierarchy(position).unwrap().unwrap().info;
+        assert_eq!(navs.len(), 1);
+        navs[0].assert_match("foo FN_DEF FileId(1) [1; 12) [4; 7)");
+    }
+
+    #[test]
+    fn test_incoming_calls() {
+        check_incoming(
+            r#"
+fn callee<|>() {}
+fn caller1() { callee(); }
+fn caller2() { caller1(); callee(); callee(); }
+"#,
+            &["caller1 [16; 42) [[31; 37)]", "caller2 [43; 90) [[69; 75), [79; 85)]"],
+        );
+    }
+
+    #[test]
+    fn test_incoming_calls_dispatched_to_impl() {
+        check_incoming(
+            r#"
+trait T { fn m(&self); }
+struct S;
+struct U;
+impl T for S { fn m<|>(&self) {} }
+impl T for U { fn m(&self) {} }
+fn a() { S.m(); }
+fn b() { U.m(); }
+"#,
+            &["a [110; 127) [[121; 122)]"],
+        );
+    }
+
+    #[test]
+    fn test_outgoing_calls() {
+        check_outgoing(
+            r#"
+trait T { fn m(&self); }
+struct S;
+impl T for S { fn m(&self) {} }
+fn f() {}
+fn caller<|>() { f(); S.m(); let s = &S; s.m(); }
+"#,
+            &["f [68; 77) [[92; 93)]", "m [51; 65) [[99; 100), [118; 119)]"],
+        );
+    }
+}
@@ -0,0 +1,764 @@
+//! Changes the parameter list of a function, updating all of its calls.
+//!
+//! The new parameter list is written like a regular one, except that the
+//! existing parameters are matched by name and may omit their type, and that
+//! new parameters may have a default value, which is passed as the argument at
+//! the existing call sites: `b, a: u64, flag: bool = false`. Changing a trait
+//! method, or a method implementing one, changes the trait and all of its
+//! impls together.
+
+use std::{error::Error, fmt, iter::Peekable, str::CharIndices};
+
+use format_buf::format;
+use hir::{db::HirDatabase, AssocItem, Crate, HasSource};
+use ra_db::SourceDatabase;
+use ra_fmt::leading_indent;
+use ra_syntax::{
+    algo::{find_node_at_offset, parse_wrapped, splice},
+    ast::{self, ArgListOwner, NameOwner, TypeAscriptionOwner},
+    AstNode,
+    SyntaxKind::{CALL_EXPR, METHOD_CALL_EXPR, PARAM_LIST, PATH_EXPR},
+    SyntaxNode, TextRange,
+};
+use ra_text_edit::TextEditBuilder;
+use rustc_hash::FxHashMap;
+
+use crate::{
+    call_hierarchy::{call_of_name_ref, function_at, implemented_trait_method, name_position},
+    db::RootDatabase,
+    references, FileId, FilePosition, SourceChange, SourceFileEdit,
+};
+
+/// The argument passed for a new parameter without a default value.
+const PLACEHOLDER: &str = "unimplemented!()";
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ChangeSignatureError(String);
+
+impl fmt::Display for ChangeSignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl Error for ChangeSignatureError {}
+
+pub(crate) fn change_signature(
+    db: &RootDatabase,
+    position: FilePosition,
+    new_params: &str,
+) -> Result<SourceChange, ChangeSignatureError> {
+    let function = function_at(db, position)
+        .ok_or_else(|| ChangeSignatureError("No function at the cursor".to_string()))?
+        .info;
+    let function = implemented_trait_method(db, function).unwrap_or(function);
+    let name = function.name(db).to_string();
+    let source = function.source(db);
+    let file_id = source.file_id.original_file(db);
+    if source.file_id != file_id.into() {
+        return Err(ChangeSignatureError(format!("`{}` is defined by a macro", name)));
+    }
+    if db.source_root(db.file_source_root(file_id)).is_library {
+        return Err(ChangeSignatureError(format!(
+            "`{}` is defined outside of the workspace",
+            name
+        )));
+    }
+    let old_params: Vec<ast::Param> =
+        source.value.param_list().into_iter().flat_map(|it| it.params()).collect();
+    let params = parse_params(new_params, &old_params, &name)?;
+
+    let mut functions = vec![function];
+    if let Some(trait_) = function.parent_trait(db) {
+        for krate in Crate::all(db) {
+            for impl_block in db.impls_for_trait(krate, trait_).iter() {
+                for item in impl_block.items(db) {
+                    match item {
+                        AssocItem::Function(it)
+                            if it.name(db) == function.name(db) && !functions.contains(&it) =>
+                        {
+                            functions.push(it)
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+
+    let has_self_param = function.has_self_param(db);
+    let mut sites: FxHashMap<FileId, Vec<(TextRange, Site)>> = FxHashMap::default();
+    let mut unsupported_refs = 0;
+    for &function in functions.iter() {
+        let source = function.source(db);
+        let file_id = source.file_id.original_file(db);
+        if source.file_id != file_id.into() {
+            return Err(ChangeSignatureError(format!(
+                "An implementation of `{}` is defined by a macro",
+                name
+            )));
+        }
+        if let Some(param_list) = source.value.param_list() {
+            let range = param_list.syntax().text_range();
+            sites.entry(file_id).or_default().push((range, Site::Declaration));
+        }
+
+        let refs = match name_position(db, function)
+            .and_then(|position| references::find_all_refs(db, position, None))
+        {
+            Some(it) => it.info,
+            None => continue,
+        };
+        for reference in refs.references() {
+            let file = db.parse(reference.file_id).tree();
+            let name_ref =
+                match find_node_at_offset::<ast::NameRef>(file.syntax(), reference.range.start()) {
+                    Some(it) => it,
+                    // The reference is in the arguments of a macro call.
+                    None => {
+                        unsupported_refs += 1;
+                        continue;
+                    }
+                };
+            let call = match call_of_name_ref(&name_ref) {
+                Some(it) => it,
+                None => {
+                    if is_value(&name_ref) {
+                        unsupported_refs += 1;
+                    }
+                    continue;
+                }
+            };
+            let file_sites = sites.entry(reference.file_id).or_default();
+            let range = call.text_range();
+            if file_sites.iter().all(|(it, _)| *it != range) {
+                // A method called through a path gets `self` as the first
+                // argument.
+                let self_arg = has_self_param && call.kind() == CALL_EXPR;
+                file_sites.push((range, Site::Call { self_arg }));
+            }
+        }
+    }
+    if unsupported_refs > 0 {
+        return Err(ChangeSignatureError(format!(
+            "Cannot change the signature of `{}`: {} reference(s) are not calls or are in macro calls",
+            name, unsupported_refs
+        )));
+    }
+
+    let mut edits: Vec<SourceFileEdit> = sites
+        .into_iter()
+        .map(|(file_id, sites)| {
+            let file = db.parse(file_id).tree();
+            let renderer = Renderer { sites: &sites, params: &params };
+            let mut edit = TextEditBuilder::default();
+            for (range, text) in renderer.replacements(file.syntax()) {
+                edit.replace(range, text);
+            }
+            SourceFileEdit { file_id, edit: edit.finish() }
+        })
+        .collect();
+    edits.sort_by_key(|it| it.file_id);
+    Ok(SourceChange::source_file_edits("change signature", edits))
+}
+
+/// Whether `name_ref` refers to the function as a value, like in
+/// `iter.map(foo)`.
+fn is_value(name_ref: &ast::NameRef) -> bool {
+    name_ref
+        .syntax()
+        .ancestors()
+        .skip(1)
+        .find(|it| !ast::PathSegment::can_cast(it.kind()) && !ast::Path::can_cast(it.kind()))
+        .map_or(false, |it| it.kind() == PATH_EXPR)
+}
+
+/// A parameter of the new signature.
+#[derive(Debug)]
+struct NewParam {
+    /// The index of the parameter in the old signature, not counting `self`.
+    old_index: Option<usize>,
+    /// The name bound by the pattern, as named in the edited function.
+    name: String,
+    pat: String,
+    /// Whether the pattern of an existing parameter was changed.
+    pat_changed: bool,
+    ty: Option<String>,
+    default: Option<String>,
+}
+
+impl NewParam {
+    fn declaration(&self, old_params: &[ast::Param]) -> String {
+        let old = match self.old_index.and_then(|it| old_params.get(it)) {
+            Some(it) => it,
+            None => {
+                return format!("{}: {}", self.pat, self.ty.as_ref().map_or("_", String::as_str))
+            }
+        };
+        if !self.pat_changed && self.ty.is_none() {
+            return old.syntax().text().to_string();
+        }
+        // Impls may name the parameters differently from the trait, and keep
+        // their own pattern then.
+        let pat = match old.pat() {
+            Some(it) if !self.pat_changed || pat_name(&it) != self.name => {
+                it.syntax().text().to_string()
+            }
+            _ => self.pat.clone(),
+        };
+        let ty = match (&self.ty, old.ascribed_type()) {
+            (Some(ty), _) => ty.clone(),
+            (None, Some(ty)) => ty.syntax().text().to_string(),
+            (None, None) => "_".to_string(),
+        };
+        format!("{}: {}", pat, ty)
+    }
+
+    fn argument(&self, old_args: &[String]) -> String {
+        let arg = match self.old_index {
+            Some(idx) => old_args.get(idx).cloned(),
+            None => self.default.clone(),
+        };
+        arg.unwrap_or_else(|| PLACEHOLDER.to_string())
+    }
+}
+
+fn parse_params(
+    text: &str,
+    old_params: &[ast::Param],
+    fn_name: &str,
+) -> Result<Vec<NewParam>, ChangeSignatureError> {
+    let old_names: Vec<Option<String>> =
+        old_params.iter().map(|it| it.pat().map(|it| pat_name(&it))).collect();
+    let mut res: Vec<NewParam> = Vec::new();
+    for entry in split_top_level(text, ',') {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        let (param, default) = match top_level_positions(entry, '=').first() {
+            Some(&idx) => (entry[..idx].trim(), Some(entry[idx + 1..].trim())),
+            None => (entry, None),
+        };
+        let (pat, ty) = match top_level_positions(param, ':').first() {
+            Some(&idx) => (param[..idx].trim(), Some(param[idx + 1..].trim())),
+            None => (param, None),
+        };
+        let pat_node = parse_param(pat, ty)
+            .ok_or_else(|| ChangeSignatureError(format!("`{}` is not a valid parameter", param)))?;
+        if let Some(default) = default {
+            if !is_expr(default) {
+                return Err(ChangeSignatureError(format!("`{}` is not an expression", default)));
+            }
+        }
+
+        let name = pat_name(&pat_node);
+        let old_index = old_names.iter().position(|it| it.as_ref() == Some(&name));
+        match old_index {
+            Some(idx) if res.iter().any(|it| it.old_index == Some(idx)) => {
+                return Err(ChangeSignatureError(format!("`{}` is listed more than once", name)));
+            }
+            Some(_) if default.is_some() => {
+                return Err(ChangeSignatureError(format!(
+                    "`{}` is an existing parameter and can't have a default value",
+                    name
+                )));
+            }
+            None if ty.is_none() => {
+                return Err(ChangeSignatureError(format!(
+                    "`{}` is not a parameter of `{}`, new parameters need a type",
+                    name, fn_name
+                )));
+            }
+            _ => (),
+        }
+        let pat_changed = old_index
+            .and_then(|idx| old_params[idx].pat())
+            .map_or(false, |it| it.syntax().text() != pat);
+        res.push(NewParam {
+            old_index,
+            name,
+            pat: pat.to_string(),
+            pat_changed,
+            ty: ty.map(str::to_string),
+            default: default.map(str::to_string),
+        });
+    }
+    Ok(res)
+}
+
+fn pat_name(pat: &ast::Pat) -> String {
+    match pat {
+        ast::Pat::BindPat(it) => it.name().map(|it| it.text().to_string()).unwrap_or_default(),
+        _ => pat.syntax().text().to_string(),
+    }
+}
+
+fn parse_param(pat: &str, ty: Option<&str>) -> Option<ast::Pat> {
+    let param_text = format!("{}: {}", pat, ty.unwrap_or("()"));
+    let param = parse_wrapped::<ast::Param>(&format!("fn f({}) {{}}", param_text))?;
+    if param.syntax().text() != param_text.as_str() {
+        return None;
+    }
+    param.pat()
+}
+
+fn is_expr(text: &str) -> bool {
+    parse_wrapped::<ast::LetStmt>(&format!("fn f() {{ let _ = {}; }}", text))
+        .and_then(|it| it.initializer())
+        .map_or(false, |it| it.syntax().text() == text)
+}
+
+fn split_top_level(text: &str, sep: char) -> Vec<&str> {
+    let mut res = Vec::new();
+    let mut start = 0;
+    for idx in top_level_positions(text, sep) {
+        res.push(&text[start..idx]);
+        start = idx + sep.len_utf8();
+    }
+    res.push(&text[start..]);
+    res
+}
+
+/// Returns the offsets of the `sep` characters in `text` which are not nested
+/// in brackets or in string and char literals. After the `=` of a default
+/// value, angle brackets are only counted in turbofishes, as they are
+/// comparisons otherwise.
+fn top_level_positions(text: &str, sep: char) -> Vec<usize> {
+    let mut res = Vec::new();
+    let mut depth = 0;
+    let mut angle_depth = 0;
+    let mut in_string = false;
+    let mut in_default = false;
+    let mut prev = ' ';
+    let mut chars = text.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        let next = chars.peek().map_or(' ', |&(_, it)| it);
+        if in_string {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '"' => in_string = false,
+                _ => (),
+            }
+        } else if c == sep
+            && depth == 0
+            && angle_depth == 0
+            && !(c == ':' && (prev == ':' || next == ':'))
+        {
+            res.push(idx);
+            in_default = false;
+        } else {
+            match c {
+                '"' => in_string = true,
+                '\'' => skip_char_literal(&mut chars),
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                '<' if !in_default || prev == ':' || angle_depth > 0 => angle_depth += 1,
+                // Not the `>` of `->`.
+                '>' if angle_depth > 0 && prev != '-' => angle_depth -= 1,
+                '=' if depth == 0 && angle_depth == 0 && next != '=' => in_default = true,
+                _ => (),
+            }
+        }
+        prev = c;
+    }
+    res
+}
+
+/// Skips the rest of a char literal after its opening `'`. Does nothing if the
+/// `'` starts a lifetime instead.
+fn skip_char_literal(chars: &mut Peekable<CharIndices>) {
+    let mut lookahead = chars.clone();
+    match lookahead.next() {
+        Some((_, '\\')) => {
+            chars.next();
+            chars.next();
+            chars.find(|&(_, c)| c == '\'');
+        }
+        Some(_) if lookahead.next().map(|(_, it)| it) == Some('\'') => {
+            chars.next();
+            chars.next();
+        }
+        _ => (),
+    }
+}
+
+/// A node which has to be updated for the new signature.
+enum Site {
+    /// The parameter list of the function or of an implementation of it.
+    Declaration,
+    Call {
+        self_arg: bool,
+    },
+}
+
+struct Renderer<'a> {
+    sites: &'a [(TextRange, Site)],
+    params: &'a [NewParam],
+}
+
+impl Renderer<'_> {
+    fn site(&self, node: &SyntaxNode) -> Option<&Site> {
+        let (_, site) = self.sites.iter().find(|(range, _)| *range == node.text_range())?;
+        let kind_matches = match site {
+            Site::Declaration => node.kind() == PARAM_LIST,
+            Site::Call { .. } => node.kind() == CALL_EXPR || node.kind() == METHOD_CALL_EXPR,
+        };
+        if kind_matches {
+            Some(site)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the replacements for the outermost sites in `node`.
+    fn replacements(&self, node: &SyntaxNode) -> Vec<(TextRange, String)> {
+        let mut res = Vec::new();
+        for child in node.children() {
+            match self.site(&child) {
+                Some(site) => res.push((child.text_range(), self.render(&child, site))),
+                None => res.extend(self.replacements(&child)),
+            }
+        }
+        res
+    }
+
+    /// Returns the text of `node` with all the sites in it updated.
+    fn apply(&self, node: &SyntaxNode) -> String {
+        match self.site(node) {
+            Some(site) => self.render(node, site),
+            None => splice(node, self.replacements(node)),
+        }
+    }
+
+    fn render(&self, node: &SyntaxNode, site: &Site) -> String {
+        match site {
+            Site::Declaration => {
+                let param_list = match ast::ParamList::cast(node.clone()) {
+                    Some(it) => it,
+                    None => return node.text().to_string(),
+                };
+                let old_params: Vec<ast::Param> = param_list.params().collect();
+                let mut items: Vec<String> = param_list
+                    .self_param()
+                    .map(|it| it.syntax().text().to_string())
+                    .into_iter()
+                    .collect();
+                items.extend(self.params.iter().map(|it| it.declaration(&old_params)));
+                render_list(node, items)
+            }
+            Site::Call { self_arg } => {
+                let (callee, arg_list) = if let Some(call) = ast::MethodCallExpr::cast(node.clone())
+                {
+                    (call.expr(), call.arg_list())
+                } else if let Some(call) = ast::CallExpr::cast(node.clone()) {
+                    (call.expr(), call.arg_list())
+                } else {
+                    return node.text().to_string();
+                };
+                let arg_list = match arg_list {
+                    Some(it) => it,
+                    None => return node.text().to_string(),
+                };
+                let mut old_args: Vec<String> =
+                    arg_list.args().map(|it| self.apply(it.syntax())).collect();
+                let mut items = Vec::new();
+                if *self_arg && !old_args.is_empty() {
+                    items.push(old_args.remove(0));
+                }
+                items.extend(self.params.iter().map(|it| it.argument(&old_args)));
+
+                let mut replacements = Vec::new();
+                if let Some(callee) = callee {
+                    replacements.push((callee.syntax().text_range(), self.apply(callee.syntax())));
+                }
+                replacements
+                    .push((arg_list.syntax().text_range(), render_list(arg_list.syntax(), items)));
+                splice(node, replacements)
+            }
+        }
+    }
+}
+
+/// Renders a parenthesized list, putting each item on its own line if the old
+/// list was written like that.
+fn render_list(list: &SyntaxNode, items: Vec<String>) -> String {
+    if items.is_empty() || !list.text().contains_char('\n') {
+        return format!("({})", items.join(", "));
+    }
+    let indent = leading_indent(list).map(|it| it.to_string()).unwrap_or_default();
+    let item_indent = list
+        .children()
+        .next()
+        .and_then(|it| leading_indent(&it))
+        .map(|it| it.to_string())
+        .unwrap_or_else(|| format!("{}    ", indent));
+    let mut buf = String::from("(");
+    for item in items {
+        format!(buf, "\n{}{},", item_indent, item);
+    }
+    format!(buf, "\n{})", indent);
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use test_utils::assert_eq_text;
+
+    use crate::{
+        mock_analysis::{analysis_and_position, single_file_with_position},
+        ChangeSignatureError,
+    };
+
+    fn check(new_params: &str, before: &str, after: &str) {
+        let (analysis, position) = single_file_with_position(before);
+        let change = analysis.change_signature(position, new_params).unwrap().unwrap();
+        let mut actual = analysis.file_text(position.file_id).unwrap().to_string();
+        for edit in change.source_file_edits {
+            assert_eq!(edit.file_id, position.file_id);
+            actual = edit.edit.apply(&actual);
+        }
+        assert_eq_text!(after, &actual);
+    }
+
+    fn check_error(new_params: &str, before: &str, expected: &str) {
+        let (analysis, position) = single_file_with_position(before);
+        let err = analysis.change_signature(position, new_params).unwrap().unwrap_err();
+        assert_eq!(err, ChangeSignatureError(expected.to_string()));
+    }
+
+    #[test]
+    fn change_signature_reorders_and_adds_params() {
+        check(
+            "b, a, flag: bool = false",
+            r#"
+fn foo<|>(a: i32, b: u32) {}
+fn main() {
+    foo(1, 2);
+    foo(foo(3, 4), 5);
+}
+"#,
+            r#"
+fn foo(b: u32, a: i32, flag: bool) {}
+fn main() {
+    foo(2, 1, false);
+    foo(5, foo(4, 3, false), false);
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn change_signature_removes_params_and_inserts_placeholder() {
+        check(
+            "a: u64, c: &str",
+            r#"
+fn foo(a: i32, b: u32) {}
+fn main() {
+    fo<|>o(1, 2);
+}
+"#,
+            r#"
+fn foo(a: u64, c: &str) {}
+fn main() {
+    foo(1, unimplemented!());
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn change_signature_of_method() {
+        check(
+            "y, x",
+            r#"
+struct S;
+impl S {
+    fn m<|>(&self, x: i32, y: i32) {}
+}
+fn main() {
+    let s = S;
+    s.m(1, 2);
+    S::m(&s, 3, 4);
+}
+"#,
+            r#"
+struct S;
+impl S {
+    fn m(&self, y: i32, x: i32) {}
+}
+fn main() {
+    let s = S;
+    s.m(2, 1);
+    S::m(&s, 4, 3);
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn change_signature_of_trait_method_changes_impls() {
+        check(
+            "n: usize, x",
+            r#"
+trait T {
+    fn m(&self, x: i32);
+}
+struct A;
+struct B;
+impl T for A {
+    fn m<|>(&self, x: i32) {}
+}
+impl T for B {
+    fn m(&self, value: i32) {}
+}
+fn main() {
+    A.m(1);
+    B.m(2);
+}
+"#,
+            r#"
+trait T {
+    fn m(&self, n: usize, x: i32);
+}
+struct A;
+struct B;
+impl T for A {
+    fn m(&self, n: usize, x: i32) {}
+}
+impl T for B {
+    fn m(&self, n: usize, value: i32) {}
+}
+fn main() {
+    A.m(unimplemented!(), 1);
+    B.m(unimplemented!(), 2);
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn change_signature_keeps_patterns_of_impls_naming_params_differently() {
+        check(
+            "mut x",
+            r#"
+trait T {
+    fn m(&self, x: i32) {}
+}
+struct A;
+struct B;
+impl T for A {
+    fn m<|>(&self, x: i32) {}
+}
+impl T for B {
+    fn m(&self, value: i32) {}
+}
+"#,
+            r#"
+trait T {
+    fn m(&self, mut x: i32) {}
+}
+struct A;
+struct B;
+impl T for A {
+    fn m(&self, mut x: i32) {}
+}
+impl T for B {
+    fn m(&self, value: i32) {}
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn change_signature_splits_params_outside_of_literals_and_turbofishes() {
+        check(
+            r#"a, s: &'static str = "b, c", sep: char = ',', m: HashMap<u32, u32> = HashMap::<u32, u32>::new()"#,
+            r#"
+fn foo<|>(a: i32) {}
+fn main() {
+    foo(1);
+}
+"#,
+            r#"
+fn foo(a: i32, s: &'static str, sep: char, m: HashMap<u32, u32>) {}
+fn main() {
+    foo(1, "b, c", ',', HashMap::<u32, u32>::new());
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn change_signature_keeps_one_param_per_line() {
+        check(
+            "b, a, c: Vec<(i32, i32)> = vec![(1, 2)]",
+            r#"
+fn foo<|>(
+    a: i32,
+    b: i32,
+) {}
+fn main() {
+    foo(
+        1,
+        2,
+    );
+}
+"#,
+            r#"
+fn foo(
+    b: i32,
+    a: i32,
+    c: Vec<(i32, i32)>,
+) {}
+fn main() {
+    foo(
+        2,
+        1,
+        vec![(1, 2)],
+    );
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn change_signature_updates_other_files() {
+        let (analysis, position) = analysis_and_position(
+            r#"
+//- /lib.rs
+mod foo;
+fn bar() { foo::foo(1); }
+//- /foo.rs
+pub fn foo<|>(a: i32) {}
+"#,
+        );
+        let change = analysis.change_signature(position, "").unwrap().unwrap();
+        let edited: Vec<String> = change
+            .source_file_edits
+            .iter()
+            .map(|edit| edit.edit.apply(analysis.file_text(edit.file_id).unwrap().trim_end()))
+            .collect();
+        assert_eq!(edited, vec!["mod foo;\nfn bar() { foo::foo(); }", "pub fn foo() {}"]);
+    }
+
+    #[test]
+    fn change_signature_errors() {
+        let code = r#"
+fn foo<|>(a: i32) {}
+fn main() { let f = foo; }
+"#;
+        check_error("a, a", code, "`a` is listed more than once");
+        check_error("b", code, "`b` is not a parameter of `foo`, new parameters need a type");
+        check_error("a = 1", code, "`a` is an existing parameter and can't have a default value");
+        check_error("b: i32 = 1 +", code, "`1 +` is not an expression");
+        check_error("a: i32)", code, "`a: i32)` is not a valid parameter");
+        check_error(
+            "a",
+            code,
+            "Cannot change the signature of `foo`: 1 reference(s) are not calls or are in macro calls",
+        );
+    }
+}
@@ -19,7 +19,7 @@ mod complete_macro_in_item_position;
 use ra_db::SourceDatabase;
 
 #[cfg(test)]
-use crate::completion::completion_item::do_completion;
+use crate::completion::completion_item::{do_completion, do_completion_with_flags};
 use crate::{
     completion::{
         completion_context::CompletionContext,
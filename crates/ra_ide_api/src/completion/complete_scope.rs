@@ -1,56 +1,85 @@
-//! FIXME: write short doc here
+//! Completion of the names in scope, and of the items which can be imported.
 
 use ra_assists::auto_import_text_edit;
 use ra_syntax::{ast, AstNode, SmolStr};
 use ra_text_edit::TextEditBuilder;
-use rustc_hash::FxHashMap;
+use rustc_hash::FxHashSet;
 
-use crate::completion::{CompletionContext, CompletionItem, CompletionKind, Completions};
+use crate::{
+    completion::{CompletionContext, CompletionItem, CompletionKind, Completions},
+    imports_locator,
+};
+
+/// Searching the symbol index for every keystroke is expensive, so only a few
+/// items are offered for import.
+const MAX_IMPORT_COMPLETIONS: usize = 20;
 
 pub(super) fn complete_scope(acc: &mut Completions, ctx: &CompletionContext) {
     if !ctx.is_trivial_path {
         return;
     }
 
+    let mut names_in_scope = FxHashSet::default();
     ctx.analyzer.process_all_names(ctx.db, &mut |name, res| {
-        acc.add_resolution(ctx, name.to_string(), &res)
+        let name = name.to_string();
+        acc.add_resolution(ctx, name.clone(), &res);
+        names_in_scope.insert(name);
     });
 
-    // auto-import
+    if ctx.db.feature_flags.get("completion.enable-auto-import") {
+        complete_auto_import(acc, ctx, &names_in_scope);
+    }
+}
+
+fn complete_auto_import(
+    acc: &mut Completions,
+    ctx: &CompletionContext,
+    names_in_scope: &FxHashSet<String>,
+) {
     // We fetch ident from the original file, because we need to pre-filter auto-imports
-    if ast::NameRef::cast(ctx.token.parent()).is_some() {
-        let import_resolver = ImportResolver::new();
-        let import_names = import_resolver.all_names(ctx.token.text());
-        import_names.into_iter().for_each(|(name, path)| {
-            let edit = {
-                let mut builder = TextEditBuilder::default();
-                builder.replace(ctx.source_range(), name.to_string());
-                auto_import_text_edit(
-                    &ctx.token.parent(),
-                    &ctx.token.parent(),
-                    &path,
-                    &mut builder,
-                );
-                builder.finish()
-            };
-
-            // Hack: copied this check form conv.rs beacause auto import can produce edits
-            // that invalidate assert in conv_with.
-            if edit
-                .as_atoms()
-                .iter()
-                .filter(|atom| !ctx.source_range().is_subrange(&atom.delete))
-                .all(|atom| ctx.source_range().intersection(&atom.delete).is_none())
-            {
-                CompletionItem::new(
-                    CompletionKind::Reference,
-                    ctx.source_range(),
-                    build_import_label(&name, &path),
-                )
-                .text_edit(edit)
-                .add_to(acc);
-            }
-        });
+    if ast::NameRef::cast(ctx.token.parent()).is_none() {
+        return;
+    }
+    let query = ctx.token.text();
+    if query.len() < 2 {
+        return;
+    }
+    let krate = match ctx.module {
+        Some(module) => module.krate(),
+        None => return,
+    };
+
+    let candidates =
+        imports_locator::find_imports_fuzzy(ctx.db, krate, query, MAX_IMPORT_COMPLETIONS);
+    for path in candidates {
+        let name = match path.last() {
+            Some(it) if !names_in_scope.contains(it.as_str()) => it.clone(),
+            _ => continue,
+        };
+        let edit = {
+            let mut builder = TextEditBuilder::default();
+            builder.replace(ctx.source_range(), name.to_string());
+            auto_import_text_edit(&ctx.token.parent(), &ctx.token.parent(), &path, &mut builder);
+            builder.finish()
+        };
+
+        // Hack: copied this check form conv.rs beacause auto import can produce edits
+        // that invalidate assert in conv_with.
+        if edit
+            .as_atoms()
+            .iter()
+            .filter(|atom| !ctx.source_range().is_subrange(&atom.delete))
+            .all(|atom| ctx.source_range().intersection(&atom.delete).is_none())
+        {
+            CompletionItem::new(
+                CompletionKind::Import,
+                ctx.source_range(),
+                build_import_label(&name, &path),
+            )
+            .lookup_by(name.to_string())
+            .text_edit(edit)
+            .add_to(acc);
+        }
     }
 }
 
@@ -74,65 +103,25 @@ fn fmt_import_path(path: &[SmolStr], buf: &mut String) {
     }
 }
 
-#[derive(Debug, Clone, Default)]
-pub(crate) struct ImportResolver {
-    // todo: use fst crate or something like that
-    dummy_names: Vec<(SmolStr, Vec<SmolStr>)>,
-}
-
-impl ImportResolver {
-    pub(crate) fn new() -> Self {
-        let dummy_names = vec![
-            (SmolStr::new("fmt"), vec![SmolStr::new("std"), SmolStr::new("fmt")]),
-            (SmolStr::new("io"), vec![SmolStr::new("std"), SmolStr::new("io")]),
-            (SmolStr::new("iter"), vec![SmolStr::new("std"), SmolStr::new("iter")]),
-            (SmolStr::new("hash"), vec![SmolStr::new("std"), SmolStr::new("hash")]),
-            (
-                SmolStr::new("Debug"),
-                vec![SmolStr::new("std"), SmolStr::new("fmt"), SmolStr::new("Debug")],
-            ),
-            (
-                SmolStr::new("Display"),
-                vec![SmolStr::new("std"), SmolStr::new("fmt"), SmolStr::new("Display")],
-            ),
-            (
-                SmolStr::new("Hash"),
-                vec![SmolStr::new("std"), SmolStr::new("hash"), SmolStr::new("Hash")],
-            ),
-            (
-                SmolStr::new("Hasher"),
-                vec![SmolStr::new("std"), SmolStr::new("hash"), SmolStr::new("Hasher")],
-            ),
-            (
-                SmolStr::new("Iterator"),
-                vec![SmolStr::new("std"), SmolStr::new("iter"), SmolStr::new("Iterator")],
-            ),
-        ];
-
-        ImportResolver { dummy_names }
-    }
-
-    // Returns a map of importable items filtered by name.
-    // The map associates item name with its full path.
-    // todo: should return Resolutions
-    pub(crate) fn all_names(&self, name: &str) -> FxHashMap<SmolStr, Vec<SmolStr>> {
-        if name.len() > 1 {
-            self.dummy_names.iter().filter(|(n, _)| n.contains(name)).cloned().collect()
-        } else {
-            FxHashMap::default()
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
-    use crate::completion::{do_completion, CompletionItem, CompletionKind};
+    use crate::completion::{
+        do_completion, do_completion_with_flags, CompletionItem, CompletionKind,
+    };
     use insta::assert_debug_snapshot;
 
     fn do_reference_completion(code: &str) -> Vec<CompletionItem> {
         do_completion(code, CompletionKind::Reference)
     }
 
+    fn do_import_completion(code: &str) -> Vec<CompletionItem> {
+        do_completion_with_flags(
+            code,
+            CompletionKind::Import,
+            &[("completion.enable-auto-import", true)],
+        )
+    }
+
     #[test]
     fn completes_bindings_from_let() {
         assert_debug_snapshot!(
@@ -873,4 +862,111 @@ mod tests {
         "###
         );
     }
+
+    #[test]
+    fn completes_items_to_import() {
+        assert_debug_snapshot!(
+            do_import_completion(
+                "
+                //- /main.rs
+                fn main() {
+                    let m = HashM<|>
+                }
+                //- /std/lib.rs
+                pub mod collections {
+                    mod hash {
+                        pub mod map {
+                            pub struct HashMap;
+                        }
+                    }
+                    pub use self::hash::map::HashMap;
+                }
+                "
+            ),
+            @r###"
+        [
+            CompletionItem {
+                label: "HashMap (std::collections::HashMap)",
+                source_range: [24; 29),
+                text_edit: TextEdit {
+                    atoms: [
+                        AtomTextEdit {
+                            delete: [0; 0),
+                            insert: "use std::collections::HashMap;\n\n",
+                        },
+                        AtomTextEdit {
+                            delete: [24; 29),
+                            insert: "HashMap",
+                        },
+                    ],
+                },
+                lookup: "HashMap",
+            },
+        ]
+        "###
+        );
+    }
+
+    #[test]
+    fn does_not_complete_imports_behind_private_modules() {
+        assert_debug_snapshot!(
+            do_import_completion(
+                "
+                //- /main.rs
+                fn main() {
+                    let m = HashM<|>
+                }
+                //- /std/lib.rs
+                pub mod collections {
+                    mod hash {
+                        pub mod map {
+                            pub struct HashMap;
+                        }
+                    }
+                }
+                "
+            ),
+            @r###"[]"###
+        );
+    }
+
+    #[test]
+    fn does_not_complete_imports_of_names_in_scope() {
+        assert_debug_snapshot!(
+            do_import_completion(
+                "
+                //- /main.rs
+                use std::collections::HashMap;
+                fn main() {
+                    let m = HashM<|>
+                }
+                //- /std/lib.rs
+                pub mod collections {
+                    pub struct HashMap;
+                }
+                "
+            ),
+            @r###"[]"###
+        );
+    }
+
+    #[test]
+    fn does_not_complete_imports_without_flag() {
+        assert_debug_snapshot!(
+            do_completion(
+                "
+                //- /main.rs
+                fn main() {
+                    let m = HashM<|>
+                }
+                //- /std/lib.rs
+                pub mod collections {
+                    pub struct HashMap;
+                }
+                ",
+                CompletionKind::Import,
+            ),
+            @r###"[]"###
+        );
+    }
 }
@@ -112,6 +112,8 @@ pub(crate) enum CompletionKind {
     Snippet,
     Postfix,
     BuiltinType,
+    /// Items which are not in scope yet, and get imported on completion.
+    Import,
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -306,13 +308,29 @@ impl Into<Vec<CompletionItem>> for Completions {
 
 #[cfg(test)]
 pub(crate) fn do_completion(code: &str, kind: CompletionKind) -> Vec<CompletionItem> {
+    do_completion_with_flags(code, kind, &[])
+}
+
+#[cfg(test)]
+pub(crate) fn do_completion_with_flags(
+    code: &str,
+    kind: CompletionKind,
+    flags: &[(&str, bool)],
+) -> Vec<CompletionItem> {
     use crate::completion::completions;
-    use crate::mock_analysis::{analysis_and_position, single_file_with_position};
-    let (analysis, position) = if code.contains("//-") {
-        analysis_and_position(code)
+    use crate::{mock_analysis::MockAnalysis, FeatureFlags};
+    let (mock, position) = if code.contains("//-") {
+        MockAnalysis::with_files_and_position(code)
     } else {
-        single_file_with_position(code)
+        let mut mock = MockAnalysis::new();
+        let position = mock.add_file_with_position("/main.rs", code);
+        (mock, position)
     };
+    let mut feature_flags = FeatureFlags::default();
+    for &(flag, value) in flags {
+        feature_flags.set(flag, value).unwrap();
+    }
+    let analysis = mock.analysis_host_with_feature_flags(feature_flags).analysis();
     let completions = completions(&analysis.db, position).unwrap();
     let completion_items: Vec<CompletionItem> = completions.into();
     let mut kind_completions: Vec<CompletionItem> =
@@ -169,8 +169,7 @@ impl Completions {
             None => return,
         };
 
-        let ast_node = macro_.source(ctx.db).value;
-        let detail = macro_label(&ast_node);
+        let detail = macro_.source(ctx.db).map(|it| macro_label(&it.value));
 
         let docs = macro_.docs(ctx.db);
         let macro_declaration = format!("{}!", name);
@@ -180,7 +179,7 @@ impl Completions {
                 .kind(CompletionItemKind::Macro)
                 .set_documentation(docs.clone())
                 .set_deprecated(is_deprecated(macro_, ctx.db))
-                .detail(detail);
+                .set_detail(detail);
 
         builder = if ctx.use_item_syntax.is_some() {
             builder.insert_text(name)
@@ -2,18 +2,27 @@
 
 use std::cell::RefCell;
 
-use hir::diagnostics::{AstDiagnostic, Diagnostic as _, DiagnosticSink};
+use hir::{
+    diagnostics::{AstDiagnostic, Diagnostic as _, DiagnosticSink},
+    HasSource,
+};
 use itertools::Itertools;
+use ra_assists::{auto_import_text_edit, fill_match_arms_text_edit};
 use ra_db::{RelativePath, SourceDatabase, SourceDatabaseExt};
 use ra_prof::profile;
 use ra_syntax::{
     algo,
-    ast::{self, make, AstNode},
-    Location, SyntaxNode, TextRange, T,
+    ast::{self, make, AstNode, NameOwner},
+    Direction, Location,
+    SyntaxKind::WHITESPACE,
+    SyntaxNode, TextRange, TextUnit, T,
 };
 use ra_text_edit::{TextEdit, TextEditBuilder};
 
-use crate::{db::RootDatabase, Diagnostic, FileId, FileSystemEdit, SourceChange, SourceFileEdit};
+use crate::{
+    db::RootDatabase, imports_locator, Diagnostic, FileId, FileSystemEdit, SourceChange,
+    SourceFileEdit,
+};
 
 #[derive(Debug, Copy, Clone)]
 pub enum Severity {
@@ -37,6 +46,11 @@ pub(crate) fn diagnostics(db: &RootDatabase, file_id: FileId) -> Vec<Diagnostic>
         check_unnecessary_braces_in_use_statement(&mut res, file_id, &node);
         check_struct_shorthand_initialization(&mut res, file_id, &node);
     }
+    let source_file = db.parse(file_id).tree();
+    let src =
+        hir::Source { file_id: file_id.into(), value: hir::ModuleSource::SourceFile(source_file) };
+    let module = hir::Module::from_definition(db, src);
+
     let res = RefCell::new(res);
     let mut sink = DiagnosticSink::new(|d| {
         res.borrow_mut().push(Diagnostic {
@@ -93,16 +107,247 @@ pub(crate) fn diagnostics(db: &RootDatabase, file_id: FileId) -> Vec<Diagnostic>
             severity: Severity::Error,
             fix: Some(fix),
         })
+    })
+    .on::<hir::diagnostics::MissingMatchArms, _>(|d| {
+        res.borrow_mut().push(Diagnostic {
+            range: d.highlight_range(),
+            message: d.message(),
+            severity: Severity::Error,
+            fix: fix_missing_match_arms(db, d),
+        })
+    })
+    .on::<hir::diagnostics::TypeMismatch, _>(|d| {
+        if !db.feature_flags.get("diagnostics.type-mismatch") {
+            return;
+        }
+        res.borrow_mut().push(Diagnostic {
+            range: d.highlight_range(),
+            message: d.message(),
+            severity: Severity::Error,
+            fix: None,
+        })
+    })
+    .on::<hir::diagnostics::UnresolvedImport, _>(|d| {
+        if !db.feature_flags.get("diagnostics.unresolved-import") {
+            return;
+        }
+        let fix = module.and_then(|module| fix_unresolved_import(db, module.krate(), d));
+        res.borrow_mut().push(Diagnostic {
+            range: d.highlight_range(),
+            message: d.message(),
+            severity: Severity::Error,
+            fix,
+        })
+    })
+    .on::<hir::diagnostics::UnresolvedPath, _>(|d| {
+        if !db.feature_flags.get("diagnostics.unresolved-path") {
+            return;
+        }
+        let fix = module.and_then(|module| fix_unresolved_path(db, module.krate(), d));
+        res.borrow_mut().push(Diagnostic {
+            range: d.highlight_range(),
+            message: d.message(),
+            severity: Severity::Error,
+            fix,
+        })
+    })
+    .on::<hir::diagnostics::UnusedVariable, _>(|d| {
+        let range =
+            d.ast(db).name().map_or_else(|| d.highlight_range(), |it| it.syntax().text_range());
+        res.borrow_mut().push(Diagnostic {
+            range,
+            message: d.message(),
+            severity: Severity::WeakWarning,
+            fix: fix_unused_variable(db, d),
+        })
+    })
+    .on::<hir::diagnostics::UnusedMut, _>(|d| {
+        res.borrow_mut().push(Diagnostic {
+            range: d.highlight_range(),
+            message: d.message(),
+            severity: Severity::WeakWarning,
+            fix: fix_unused_mut(db, d),
+        })
+    })
+    .on::<hir::diagnostics::UnusedImport, _>(|d| {
+        res.borrow_mut().push(Diagnostic {
+            range: d.highlight_range(),
+            message: d.message(),
+            severity: Severity::WeakWarning,
+            fix: fix_unused_import(db, d),
+        })
     });
-    let source_file = db.parse(file_id).tree();
-    let src =
-        hir::Source { file_id: file_id.into(), value: hir::ModuleSource::SourceFile(source_file) };
-    if let Some(m) = hir::Module::from_definition(db, src) {
+    if let Some(m) = module {
         m.diagnostics(db, &mut sink);
+        if db.feature_flags.get("diagnostics.unused") {
+            m.unused_diagnostics(db, &mut sink);
+        }
     };
     drop(sink);
     res.into_inner()
 }
+
+/// Adds arms for the enum variants which are not matched yet.
+fn fix_missing_match_arms(
+    db: &RootDatabase,
+    d: &hir::diagnostics::MissingMatchArms,
+) -> Option<SourceChange> {
+    let file_id = d.file.original_file(db);
+    if d.file != file_id.into() || d.missed_variants.is_empty() {
+        return None;
+    }
+    let variants = d.missed_variants.iter().map(|it| it.source(db).value);
+    let mut builder = TextEditBuilder::default();
+    fill_match_arms_text_edit(&d.ast(db), variants, &mut builder);
+    Some(SourceChange::source_file_edit_from("fill match arms", file_id, builder.finish()))
+}
+
+/// Replaces the path of an unresolved `use foo::Bar;` with the path of an item
+/// called `Bar`.
+fn fix_unresolved_import(
+    db: &RootDatabase,
+    krate: hir::Crate,
+    d: &hir::diagnostics::UnresolvedImport,
+) -> Option<SourceChange> {
+    let file_id = d.file.original_file(db);
+    if d.file != file_id.into() {
+        return None;
+    }
+    let use_tree = d.ast(db);
+    // Nested trees and globs would need more than a simple replacement.
+    if use_tree.syntax().parent().and_then(ast::UseItem::cast).is_none()
+        || use_tree.use_tree_list().is_some()
+        || use_tree.syntax().children_with_tokens().any(|it| it.kind() == T![*])
+    {
+        return None;
+    }
+    let path = use_tree.path()?;
+    let name = path.segment()?.name_ref()?;
+    let candidate = imports_locator::find_imports(db, krate, name.text()).into_iter().next()?;
+    let candidate = candidate.iter().join("::");
+    let edit = TextEdit::replace(path.syntax().text_range(), candidate.clone());
+    Some(SourceChange::source_file_edit_from(format!("import `{}`", candidate), file_id, edit))
+}
+
+/// Adds a `use` for the first segment of an unresolved path.
+fn fix_unresolved_path(
+    db: &RootDatabase,
+    krate: hir::Crate,
+    d: &hir::diagnostics::UnresolvedPath,
+) -> Option<SourceChange> {
+    let file_id = d.file.original_file(db);
+    let path = d.ast(db);
+    let mut first_path = path.clone();
+    while let Some(qualifier) = first_path.qualifier() {
+        first_path = qualifier;
+    }
+    let name = first_path.segment()?.name_ref()?;
+    let candidate = imports_locator::find_imports(db, krate, name.text()).into_iter().next()?;
+    let mut builder = TextEditBuilder::default();
+    auto_import_text_edit(path.syntax(), path.syntax(), &candidate, &mut builder);
+    let label = format!("import `{}`", candidate.iter().join("::"));
+    Some(SourceChange::source_file_edit_from(label, file_id, builder.finish()))
+}
+
+/// Prefixes the name of an unused binding with `_`, expanding `S { x }` to
+/// `S { x: _x }`.
+fn fix_unused_variable(
+    db: &RootDatabase,
+    d: &hir::diagnostics::UnusedVariable,
+) -> Option<SourceChange> {
+    let file_id = d.file.original_file(db);
+    if d.file != file_id.into() {
+        return None;
+    }
+    let bind_pat = d.ast(db);
+    let name = bind_pat.name()?;
+    let new_name = format!("_{}", name.text());
+    let is_shorthand = bind_pat.syntax().parent().and_then(ast::RecordFieldPatList::cast).is_some();
+    let edit = if is_shorthand {
+        // Keeps the `ref` and `mut` of the binding.
+        let range = bind_pat.syntax().text_range();
+        let prefix_len = (name.syntax().text_range().start() - range.start()).to_usize();
+        let prefix = &bind_pat.syntax().text().to_string()[..prefix_len];
+        TextEdit::replace(range, format!("{}: {}{}", name.text(), prefix, new_name))
+    } else {
+        TextEdit::replace(name.syntax().text_range(), new_name.clone())
+    };
+    Some(SourceChange::source_file_edit_from(format!("rename to `{}`", new_name), file_id, edit))
+}
+
+/// Removes the `mut` of a binding which is never mutated.
+fn fix_unused_mut(db: &RootDatabase, d: &hir::diagnostics::UnusedMut) -> Option<SourceChange> {
+    let file_id = d.file.original_file(db);
+    if d.file != file_id.into() {
+        return None;
+    }
+    let bind_pat = d.ast(db);
+    let mut_token = bind_pat.syntax().children_with_tokens().find(|it| it.kind() == T![mut])?;
+    let end = match mut_token.next_sibling_or_token() {
+        Some(it) if it.kind() == WHITESPACE => it.text_range().end(),
+        _ => mut_token.text_range().end(),
+    };
+    let edit = TextEdit::delete(TextRange::from_to(mut_token.text_range().start(), end));
+    Some(SourceChange::source_file_edit_from("remove `mut`", file_id, edit))
+}
+
+/// Removes an unused `use` tree, along with its item if nothing else is left.
+fn fix_unused_import(
+    db: &RootDatabase,
+    d: &hir::diagnostics::UnusedImport,
+) -> Option<SourceChange> {
+    let file_id = d.file.original_file(db);
+    if d.file != file_id.into() {
+        return None;
+    }
+    let edit = TextEdit::delete(use_tree_removal_range(&d.ast(db))?);
+    Some(SourceChange::source_file_edit_from("remove unused import", file_id, edit))
+}
+
+fn use_tree_removal_range(use_tree: &ast::UseTree) -> Option<TextRange> {
+    let parent = use_tree.syntax().parent()?;
+    if let Some(use_item) = ast::UseItem::cast(parent.clone()) {
+        // Removes the line of the item, or the whitespace after it if it is
+        // the first one.
+        let range = use_item.syntax().text_range();
+        let prev = use_item.syntax().prev_sibling_or_token().and_then(|it| it.into_token());
+        if let Some(ws) = prev.filter(|it| it.kind() == WHITESPACE) {
+            if let Some(idx) = ws.text().rfind('\n') {
+                let start = ws.text_range().start() + TextUnit::from_usize(idx);
+                return Some(TextRange::from_to(start, range.end()));
+            }
+        }
+        let end = match use_item.syntax().next_sibling_or_token() {
+            Some(it) if it.kind() == WHITESPACE => it.text_range().end(),
+            _ => range.end(),
+        };
+        return Some(TextRange::from_to(range.start(), end));
+    }
+    let use_tree_list = ast::UseTreeList::cast(parent)?;
+    if use_tree_list.use_trees().count() == 1 {
+        let parent_tree = use_tree_list.syntax().parent().and_then(ast::UseTree::cast)?;
+        return use_tree_removal_range(&parent_tree);
+    }
+    let range = use_tree.syntax().text_range();
+    let next = algo::non_trivia_sibling(use_tree.syntax().clone().into(), Direction::Next);
+    match next {
+        Some(comma) if comma.kind() == T![,] => {
+            let end = match algo::non_trivia_sibling(comma.clone(), Direction::Next) {
+                Some(it) if it.kind() != T!['}'] => it.text_range().start(),
+                _ => comma.text_range().end(),
+            };
+            Some(TextRange::from_to(range.start(), end))
+        }
+        _ => {
+            let prev = algo::non_trivia_sibling(use_tree.syntax().clone().into(), Direction::Prev)?;
+            if prev.kind() != T![,] {
+                return None;
+            }
+            Some(TextRange::from_to(prev.text_range().start(), range.end()))
+        }
+    }
+}
+
 fn location_to_range(location: Location) -> TextRange {
     match location {
         Location::Offset(offset) => TextRange::offset_len(offset, 1.into()),
@@ -194,12 +439,23 @@ mod tests {
     use ra_syntax::SourceFile;
     use test_utils::assert_eq_text;
 
-    use crate::mock_analysis::{analysis_and_position, single_file};
+    use crate::{
+        mock_analysis::{analysis_and_position, single_file, MockAnalysis},
+        Analysis, FeatureFlags,
+    };
 
     use super::*;
 
     type DiagnosticChecker = fn(&mut Vec<Diagnostic>, FileId, &SyntaxNode) -> Option<()>;
 
+    fn analysis_with_flags(mock: MockAnalysis, flags: &[&str]) -> Analysis {
+        let mut feature_flags = FeatureFlags::default();
+        for flag in flags {
+            feature_flags.set(flag, true).unwrap();
+        }
+        mock.analysis_host_with_feature_flags(feature_flags).analysis()
+    }
+
     fn check_not_applicable(code: &str, func: DiagnosticChecker) {
         let parse = SourceFile::parse(code);
         let mut diagnostics = Vec::new();
@@ -288,6 +544,19 @@ mod tests {
         assert_eq!(diagnostics.len(), 0);
     }
 
+    /// Like `check_apply_diagnostic_fix`, with the diagnostics about unused code
+    /// enabled.
+    fn check_apply_unused_fix(before: &str, after: &str) {
+        let mut mock = MockAnalysis::new();
+        let file_id = mock.add_file("/main.rs", before);
+        let analysis = analysis_with_flags(mock, &["diagnostics.unused"]);
+        let diagnostic = analysis.diagnostics(file_id).unwrap().pop().unwrap();
+        let mut fix = diagnostic.fix.unwrap();
+        let edit = fix.source_file_edits.pop().unwrap().edit;
+        let actual = edit.apply(&before);
+        assert_eq_text!(after, &actual);
+    }
+
     #[test]
     fn test_wrap_return_type() {
         let before = r#"
@@ -521,6 +790,101 @@ mod tests {
         check_no_diagnostic(content);
     }
 
+    #[test]
+    fn test_fill_missing_match_arms() {
+        let before = r"
+            enum E { A, B(u32), C { x: u32 } }
+
+            fn test_fn(e: E) {
+                match e {
+                    E::A => (),
+                }
+            }
+        ";
+        let after = r"
+            enum E { A, B(u32), C { x: u32 } }
+
+            fn test_fn(e: E) {
+                match e {
+                    E::A => (),
+                    E::B(_) => (),
+                    E::C { x } => (),
+                }
+            }
+        ";
+        check_apply_diagnostic_fix(before, after);
+    }
+
+    #[test]
+    fn test_fill_missing_match_arms_after_arm_without_comma() {
+        let before = r"
+            enum E { A, B }
+
+            fn test_fn(e: E) {
+                match e {
+                    E::B => ()
+                }
+            }
+        ";
+        let after = r"
+            enum E { A, B }
+
+            fn test_fn(e: E) {
+                match e {
+                    E::B => (),
+                    E::A => (),
+                }
+            }
+        ";
+        check_apply_diagnostic_fix(before, after);
+    }
+
+    #[test]
+    fn test_fill_missing_match_arms_keeps_arm_indent() {
+        let before = "fn f(e: E) {\n\tmatch e {\n\t\tE::A => (),\n\t}\n}\nenum E { A, B }\n";
+        let after =
+            "fn f(e: E) {\n\tmatch e {\n\t\tE::A => (),\n\t\tE::B => (),\n\t}\n}\nenum E { A, B }\n";
+        check_apply_diagnostic_fix(before, after);
+    }
+
+    #[test]
+    fn test_fill_missing_match_arms_in_empty_match() {
+        let before = r"
+            enum E { A, B }
+
+            fn test_fn(e: E) {
+                match e {}
+            }
+        ";
+        let after = r"
+            enum E { A, B }
+
+            fn test_fn(e: E) {
+                match e {
+                    E::A => (),
+                    E::B => (),
+                }
+            }
+        ";
+        check_apply_diagnostic_fix(before, after);
+    }
+
+    #[test]
+    fn test_no_missing_match_arms_diagnostic() {
+        let content = r"
+            enum E { A, B(bool) }
+
+            fn test_fn(e: &E, b: bool) {
+                match e {
+                    E::A | E::B(true) => (),
+                    E::B(false) if b => (),
+                    &E::B(_) => (),
+                }
+            }
+        ";
+        check_no_diagnostic(content);
+    }
+
     #[test]
     fn test_unresolved_module_diagnostic() {
         let (analysis, file_id) = single_file("mod foo;");
@@ -551,6 +915,248 @@ mod tests {
         "###);
     }
 
+    #[test]
+    fn test_unresolved_import_fix() {
+        let before = r#"
+            //- /main.rs
+            use std::Hash<|>Map;
+
+            //- /std/lib.rs
+            pub mod collections {
+                mod hash {
+                    pub mod map {
+                        pub struct HashMap;
+                    }
+                }
+                pub use self::hash::map::HashMap;
+            }
+        "#;
+        let (mock, file_position) = MockAnalysis::with_files_and_position(before);
+        let analysis = analysis_with_flags(mock, &["diagnostics.unresolved-import"]);
+        let diagnostic = analysis.diagnostics(file_position.file_id).unwrap().pop().unwrap();
+        let mut fix = diagnostic.fix.unwrap();
+        assert_eq!(fix.label, "import `std::collections::HashMap`");
+        let edit = fix.source_file_edits.pop().unwrap().edit;
+        let actual = edit.apply(&analysis.file_text(file_position.file_id).unwrap());
+        assert_eq_text!("use std::collections::HashMap;", actual.trim_end());
+    }
+
+    #[test]
+    fn test_unresolved_import_is_off_by_default() {
+        check_no_diagnostic("use foo::Bar;");
+    }
+
+    #[test]
+    fn test_unresolved_path_fix() {
+        let before = r#"
+            //- /main.rs
+            fn main() {
+                let map = Hash<|>Map::new();
+            }
+
+            //- /std/lib.rs
+            pub mod collections {
+                pub struct HashMap;
+                impl HashMap {
+                    pub fn new() -> HashMap { HashMap }
+                }
+            }
+        "#;
+        let (mock, file_position) = MockAnalysis::with_files_and_position(before);
+        let analysis = analysis_with_flags(mock, &["diagnostics.unresolved-path"]);
+        let diagnostic = analysis.diagnostics(file_position.file_id).unwrap().pop().unwrap();
+        let mut fix = diagnostic.fix.unwrap();
+        assert_eq!(fix.label, "import `std::collections::HashMap`");
+        let edit = fix.source_file_edits.pop().unwrap().edit;
+        let actual = edit.apply(&analysis.file_text(file_position.file_id).unwrap());
+        assert_eq_text!(
+            "use std::collections::HashMap;\n\nfn main() {\n    let map = HashMap::new();\n}\n",
+            &actual
+        );
+    }
+
+    #[test]
+    fn test_unresolved_path_without_candidates() {
+        let mut mock = MockAnalysis::new();
+        let file_id = mock.add_file("/main.rs", "fn main() { foo(); }");
+        let analysis = analysis_with_flags(mock, &["diagnostics.unresolved-path"]);
+        let diagnostics = analysis.diagnostics(file_id).unwrap();
+        assert_debug_snapshot!(diagnostics, @r###"
+        [
+            Diagnostic {
+                message: "unresolved path",
+                range: [12; 15),
+                fix: None,
+                severity: Error,
+            },
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_unresolved_path_is_off_by_default() {
+        check_no_diagnostic("fn main() { foo(); }");
+    }
+
+    #[test]
+    fn test_no_unresolved_path_diagnostic_for_block_local_items() {
+        let mut mock = MockAnalysis::new();
+        let file_id = mock.add_file(
+            "/main.rs",
+            r"
+            fn main() {
+                use std::collections::HashMap;
+                fn local() -> i32 { LOCAL }
+                const LOCAL: i32 = 92;
+                struct S;
+                let _map = HashMap::new();
+                let _s: S = S;
+                let _x = local();
+            }
+            ",
+        );
+        let analysis = analysis_with_flags(mock, &["diagnostics.unresolved-path"]);
+        let diagnostics = analysis.diagnostics(file_id).unwrap();
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_unused_variable_fix() {
+        let before = r"
+            fn test_fn() {
+                let x = 92;
+            }
+        ";
+        let after = r"
+            fn test_fn() {
+                let _x = 92;
+            }
+        ";
+        check_apply_unused_fix(before, after);
+    }
+
+    #[test]
+    fn test_unused_variable_fix_in_record_pattern() {
+        let before = r"
+            struct S { x: i32, y: i32 }
+
+            fn test_fn(s: S) {
+                let S { x, ref mut y } = s;
+                *y += x;
+                let S { x: _, mut y } = s;
+            }
+        ";
+        let after = r"
+            struct S { x: i32, y: i32 }
+
+            fn test_fn(s: S) {
+                let S { x, ref mut y } = s;
+                *y += x;
+                let S { x: _, y: mut _y } = s;
+            }
+        ";
+        check_apply_unused_fix(before, after);
+    }
+
+    #[test]
+    fn test_unused_mut_fix() {
+        let before = r"
+            fn test_fn() {
+                let mut x = 92;
+                let _y = x;
+            }
+        ";
+        let after = r"
+            fn test_fn() {
+                let x = 92;
+                let _y = x;
+            }
+        ";
+        check_apply_unused_fix(before, after);
+    }
+
+    #[test]
+    fn test_unused_import_fix() {
+        let before = r"
+            mod foo { pub struct A; pub struct B; }
+            use foo::A;
+            use foo::B;
+
+            fn test_fn() -> A { A }
+        ";
+        let after = r"
+            mod foo { pub struct A; pub struct B; }
+            use foo::A;
+
+            fn test_fn() -> A { A }
+        ";
+        check_apply_unused_fix(before, after);
+    }
+
+    #[test]
+    fn test_unused_import_fix_in_use_tree_list() {
+        let before = r"
+            mod foo { pub struct A; pub struct B; pub struct C; }
+            use foo::{A, B, C};
+
+            fn test_fn() -> (A, C) { (A, C) }
+        ";
+        let after = r"
+            mod foo { pub struct A; pub struct B; pub struct C; }
+            use foo::{A, C};
+
+            fn test_fn() -> (A, C) { (A, C) }
+        ";
+        check_apply_unused_fix(before, after);
+
+        let before = r"
+            mod foo { pub struct A; pub mod bar { pub struct B; } }
+            use foo::{A, bar::{B}};
+
+            fn test_fn() -> A { A }
+        ";
+        let after = r"
+            mod foo { pub struct A; pub mod bar { pub struct B; } }
+            use foo::{A};
+
+            fn test_fn() -> A { A }
+        ";
+        check_apply_unused_fix(before, after);
+    }
+
+    #[test]
+    fn test_no_unused_diagnostics() {
+        let content = r"
+            mod foo { pub struct A; }
+            use foo::A;
+
+            fn test_fn(_unused: i32) -> A {
+                let mut v = A;
+                v = A;
+                let _x = 1;
+                v
+            }
+        ";
+        let mut mock = MockAnalysis::new();
+        let file_id = mock.add_file("/main.rs", content);
+        let analysis = analysis_with_flags(mock, &["diagnostics.unused"]);
+        assert_eq!(analysis.diagnostics(file_id).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_unused_diagnostics_are_off_by_default() {
+        check_no_diagnostic(
+            r"
+            mod foo { pub struct A; }
+            use foo::A;
+
+            fn test_fn(unused: i32) {
+                let mut x = 1;
+            }
+        ",
+        );
+    }
+
     #[test]
     fn test_check_unnecessary_braces_in_use_statement() {
         check_not_applicable(
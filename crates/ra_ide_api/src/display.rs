@@ -7,7 +7,7 @@ mod structure;
 mod short_label;
 
 use ra_syntax::{
-    ast::{self, AstNode, AttrsOwner, NameOwner, TypeParamsOwner},
+    ast::{self, AstNode, AttrsOwner, NameOwner, TypeParamsOwner, VisibilityOwner},
     SyntaxKind::{ATTR, COMMENT},
 };
 
@@ -61,6 +61,37 @@ pub(crate) fn where_predicates<N: TypeParamsOwner>(node: &N) -> Vec<String> {
     res
 }
 
+/// Renders the header of a type or a trait, with its generic parameters, like
+/// `pub struct Foo<T: Clone>`.
+///
+/// This works on the syntax, like `FunctionSignature`, rather than with
+/// `HirDisplay`: the generic parameters of hir have no lifetimes and no
+/// defaults, and their bounds are lowered to predicates, so the header would
+/// not read the way it was written.
+pub(crate) fn generic_item_label<N>(node: &N, keyword: &str) -> Option<String>
+where
+    N: NameOwner + VisibilityOwner + TypeParamsOwner,
+{
+    let mut buf = node.visibility().map(|v| format!("{} ", v.syntax())).unwrap_or_default();
+    buf.push_str(keyword);
+    buf.push_str(node.name()?.text().as_str());
+    let generic_parameters = generic_parameters(node);
+    if !generic_parameters.is_empty() {
+        buf.push_str(&format!("<{}>", generic_parameters.join(", ")));
+    }
+    Some(buf)
+}
+
+/// Renders the where clause of `node` the way `FunctionSignature` does, on its
+/// own line.
+pub(crate) fn where_clause_label<N: TypeParamsOwner>(node: &N) -> String {
+    let where_predicates = where_predicates(node);
+    if where_predicates.is_empty() {
+        return String::new();
+    }
+    format!("\nwhere {}", where_predicates.join(",\n      "))
+}
+
 pub(crate) fn macro_label(node: &ast::MacroCall) -> String {
     let name = node.name().map(|name| name.syntax().text().to_string()).unwrap_or_default();
     let vis = if node.has_atom_attr("macro_export") { "#[macro_export]\n" } else { "" };
@@ -34,6 +34,11 @@ pub struct FunctionSignature {
     pub generic_parameters: Vec<String>,
     /// Parameters of the function
     pub parameters: Vec<String>,
+    /// Parameter names of the function, with an empty string for parameters
+    /// which are not simple bindings
+    pub parameter_names: Vec<String>,
+    /// Whether the first parameter is `self`
+    pub has_self_param: bool,
     /// Optional return type
     pub ret_type: Option<String>,
     /// Where predicates
@@ -75,6 +80,8 @@ impl FunctionSignature {
                 name: node.name().map(|n| n.text().to_string()),
                 ret_type: node.name().map(|n| n.text().to_string()),
                 parameters: params,
+                parameter_names: vec![],
+                has_self_param: false,
                 generic_parameters: generic_parameters(&node),
                 where_predicates: where_predicates(&node),
                 doc: None,
@@ -117,6 +124,8 @@ impl FunctionSignature {
                 name: Some(name),
                 ret_type: None,
                 parameters: params,
+                parameter_names: vec![],
+                has_self_param: false,
                 generic_parameters: vec![],
                 where_predicates: vec![],
                 doc: None,
@@ -126,7 +135,7 @@ impl FunctionSignature {
     }
 
     pub(crate) fn from_macro(db: &db::RootDatabase, macro_def: hir::MacroDef) -> Option<Self> {
-        let node: ast::MacroCall = macro_def.source(db).value;
+        let node: ast::MacroCall = macro_def.source(db)?.value;
 
         let params = vec![];
 
@@ -137,6 +146,8 @@ impl FunctionSignature {
                 name: node.name().map(|n| n.text().to_string()),
                 ret_type: None,
                 parameters: params,
+                parameter_names: vec![],
+                has_self_param: false,
                 generic_parameters: vec![],
                 where_predicates: vec![],
                 doc: None,
@@ -160,6 +171,23 @@ impl From<&'_ ast::FnDef> for FunctionSignature {
             res
         }
 
+        fn param_name_list(node: &ast::FnDef) -> Vec<String> {
+            let mut res = vec![];
+            if let Some(param_list) = node.param_list() {
+                if param_list.self_param().is_some() {
+                    res.push("self".to_string())
+                }
+
+                res.extend(param_list.params().map(|param| match param.pat() {
+                    Some(ast::Pat::BindPat(it)) if it.pat().is_none() => {
+                        it.name().map(|name| name.text().to_string()).unwrap_or_default()
+                    }
+                    _ => String::new(),
+                }));
+            }
+            res
+        }
+
         FunctionSignature {
             kind: CallableKind::Function,
             visibility: node.visibility().map(|n| n.syntax().text().to_string()),
@@ -169,6 +197,8 @@ impl From<&'_ ast::FnDef> for FunctionSignature {
                 .and_then(|r| r.type_ref())
                 .map(|n| n.syntax().text().to_string()),
             parameters: param_list(node),
+            parameter_names: param_name_list(node),
+            has_self_param: node.param_list().and_then(|it| it.self_param()).is_some(),
             generic_parameters: generic_parameters(node),
             where_predicates: where_predicates(node),
             // docs are processed separately
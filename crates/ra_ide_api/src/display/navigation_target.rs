@@ -307,7 +307,11 @@ impl ToNav for hir::StructField {
 
 impl ToNav for hir::MacroDef {
     fn to_nav(&self, db: &RootDatabase) -> NavigationTarget {
-        let src = self.source(db);
+        let src = match self.source(db) {
+            Some(it) => it,
+            // Procedural macros have no source, navigate to their crate instead.
+            None => return self.crate_root(db).to_nav(db),
+        };
         log::debug!("nav target {:#?}", src.value.syntax());
         NavigationTarget::from_named(
             db,
@@ -0,0 +1,171 @@
+//! Resolves intra-doc links, like ``[`Vec`]`` or `[vectors](Vec)`, in doc
+//! comments to the items they refer to.
+//!
+//! Resolved links are rewritten as inline links to the path of the item, so
+//! that the client only has to replace the destinations it knows about.
+
+use std::ops::Range;
+
+use hir::{PathResolution, Source};
+use pulldown_cmark::{CowStr, Event, LinkType, Options, Parser, Tag};
+use ra_syntax::{ast, SyntaxNode};
+
+use crate::{db::RootDatabase, display::ToNav, NavigationTarget};
+
+/// An intra-doc link, resolved to the item it refers to.
+#[derive(Debug, Clone)]
+pub struct DocLink {
+    /// The destination of the link in the rewritten docs.
+    pub path: String,
+    pub target: NavigationTarget,
+}
+
+/// Resolves the links in the `docs` of `item` in the scope of `item`, and
+/// returns the rewritten docs.
+pub(crate) fn resolve_doc_links(
+    db: &RootDatabase,
+    item: Source<&SyntaxNode>,
+    docs: &str,
+    links: &mut Vec<DocLink>,
+) -> String {
+    let analyzer = hir::SourceAnalyzer::new(db, item, None);
+    rewrite_links(docs, |path| {
+        if let Some(link) = links.iter().find(|it| it.path == path) {
+            return Some(link.path.clone());
+        }
+        let target = resolve_path(db, &analyzer, path)?;
+        links.push(DocLink { path: path.to_string(), target });
+        Some(path.to_string())
+    })
+}
+
+fn resolve_path(
+    db: &RootDatabase,
+    analyzer: &hir::SourceAnalyzer,
+    path: &str,
+) -> Option<NavigationTarget> {
+    let path = hir::Path::from_ast(ast::Path::parse(path)?)?;
+    match analyzer.resolve_hir_path(db, &path)? {
+        PathResolution::Def(def) => NavigationTarget::from_def(db, def),
+        PathResolution::AssocItem(it) => Some(it.to_nav(db)),
+        PathResolution::Macro(it) => {
+            if it.is_proc_macro() {
+                return None;
+            }
+            Some(it.to_nav(db))
+        }
+        PathResolution::Local(_)
+        | PathResolution::GenericParam(_)
+        | PathResolution::SelfType(_) => None,
+    }
+}
+
+/// Rewrites the links of `docs` for which `resolve` returns a destination as
+/// inline links. Links with a reference definition are left alone, the
+/// definition decides where they point to.
+pub fn rewrite_links(docs: &str, mut resolve: impl FnMut(&str) -> Option<String>) -> String {
+    // Links without a definition, like ``[`Vec`]``, point to their label.
+    let broken_link = |label: &str, _: &str| Some((label.to_string(), String::new()));
+    let parser = Parser::new_with_broken_link_callback(docs, Options::empty(), Some(&broken_link));
+
+    let mut buf = String::with_capacity(docs.len());
+    let mut copied = 0;
+    let mut link: Option<(Range<usize>, CowStr, usize)> = None;
+    for (event, range) in parser.into_offset_iter() {
+        match event {
+            Event::Start(Tag::Link(link_type, dest, _)) => match link_type {
+                LinkType::Inline
+                | LinkType::ReferenceUnknown
+                | LinkType::CollapsedUnknown
+                | LinkType::ShortcutUnknown => link = Some((range.clone(), dest, range.start + 1)),
+                _ => (),
+            },
+            Event::End(Tag::Link(..)) => {
+                let (range, dest, text_end) = match link.take() {
+                    Some(it) => it,
+                    None => continue,
+                };
+                let dest = match link_path(&dest).and_then(|path| resolve(path)) {
+                    Some(it) => it,
+                    None => continue,
+                };
+                buf.push_str(&docs[copied..range.start]);
+                buf.push_str(&docs[range.start..text_end]);
+                buf.push_str("](");
+                buf.push_str(&dest);
+                buf.push(')');
+                copied = range.end;
+            }
+            _ => {
+                if let Some((_, _, text_end)) = &mut link {
+                    *text_end = range.end.max(*text_end);
+                }
+            }
+        }
+    }
+    buf.push_str(&docs[copied..]);
+    buf
+}
+
+/// Returns the path a link destination refers to, if it is one: `` `Foo::bar()` ``
+/// refers to `Foo::bar`.
+fn link_path(dest: &str) -> Option<&str> {
+    let path = dest.trim().trim_matches('`').trim_end_matches("()").trim_end_matches('!');
+    ast::Path::parse(path).map(|_| path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rewrite_links;
+
+    fn check(docs: &str, expected: &str) {
+        let actual = rewrite_links(docs, |path| {
+            if path.starts_with("Known") {
+                Some(path.to_string())
+            } else {
+                None
+            }
+        });
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn rewrites_resolved_links() {
+        check(
+            "See [`Known`], [that](Known::f()) and [`known`](`Known!`).",
+            "See [`Known`](Known), [that](Known::f) and [`known`](Known).",
+        );
+    }
+
+    #[test]
+    fn keeps_unresolved_and_non_path_links() {
+        check(
+            "[`Unknown`], [site](https://example.com), [a] [b][Known]\n[Known]: Known",
+            "[`Unknown`], [site](https://example.com), [a] [b][Known]\n[Known]: Known",
+        );
+    }
+
+    #[test]
+    fn keeps_keyword_links() {
+        assert_eq!(super::link_path("`true`"), None);
+        assert_eq!(super::link_path("Known::match"), None);
+        assert_eq!(super::link_path("self::Known"), Some("self::Known"));
+        assert_eq!(super::link_path("Known::<"), None);
+    }
+
+    #[test]
+    fn skips_code() {
+        check(
+            "```\nlet x = [Known];\n```\n[Known] and `[Known]`",
+            "```\nlet x = [Known];\n```\n[Known](Known) and `[Known]`",
+        );
+    }
+
+    #[test]
+    fn rewrites_links_without_definition() {
+        check(
+            "[`Known`][], [*b*][Known::f] and [c][Unknown]",
+            "[`Known`](Known), [*b*](Known::f) and [c][Unknown]",
+        );
+    }
+}
@@ -3,41 +3,79 @@ use std::iter::successors;
 
 use hir::Source;
 use ra_db::FileId;
-use ra_syntax::{ast, AstNode, SyntaxNode, SyntaxToken};
+use ra_syntax::{ast, AstNode, SyntaxNode, SyntaxToken, TextRange};
 
 use crate::{db::RootDatabase, FileRange};
 
+/// Maps the range of `node`, which might be in a macro expansion, to the file
+/// the outermost macro call is in. If the tokens of `node` do not all come
+/// from the arguments of the macro calls, the range of the first token which
+/// can be mapped is used, and failing that, the range of the outermost macro
+/// call.
 pub(crate) fn original_range(db: &RootDatabase, node: Source<&SyntaxNode>) -> FileRange {
-    let expansion = match node.file_id.expansion_info(db) {
-        None => {
-            return FileRange {
-                file_id: node.file_id.original_file(db),
-                range: node.value.text_range(),
-            }
-        }
-        Some(it) => it,
-    };
-    // FIXME: the following completely wrong.
-    //
-    // *First*, we should try to map first and last tokens of node, and, if that
-    // fails, return the range of the overall macro expansions.
-    //
-    // *Second*, we should handle recurside macro expansions
-
+    if let Some(range) = original_range_by_tokens(db, node) {
+        return range;
+    }
     let token = node
         .value
         .descendants_with_tokens()
         .filter_map(|it| it.into_token())
-        .find_map(|it| expansion.map_token_up(node.with_value(&it)));
+        .find_map(|it| original_token(db, node.with_value(it)));
+    if let Some(token) = token {
+        return FileRange {
+            file_id: token.file_id.original_file(db),
+            range: token.value.text_range(),
+        };
+    }
+    let mut call = node.map(|it| it.clone());
+    while let Some(it) = call.file_id.call_node(db) {
+        call = it;
+    }
+    FileRange { file_id: call.file_id.original_file(db), range: call.value.text_range() }
+}
 
-    match token {
-        Some(it) => {
-            FileRange { file_id: it.file_id.original_file(db), range: it.value.text_range() }
-        }
-        None => {
-            FileRange { file_id: node.file_id.original_file(db), range: node.value.text_range() }
+/// Maps `token` up through nested macro calls, to either a macro call or a
+/// macro definition.
+fn original_token(
+    db: &RootDatabase,
+    mut token: Source<SyntaxToken>,
+) -> Option<Source<SyntaxToken>> {
+    while let Some(expansion) = token.file_id.expansion_info(db) {
+        token = expansion.map_token_up(token.as_ref())?;
+    }
+    Some(token)
+}
+
+/// Maps the first and the last tokens of `node` up through nested macro
+/// calls. Returns `None` if any of them comes from a macro definition.
+pub(crate) fn original_range_by_tokens(
+    db: &RootDatabase,
+    node: Source<&SyntaxNode>,
+) -> Option<FileRange> {
+    let mut first = node.with_value(node.value.first_token()?);
+    let mut last = node.with_value(node.value.last_token()?);
+    while let Some(expansion) = first.file_id.expansion_info(db) {
+        // Tokens can also come from the macro definition.
+        let call = first.file_id.call_node(db)?;
+        let is_in_call = |token: &Source<SyntaxToken>| {
+            token.file_id == call.file_id
+                && token.value.text_range().is_subrange(&call.value.text_range())
+        };
+        first = expansion.map_token_up(first.as_ref())?;
+        last = expansion.map_token_up(last.as_ref())?;
+        if !is_in_call(&first) || !is_in_call(&last) {
+            return None;
         }
     }
+    let start = first.value.text_range().start();
+    let end = last.value.text_range().end();
+    if start > end {
+        return None;
+    }
+    Some(FileRange {
+        file_id: first.file_id.original_file(db),
+        range: TextRange::from_to(start, end),
+    })
 }
 
 pub(crate) fn descend_into_macros(
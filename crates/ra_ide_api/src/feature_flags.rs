@@ -53,9 +53,17 @@ impl Default for FeatureFlags {
     fn default() -> FeatureFlags {
         FeatureFlags::new(&[
             ("lsp.diagnostics", true),
+            ("lsp.hover.link-positions", false),
             ("completion.insertion.add-call-parenthesis", true),
             ("completion.enable-postfix", true),
+            ("completion.enable-auto-import", false),
+            ("inlay-hints.parameter-names", true),
+            ("inlay-hints.chaining", true),
             ("notifications.workspace-loaded", true),
+            ("diagnostics.type-mismatch", false),
+            ("diagnostics.unresolved-import", false),
+            ("diagnostics.unresolved-path", false),
+            ("diagnostics.unused", false),
         ])
     }
 }
@@ -674,6 +674,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn goto_from_nested_macros() {
+        check_goto(
+            "
+            //- /lib.rs
+            macro_rules! id {
+                ($($tt:tt)*) => { $($tt)* }
+            }
+            fn foo() {}
+            fn main() {
+                id!(id!(fo<|>o()));
+            }
+            ",
+            "foo FN_DEF FileId(1) [52; 63) [55; 58)",
+        );
+    }
+
+    #[test]
+    fn goto_local_from_nested_macros() {
+        check_goto(
+            "
+            //- /lib.rs
+            macro_rules! id {
+                ($($tt:tt)*) => { $($tt)* }
+            }
+            fn main() {
+                let x = 1;
+                id!(id!(x<|> + 1));
+            }
+            ",
+            "x BIND_PAT FileId(1) [72; 73) [72; 73)",
+        );
+    }
+
     #[test]
     fn goto_from_macro() {
         check_goto(
@@ -1,19 +1,23 @@
 //! FIXME: write short doc here
 
-use hir::{db::AstDatabase, Adt, HasSource, HirDisplay};
+use hir::{
+    db::{AstDatabase, HirDatabase},
+    Adt, Crate, HasSource, HirDisplay, Source,
+};
 use ra_db::SourceDatabase;
 use ra_syntax::{
     algo::find_covering_element,
-    ast::{self, DocCommentsOwner},
+    ast::{self, DocCommentsOwner, NameOwner, VisibilityOwner},
     match_ast, AstNode,
 };
 
 use crate::{
     db::RootDatabase,
     display::{
-        description_from_symbol, docs_from_symbol, macro_label, rust_code_markup,
-        rust_code_markup_with_doc, ShortLabel,
+        description_from_symbol, docs_from_symbol, generic_item_label, macro_label,
+        rust_code_markup, rust_code_markup_with_doc, where_clause_label, ShortLabel,
     },
+    doc_links::{resolve_doc_links, DocLink},
     expand::descend_into_macros,
     references::{classify_name, classify_name_ref, NameKind, NameKind::*},
     FilePosition, FileRange, RangeInfo,
@@ -24,6 +28,7 @@ use crate::{
 pub struct HoverResult {
     results: Vec<String>,
     exact: bool,
+    doc_links: Vec<DocLink>,
 }
 
 impl Default for HoverResult {
@@ -38,6 +43,7 @@ impl HoverResult {
             results: Vec::new(),
             // We assume exact by default
             exact: true,
+            doc_links: Vec::new(),
         }
     }
 
@@ -65,6 +71,12 @@ impl HoverResult {
         &self.results
     }
 
+    /// The intra-doc links of the results, which were rewritten as links to
+    /// the path of the item they refer to.
+    pub fn doc_links(&self) -> &[DocLink] {
+        &self.doc_links
+    }
+
     /// Returns the results converted into markup
     /// for displaying in a UI
     pub fn to_markup(&self) -> String {
@@ -97,48 +109,58 @@ fn hover_text_from_name_kind(
     db: &RootDatabase,
     name_kind: NameKind,
     no_fallback: &mut bool,
+    doc_links: &mut Vec<DocLink>,
 ) -> Option<String> {
     return match name_kind {
         Macro(it) => {
-            let src = it.source(db);
-            hover_text(src.value.doc_comment_text(), Some(macro_label(&src.value)))
+            let src = it.source(db)?;
+            let docs = docs_with_links(db, src.as_ref(), doc_links);
+            hover_text(docs, Some(macro_label(&src.value)))
         }
         Field(it) => {
             let src = it.source(db);
-            match src.value {
-                hir::FieldSource::Named(it) => hover_text(it.doc_comment_text(), it.short_label()),
+            match &src.value {
+                hir::FieldSource::Named(field) => {
+                    let docs = docs_with_links(db, src.with_value(field), doc_links);
+                    let vis =
+                        field.visibility().map(|v| format!("{} ", v.syntax())).unwrap_or_default();
+                    let label =
+                        format!("{}{}: {}", vis, field.name()?.text(), it.ty(db).display(db));
+                    hover_text(docs, Some(label))
+                }
                 _ => None,
             }
         }
         AssocItem(it) => match it {
-            hir::AssocItem::Function(it) => from_def_source(db, it),
-            hir::AssocItem::Const(it) => from_def_source(db, it),
-            hir::AssocItem::TypeAlias(it) => from_def_source(db, it),
+            hir::AssocItem::Function(it) => from_def_source(db, it, doc_links),
+            hir::AssocItem::Const(it) => from_def_source(db, it, doc_links),
+            hir::AssocItem::TypeAlias(it) => from_type_alias(db, it, doc_links),
         },
         Def(it) => match it {
-            hir::ModuleDef::Module(it) => match it.definition_source(db).value {
-                hir::ModuleSource::Module(it) => {
-                    hover_text(it.doc_comment_text(), it.short_label())
+            hir::ModuleDef::Module(it) => {
+                let src = it.definition_source(db);
+                match &src.value {
+                    hir::ModuleSource::Module(module) => {
+                        let docs = docs_with_links(db, src.with_value(module), doc_links);
+                        hover_text(docs, module.short_label())
+                    }
+                    _ => None,
                 }
-                _ => None,
-            },
-            hir::ModuleDef::Function(it) => from_def_source(db, it),
-            hir::ModuleDef::Adt(Adt::Struct(it)) => from_def_source(db, it),
-            hir::ModuleDef::Adt(Adt::Union(it)) => from_def_source(db, it),
-            hir::ModuleDef::Adt(Adt::Enum(it)) => from_def_source(db, it),
-            hir::ModuleDef::EnumVariant(it) => from_def_source(db, it),
-            hir::ModuleDef::Const(it) => from_def_source(db, it),
-            hir::ModuleDef::Static(it) => from_def_source(db, it),
-            hir::ModuleDef::Trait(it) => from_def_source(db, it),
-            hir::ModuleDef::TypeAlias(it) => from_def_source(db, it),
+            }
+            hir::ModuleDef::Function(it) => from_def_source(db, it, doc_links),
+            hir::ModuleDef::Adt(it) => from_adt(db, it, doc_links),
+            hir::ModuleDef::EnumVariant(it) => from_def_source(db, it, doc_links),
+            hir::ModuleDef::Const(it) => from_def_source(db, it, doc_links),
+            hir::ModuleDef::Static(it) => from_def_source(db, it, doc_links),
+            hir::ModuleDef::Trait(it) => {
+                let (docs, label) = from_generic_def_source(db, it, "trait ", doc_links);
+                hover_text(docs, label)
+            }
+            hir::ModuleDef::TypeAlias(it) => from_type_alias(db, it, doc_links),
             hir::ModuleDef::BuiltinType(it) => Some(it.to_string()),
         },
         SelfType(ty) => match ty.as_adt() {
-            Some((adt_def, _)) => match adt_def {
-                hir::Adt::Struct(it) => from_def_source(db, it),
-                hir::Adt::Union(it) => from_def_source(db, it),
-                hir::Adt::Enum(it) => from_def_source(db, it),
-            },
+            Some((adt_def, _)) => from_adt(db, adt_def, doc_links),
             _ => None,
         },
         Local(_) => {
@@ -152,16 +174,119 @@ fn hover_text_from_name_kind(
         }
     };
 
-    fn from_def_source<A, D>(db: &RootDatabase, def: D) -> Option<String>
+    fn from_def_source<A, D>(
+        db: &RootDatabase,
+        def: D,
+        doc_links: &mut Vec<DocLink>,
+    ) -> Option<String>
     where
         D: HasSource<Ast = A>,
         A: ast::DocCommentsOwner + ast::NameOwner + ShortLabel,
     {
         let src = def.source(db);
-        hover_text(src.value.doc_comment_text(), src.value.short_label())
+        let docs = docs_with_links(db, src.as_ref(), doc_links);
+        hover_text(docs, src.value.short_label())
+    }
+
+    fn from_adt(db: &RootDatabase, adt: Adt, doc_links: &mut Vec<DocLink>) -> Option<String> {
+        let (docs, label) = match adt {
+            Adt::Struct(it) => from_generic_def_source(db, it, "struct ", doc_links),
+            Adt::Union(it) => from_generic_def_source(db, it, "union ", doc_links),
+            Adt::Enum(it) => from_generic_def_source(db, it, "enum ", doc_links),
+        };
+        hover_text(with_implemented_traits(db, adt, docs), label)
+    }
+
+    /// Returns the docs and the full header of a type or a trait.
+    fn from_generic_def_source<A, D>(
+        db: &RootDatabase,
+        def: D,
+        keyword: &str,
+        doc_links: &mut Vec<DocLink>,
+    ) -> (Option<String>, Option<String>)
+    where
+        D: HasSource<Ast = A>,
+        A: ast::DocCommentsOwner + ast::NameOwner + ast::VisibilityOwner + ast::TypeParamsOwner,
+    {
+        let src = def.source(db);
+        let docs = docs_with_links(db, src.as_ref(), doc_links);
+        let label = generic_item_label(&src.value, keyword)
+            .map(|label| label + &where_clause_label(&src.value));
+        (docs, label)
+    }
+
+    fn from_type_alias(
+        db: &RootDatabase,
+        it: hir::TypeAlias,
+        doc_links: &mut Vec<DocLink>,
+    ) -> Option<String> {
+        let src = it.source(db);
+        let docs = docs_with_links(db, src.as_ref(), doc_links);
+        let mut label = generic_item_label(&src.value, "type ")?;
+        label.push_str(&where_clause_label(&src.value));
+        if src.value.type_ref().is_some() {
+            label.push_str(&format!(" = {}", it.ty(db).display(db)));
+        }
+        hover_text(docs, Some(label))
+    }
+}
+
+fn docs_with_links<N: ast::DocCommentsOwner>(
+    db: &RootDatabase,
+    src: Source<&N>,
+    doc_links: &mut Vec<DocLink>,
+) -> Option<String> {
+    let docs = src.value.doc_comment_text()?;
+    Some(resolve_doc_links(db, src.map(|it| it.syntax()), &docs, doc_links))
+}
+
+/// Appends the traits implemented by `adt` to its docs.
+fn with_implemented_traits(db: &RootDatabase, adt: Adt, docs: Option<String>) -> Option<String> {
+    let traits = implemented_traits(db, adt);
+    if traits.is_empty() {
+        return docs;
+    }
+    let traits: Vec<String> = traits.iter().map(|it| format!("`{}`", it)).collect();
+    let traits = format!("Implements {}", traits.join(", "));
+    match docs {
+        Some(docs) => Some(format!("{}\n\n{}", docs, traits)),
+        None => Some(traits),
     }
 }
 
+/// Returns the traits implemented for `adt`. The impls can only be in the crate
+/// of `adt` or in the crates depending on it, directly or not.
+fn implemented_traits(db: &RootDatabase, adt: Adt) -> Vec<String> {
+    let all_crates = Crate::all(db);
+    let mut crates = vec![adt.module(db).krate()];
+    loop {
+        let dependents = all_crates
+            .iter()
+            .filter(|krate| !crates.contains(*krate))
+            .filter(|krate| krate.dependencies(db).iter().any(|dep| crates.contains(&dep.krate)))
+            .copied()
+            .collect::<Vec<_>>();
+        if dependents.is_empty() {
+            break;
+        }
+        crates.extend(dependents);
+    }
+    let mut res = Vec::new();
+    for krate in crates {
+        for impl_block in db.impls_in_crate(krate).all_impls() {
+            match impl_block.target_ty(db).as_adt() {
+                Some((it, _)) if it == adt => (),
+                _ => continue,
+            }
+            let name = impl_block.target_trait_ref(db).and_then(|it| it.trait_.name(db));
+            res.extend(name.map(|it| it.to_string()));
+        }
+    }
+    res.sort();
+    res.dedup();
+    res
+}
+
 pub(crate) fn hover(db: &RootDatabase, position: FilePosition) -> Option<RangeInfo<HoverResult>> {
     let file = db.parse_or_expand(position.file_id.into())?;
     let token = file.token_at_offset(position.offset).filter(|it| !it.kind().is_trivia()).next()?;
@@ -176,7 +301,13 @@ pub(crate) fn hover(db: &RootDatabase, position: FilePosition) -> Option<RangeIn
                 if let Some(name_kind) =
                     classify_name_ref(db, token.with_value(&name_ref)).map(|d| d.kind)
                 {
-                    res.extend(hover_text_from_name_kind(db, name_kind, &mut no_fallback))
+                    let text = hover_text_from_name_kind(
+                        db,
+                        name_kind,
+                        &mut no_fallback,
+                        &mut res.doc_links,
+                    );
+                    res.extend(text);
                 }
 
                 if res.is_empty() && !no_fallback {
@@ -197,7 +328,9 @@ pub(crate) fn hover(db: &RootDatabase, position: FilePosition) -> Option<RangeIn
             },
             ast::Name(name) => {
                 if let Some(name_kind) = classify_name(db, token.with_value(&name)).map(|d| d.kind) {
-                    res.extend(hover_text_from_name_kind(db, name_kind, &mut true));
+                    let text =
+                        hover_text_from_name_kind(db, name_kind, &mut true, &mut res.doc_links);
+                    res.extend(text);
                 }
 
                 if !res.is_empty() {
@@ -732,4 +865,104 @@ fn func(foo: i32) { if true { <|>foo; }; }
             &["fn foo()"],
         );
     }
+
+    #[test]
+    fn hover_shows_generics_and_implemented_traits_of_type() {
+        check_hover_result(
+            r#"
+            //- /main.rs
+            trait Marker {}
+            trait Other {}
+            struct Foo<T: Clone> where T: Copy { t: T }
+            impl<T: Clone> Marker for Foo<T> where T: Copy {}
+            impl<T: Clone> Foo<T> where T: Copy {}
+            fn main() {
+                let foo: Fo<|>o<u32>;
+            }
+        "#,
+            &["
+struct Foo<T: Clone>
+where T: Copy
+```
+
+Implements `Marker`
+            "
+            .trim()],
+        );
+    }
+
+    #[test]
+    fn hover_shows_type_alias_with_its_type() {
+        check_hover_result(
+            r#"
+            //- /main.rs
+            struct Foo<T>(T);
+            type Al<|>ias<T> = Foo<Foo<T>>;
+        "#,
+            &["type Alias<T> = Foo<Foo<T>>"],
+        );
+    }
+
+    #[test]
+    fn hover_resolves_intra_doc_links() {
+        let (analysis, position) = single_file_with_position(
+            "
+            mod m { pub struct Bar; }
+            struct Bar;
+            /// Wraps a [`Bar`] from [the module](m::Bar), unlike [`Missing`].
+            struct Fo<|>o;
+            ",
+        );
+        let hover = analysis.hover(position).unwrap().unwrap();
+        assert_eq!(
+            hover.info.first(),
+            Some(
+                "```rust\nstruct Foo\n```\n\n\
+                 Wraps a [`Bar`](Bar) from [the module](m::Bar), unlike [`Missing`]."
+            )
+        );
+        let links: Vec<String> = hover
+            .info
+            .doc_links()
+            .iter()
+            .map(|it| format!("{} {:?}", it.path, it.target.full_range()))
+            .collect();
+        assert_eq!(links, ["Bar [51; 62)", "m::Bar [21; 36)"]);
+    }
+
+    #[test]
+    fn hover_ignores_keyword_doc_links() {
+        let (analysis, position) = single_file_with_position(
+            "
+            /// Returns [`true`] or [`match`], see [`fn`](struct).
+            fn fo<|>o() -> bool { true }
+            ",
+        );
+        let hover = analysis.hover(position).unwrap().unwrap();
+        assert_eq!(
+            hover.info.first(),
+            Some(
+                "```rust\nfn foo() -> bool\n```\n\n\
+                 Returns [`true`] or [`match`], see [`fn`](struct)."
+            )
+        );
+        assert!(hover.info.doc_links().is_empty());
+    }
+
+    #[test]
+    fn test_hover_through_nested_macros() {
+        check_hover_result(
+            "
+            //- /lib.rs
+            macro_rules! id {
+                ($($tt:tt)*) => { $($tt)* }
+            }
+            fn foo() {}
+            fn main() {
+                id!(id!(fo<|>o()));
+            }
+            ",
+            &["fn foo()"],
+        );
+    }
 }
@@ -0,0 +1,207 @@
+//! Finds the paths under which items with a given name can be imported.
+//!
+//! Candidates come from the symbol index, so this only works for items which
+//! are declared directly in a module, and not for items produced by macros.
+//! Items of other crates are imported through the public paths of the
+//! dependencies, so that `HashMap` is imported as `std::collections::HashMap`
+//! rather than from the private module which defines it.
+
+use std::collections::VecDeque;
+
+use hir::{
+    Crate, Either, HasSource, Import, Module, ModuleDef, ModuleSource, Name, ScopeDef, Source,
+};
+use itertools::Itertools;
+use ra_syntax::{
+    ast, AstNode, SmolStr,
+    SyntaxKind::{ITEM_LIST, MODULE, SOURCE_FILE, VISIBILITY},
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    db::RootDatabase,
+    symbol_index::{self, FileSymbol},
+    Query,
+};
+
+const MAX_CANDIDATES: usize = 40;
+/// Many symbols can't be imported, so more of them are looked up than the
+/// number of paths which are returned.
+const SYMBOLS_PER_CANDIDATE: usize = 10;
+
+/// Returns the paths of the items named `name` which can be imported into
+/// `krate`, like `["crate", "foo", "Bar"]` or `["std", "collections", "HashMap"]`.
+///
+/// Items from `krate` itself come first, shorter paths are preferred.
+pub(crate) fn find_imports(db: &RootDatabase, krate: Crate, name: &str) -> Vec<Vec<SmolStr>> {
+    find(db, krate, name, true, MAX_CANDIDATES)
+}
+
+/// Like `find_imports`, but for the items whose name fuzzy matches `query`.
+/// At most `limit` paths are returned.
+pub(crate) fn find_imports_fuzzy(
+    db: &RootDatabase,
+    krate: Crate,
+    query: &str,
+    limit: usize,
+) -> Vec<Vec<SmolStr>> {
+    find(db, krate, query, false, limit)
+}
+
+fn find(
+    db: &RootDatabase,
+    krate: Crate,
+    name: &str,
+    exact: bool,
+    limit: usize,
+) -> Vec<Vec<SmolStr>> {
+    let mut symbols = Vec::new();
+    for &libs in &[false, true] {
+        let mut query = Query::new(name.to_string());
+        if exact {
+            query.exact();
+        }
+        query.limit(limit * SYMBOLS_PER_CANDIDATE);
+        if libs {
+            query.libs();
+        }
+        symbols.extend(symbol_index::world_symbols(db, query));
+    }
+
+    let mut public_paths = FxHashMap::default();
+    let mut res = symbols
+        .iter()
+        .filter_map(|symbol| import_path(db, krate, symbol, &mut public_paths))
+        .collect::<Vec<_>>();
+    res.sort_by_key(|path| (path[0].as_str() != "crate", path.len(), path.iter().join("::")));
+    res.dedup();
+    res.truncate(limit);
+    res
+}
+
+fn import_path(
+    db: &RootDatabase,
+    krate: Crate,
+    symbol: &FileSymbol,
+    public_paths: &mut FxHashMap<Crate, FxHashMap<ModuleDef, Vec<SmolStr>>>,
+) -> Option<Vec<SmolStr>> {
+    let parse = db.parse(symbol.file_id);
+    let node = symbol.ptr.to_node(parse.tree().syntax());
+
+    // Only items declared directly in a module can be imported, not the ones
+    // from impl blocks or function bodies.
+    let parent = node.parent()?;
+    let in_module = match parent.kind() {
+        SOURCE_FILE => true,
+        ITEM_LIST => parent.parent().map_or(false, |it| it.kind() == MODULE),
+        _ => false,
+    };
+    if !in_module {
+        return None;
+    }
+
+    let module_source =
+        ModuleSource::from_child_node(db, Source::new(symbol.file_id.into(), &parent));
+    let module =
+        hir::Module::from_definition(db, Source::new(symbol.file_id.into(), module_source))?;
+
+    if module.krate() == krate {
+        let mut res = vec![SmolStr::new("crate")];
+        for module in module.path_to_root(db).into_iter().rev().skip(1) {
+            res.push(SmolStr::new(module.name(db)?.to_string()));
+        }
+        res.push(symbol.name.clone());
+        return Some(res);
+    }
+
+    // Items of other crates are imported through the paths the dependencies
+    // make public, which may be re-exports, like `std::collections::HashMap`.
+    let def = module.scope(db).into_iter().find_map(|(name, def, import)| match def {
+        ScopeDef::ModuleDef(def) if import.is_none() && name.to_string() == symbol.name => {
+            Some(def)
+        }
+        _ => None,
+    })?;
+    krate
+        .dependencies(db)
+        .into_iter()
+        .filter_map(|dep| {
+            let paths =
+                public_paths.entry(dep.krate).or_insert_with(|| find_public_paths(db, dep.krate));
+            let path = paths.get(&def)?;
+            let mut res = vec![SmolStr::new(dep.name.to_string())];
+            res.extend(path.iter().cloned());
+            Some(res)
+        })
+        .min_by_key(|path| path.len())
+}
+
+/// Returns the shortest public path to each item reachable from the root of
+/// `krate`, following only public modules and public re-exports.
+fn find_public_paths(db: &RootDatabase, krate: Crate) -> FxHashMap<ModuleDef, Vec<SmolStr>> {
+    let mut res = FxHashMap::default();
+    let root = match krate.root_module(db) {
+        Some(it) => it,
+        None => return res,
+    };
+    let mut visited = FxHashSet::default();
+    visited.insert(root);
+    let mut queue = VecDeque::new();
+    queue.push_back((root, Vec::new()));
+    while let Some((module, path)) = queue.pop_front() {
+        for (name, def, import) in module.scope(db) {
+            let def = match def {
+                ScopeDef::ModuleDef(it) => it,
+                _ => continue,
+            };
+            if !is_public(db, module, &name, import) {
+                continue;
+            }
+            let mut def_path: Vec<SmolStr> = path.clone();
+            def_path.push(SmolStr::new(name.to_string()));
+            if let ModuleDef::Module(child) = def {
+                if child.krate() == krate && visited.insert(child) {
+                    queue.push_back((child, def_path.clone()));
+                }
+            }
+            res.entry(def).or_insert(def_path);
+        }
+    }
+    res
+}
+
+/// Checks if the `name` entry of the `module` scope is visible from other
+/// crates: the `use` or `extern crate` item importing it, or the item
+/// declaring it, has to be `pub`. Items produced by macros are not considered.
+fn is_public(db: &RootDatabase, module: Module, name: &Name, import: Option<Import>) -> bool {
+    let node = match import {
+        Some(import) => match import.source(db).value {
+            Either::A(use_tree) => match use_tree.syntax().ancestors().find_map(ast::UseItem::cast)
+            {
+                Some(it) => it.syntax().clone(),
+                None => return false,
+            },
+            Either::B(extern_crate) => extern_crate.syntax().clone(),
+        },
+        None => {
+            let items = match module.definition_source(db).value {
+                ModuleSource::SourceFile(it) => it.syntax().clone(),
+                ModuleSource::Module(it) => match it.item_list() {
+                    Some(it) => it.syntax().clone(),
+                    None => return false,
+                },
+            };
+            let name = name.to_string();
+            let item = items.children().find(|item| {
+                item.children()
+                    .find_map(ast::Name::cast)
+                    .map_or(false, |it| it.text().as_str() == name)
+            });
+            match item {
+                Some(it) => it,
+                None => return false,
+            }
+        }
+    };
+    node.children().any(|it| it.kind() == VISIBILITY && it.text() == "pub")
+}
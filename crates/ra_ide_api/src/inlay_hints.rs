@@ -1,15 +1,20 @@
 //! FIXME: write short doc here
 
-use crate::{db::RootDatabase, FileId};
+use crate::{db::RootDatabase, FileId, FunctionSignature};
 use hir::{HirDisplay, SourceAnalyzer, Ty};
 use ra_syntax::{
-    ast::{self, AstNode, TypeAscriptionOwner},
-    match_ast, SmolStr, SourceFile, SyntaxKind, SyntaxNode, TextRange,
+    ast::{self, ArgListOwner, AstNode, TypeAscriptionOwner},
+    match_ast, Direction, NodeOrToken, SmolStr, SourceFile, SyntaxKind, SyntaxNode, TextRange,
 };
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum InlayKind {
     TypeHint,
+    /// The name of the parameter, shown before an argument.
+    ParameterHint,
+    /// The type of an intermediate expression of a method chain, shown at the
+    /// end of its line.
+    ChainingHint,
 }
 
 #[derive(Debug)]
@@ -25,11 +30,22 @@ pub(crate) fn inlay_hints(
     file: &SourceFile,
     max_inlay_hint_length: Option<usize>,
 ) -> Vec<InlayHint> {
-    file.syntax()
-        .descendants()
-        .map(|node| get_inlay_hints(db, file_id, &node, max_inlay_hint_length).unwrap_or_default())
-        .flatten()
-        .collect()
+    let parameter_hints = db.feature_flags.get("inlay-hints.parameter-names");
+    let chaining_hints = db.feature_flags.get("inlay-hints.chaining");
+
+    let mut res = Vec::new();
+    for node in file.syntax().descendants() {
+        res.extend(get_inlay_hints(db, file_id, &node, max_inlay_hint_length).unwrap_or_default());
+        if let Some(expr) = ast::Expr::cast(node) {
+            if parameter_hints {
+                res.extend(get_param_name_hints(db, file_id, &expr).unwrap_or_default());
+            }
+            if chaining_hints {
+                res.extend(get_chaining_hint(db, file_id, &expr, max_inlay_hint_length));
+            }
+        }
+    }
+    res
 }
 
 fn get_inlay_hints(
@@ -87,6 +103,130 @@ fn get_inlay_hints(
     }
 }
 
+fn get_param_name_hints(
+    db: &RootDatabase,
+    file_id: FileId,
+    expr: &ast::Expr,
+) -> Option<Vec<InlayHint>> {
+    let (args, is_method_call) = match expr {
+        ast::Expr::CallExpr(it) => (it.arg_list()?.args(), false),
+        ast::Expr::MethodCallExpr(it) => (it.arg_list()?.args(), true),
+        _ => return None,
+    };
+    let signature = get_fn_signature(db, file_id, expr)?;
+    // The receiver of a method call is the `self` parameter.
+    let n_params_to_skip = if is_method_call && signature.has_self_param { 1 } else { 0 };
+    let n_params = signature.parameter_names.len() - n_params_to_skip;
+
+    let hints = signature
+        .parameter_names
+        .iter()
+        .skip(n_params_to_skip)
+        .zip(args)
+        .filter(|(param_name, arg)| should_show_param_hint(&signature, n_params, param_name, arg))
+        .map(|(param_name, arg)| InlayHint {
+            range: arg.syntax().text_range(),
+            kind: InlayKind::ParameterHint,
+            label: param_name.as_str().into(),
+        })
+        .collect();
+    Some(hints)
+}
+
+fn get_fn_signature(
+    db: &RootDatabase,
+    file_id: FileId,
+    expr: &ast::Expr,
+) -> Option<FunctionSignature> {
+    let analyzer = SourceAnalyzer::new(db, hir::Source::new(file_id.into(), expr.syntax()), None);
+    match expr {
+        ast::Expr::CallExpr(it) => {
+            let (callable_def, _subst) = analyzer.type_of(db, &it.expr()?)?.as_callable()?;
+            match callable_def {
+                hir::CallableDef::Function(it) => Some(FunctionSignature::from_hir(db, it)),
+                hir::CallableDef::Struct(it) => FunctionSignature::from_struct(db, it),
+                hir::CallableDef::EnumVariant(it) => FunctionSignature::from_enum_variant(db, it),
+            }
+        }
+        ast::Expr::MethodCallExpr(it) => {
+            let function = analyzer.resolve_method_call(it)?;
+            Some(FunctionSignature::from_hir(db, function))
+        }
+        _ => None,
+    }
+}
+
+/// Hints are noise when the argument already says what the parameter is, like
+/// in `foo(bar)` with a `bar` parameter, or in `set_len(10)`.
+fn should_show_param_hint(
+    signature: &FunctionSignature,
+    n_params: usize,
+    param_name: &str,
+    arg: &ast::Expr,
+) -> bool {
+    if param_name.is_empty() || param_name.starts_with('_') || param_name == "self" {
+        return false;
+    }
+    if arg_name(arg).map_or(false, |name| name.trim_start_matches('_') == param_name) {
+        return false;
+    }
+    if n_params == 1 {
+        if let Some(fn_name) = &signature.name {
+            if fn_name == param_name || fn_name.ends_with(&format!("_{}", param_name)) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Returns the name which describes the value of `expr`, if there is one.
+fn arg_name(expr: &ast::Expr) -> Option<String> {
+    match expr {
+        ast::Expr::PathExpr(it) => Some(it.path()?.segment()?.name_ref()?.text().to_string()),
+        ast::Expr::FieldExpr(it) => Some(it.name_ref()?.text().to_string()),
+        ast::Expr::MethodCallExpr(it) => Some(it.name_ref()?.text().to_string()),
+        ast::Expr::RefExpr(it) => arg_name(&it.expr()?),
+        ast::Expr::PrefixExpr(it) => arg_name(&it.expr()?),
+        _ => None,
+    }
+}
+
+/// Expressions followed by a newline and a `.` are a part of a method chain
+/// spanning several lines.
+fn get_chaining_hint(
+    db: &RootDatabase,
+    file_id: FileId,
+    expr: &ast::Expr,
+    max_inlay_hint_length: Option<usize>,
+) -> Option<InlayHint> {
+    let mut tokens = expr
+        .syntax()
+        .siblings_with_tokens(Direction::Next)
+        .filter_map(NodeOrToken::into_token)
+        .filter(|it| match it.kind() {
+            SyntaxKind::WHITESPACE => it.text().contains('\n'),
+            SyntaxKind::COMMENT => false,
+            _ => true,
+        });
+    match (tokens.next()?.kind(), tokens.next()?.kind()) {
+        (SyntaxKind::WHITESPACE, SyntaxKind::DOT) => (),
+        _ => return None,
+    }
+
+    let analyzer = SourceAnalyzer::new(db, hir::Source::new(file_id.into(), expr.syntax()), None);
+    let ty = analyzer.type_of(db, expr)?;
+    if let Ty::Apply(_) = ty {
+        Some(InlayHint {
+            range: expr.syntax().text_range(),
+            kind: InlayKind::ChainingHint,
+            label: ty.display_truncated(db, max_inlay_hint_length).to_string().into(),
+        })
+    } else {
+        None
+    }
+}
+
 fn get_pat_type_hints(
     db: &RootDatabase,
     analyzer: &SourceAnalyzer,
@@ -551,4 +691,84 @@ fn main() {
         "###
         );
     }
+
+    #[test]
+    fn parameter_hints() {
+        let (analysis, file_id) = single_file(
+            r#"
+fn foo(param: u32, other: bool, _ignored: u32) {}
+struct S;
+impl S {
+    fn method(&self, param: u32) {}
+    fn set_len(&mut self, len: usize) {}
+}
+fn main() {
+    let param: u32 = 1;
+    let s: S = S;
+    foo(param, true, 3);
+    s.method(2);
+    S::method(&s, 2);
+    s.set_len(0);
+}"#,
+        );
+
+        assert_debug_snapshot!(analysis.inlay_hints(file_id, None).unwrap(), @r###"
+        [
+            InlayHint {
+                range: [218; 222),
+                kind: ParameterHint,
+                label: "other",
+            },
+            InlayHint {
+                range: [241; 242),
+                kind: ParameterHint,
+                label: "param",
+            },
+            InlayHint {
+                range: [263; 264),
+                kind: ParameterHint,
+                label: "param",
+            },
+        ]
+        "###
+        );
+    }
+
+    #[test]
+    fn chaining_hints() {
+        let (analysis, file_id) = single_file(
+            r#"
+struct A(B);
+impl A { fn into_b(self) -> B { self.0 } }
+struct B(C);
+impl B { fn into_c(self) -> C { self.0 } }
+struct C;
+fn main() {
+    let c = A(B(C))
+        .into_b() // comment
+        .into_c();
+}"#,
+        );
+
+        assert_debug_snapshot!(analysis.inlay_hints(file_id, None).unwrap(), @r###"
+        [
+            InlayHint {
+                range: [143; 144),
+                kind: TypeHint,
+                label: "C",
+            },
+            InlayHint {
+                range: [147; 172),
+                kind: ChainingHint,
+                label: "B",
+            },
+            InlayHint {
+                range: [147; 154),
+                kind: ChainingHint,
+                label: "A",
+            },
+        ]
+        "###
+        );
+    }
 }
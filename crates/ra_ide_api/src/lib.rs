@@ -24,13 +24,17 @@ mod goto_definition;
 mod goto_type_definition;
 mod extend_selection;
 mod hover;
+mod doc_links;
 mod call_info;
+mod call_hierarchy;
+mod change_signature;
 mod syntax_highlighting;
 mod parent_module;
 mod references;
 mod impls;
 mod assists;
 mod diagnostics;
+mod imports_locator;
 mod syntax_tree;
 mod folding_ranges;
 mod line_index;
@@ -43,6 +47,7 @@ mod inlay_hints;
 mod wasm_shims;
 mod expand;
 mod expand_macro;
+mod ssr;
 
 #[cfg(test)]
 mod marks;
@@ -62,10 +67,13 @@ use crate::{db::LineIndexDatabase, display::ToNav, symbol_index::FileSymbol};
 
 pub use crate::{
     assists::{Assist, AssistId},
+    call_hierarchy::CallItem,
     change::{AnalysisChange, LibraryData},
+    change_signature::ChangeSignatureError,
     completion::{CompletionItem, CompletionItemKind, InsertTextFormat},
     diagnostics::Severity,
     display::{file_structure, FunctionSignature, NavigationTarget, StructureNode},
+    doc_links::{rewrite_links, DocLink},
     expand_macro::ExpandedMacro,
     feature_flags::FeatureFlags,
     folding_ranges::{Fold, FoldKind},
@@ -73,16 +81,20 @@ pub use crate::{
     inlay_hints::{InlayHint, InlayKind},
     line_index::{LineCol, LineIndex},
     line_index_utils::translate_offset_with_edit,
-    references::{ReferenceSearchResult, SearchScope},
+    references::{ReferenceSearchResult, RenameError, SearchScope},
     runnables::{Runnable, RunnableKind},
     source_change::{FileSystemEdit, SourceChange, SourceFileEdit},
-    syntax_highlighting::HighlightedRange,
+    ssr::SsrError,
+    syntax_highlighting::{
+        Highlight, HighlightModifier, HighlightModifiers, HighlightTag, HighlightedRange,
+    },
 };
 
 pub use hir::Documentation;
 pub use ra_db::{
     Canceled, CrateGraph, CrateId, Edition, FileId, FilePosition, FileRange, SourceRootId,
 };
+pub use ra_fmt::FormatOptions;
 
 pub type Cancelable<T> = Result<T, Canceled>;
 
@@ -315,6 +327,24 @@ impl Analysis {
         })
     }
 
+    /// Returns an edit formatting the range with the built-in formatter. It
+    /// only fixes up whitespace and trailing commas, but, unlike `rustfmt`,
+    /// works for parts of a file and for files with syntax errors.
+    pub fn format_range(
+        &self,
+        frange: FileRange,
+        options: FormatOptions,
+    ) -> Cancelable<SourceChange> {
+        self.with_db(|db| {
+            let parse = db.parse(frange.file_id);
+            let file_edit = SourceFileEdit {
+                file_id: frange.file_id,
+                edit: ra_fmt::format_range(&parse.tree(), frange.range, options),
+            };
+            SourceChange::source_file_edit("format", file_edit)
+        })
+    }
+
     /// Returns an edit which should be applied when opening a new line, fixing
     /// up minor stuff like continuing the comment.
     pub fn on_enter(&self, position: FilePosition) -> Cancelable<Option<SourceChange>> {
@@ -402,6 +432,25 @@ impl Analysis {
         self.with_db(|db| references::find_all_refs(db, position, search_scope).map(|it| it.info))
     }
 
+    /// Returns the function defined or called at `position`, as the root of
+    /// a call hierarchy.
+    pub fn call_hierarchy(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Option<RangeInfo<Vec<NavigationTarget>>>> {
+        self.with_db(|db| call_hierarchy::call_hierarchy(db, position))
+    }
+
+    /// Returns the functions calling the function at `position`.
+    pub fn incoming_calls(&self, position: FilePosition) -> Cancelable<Option<Vec<CallItem>>> {
+        self.with_db(|db| call_hierarchy::incoming_calls(db, position))
+    }
+
+    /// Returns the functions called by the function at `position`.
+    pub fn outgoing_calls(&self, position: FilePosition) -> Cancelable<Option<Vec<CallItem>>> {
+        self.with_db(|db| call_hierarchy::outgoing_calls(db, position))
+    }
+
     /// Returns a short text describing element at position.
     pub fn hover(&self, position: FilePosition) -> Cancelable<Option<RangeInfo<HoverResult>>> {
         self.with_db(|db| hover::hover(db, position))
@@ -463,16 +512,47 @@ impl Analysis {
         self.with_db(|db| hover::type_of(db, frange))
     }
 
+    /// Returns the range of the name `rename` would rename at the position.
+    pub fn prepare_rename(&self, position: FilePosition) -> Cancelable<Option<RangeInfo<()>>> {
+        self.with_db(|db| references::prepare_rename(db, position))
+    }
+
     /// Returns the edit required to rename reference at the position to the new
-    /// name.
+    /// name. Fails if some of the references come from a macro definition.
     pub fn rename(
         &self,
         position: FilePosition,
         new_name: &str,
-    ) -> Cancelable<Option<RangeInfo<SourceChange>>> {
+    ) -> Cancelable<Result<Option<RangeInfo<SourceChange>>, RenameError>> {
         self.with_db(|db| references::rename(db, position, new_name))
     }
 
+    /// Replaces the code matching the search pattern of the `query` in all
+    /// workspace files. The paths of the pattern are resolved at `position`.
+    /// If `parse_only` is set, only checks that the query is valid.
+    pub fn structural_search_replace(
+        &self,
+        position: FilePosition,
+        query: &str,
+        parse_only: bool,
+    ) -> Cancelable<Result<SourceChange, SsrError>> {
+        self.with_db(|db| {
+            let edits = ssr::parse_search_replace(db, position, query, parse_only)?;
+            Ok(SourceChange::source_file_edits("ssr", edits))
+        })
+    }
+
+    /// Changes the parameters of the function at the position to `new_params`,
+    /// updating its calls and, for trait methods, the trait and its impls.
+    /// Fails if some of the references are not calls.
+    pub fn change_signature(
+        &self,
+        position: FilePosition,
+        new_params: &str,
+    ) -> Cancelable<Result<SourceChange, ChangeSignatureError>> {
+        self.with_db(|db| change_signature::change_signature(db, position, new_params))
+    }
+
     /// Performs an operation on that may be Canceled.
     fn with_db<F: FnOnce(&db::RootDatabase) -> T + std::panic::UnwindSafe, T>(
         &self,
@@ -8,29 +8,37 @@
 //! for text occurrences of the identifier. If there's an `ast::NameRef`
 //! at the index that the match starts at and its tree parent is
 //! resolved to the search element definition, we get a reference.
+//! Identifiers in the arguments of macro calls are mapped into the expansion
+//! of the call, and classified there.
 
 mod classify;
 mod name_definition;
 mod rename;
 mod search_scope;
 
-use hir::Source;
+use hir::{db::AstDatabase, Source};
 use once_cell::unsync::Lazy;
 use ra_db::{SourceDatabase, SourceDatabaseExt};
 use ra_prof::profile;
-use ra_syntax::{algo::find_node_at_offset, ast, AstNode, SourceFile, SyntaxNode, TextUnit};
+use ra_syntax::{
+    algo::find_node_at_offset, ast, AstNode, SourceFile, SyntaxKind::IDENT, SyntaxNode,
+    SyntaxToken, TextRange, TextUnit,
+};
 
 use crate::{
-    db::RootDatabase, display::ToNav, FilePosition, FileRange, NavigationTarget, RangeInfo,
+    db::RootDatabase,
+    display::ToNav,
+    expand::{descend_into_macros, original_range_by_tokens},
+    FileId, FilePosition, FileRange, NavigationTarget, RangeInfo,
 };
 
 pub(crate) use self::{
     classify::{classify_name, classify_name_ref},
     name_definition::{NameDefinition, NameKind},
-    rename::rename,
+    rename::{prepare_rename, rename},
 };
 
-pub use self::search_scope::SearchScope;
+pub use self::{rename::RenameError, search_scope::SearchScope};
 
 #[derive(Debug, Clone)]
 pub struct ReferenceSearchResult {
@@ -114,10 +122,35 @@ fn find_name<'a>(
         let range = name.syntax().text_range();
         return Some(RangeInfo::new(range, (name.text().to_string(), def)));
     }
-    let name_ref = find_node_at_offset::<ast::NameRef>(&syntax, position.offset)?;
-    let def = classify_name_ref(db, Source::new(position.file_id.into(), &name_ref))?;
-    let range = name_ref.syntax().text_range();
-    Some(RangeInfo::new(range, (name_ref.text().to_string(), def)))
+    if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(&syntax, position.offset) {
+        let def = classify_name_ref(db, Source::new(position.file_id.into(), &name_ref))?;
+        let range = name_ref.syntax().text_range();
+        return Some(RangeInfo::new(range, (name_ref.text().to_string(), def)));
+    }
+    let token = syntax.token_at_offset(position.offset).find(|it| it.kind() == IDENT)?;
+    let range = token.text_range();
+    let name = token.text().to_string();
+    let def = classify_token_in_macro_call(db, position.file_id, token)?;
+    Some(RangeInfo::new(range, (name, def)))
+}
+
+/// Classifies an identifier in the arguments of a macro call, by mapping it
+/// into the expansion of the call.
+fn classify_token_in_macro_call(
+    db: &RootDatabase,
+    file_id: FileId,
+    token: SyntaxToken,
+) -> Option<NameDefinition> {
+    let token = descend_into_macros(db, file_id, token);
+    if token.file_id == file_id.into() {
+        return None;
+    }
+    let parent = token.value.parent();
+    if let Some(name_ref) = ast::NameRef::cast(parent.clone()) {
+        return classify_name_ref(db, token.with_value(&name_ref));
+    }
+    let name = ast::Name::cast(parent)?;
+    classify_name(db, token.with_value(&name))
 }
 
 fn process_definition(
@@ -134,30 +167,116 @@ fn process_definition(
     for (file_id, search_range) in scope {
         let text = db.file_text(file_id);
         let parse = Lazy::new(|| SourceFile::parse(&text));
+        let is_in_search_range =
+            |range: TextRange| search_range.map_or(true, |it| range.is_subrange(&it));
 
         for (idx, _) in text.match_indices(pat) {
             let offset = TextUnit::from_usize(idx);
 
-            if let Some(name_ref) =
+            let (range, d) = if let Some(name_ref) =
                 find_node_at_offset::<ast::NameRef>(parse.tree().syntax(), offset)
             {
                 let range = name_ref.syntax().text_range();
-                if let Some(search_range) = search_range {
-                    if !range.is_subrange(&search_range) {
-                        continue;
-                    }
+                if !is_in_search_range(range) {
+                    continue;
                 }
-                if let Some(d) = classify_name_ref(db, Source::new(file_id.into(), &name_ref)) {
-                    if d == def {
-                        refs.push(FileRange { file_id, range });
-                    }
+                (range, classify_name_ref(db, Source::new(file_id.into(), &name_ref)))
+            } else {
+                // The identifier might be in the arguments of a macro call.
+                let token = match parse.tree().syntax().token_at_offset(offset).right_biased() {
+                    Some(it) if it.kind() == IDENT && it.text() == pat => it,
+                    _ => continue,
+                };
+                let range = token.text_range();
+                if !is_in_search_range(range) {
+                    continue;
                 }
+                (range, classify_token_in_macro_call(db, file_id, token))
+            };
+            if d.as_ref() == Some(&def) {
+                refs.push(FileRange { file_id, range });
             }
         }
     }
     refs
 }
 
+/// Finds the macro calls in the search scope of the definition at `position`
+/// which expand to references coming from the macro definition rather than
+/// from the arguments of the call. Such references cannot be edited without
+/// changing the macro itself.
+pub(crate) fn find_refs_in_macro_definitions(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Vec<FileRange> {
+    let _p = profile("find_refs_in_macro_definitions");
+    let parse = db.parse(position.file_id);
+    let RangeInfo { info: (name, def), .. } = match find_name(db, parse.tree().syntax(), position) {
+        Some(it) => it,
+        None => return Vec::new(),
+    };
+
+    let mut res = Vec::new();
+    for (file_id, search_range) in def.search_scope(db) {
+        let parse = db.parse(file_id);
+        for call in parse.tree().syntax().descendants().filter_map(ast::MacroCall::cast) {
+            let range = call.syntax().text_range();
+            if search_range.map_or(false, |it| !range.is_subrange(&it)) {
+                continue;
+            }
+            if has_unmappable_ref(db, Source::new(file_id.into(), call), &name, &def) {
+                res.push(FileRange { file_id, range });
+            }
+        }
+    }
+    res
+}
+
+fn has_unmappable_ref(
+    db: &RootDatabase,
+    call: Source<ast::MacroCall>,
+    name: &str,
+    def: &NameDefinition,
+) -> bool {
+    let mut calls = vec![call];
+    while let Some(call) = calls.pop() {
+        let analyzer = hir::SourceAnalyzer::new(db, call.as_ref().map(|it| it.syntax()), None);
+        let file_id = match analyzer.expand(db, call.as_ref()) {
+            Some(it) => it.file_id(),
+            None => continue,
+        };
+        let expanded = match db.parse_or_expand(file_id) {
+            Some(it) => it,
+            None => continue,
+        };
+        for node in expanded.descendants() {
+            if let Some(call) = ast::MacroCall::cast(node.clone()) {
+                calls.push(Source::new(file_id, call));
+                continue;
+            }
+            let d = if let Some(name_ref) = ast::NameRef::cast(node.clone()) {
+                if name_ref.text() != name {
+                    continue;
+                }
+                classify_name_ref(db, Source::new(file_id, &name_ref))
+            } else if let Some(it) = ast::Name::cast(node.clone()) {
+                if it.text() != name {
+                    continue;
+                }
+                classify_name(db, Source::new(file_id, &it))
+            } else {
+                continue;
+            };
+            if d.as_ref() == Some(def)
+                && original_range_by_tokens(db, Source::new(file_id, &node)).is_none()
+            {
+                return true;
+            }
+        }
+    }
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
@@ -385,6 +504,36 @@ mod tests {
         assert_eq!(refs.len(), 3);
     }
 
+    #[test]
+    fn test_find_all_refs_in_macro_calls() {
+        let code = r#"
+        macro_rules! id { ($($tt:tt)*) => { $($tt)* } }
+        fn main() {
+            let foo<|> = 1;
+            id!(foo);
+            id!(id!(foo + foo));
+        }"#;
+
+        let refs = get_all_refs(code);
+        let ranges: Vec<_> = refs.references().iter().map(|it| it.range).collect();
+        assert_eq!(format!("{:?}", ranges), "[[118; 121), [144; 147), [150; 153)]");
+    }
+
+    #[test]
+    fn test_find_all_refs_from_macro_call() {
+        let code = r#"
+        macro_rules! id { ($($tt:tt)*) => { $($tt)* } }
+        fn foo() {}
+        fn main() {
+            id!(fo<|>o());
+            foo();
+        }"#;
+
+        let refs = get_all_refs(code);
+        assert_eq!(refs.declaration().name(), "foo");
+        assert_eq!(refs.len(), 3);
+    }
+
     fn get_all_refs(text: &str) -> ReferenceSearchResult {
         let (analysis, position) = single_file_with_position(text);
         analysis.find_all_refs(position, None).unwrap().unwrap()
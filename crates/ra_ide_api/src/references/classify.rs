@@ -6,7 +6,7 @@ use ra_syntax::{ast, match_ast, AstNode};
 use test_utils::tested_by;
 
 use super::{
-    name_definition::{from_assoc_item, from_module_def, from_struct_field},
+    name_definition::{from_assoc_item, from_macro_def, from_module_def, from_struct_field},
     NameDefinition, NameKind,
 };
 use crate::db::RootDatabase;
@@ -99,16 +99,8 @@ pub(crate) fn classify_name(db: &RootDatabase, name: Source<&ast::Name>) -> Opti
             },
             ast::MacroCall(it) => {
                 let src = name.with_value(it);
-                let def = hir::MacroDef::from_source(db, src.clone())?;
-
-                let module_src = ModuleSource::from_child_node(db, src.as_ref().map(|it| it.syntax()));
-                let module = Module::from_definition(db, src.with_value(module_src))?;
-
-                Some(NameDefinition {
-                    visibility: None,
-                    container: module,
-                    kind: NameKind::Macro(def),
-                })
+                let def = hir::MacroDef::from_source(db, src)?;
+                from_macro_def(db, def)
             },
             _ => None,
         }
@@ -156,9 +148,10 @@ pub(crate) fn classify_name_ref(
 
     if let Some(macro_call) = parent.ancestors().find_map(ast::MacroCall::cast) {
         tested_by!(goto_definition_works_for_macros);
-        if let Some(macro_def) = analyzer.resolve_macro_call(db, &macro_call) {
-            let kind = NameKind::Macro(macro_def);
-            return Some(NameDefinition { kind, container, visibility });
+        if let Some(macro_def) =
+            analyzer.resolve_macro_call(db, &macro_call).filter(|it| !it.is_proc_macro())
+        {
+            return from_macro_def(db, macro_def);
         }
     }
 
@@ -178,8 +171,10 @@ pub(crate) fn classify_name_ref(
             Some(NameDefinition { kind, container, visibility })
         }
         PathResolution::Macro(def) => {
-            let kind = NameKind::Macro(def);
-            Some(NameDefinition { kind, container, visibility })
+            if def.is_proc_macro() {
+                return None;
+            }
+            from_macro_def(db, def)
         }
         PathResolution::SelfType(impl_block) => {
             let ty = impl_block.target_ty(db);
@@ -4,10 +4,10 @@
 //! Note that the reference search is possible for not all of the classified items.
 
 use hir::{
-    Adt, AssocItem, GenericParam, HasSource, Local, MacroDef, Module, ModuleDef, StructField, Ty,
-    VariantDef,
+    Adt, AssocItem, FromSource, GenericParam, HasSource, Local, MacroDef, Module, ModuleDef,
+    ModuleSource, StructField, Ty, VariantDef,
 };
-use ra_syntax::{ast, ast::VisibilityOwner};
+use ra_syntax::{ast, ast::VisibilityOwner, AstNode};
 
 use crate::db::RootDatabase;
 
@@ -40,6 +40,13 @@ pub(super) fn from_assoc_item(db: &RootDatabase, item: AssocItem) -> NameDefinit
     NameDefinition { kind, container, visibility }
 }
 
+pub(super) fn from_macro_def(db: &RootDatabase, def: MacroDef) -> Option<NameDefinition> {
+    let src = def.source(db)?;
+    let module_src = ModuleSource::from_child_node(db, src.as_ref().map(|it| it.syntax()));
+    let container = Module::from_definition(db, src.with_value(module_src))?;
+    Some(NameDefinition { kind: NameKind::Macro(def), container, visibility: None })
+}
+
 pub(super) fn from_struct_field(db: &RootDatabase, field: StructField) -> NameDefinition {
     let kind = NameKind::Field(field);
     let parent = field.parent_def(db);
@@ -1,8 +1,19 @@
-//! FIXME: write short doc here
+//! Renames the definition at the cursor and all of its references.
+//!
+//! References in macro calls are renamed by editing the arguments of the
+//! calls. If the name is used by code coming from a macro definition, the
+//! rename is refused, as editing the definition would affect the other uses
+//! of the macro.
+
+use std::{error::Error, fmt};
 
 use hir::ModuleSource;
 use ra_db::{RelativePath, RelativePathBuf, SourceDatabase, SourceDatabaseExt};
-use ra_syntax::{algo::find_node_at_offset, ast, AstNode, SyntaxNode};
+use ra_syntax::{
+    algo::find_node_at_offset,
+    ast::{self, AttrsOwner},
+    AstNode, SyntaxNode,
+};
 use ra_text_edit::TextEdit;
 
 use crate::{
@@ -10,25 +21,47 @@ use crate::{
     SourceFileEdit, TextRange,
 };
 
-use super::find_all_refs;
+use super::{find_all_refs, find_refs_in_macro_definitions};
+
+#[derive(Debug)]
+pub struct RenameError(String);
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl Error for RenameError {}
 
 pub(crate) fn rename(
     db: &RootDatabase,
     position: FilePosition,
     new_name: &str,
-) -> Option<RangeInfo<SourceChange>> {
+) -> Result<Option<RangeInfo<SourceChange>>, RenameError> {
     let parse = db.parse(position.file_id);
     if let Some((ast_name, ast_module)) =
         find_name_and_module_at_offset(parse.tree().syntax(), position)
     {
         let range = ast_name.syntax().text_range();
-        rename_mod(db, &ast_name, &ast_module, position, new_name)
-            .map(|info| RangeInfo::new(range, info))
+        Ok(rename_mod(db, &ast_name, &ast_module, position, new_name)
+            .map(|info| RangeInfo::new(range, info)))
     } else {
         rename_reference(db, position, new_name)
     }
 }
 
+/// Returns the range of the name which `rename` would rename. Unlike `rename`,
+/// this doesn't check whether the references can be edited, so it stays cheap
+/// enough to run before the new name is known.
+pub(crate) fn prepare_rename(db: &RootDatabase, position: FilePosition) -> Option<RangeInfo<()>> {
+    let parse = db.parse(position.file_id);
+    if let Some((ast_name, _)) = find_name_and_module_at_offset(parse.tree().syntax(), position) {
+        return Some(RangeInfo::new(ast_name.syntax().text_range(), ()));
+    }
+    find_all_refs(db, position, None).map(|it| RangeInfo::new(it.range, ()))
+}
+
 fn find_name_and_module_at_offset(
     syntax: &SyntaxNode,
     position: FilePosition,
@@ -56,29 +89,44 @@ fn rename_mod(
     let mut source_file_edits = Vec::new();
     let mut file_system_edits = Vec::new();
     let module_src = hir::Source { file_id: position.file_id.into(), value: ast_module.clone() };
-    if let Some(module) = hir::Module::from_declaration(db, module_src) {
+    // Modules with a `#[path]` attribute don't depend on their name.
+    let has_path_attr =
+        ast_module.attrs().any(|attr| attr.simple_name().map_or(false, |it| it == "path"));
+    if let Some(module) = hir::Module::from_declaration(db, module_src).filter(|_| !has_path_attr) {
         let src = module.definition_source(db);
         let file_id = src.file_id.original_file(db);
         match src.value {
             ModuleSource::SourceFile(..) => {
+                let source_root = db.file_source_root(file_id);
                 let mod_path: RelativePathBuf = db.file_relative_path(file_id);
-                // mod is defined in path/to/dir/mod.rs
-                let dst_path = if mod_path.file_stem() == Some("mod") {
-                    mod_path
-                        .parent()
-                        .and_then(|p| p.parent())
-                        .or_else(|| Some(RelativePath::new("")))
-                        .map(|p| p.join(new_name).join("mod.rs"))
+                if mod_path.file_stem() == Some("mod") {
+                    // mod is defined in path/to/dir/mod.rs, move the whole directory
+                    let dir = mod_path.parent().unwrap_or_else(|| RelativePath::new(""));
+                    let parent = dir.parent().unwrap_or_else(|| RelativePath::new(""));
+                    file_system_edits.push(FileSystemEdit::MoveDir {
+                        source_root,
+                        src_path: dir.to_relative_path_buf(),
+                        dst_path: parent.join(new_name),
+                    });
                 } else {
-                    Some(mod_path.with_file_name(new_name).with_extension("rs"))
-                };
-                if let Some(path) = dst_path {
-                    let move_file = FileSystemEdit::MoveFile {
+                    file_system_edits.push(FileSystemEdit::MoveFile {
                         src: file_id,
-                        dst_source_root: db.file_source_root(position.file_id),
-                        dst_path: path,
-                    };
-                    file_system_edits.push(move_file);
+                        dst_source_root: source_root,
+                        dst_path: mod_path.with_file_name(new_name).with_extension("rs"),
+                    });
+                    // submodules of path/to/foo.rs live in path/to/foo/
+                    let dir = mod_path.with_file_name(mod_path.file_stem().unwrap_or_default());
+                    let has_submodules = db
+                        .source_root(source_root)
+                        .walk()
+                        .any(|it| db.file_relative_path(it).starts_with(&dir));
+                    if has_submodules {
+                        file_system_edits.push(FileSystemEdit::MoveDir {
+                            source_root,
+                            src_path: dir,
+                            dst_path: mod_path.with_file_name(new_name),
+                        });
+                    }
                 }
             }
             ModuleSource::Module(..) => {}
@@ -90,6 +138,13 @@ fn rename_mod(
         edit: TextEdit::replace(ast_name.syntax().text_range(), new_name.into()),
     };
     source_file_edits.push(edit);
+    if let Some(RangeInfo { info: refs, .. }) = find_all_refs(db, position, None) {
+        source_file_edits.extend(
+            refs.references()
+                .iter()
+                .map(|range| source_edit_from_file_id_range(range.file_id, range.range, new_name)),
+        );
+    }
 
     Some(SourceChange::from_edits("rename", source_file_edits, file_system_edits))
 }
@@ -98,8 +153,20 @@ fn rename_reference(
     db: &RootDatabase,
     position: FilePosition,
     new_name: &str,
-) -> Option<RangeInfo<SourceChange>> {
-    let RangeInfo { range, info: refs } = find_all_refs(db, position, None)?;
+) -> Result<Option<RangeInfo<SourceChange>>, RenameError> {
+    let RangeInfo { range, info: refs } = match find_all_refs(db, position, None) {
+        Some(it) => it,
+        None => return Ok(None),
+    };
+
+    let conflicts = find_refs_in_macro_definitions(db, position);
+    if !conflicts.is_empty() {
+        return Err(RenameError(format!(
+            "Cannot rename `{}`: {} macro call(s) use it in code coming from the macro definition",
+            refs.declaration().name(),
+            conflicts.len()
+        )));
+    }
 
     let edit = refs
         .into_iter()
@@ -107,10 +174,10 @@ fn rename_reference(
         .collect::<Vec<_>>();
 
     if edit.is_empty() {
-        return None;
+        return Ok(None);
     }
 
-    Some(RangeInfo::new(range, SourceChange::source_file_edits("rename", edit)))
+    Ok(Some(RangeInfo::new(range, SourceChange::source_file_edits("rename", edit))))
 }
 
 #[cfg(test)]
@@ -120,7 +187,8 @@ mod tests {
     use test_utils::assert_eq_text;
 
     use crate::{
-        mock_analysis::analysis_and_position, mock_analysis::single_file_with_position, FileId,
+        mock_analysis::analysis_and_position, mock_analysis::single_file_with_position,
+        mock_analysis::MockAnalysis, FileId,
     };
 
     #[test]
@@ -199,6 +267,108 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_rename_in_macro_calls() {
+        test_rename(
+            r#"
+    macro_rules! id { ($($tt:tt)*) => { $($tt)* } }
+    fn main() {
+        let i<|> = 1;
+        id!(i + 1);
+        id!(id!(i));
+    }"#,
+            "j",
+            r#"
+    macro_rules! id { ($($tt:tt)*) => { $($tt)* } }
+    fn main() {
+        let j = 1;
+        id!(j + 1);
+        id!(id!(j));
+    }"#,
+        );
+    }
+
+    #[test]
+    fn test_rename_macro_rules() {
+        test_rename(
+            r#"
+    macro_rules! foo<|> { () => {} }
+    fn main() {
+        foo!();
+    }"#,
+            "bar",
+            r#"
+    macro_rules! bar { () => {} }
+    fn main() {
+        bar!();
+    }"#,
+        );
+    }
+
+    #[test]
+    fn test_rename_macro_use_macro() {
+        test_rename_ranges(
+            "
+            //- /lib.rs
+            #[macro_use]
+            mod foo;
+            fn f() {
+                bar!();
+            }
+
+            //- /foo.rs
+            macro_rules! bar<|> {
+                () => {};
+            }
+            fn g() {
+                bar!();
+            }
+            ",
+            "[(FileId(1), [35; 38)), (FileId(2), [13; 16)), (FileId(2), [48; 51))]",
+        );
+    }
+
+    #[test]
+    fn test_rename_exported_macro() {
+        test_rename_ranges(
+            "
+            //- /lib.rs
+            use foo::bar;
+            fn f() {
+                bar!();
+            }
+
+            //- /foo/lib.rs
+            #[macro_export]
+            macro_rules! bar<|> {
+                () => {};
+            }
+            ",
+            "[(FileId(1), [9; 12)), (FileId(1), [27; 30)), (FileId(2), [29; 32))]",
+        );
+    }
+
+    #[test]
+    fn test_rename_used_by_macro_definition() {
+        let (analysis, position) = single_file_with_position(
+            "
+            macro_rules! call_foo { () => { foo() } }
+            fn foo<|>() {}
+            fn main() {
+                call_foo!();
+            }
+            ",
+        );
+        let err = analysis.rename(position, "bar").unwrap().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Cannot rename `foo`: 1 macro call(s) use it in code coming from the macro definition"
+        );
+        // The macro calls are only checked by the rename itself.
+        let range = analysis.prepare_rename(position).unwrap().unwrap().range;
+        assert_eq!(&analysis.file_text(position.file_id).unwrap()[range], "foo");
+    }
+
     #[test]
     fn test_rename_mod() {
         let (analysis, position) = analysis_and_position(
@@ -214,7 +384,7 @@ mod tests {
             ",
         );
         let new_name = "foo2";
-        let source_change = analysis.rename(position, new_name).unwrap();
+        let source_change = analysis.rename(position, new_name).unwrap().unwrap();
         assert_debug_snapshot!(&source_change,
 @r###"
         Some(
@@ -266,7 +436,7 @@ mod tests {
             ",
         );
         let new_name = "foo2";
-        let source_change = analysis.rename(position, new_name).unwrap();
+        let source_change = analysis.rename(position, new_name).unwrap().unwrap();
         assert_debug_snapshot!(&source_change,
         @r###"
         Some(
@@ -290,14 +460,12 @@ mod tests {
                         },
                     ],
                     file_system_edits: [
-                        MoveFile {
-                            src: FileId(
-                                2,
-                            ),
-                            dst_source_root: SourceRootId(
+                        MoveDir {
+                            source_root: SourceRootId(
                                 0,
                             ),
-                            dst_path: "foo2/mod.rs",
+                            src_path: "foo",
+                            dst_path: "foo2",
                         },
                     ],
                     cursor_position: None,
@@ -308,9 +476,109 @@ mod tests {
                );
     }
 
+    #[test]
+    fn test_rename_mod_with_submodules() {
+        let (analysis, position) = analysis_and_position(
+            "
+            //- /lib.rs
+            mod fo<|>o;
+            //- /foo.rs
+            mod bar;
+            //- /foo/bar.rs
+            // empty
+            ",
+        );
+        let source_change = analysis.rename(position, "foo2").unwrap().unwrap().unwrap();
+        assert_debug_snapshot!(&source_change.info.file_system_edits,
+        @r###"
+        [
+            MoveFile {
+                src: FileId(
+                    2,
+                ),
+                dst_source_root: SourceRootId(
+                    0,
+                ),
+                dst_path: "foo2.rs",
+            },
+            MoveDir {
+                source_root: SourceRootId(
+                    0,
+                ),
+                src_path: "foo",
+                dst_path: "foo2",
+            },
+        ]
+        "###
+        );
+    }
+
+    #[test]
+    fn test_rename_mod_referenced_from_inside_its_dir() {
+        let (analysis, position) = analysis_and_position(
+            "
+            //- /lib.rs
+            mod fo<|>o;
+            //- /foo/mod.rs
+            mod bar;
+            pub struct Baz;
+            //- /foo/bar.rs
+            use crate::foo::Baz;
+            ",
+        );
+        let source_change = analysis.rename(position, "foo2").unwrap().unwrap().unwrap();
+        assert_debug_snapshot!(&source_change.info.source_file_edits,
+        @r###"
+        [
+            SourceFileEdit {
+                file_id: FileId(
+                    1,
+                ),
+                edit: TextEdit {
+                    atoms: [
+                        AtomTextEdit {
+                            delete: [4; 7),
+                            insert: "foo2",
+                        },
+                    ],
+                },
+            },
+            SourceFileEdit {
+                file_id: FileId(
+                    3,
+                ),
+                edit: TextEdit {
+                    atoms: [
+                        AtomTextEdit {
+                            delete: [11; 14),
+                            insert: "foo2",
+                        },
+                    ],
+                },
+            },
+        ]
+        "###
+        );
+    }
+
+    #[test]
+    fn test_rename_mod_with_path_attr() {
+        let (analysis, position) = analysis_and_position(
+            "
+            //- /lib.rs
+            #[path = \"baz.rs\"]
+            mod fo<|>o;
+            //- /baz.rs
+            // empty
+            ",
+        );
+        let source_change = analysis.rename(position, "foo2").unwrap().unwrap().unwrap();
+        assert!(source_change.info.file_system_edits.is_empty());
+    }
+
     fn test_rename(text: &str, new_name: &str, expected: &str) {
         let (analysis, position) = single_file_with_position(text);
-        let source_change = analysis.rename(position, new_name).unwrap();
+        let source_change = analysis.rename(position, new_name).unwrap().unwrap();
         let mut text_edit_builder = TextEditBuilder::default();
         let mut file_id: Option<FileId> = None;
         if let Some(change) = source_change {
@@ -325,4 +593,17 @@ mod tests {
             text_edit_builder.finish().apply(&*analysis.file_text(file_id.unwrap()).unwrap());
         assert_eq_text!(expected, &*result);
     }
+
+    fn test_rename_ranges(fixture: &str, expected: &str) {
+        let (mock, position) = MockAnalysis::with_files_and_position(fixture);
+        let source_change = mock.analysis().rename(position, "new_name").unwrap().unwrap();
+        let mut ranges = Vec::new();
+        for edit in source_change.unwrap().info.source_file_edits {
+            for atom in edit.edit.as_atoms() {
+                ranges.push((edit.file_id, atom.delete));
+            }
+        }
+        ranges.sort_by_key(|(file_id, range)| (file_id.0, range.start()));
+        assert_eq!(format!("{:?}", ranges), expected);
+    }
 }
@@ -7,7 +7,7 @@ use std::mem;
 use hir::{DefWithBody, HasSource, ModuleSource};
 use ra_db::{FileId, SourceDatabase, SourceDatabaseExt};
 use ra_prof::profile;
-use ra_syntax::{AstNode, TextRange};
+use ra_syntax::{ast::AttrsOwner, AstNode, TextRange};
 use rustc_hash::FxHashMap;
 
 use crate::db::RootDatabase;
@@ -71,6 +71,15 @@ impl NameDefinition {
         let module_src = self.container.definition_source(db);
         let file_id = module_src.file_id.original_file(db);
 
+        if let NameKind::Macro(mac) = self.kind {
+            // `macro_rules!` macros are visible in the modules after them, and
+            // in the parents of `#[macro_use]` modules.
+            // Procedural macros are always exported.
+            let is_exported =
+                mac.source(db).map_or(true, |it| it.value.has_atom_attr("macro_export"));
+            return self.crate_scope(db, is_exported);
+        }
+
         if let NameKind::Local(var) = self.kind {
             let range = match var.parent(db) {
                 DefWithBody::Function(f) => f.source(db).value.syntax().text_range(),
@@ -109,28 +118,13 @@ impl NameDefinition {
         }
 
         if vis.as_str() != "" {
-            let source_root_id = db.file_source_root(file_id);
-            let source_root = db.source_root(source_root_id);
-            let mut res = source_root.walk().map(|id| (id, None)).collect::<FxHashMap<_, _>>();
-
             // FIXME: add "pub(in path)"
 
             if vis.as_str() == "pub(crate)" {
-                return SearchScope::new(res);
+                return self.crate_scope(db, false);
             }
             if vis.as_str() == "pub" {
-                let krate = self.container.krate();
-                let crate_graph = db.crate_graph();
-                for crate_id in crate_graph.iter() {
-                    let mut crate_deps = crate_graph.dependencies(crate_id);
-                    if crate_deps.any(|dep| dep.crate_id() == krate.crate_id()) {
-                        let root_file = crate_graph.crate_root(crate_id);
-                        let source_root_id = db.file_source_root(root_file);
-                        let source_root = db.source_root(source_root_id);
-                        res.extend(source_root.walk().map(|id| (id, None)));
-                    }
-                }
-                return SearchScope::new(res);
+                return self.crate_scope(db, true);
             }
         }
 
@@ -140,6 +134,38 @@ impl NameDefinition {
             ModuleSource::SourceFile(_) => None,
         };
         res.insert(file_id, range);
+        // Private items are visible in the descendants of their module too,
+        // and those may live in files of their own.
+        let mut modules = self.container.children(db).collect::<Vec<_>>();
+        while let Some(module) = modules.pop() {
+            let file_id = module.definition_source(db).file_id.original_file(db);
+            res.entry(file_id).or_insert(None);
+            modules.extend(module.children(db));
+        }
+        SearchScope::new(res)
+    }
+
+    /// All the files of the crate of the container, and optionally of the
+    /// crates depending on it.
+    fn crate_scope(&self, db: &RootDatabase, with_dependents: bool) -> SearchScope {
+        let file_id = self.container.definition_source(db).file_id.original_file(db);
+        let source_root_id = db.file_source_root(file_id);
+        let source_root = db.source_root(source_root_id);
+        let mut res = source_root.walk().map(|id| (id, None)).collect::<FxHashMap<_, _>>();
+
+        if with_dependents {
+            let krate = self.container.krate();
+            let crate_graph = db.crate_graph();
+            for crate_id in crate_graph.iter() {
+                let mut crate_deps = crate_graph.dependencies(crate_id);
+                if crate_deps.any(|dep| dep.crate_id() == krate.crate_id()) {
+                    let root_file = crate_graph.crate_root(crate_id);
+                    let source_root_id = db.file_source_root(root_file);
+                    let source_root = db.source_root(source_root_id);
+                    res.extend(source_root.walk().map(|id| (id, None)));
+                }
+            }
+        }
         SearchScope::new(res)
     }
 }
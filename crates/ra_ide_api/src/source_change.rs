@@ -99,6 +99,7 @@ pub struct SourceFileEdit {
 pub enum FileSystemEdit {
     CreateFile { source_root: SourceRootId, path: RelativePathBuf },
     MoveFile { src: FileId, dst_source_root: SourceRootId, dst_path: RelativePathBuf },
+    MoveDir { source_root: SourceRootId, src_path: RelativePathBuf, dst_path: RelativePathBuf },
 }
 
 pub(crate) struct SingleFileChange {
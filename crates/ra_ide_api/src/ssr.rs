@@ -0,0 +1,618 @@
+//! Structural search and replace.
+//!
+//! A rule looks like `foo($a, $b) ==>> bar($b, $a)`. Both sides are Rust
+//! expressions or types, in which `$name` is a placeholder. Code matches the
+//! search pattern if it has the same syntax tree, ignoring whitespace and
+//! comments, with placeholders matching any expression or type. The paths of
+//! the pattern are resolved in the scope the query was issued in, and match
+//! the paths of the code resolving to the same item, so `m::f()` matches `f()`
+//! if `f` was imported from `m`, even as `use m::f as g`.
+
+use std::{error::Error, fmt};
+
+use hir::{PathResolution, Source, SourceAnalyzer};
+use ra_db::SourceDatabase;
+use ra_syntax::{
+    algo::{parse_wrapped, splice},
+    ast::{self, NameOwner},
+    AstNode, NodeOrToken, SmolStr, SyntaxElement,
+    SyntaxKind::{self, *},
+    SyntaxNode, TextRange,
+};
+use ra_text_edit::TextEditBuilder;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    db::RootDatabase, symbol_index::SymbolsDatabase, FileId, FilePosition, SourceFileEdit,
+};
+
+/// Placeholders are replaced with identifiers with this prefix, so that the
+/// rule can be parsed as Rust code.
+const PLACEHOLDER_PREFIX: &str = "__ssr_";
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SsrError(String);
+
+impl fmt::Display for SsrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Parse error: {}", self.0)
+    }
+}
+
+impl Error for SsrError {}
+
+pub(crate) fn parse_search_replace(
+    db: &RootDatabase,
+    position: FilePosition,
+    query: &str,
+    parse_only: bool,
+) -> Result<Vec<SourceFileEdit>, SsrError> {
+    let rule = parse_rule(query)?;
+    if parse_only {
+        return Ok(Vec::new());
+    }
+
+    let resolved_paths = resolve_pattern_paths(db, &rule, position);
+    let mut edits = Vec::new();
+    for &root in db.local_roots().iter() {
+        let sr = db.source_root(root);
+        for file_id in sr.walk() {
+            let matcher = Matcher { db, file_id, rule: &rule, resolved_paths: &resolved_paths };
+            let file = db.parse(file_id).tree();
+            let mut matches = Vec::new();
+            matcher.find_matches(file.syntax(), &mut matches);
+            if matches.is_empty() {
+                continue;
+            }
+            let mut edit = TextEditBuilder::default();
+            for m in matches.iter() {
+                edit.replace(m.node.text_range(), matcher.render(m));
+            }
+            edits.push(SourceFileEdit { file_id, edit: edit.finish() });
+        }
+    }
+    Ok(edits)
+}
+
+#[derive(Debug)]
+struct SsrRule {
+    pattern: SyntaxNode,
+    template: SyntaxNode,
+}
+
+fn parse_rule(query: &str) -> Result<SsrRule, SsrError> {
+    let mut parts = query.split("==>>");
+    let pattern = parts.next().unwrap_or_default();
+    let template = parts.next().ok_or_else(|| SsrError("Cannot find delimiter `==>>`".into()))?;
+    if parts.next().is_some() {
+        return Err(SsrError("More than one delimiter found".into()));
+    }
+
+    let pattern = parse_fragment(pattern)?;
+    let template = parse_fragment(template)?;
+    if placeholder_name(&pattern).is_some() {
+        return Err(SsrError("The search pattern can't be a lone placeholder".into()));
+    }
+    let defined: Vec<String> =
+        pattern.descendants().filter_map(|it| placeholder_name(&it)).collect();
+    for name in template.descendants().filter_map(|it| placeholder_name(&it)) {
+        if !defined.contains(&name) {
+            return Err(SsrError(format!("`${}` is not defined in the search pattern", name)));
+        }
+    }
+    Ok(SsrRule { pattern, template })
+}
+
+/// Parses `text` as an expression or, failing that, as a type.
+fn parse_fragment(text: &str) -> Result<SyntaxNode, SsrError> {
+    let text = text.trim();
+    let source = substitute_placeholders(text)?;
+
+    let expr = parse_wrapped::<ast::LetStmt>(&format!("fn f() {{ let _ = {}; }}", source))
+        .and_then(|it| it.initializer())
+        .map(|it| it.syntax().clone());
+    let ty = parse_wrapped::<ast::TypeAliasDef>(&format!("type T = {};", source))
+        .and_then(|it| it.type_ref())
+        .map(|it| it.syntax().clone());
+    expr.filter(|it| it.text() == source.as_str())
+        .or_else(|| ty.filter(|it| it.text() == source.as_str()))
+        .ok_or_else(|| SsrError(format!("`{}` is not an expression or a type", text)))
+}
+
+/// Replaces each `$name` with an identifier which can't clash with real code.
+fn substitute_placeholders(text: &str) -> Result<String, SsrError> {
+    let mut res = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            res.push(c);
+            continue;
+        }
+        let mut name = String::new();
+        while let Some(&c) = chars.peek() {
+            if !(c.is_alphanumeric() || c == '_') {
+                break;
+            }
+            name.push(c);
+            chars.next();
+        }
+        if name.is_empty() {
+            return Err(SsrError("Expected a placeholder name after `$`".into()));
+        }
+        res.push_str(PLACEHOLDER_PREFIX);
+        res.push_str(&name);
+    }
+    Ok(res)
+}
+
+/// Returns the name of the placeholder, if `node` is one.
+fn placeholder_name(node: &SyntaxNode) -> Option<String> {
+    match node.kind() {
+        PATH_EXPR | PATH_TYPE => (),
+        _ => return None,
+    }
+    let text = node.text().to_string();
+    if !text.starts_with(PLACEHOLDER_PREFIX) {
+        return None;
+    }
+    let name = &text[PLACEHOLDER_PREFIX.len()..];
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// A path of the search pattern, resolved to an item.
+struct ResolvedPath {
+    resolution: PathResolution,
+    /// The names the item can be referred to by: its own and the aliases
+    /// renaming imports give to it.
+    names: FxHashSet<SmolStr>,
+}
+
+/// Resolves the paths of the search pattern in the scope of `position`, where
+/// the query was issued. Paths with type arguments, or which don't resolve to
+/// an item, are left out: they are compared syntactically.
+fn resolve_pattern_paths(
+    db: &RootDatabase,
+    rule: &SsrRule,
+    position: FilePosition,
+) -> FxHashMap<TextRange, ResolvedPath> {
+    let file = db.parse(position.file_id).tree();
+    let node = match file.syntax().token_at_offset(position.offset).next() {
+        Some(token) => token.parent(),
+        None => file.syntax().clone(),
+    };
+    let analyzer =
+        SourceAnalyzer::new(db, Source::new(position.file_id.into(), &node), Some(position.offset));
+    let renames = renaming_imports(db);
+
+    let mut res = FxHashMap::default();
+    for path in rule.pattern.descendants().filter_map(ast::Path::cast) {
+        if path.syntax().descendants().any(|it| it.kind() == TYPE_ARG_LIST) {
+            continue;
+        }
+        let name = match path.segment().and_then(|it| it.name_ref()) {
+            Some(it) => it.text().clone(),
+            None => continue,
+        };
+        let range = path.syntax().text_range();
+        let resolution =
+            hir::Path::from_ast(path).and_then(|it| analyzer.resolve_hir_path(db, &it));
+        let resolution = match resolution {
+            Some(it @ PathResolution::Def(_))
+            | Some(it @ PathResolution::AssocItem(_))
+            | Some(it @ PathResolution::Macro(_)) => it,
+            _ => continue,
+        };
+        let mut names = FxHashSet::default();
+        names.insert(name);
+        loop {
+            let aliases = renames
+                .iter()
+                .filter(|(name, alias)| names.contains(name) && !names.contains(alias))
+                .map(|(_, alias)| alias.clone())
+                .collect::<Vec<_>>();
+            if aliases.is_empty() {
+                break;
+            }
+            names.extend(aliases);
+        }
+        res.insert(range, ResolvedPath { resolution, names });
+    }
+    res
+}
+
+/// Returns the imported names and their aliases of the renaming imports of the
+/// workspace, like `(f, g)` for `use m::f as g`.
+fn renaming_imports(db: &RootDatabase) -> Vec<(SmolStr, SmolStr)> {
+    let mut res = Vec::new();
+    for &root in db.local_roots().iter() {
+        for file_id in db.source_root(root).walk() {
+            let file = db.parse(file_id).tree();
+            for use_tree in file.syntax().descendants().filter_map(ast::UseTree::cast) {
+                let name = use_tree.path().and_then(|it| it.segment()).and_then(|it| it.name_ref());
+                let alias = use_tree.alias().and_then(|it| it.name());
+                if let (Some(name), Some(alias)) = (name, alias) {
+                    res.push((name.text().clone(), alias.text().clone()));
+                }
+            }
+        }
+    }
+    res
+}
+
+#[derive(Debug)]
+struct Match {
+    node: SyntaxNode,
+    placeholders: FxHashMap<String, SyntaxNode>,
+}
+
+struct Matcher<'a> {
+    db: &'a RootDatabase,
+    file_id: FileId,
+    rule: &'a SsrRule,
+    resolved_paths: &'a FxHashMap<TextRange, ResolvedPath>,
+}
+
+impl<'a> Matcher<'a> {
+    /// Collects the matches in `node`, outermost first. Matched nodes are not
+    /// searched any further: nested matches are handled when rendering.
+    fn find_matches(&self, node: &SyntaxNode, res: &mut Vec<Match>) {
+        let mut placeholders = FxHashMap::default();
+        if self.match_node(&self.rule.pattern, node, &mut placeholders) {
+            res.push(Match { node: node.clone(), placeholders });
+            return;
+        }
+        for child in node.children() {
+            self.find_matches(&child, res);
+        }
+    }
+
+    fn match_node(
+        &self,
+        pattern: &SyntaxNode,
+        code: &SyntaxNode,
+        placeholders: &mut FxHashMap<String, SyntaxNode>,
+    ) -> bool {
+        if let Some(name) = placeholder_name(pattern) {
+            let same_category = match pattern.kind() {
+                PATH_EXPR => ast::Expr::can_cast(code.kind()),
+                _ => ast::TypeRef::can_cast(code.kind()),
+            };
+            if !same_category {
+                return false;
+            }
+            // A placeholder which is used twice must match the same code.
+            return match placeholders.get(&name) {
+                Some(bound) => same_tokens(bound, code),
+                None => {
+                    placeholders.insert(name, code.clone());
+                    true
+                }
+            };
+        }
+        if pattern.kind() != code.kind() {
+            return false;
+        }
+        if pattern.kind() == PATH {
+            return self.match_path(pattern, code, placeholders);
+        }
+        self.match_children(pattern, code, placeholders)
+    }
+
+    fn match_children(
+        &self,
+        pattern: &SyntaxNode,
+        code: &SyntaxNode,
+        placeholders: &mut FxHashMap<String, SyntaxNode>,
+    ) -> bool {
+        let pattern_children = significant_children(pattern);
+        let code_children = significant_children(code);
+        if pattern_children.len() != code_children.len() {
+            return false;
+        }
+        pattern_children.iter().zip(code_children.iter()).all(|pair| match pair {
+            (NodeOrToken::Node(pattern), NodeOrToken::Node(code)) => {
+                self.match_node(pattern, code, placeholders)
+            }
+            (NodeOrToken::Token(pattern), NodeOrToken::Token(code)) => {
+                pattern.kind() == code.kind() && pattern.text() == code.text()
+            }
+            _ => false,
+        })
+    }
+
+    /// Paths match if the code resolves to the item the pattern resolved to.
+    /// Code which doesn't resolve matches only the same path.
+    fn match_path(
+        &self,
+        pattern: &SyntaxNode,
+        code: &SyntaxNode,
+        placeholders: &mut FxHashMap<String, SyntaxNode>,
+    ) -> bool {
+        let resolved = match self.resolved_paths.get(&pattern.text_range()) {
+            Some(it) => it,
+            None => return self.match_children(pattern, code, placeholders),
+        };
+        // Building a `SourceAnalyzer` is expensive, so only the paths which
+        // name the item in some way are resolved.
+        let code_path = ast::Path::cast(code.clone()).unwrap();
+        let name = code_path.segment().and_then(|it| it.name_ref());
+        if !name.map_or(false, |it| resolved.names.contains(it.text())) {
+            return false;
+        }
+        let analyzer = SourceAnalyzer::new(self.db, Source::new(self.file_id.into(), code), None);
+        match analyzer.resolve_path(self.db, &code_path) {
+            Some(it) => it == resolved.resolution,
+            None => same_tokens(pattern, code),
+        }
+    }
+
+    fn render(&self, m: &Match) -> String {
+        let template = &self.rule.template;
+        let replacements = template
+            .descendants()
+            .filter_map(|node| {
+                let bound = m.placeholders.get(&placeholder_name(&node)?)?;
+                let mut text = self.replace_in(bound);
+                if needs_parens(&node, bound) {
+                    text = format!("({})", text);
+                }
+                Some((node.text_range(), text))
+            })
+            .collect();
+        splice(template, replacements)
+    }
+
+    /// Returns the text of `node`, with the rule applied to everything inside.
+    fn replace_in(&self, node: &SyntaxNode) -> String {
+        let mut matches = Vec::new();
+        self.find_matches(node, &mut matches);
+        let replacements = matches.iter().map(|m| (m.node.text_range(), self.render(m))).collect();
+        splice(node, replacements)
+    }
+}
+
+/// Checks if `replacement` has to be parenthesized when substituted for the
+/// `placeholder` in the template, like in `$a.foo()` with `$a = 1 + 2`.
+fn needs_parens(placeholder: &SyntaxNode, replacement: &SyntaxNode) -> bool {
+    let parent_binds_tighter = match placeholder.parent().map(|it| it.kind()) {
+        Some(METHOD_CALL_EXPR)
+        | Some(FIELD_EXPR)
+        | Some(INDEX_EXPR)
+        | Some(TRY_EXPR)
+        | Some(CAST_EXPR)
+        | Some(PREFIX_EXPR)
+        | Some(REF_EXPR)
+        | Some(BIN_EXPR)
+        | Some(AWAIT_EXPR)
+        | Some(CALL_EXPR) => true,
+        _ => false,
+    };
+    let binds_loosely = match replacement.kind() {
+        BIN_EXPR | RANGE_EXPR | CAST_EXPR | PREFIX_EXPR | REF_EXPR | LAMBDA_EXPR | BOX_EXPR
+        | RETURN_EXPR | BREAK_EXPR => true,
+        _ => false,
+    };
+    parent_binds_tighter && binds_loosely
+}
+
+fn significant_children(node: &SyntaxNode) -> Vec<SyntaxElement> {
+    node.children_with_tokens().filter(|it| !it.kind().is_trivia()).collect()
+}
+
+fn same_tokens(a: &SyntaxNode, b: &SyntaxNode) -> bool {
+    fn tokens(node: &SyntaxNode) -> impl Iterator<Item = (SyntaxKind, String)> {
+        node.descendants_with_tokens()
+            .filter_map(|it| it.into_token())
+            .filter(|it| !it.kind().is_trivia())
+            .map(|it| (it.kind(), it.text().to_string()))
+    }
+    tokens(a).eq(tokens(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use test_utils::assert_eq_text;
+
+    use crate::{
+        mock_analysis::{single_file, single_file_with_position},
+        FilePosition, SsrError,
+    };
+
+    /// Applies the rule, issued at the start of the file or at the `<|>`
+    /// marker, to `before`.
+    fn check(query: &str, before: &str, after: &str) {
+        let (analysis, position) = if before.contains("<|>") {
+            single_file_with_position(before)
+        } else {
+            let (analysis, file_id) = single_file(before);
+            (analysis, FilePosition { file_id, offset: 0.into() })
+        };
+        let change = analysis.structural_search_replace(position, query, false).unwrap().unwrap();
+        let mut actual = analysis.file_text(position.file_id).unwrap().to_string();
+        for edit in change.source_file_edits {
+            assert_eq!(edit.file_id, position.file_id);
+            actual = edit.edit.apply(&actual);
+        }
+        assert_eq_text!(after, &actual);
+    }
+
+    fn check_error(query: &str, expected: &str) {
+        let (analysis, file_id) = single_file("");
+        let position = FilePosition { file_id, offset: 0.into() };
+        let err = analysis.structural_search_replace(position, query, true).unwrap().unwrap_err();
+        assert_eq!(err, SsrError(expected.to_string()));
+    }
+
+    #[test]
+    fn ssr_parse_errors() {
+        check_error("foo($a)", "Cannot find delimiter `==>>`");
+        check_error("foo($a) ==>> bar($a) ==>> baz($a)", "More than one delimiter found");
+        check_error("foo($a) ==>> bar($b)", "`$b` is not defined in the search pattern");
+        check_error("foo($) ==>> bar()", "Expected a placeholder name after `$`");
+        check_error("$a ==>> bar($a)", "The search pattern can't be a lone placeholder");
+        check_error("fn foo() {} ==>> bar()", "`fn foo() {}` is not an expression or a type");
+    }
+
+    #[test]
+    fn ssr_swaps_arguments() {
+        check(
+            "foo($a, $b) ==>> bar($b, $a)",
+            "fn main() { foo(1, x + y); foo(foo, bar(2, 3)); }",
+            "fn main() { bar(x + y, 1); bar(bar(2, 3), foo); }",
+        );
+    }
+
+    #[test]
+    fn ssr_ignores_whitespace_and_comments() {
+        check(
+            "foo($a) ==>> bar($a)",
+            "fn main() { foo( 1 /* one */ ); foo(\n    2\n); }",
+            "fn main() { bar(1); bar(2); }",
+        );
+    }
+
+    #[test]
+    fn ssr_repeated_placeholder() {
+        check(
+            "$a + $a ==>> 2 * $a",
+            "fn main() { let x = y + y; let z = y + 1; }",
+            "fn main() { let x = 2 * y; let z = y + 1; }",
+        );
+    }
+
+    #[test]
+    fn ssr_resolves_paths() {
+        check(
+            "m::f() ==>> g()",
+            r#"
+mod m { pub fn f() {} }
+mod n { pub fn f() {} }
+use m::f;
+fn main() {
+    f();
+    m::f();
+    crate::m::f();
+    n::f();
+}
+"#,
+            r#"
+mod m { pub fn f() {} }
+mod n { pub fn f() {} }
+use m::f;
+fn main() {
+    g();
+    g();
+    g();
+    n::f();
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn ssr_matches_renaming_imports() {
+        check(
+            "m::f() ==>> h()",
+            r#"
+mod m { pub fn f() {} }
+mod n { pub use crate::m::f as g; }
+use m::f as g;
+fn main() {
+    g();
+    n::g();
+    f();
+}
+"#,
+            r#"
+mod m { pub fn f() {} }
+mod n { pub use crate::m::f as g; }
+use m::f as g;
+fn main() {
+    h();
+    h();
+    f();
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn ssr_resolves_pattern_where_issued() {
+        check(
+            "f() ==>> g()",
+            r#"
+mod m {
+    pub fn f() {}
+    fn test() { <|>f(); }
+}
+mod n {
+    pub fn f() {}
+    fn test() { f(); }
+}
+fn main() { m::f(); }
+"#,
+            r#"
+mod m {
+    pub fn f() {}
+    fn test() { g(); }
+}
+mod n {
+    pub fn f() {}
+    fn test() { f(); }
+}
+fn main() { g(); }
+"#,
+        );
+    }
+
+    #[test]
+    fn ssr_types() {
+        check(
+            "Option<$t> ==>> Maybe<$t>",
+            "fn f(x: Option<i32>) -> Option<Vec<u8>> {}",
+            "fn f(x: Maybe<i32>) -> Maybe<Vec<u8>> {}",
+        );
+    }
+
+    #[test]
+    fn ssr_unwrap_or_default() {
+        check(
+            "$a.unwrap_or(Default::default()) ==>> $a.unwrap_or_default()",
+            r#"
+fn main() {
+    x.unwrap_or(Default::default());
+    x.unwrap_or(Vec::default());
+    x.unwrap_or(Default::new());
+}
+"#,
+            r#"
+fn main() {
+    x.unwrap_or_default();
+    x.unwrap_or(Vec::default());
+    x.unwrap_or(Default::new());
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn ssr_adds_parens() {
+        check(
+            "foo($a) ==>> $a.foo()",
+            "fn main() { foo(1 + 2); foo(x); }",
+            "fn main() { (1 + 2).foo(); x.foo(); }",
+        );
+    }
+
+    #[test]
+    fn ssr_replaces_nested_matches() {
+        check(
+            "foo($a) ==>> $a.foo()",
+            "fn main() { foo(foo(x)); }",
+            "fn main() { x.foo().foo(); }",
+        );
+    }
+}
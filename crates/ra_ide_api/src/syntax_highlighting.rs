@@ -1,5 +1,7 @@
 //! FIXME: write short doc here
 
+mod tags;
+
 use rustc_hash::{FxHashMap, FxHashSet};
 
 use hir::{Mutability, Name, Source};
@@ -9,17 +11,16 @@ use ra_syntax::{ast, AstNode, Direction, SyntaxElement, SyntaxKind, SyntaxKind::
 
 use crate::{
     db::RootDatabase,
-    references::{
-        classify_name, classify_name_ref,
-        NameKind::{self, *},
-    },
+    references::{classify_name, classify_name_ref, NameDefinition, NameKind::*},
     FileId,
 };
 
+pub use tags::{Highlight, HighlightModifier, HighlightModifiers, HighlightTag};
+
 #[derive(Debug)]
 pub struct HighlightedRange {
     pub range: TextRange,
-    pub tag: &'static str,
+    pub highlight: Highlight,
     pub binding_hash: Option<u64>,
 }
 
@@ -66,38 +67,41 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
             continue;
         }
         let mut binding_hash = None;
-        let tag = match node.kind() {
+        let highlight: Highlight = match node.kind() {
             FN_DEF => {
                 bindings_shadow_count.clear();
                 continue;
             }
-            COMMENT => "comment",
-            STRING | RAW_STRING | RAW_BYTE_STRING | BYTE_STRING => "string",
-            ATTR => "attribute",
+            COMMENT => HighlightTag::Comment.into(),
+            STRING | RAW_STRING | RAW_BYTE_STRING | BYTE_STRING => {
+                HighlightTag::StringLiteral.into()
+            }
+            ATTR => HighlightTag::Attribute.into(),
             NAME_REF => {
                 if node.ancestors().any(|it| it.kind() == ATTR) {
                     continue;
                 }
 
                 let name_ref = node.as_node().cloned().and_then(ast::NameRef::cast).unwrap();
-                let name_kind =
-                    classify_name_ref(db, Source::new(file_id.into(), &name_ref)).map(|d| d.kind);
+                let name_def = classify_name_ref(db, Source::new(file_id.into(), &name_ref));
 
-                if let Some(Local(local)) = &name_kind {
+                if let Some(Local(local)) = name_def.as_ref().map(|it| &it.kind) {
                     if let Some(name) = local.name(db) {
                         let shadow_count = bindings_shadow_count.entry(name.clone()).or_default();
                         binding_hash = Some(calc_binding_hash(file_id, &name, *shadow_count))
                     }
                 };
 
-                name_kind.map_or("text", |it| highlight_name(db, it))
+                match name_def {
+                    Some(name_def) => highlight_name(db, name_def),
+                    None => HighlightTag::UnresolvedReference.into(),
+                }
             }
             NAME => {
                 let name = node.as_node().cloned().and_then(ast::Name::cast).unwrap();
-                let name_kind =
-                    classify_name(db, Source::new(file_id.into(), &name)).map(|d| d.kind);
+                let name_def = classify_name(db, Source::new(file_id.into(), &name));
 
-                if let Some(Local(local)) = &name_kind {
+                if let Some(Local(local)) = name_def.as_ref().map(|it| &it.kind) {
                     if let Some(name) = local.name(db) {
                         let shadow_count = bindings_shadow_count.entry(name.clone()).or_default();
                         *shadow_count += 1;
@@ -105,20 +109,29 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
                     }
                 };
 
-                match name_kind {
-                    Some(name_kind) => highlight_name(db, name_kind),
-                    None => name.syntax().parent().map_or("function", |x| match x.kind() {
-                        TYPE_PARAM | STRUCT_DEF | ENUM_DEF | TRAIT_DEF | TYPE_ALIAS_DEF => "type",
-                        RECORD_FIELD_DEF => "field",
-                        _ => "function",
-                    }),
-                }
+                let highlight = match name_def {
+                    Some(name_def) => highlight_name(db, name_def),
+                    None => {
+                        let tag = match name.syntax().parent().map(|it| it.kind()) {
+                            Some(TYPE_PARAM) => HighlightTag::TypeParam,
+                            Some(STRUCT_DEF) => HighlightTag::Struct,
+                            Some(ENUM_DEF) => HighlightTag::Enum,
+                            Some(TRAIT_DEF) => HighlightTag::Trait,
+                            Some(TYPE_ALIAS_DEF) => HighlightTag::TypeAlias,
+                            Some(RECORD_FIELD_DEF) => HighlightTag::Field,
+                            _ => HighlightTag::Function,
+                        };
+                        tag.into()
+                    }
+                };
+                highlight | HighlightModifier::Declaration
             }
-            INT_NUMBER | FLOAT_NUMBER | CHAR | BYTE => "literal",
-            LIFETIME => "parameter",
-            T![unsafe] => "keyword.unsafe",
-            k if is_control_keyword(k) => "keyword.control",
-            k if k.is_keyword() => "keyword",
+            INT_NUMBER | FLOAT_NUMBER => HighlightTag::NumericLiteral.into(),
+            CHAR | BYTE => HighlightTag::CharLiteral.into(),
+            LIFETIME => HighlightTag::Lifetime.into(),
+            T![unsafe] => HighlightTag::Keyword | HighlightModifier::Unsafe,
+            k if is_control_keyword(k) => HighlightTag::Keyword | HighlightModifier::Control,
+            k if k.is_keyword() => HighlightTag::Keyword.into(),
             _ => {
                 if let Some(macro_call) = node.as_node().cloned().and_then(ast::MacroCall::cast) {
                     if let Some(path) = macro_call.path() {
@@ -135,7 +148,7 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
                                 }
                                 res.push(HighlightedRange {
                                     range: TextRange::from_to(range_start, range_end),
-                                    tag: "macro",
+                                    highlight: HighlightTag::Macro.into(),
                                     binding_hash: None,
                                 })
                             }
@@ -145,7 +158,7 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
                 continue;
             }
         };
-        res.push(HighlightedRange { range: node.text_range(), tag, binding_hash })
+        res.push(HighlightedRange { range: node.text_range(), highlight, binding_hash })
     }
     res
 }
@@ -192,7 +205,8 @@ pub(crate) fn highlight_as_html(db: &RootDatabase, file_id: FileId, rainbow: boo
         if ranges.is_empty() {
             buf.push_str(&text);
         } else {
-            let classes = ranges.iter().map(|x| x.tag).collect::<Vec<_>>().join(" ");
+            let classes =
+                ranges.iter().map(|x| x.highlight.decoration_tag()).collect::<Vec<_>>().join(" ");
             let binding_hash = ranges.first().and_then(|x| x.binding_hash);
             let color = match (rainbow, binding_hash) {
                 (true, Some(hash)) => format!(
@@ -209,35 +223,54 @@ pub(crate) fn highlight_as_html(db: &RootDatabase, file_id: FileId, rainbow: boo
     buf
 }
 
-fn highlight_name(db: &RootDatabase, name_kind: NameKind) -> &'static str {
-    match name_kind {
-        Macro(_) => "macro",
-        Field(_) => "field",
-        AssocItem(hir::AssocItem::Function(_)) => "function",
-        AssocItem(hir::AssocItem::Const(_)) => "constant",
-        AssocItem(hir::AssocItem::TypeAlias(_)) => "type",
-        Def(hir::ModuleDef::Module(_)) => "module",
-        Def(hir::ModuleDef::Function(_)) => "function",
-        Def(hir::ModuleDef::Adt(_)) => "type",
-        Def(hir::ModuleDef::EnumVariant(_)) => "constant",
-        Def(hir::ModuleDef::Const(_)) => "constant",
-        Def(hir::ModuleDef::Static(_)) => "constant",
-        Def(hir::ModuleDef::Trait(_)) => "type",
-        Def(hir::ModuleDef::TypeAlias(_)) => "type",
-        Def(hir::ModuleDef::BuiltinType(_)) => "type",
-        SelfType(_) => "type",
-        GenericParam(_) => "type",
+fn highlight_name(db: &RootDatabase, name_def: NameDefinition) -> Highlight {
+    let mut highlight: Highlight = match name_def.kind {
+        Macro(_) => HighlightTag::Macro.into(),
+        Field(_) => HighlightTag::Field.into(),
+        AssocItem(hir::AssocItem::Function(it)) => {
+            if it.has_self_param(db) {
+                HighlightTag::Function.into()
+            } else {
+                HighlightTag::Function | HighlightModifier::Static
+            }
+        }
+        AssocItem(hir::AssocItem::Const(_)) => HighlightTag::Constant.into(),
+        AssocItem(hir::AssocItem::TypeAlias(_)) => HighlightTag::TypeAlias.into(),
+        Def(hir::ModuleDef::Module(_)) => HighlightTag::Module.into(),
+        Def(hir::ModuleDef::Function(_)) => HighlightTag::Function.into(),
+        Def(hir::ModuleDef::Adt(hir::Adt::Struct(_))) => HighlightTag::Struct.into(),
+        Def(hir::ModuleDef::Adt(hir::Adt::Enum(_))) => HighlightTag::Enum.into(),
+        Def(hir::ModuleDef::Adt(hir::Adt::Union(_))) => HighlightTag::Union.into(),
+        Def(hir::ModuleDef::EnumVariant(_)) => HighlightTag::EnumVariant.into(),
+        Def(hir::ModuleDef::Const(_)) => HighlightTag::Constant.into(),
+        Def(hir::ModuleDef::Static(_)) => HighlightTag::Static | HighlightModifier::Static,
+        Def(hir::ModuleDef::Trait(_)) => HighlightTag::Trait.into(),
+        Def(hir::ModuleDef::TypeAlias(_)) => HighlightTag::TypeAlias.into(),
+        Def(hir::ModuleDef::BuiltinType(_)) => HighlightTag::BuiltinType.into(),
+        SelfType(_) => HighlightTag::SelfType.into(),
+        GenericParam(_) => HighlightTag::TypeParam.into(),
         Local(local) => {
-            if local.is_mut(db) {
-                "variable.mut"
+            let is_mut = local.is_mut(db)
+                || match local.ty(db).as_reference() {
+                    Some((_, Mutability::Mut)) => true,
+                    _ => false,
+                };
+            if is_mut {
+                HighlightTag::Local | HighlightModifier::Mutable
             } else {
-                match local.ty(db).as_reference() {
-                    Some((_, Mutability::Mut)) => "variable.mut",
-                    _ => "variable",
-                }
+                HighlightTag::Local.into()
             }
         }
+    };
+    if is_from_library(db, name_def.container) {
+        highlight |= HighlightModifier::Library;
     }
+    highlight
+}
+
+fn is_from_library(db: &RootDatabase, module: hir::Module) -> bool {
+    let file_id = module.definition_source(db).file_id.original_file(db);
+    db.source_root(db.file_source_root(file_id)).is_library
 }
 
 //FIXME: like, real html escaping
@@ -270,7 +303,7 @@ pre                 { color: #DCDCCC; background: #3F3F3F; font-size: 22px; padd
 
 #[cfg(test)]
 mod tests {
-    use crate::mock_analysis::single_file;
+    use crate::{mock_analysis::single_file, HighlightTag};
     use test_utils::{assert_eq_text, project_dir, read_text};
 
     #[test]
@@ -340,4 +373,42 @@ fn bar() {
         std::fs::write(dst_file, &actual_html).unwrap();
         assert_eq_text!(expected_html, actual_html);
     }
+
+    #[test]
+    fn test_highlight_modifiers() {
+        let (analysis, file_id) = single_file(
+            r#"
+struct Foo;
+impl Foo {
+    fn new() -> Foo { Foo }
+    fn get(&self) {}
+}
+fn main() {
+    let mut x = Foo::new();
+    x.get();
+}
+"#,
+        );
+        let text = analysis.file_text(file_id).unwrap();
+        let highlights = analysis
+            .highlight(file_id)
+            .unwrap()
+            .into_iter()
+            .filter(|it| it.highlight.tag != HighlightTag::Keyword)
+            .filter(|it| it.highlight.modifiers.iter().next().is_some())
+            .map(|it| format!("{}: {}", &text[it.range], it.highlight))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            highlights,
+            vec![
+                "Foo: struct.declaration",
+                "new: function.declaration.static",
+                "get: function.declaration",
+                "main: function.declaration",
+                "x: variable.declaration.mutable",
+                "new: function.static",
+                "x: variable.mutable",
+            ]
+        );
+    }
 }
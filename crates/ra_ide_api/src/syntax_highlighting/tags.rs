@@ -0,0 +1,224 @@
+//! The typed highlighting model: each highlighted range gets a tag saying
+//! what kind of thing it is, and a set of modifiers.
+
+use std::{fmt, ops};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Highlight {
+    pub tag: HighlightTag,
+    pub modifiers: HighlightModifiers,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HighlightModifiers(u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HighlightTag {
+    Attribute,
+    BuiltinType,
+    CharLiteral,
+    Comment,
+    Constant,
+    Enum,
+    EnumVariant,
+    Field,
+    Function,
+    Keyword,
+    Lifetime,
+    Local,
+    Macro,
+    Module,
+    NumericLiteral,
+    SelfType,
+    Static,
+    StringLiteral,
+    Struct,
+    Trait,
+    TypeAlias,
+    TypeParam,
+    Union,
+    UnresolvedReference,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum HighlightModifier {
+    /// Control flow keywords, like `if` and `return`.
+    Control = 0,
+    /// The name in the definition of an item or a binding.
+    Declaration,
+    /// Items defined outside of the workspace.
+    Library,
+    Mutable,
+    /// Statics and associated functions without `self`.
+    Static,
+    Unsafe,
+}
+
+impl HighlightTag {
+    fn as_str(self) -> &'static str {
+        match self {
+            HighlightTag::Attribute => "attribute",
+            HighlightTag::BuiltinType => "builtin_type",
+            HighlightTag::CharLiteral => "char_literal",
+            HighlightTag::Comment => "comment",
+            HighlightTag::Constant => "constant",
+            HighlightTag::Enum => "enum",
+            HighlightTag::EnumVariant => "enum_variant",
+            HighlightTag::Field => "field",
+            HighlightTag::Function => "function",
+            HighlightTag::Keyword => "keyword",
+            HighlightTag::Lifetime => "lifetime",
+            HighlightTag::Local => "variable",
+            HighlightTag::Macro => "macro",
+            HighlightTag::Module => "module",
+            HighlightTag::NumericLiteral => "numeric_literal",
+            HighlightTag::SelfType => "self_type",
+            HighlightTag::Static => "static",
+            HighlightTag::StringLiteral => "string_literal",
+            HighlightTag::Struct => "struct",
+            HighlightTag::Trait => "trait",
+            HighlightTag::TypeAlias => "type_alias",
+            HighlightTag::TypeParam => "type_param",
+            HighlightTag::Union => "union",
+            HighlightTag::UnresolvedReference => "unresolved_reference",
+        }
+    }
+}
+
+impl fmt::Display for HighlightTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl HighlightModifier {
+    pub const ALL: &'static [HighlightModifier] = &[
+        HighlightModifier::Control,
+        HighlightModifier::Declaration,
+        HighlightModifier::Library,
+        HighlightModifier::Mutable,
+        HighlightModifier::Static,
+        HighlightModifier::Unsafe,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            HighlightModifier::Control => "control",
+            HighlightModifier::Declaration => "declaration",
+            HighlightModifier::Library => "library",
+            HighlightModifier::Mutable => "mutable",
+            HighlightModifier::Static => "static",
+            HighlightModifier::Unsafe => "unsafe",
+        }
+    }
+
+    fn mask(self) -> u32 {
+        1 << (self as u32)
+    }
+}
+
+impl fmt::Display for HighlightModifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl HighlightModifiers {
+    pub fn contains(self, modifier: HighlightModifier) -> bool {
+        self.0 & modifier.mask() != 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = HighlightModifier> {
+        HighlightModifier::ALL.iter().copied().filter(move |it| self.contains(*it))
+    }
+}
+
+impl ops::BitOrAssign<HighlightModifier> for HighlightModifiers {
+    fn bitor_assign(&mut self, rhs: HighlightModifier) {
+        self.0 |= rhs.mask();
+    }
+}
+
+impl Highlight {
+    pub(crate) fn new(tag: HighlightTag) -> Highlight {
+        Highlight { tag, modifiers: HighlightModifiers::default() }
+    }
+
+    /// The tag used by the `rust-analyzer/publishDecorations` notification and
+    /// by the HTML output. Those predate modifiers and finer grained tags, so
+    /// several tags are merged, and most modifiers are dropped.
+    pub fn decoration_tag(self) -> &'static str {
+        match self.tag {
+            HighlightTag::Attribute => "attribute",
+            HighlightTag::CharLiteral | HighlightTag::NumericLiteral => "literal",
+            HighlightTag::Comment => "comment",
+            HighlightTag::Constant | HighlightTag::EnumVariant | HighlightTag::Static => "constant",
+            HighlightTag::BuiltinType
+            | HighlightTag::Enum
+            | HighlightTag::SelfType
+            | HighlightTag::Struct
+            | HighlightTag::Trait
+            | HighlightTag::TypeAlias
+            | HighlightTag::TypeParam
+            | HighlightTag::Union => "type",
+            HighlightTag::Field => "field",
+            HighlightTag::Function => "function",
+            HighlightTag::Keyword if self.modifiers.contains(HighlightModifier::Unsafe) => {
+                "keyword.unsafe"
+            }
+            HighlightTag::Keyword if self.modifiers.contains(HighlightModifier::Control) => {
+                "keyword.control"
+            }
+            HighlightTag::Keyword => "keyword",
+            HighlightTag::Lifetime => "parameter",
+            HighlightTag::Local if self.modifiers.contains(HighlightModifier::Mutable) => {
+                "variable.mut"
+            }
+            HighlightTag::Local => "variable",
+            HighlightTag::Macro => "macro",
+            HighlightTag::Module => "module",
+            HighlightTag::StringLiteral => "string",
+            HighlightTag::UnresolvedReference => "text",
+        }
+    }
+}
+
+impl From<HighlightTag> for Highlight {
+    fn from(tag: HighlightTag) -> Highlight {
+        Highlight::new(tag)
+    }
+}
+
+impl ops::BitOr<HighlightModifier> for HighlightTag {
+    type Output = Highlight;
+
+    fn bitor(self, rhs: HighlightModifier) -> Highlight {
+        Highlight::new(self) | rhs
+    }
+}
+
+impl ops::BitOr<HighlightModifier> for Highlight {
+    type Output = Highlight;
+
+    fn bitor(mut self, rhs: HighlightModifier) -> Highlight {
+        self.modifiers |= rhs;
+        self
+    }
+}
+
+impl ops::BitOrAssign<HighlightModifier> for Highlight {
+    fn bitor_assign(&mut self, rhs: HighlightModifier) {
+        self.modifiers |= rhs;
+    }
+}
+
+impl fmt::Display for Highlight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.tag)?;
+        for modifier in self.modifiers.iter() {
+            write!(f, ".{}", modifier)?;
+        }
+        Ok(())
+    }
+}
@@ -3,19 +3,52 @@
 use lsp_types::{
     CodeActionProviderCapability, CodeLensOptions, CompletionOptions,
     DocumentOnTypeFormattingOptions, FoldingRangeProviderCapability, GenericCapability,
-    ImplementationProviderCapability, RenameOptions, RenameProviderCapability, ServerCapabilities,
+    ImplementationProviderCapability, RenameOptions, RenameProviderCapability, SaveOptions,
     SignatureHelpOptions, TextDocumentSyncCapability, TextDocumentSyncKind,
     TextDocumentSyncOptions, TypeDefinitionProviderCapability,
 };
+use serde::Serialize;
+
+use crate::{
+    req::{SemanticTokensDocumentProvider, SemanticTokensOptions},
+    semantic_tokens,
+};
+
+/// `lsp_types::ServerCapabilities` doesn't know about semantic tokens and
+/// call hierarchy yet, so they are added next to it.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerCapabilities {
+    #[serde(flatten)]
+    pub lsp: lsp_types::ServerCapabilities,
+    pub semantic_tokens_provider: Option<SemanticTokensOptions>,
+    pub call_hierarchy_provider: Option<bool>,
+}
 
 pub fn server_capabilities() -> ServerCapabilities {
     ServerCapabilities {
+        lsp: lsp_capabilities(),
+        semantic_tokens_provider: Some(semantic_tokens()),
+        call_hierarchy_provider: Some(true),
+    }
+}
+
+fn semantic_tokens() -> SemanticTokensOptions {
+    SemanticTokensOptions {
+        legend: semantic_tokens::legend(),
+        range_provider: Some(true),
+        document_provider: Some(SemanticTokensDocumentProvider { edits: Some(true) }),
+    }
+}
+
+fn lsp_capabilities() -> lsp_types::ServerCapabilities {
+    lsp_types::ServerCapabilities {
         text_document_sync: Some(TextDocumentSyncCapability::Options(TextDocumentSyncOptions {
             open_close: Some(true),
-            change: Some(TextDocumentSyncKind::Full),
+            change: Some(TextDocumentSyncKind::Incremental),
             will_save: None,
             will_save_wait_until: None,
-            save: None,
+            save: Some(SaveOptions::default()),
         })),
         hover_provider: Some(true),
         completion_provider: Some(CompletionOptions {
@@ -35,7 +68,7 @@ pub fn server_capabilities() -> ServerCapabilities {
         code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
         code_lens_provider: Some(CodeLensOptions { resolve_provider: Some(true) }),
         document_formatting_provider: Some(true),
-        document_range_formatting_provider: None,
+        document_range_formatting_provider: Some(true),
         document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
             first_trigger_character: "=".to_string(),
             more_trigger_character: Some(vec![".".to_string(), ">".to_string()]),
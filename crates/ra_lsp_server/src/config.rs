@@ -7,6 +7,7 @@
 //! configure the server itself, feature flags are passed into analysis, and
 //! tweak things like automatic insertion of `()` in completions.
 
+use ra_project_model::CargoConfig;
 use rustc_hash::FxHashMap;
 
 use serde::{Deserialize, Deserializer};
@@ -35,8 +36,20 @@ pub struct ServerConfig {
     #[serde(deserialize_with = "nullable_bool_true")]
     pub with_sysroot: bool,
 
+    /// Path to the proc-macro server executable. Procedural macros are not
+    /// expanded if it is not set.
+    pub proc_macro_srv: Option<String>,
+
+    /// Controls loading of build script output, see `CargoConfig`.
+    pub cargo_config: CargoConfig,
+
     /// Fine grained feature flags to disable specific features.
     pub feature_flags: FxHashMap<String, bool>,
+
+    /// Command run when a file is saved, whose rustc diagnostics are published
+    /// next to ours, like `["cargo", "check", "--message-format=json"]`.
+    /// Nothing is run if it is empty.
+    pub check_command: Vec<String>,
 }
 
 impl Default for ServerConfig {
@@ -48,7 +61,10 @@ impl Default for ServerConfig {
             lru_capacity: None,
             max_inlay_hint_length: None,
             with_sysroot: true,
+            proc_macro_srv: None,
+            cargo_config: CargoConfig::default(),
             feature_flags: FxHashMap::default(),
+            check_command: Vec::new(),
         }
     }
 }
@@ -1,21 +1,23 @@
 //! FIXME: write short doc here
 
+use std::path::Path;
+
 use lsp_types::{
     self, CreateFile, DiagnosticSeverity, DocumentChangeOperation, DocumentChanges, Documentation,
-    Location, LocationLink, MarkupContent, MarkupKind, Position, Range, RenameFile, ResourceOp,
-    SymbolKind, TextDocumentEdit, TextDocumentIdentifier, TextDocumentItem,
+    FormattingOptions, Location, LocationLink, MarkupContent, MarkupKind, Position, Range,
+    RenameFile, ResourceOp, SymbolKind, TextDocumentEdit, TextDocumentIdentifier, TextDocumentItem,
     TextDocumentPositionParams, Url, VersionedTextDocumentIdentifier, WorkspaceEdit,
 };
 use ra_ide_api::{
     translate_offset_with_edit, CompletionItem, CompletionItemKind, FileId, FilePosition,
-    FileRange, FileSystemEdit, Fold, FoldKind, InsertTextFormat, LineCol, LineIndex,
+    FileRange, FileSystemEdit, Fold, FoldKind, FormatOptions, InsertTextFormat, LineCol, LineIndex,
     NavigationTarget, RangeInfo, Severity, SourceChange, SourceFileEdit,
 };
 use ra_syntax::{SyntaxKind, TextRange, TextUnit};
 use ra_text_edit::{AtomTextEdit, TextEdit};
 use ra_vfs::LineEndings;
 
-use crate::{req, world::WorldSnapshot, Result};
+use crate::{flycheck::RustcSpan, req, world::WorldSnapshot, Result};
 
 pub trait Conv {
     type Output;
@@ -90,6 +92,13 @@ impl Conv for Severity {
     }
 }
 
+impl Conv for FormattingOptions {
+    type Output = FormatOptions;
+    fn conv(self) -> FormatOptions {
+        FormatOptions { tab_size: self.tab_size as usize, insert_spaces: self.insert_spaces }
+    }
+}
+
 impl ConvWith<(&LineIndex, LineEndings)> for CompletionItem {
     type Output = ::lsp_types::CompletionItem;
 
@@ -381,13 +390,15 @@ impl TryConvWith<&WorldSnapshot> for SourceChange {
                 })
             }
         };
+        // Text edits refer to files by their current URIs, so they go before
+        // the operations which may move those files.
         let mut document_changes: Vec<DocumentChangeOperation> = Vec::new();
-        for resource_op in self.file_system_edits.try_conv_with(world)? {
-            document_changes.push(DocumentChangeOperation::Op(resource_op));
-        }
         for text_document_edit in self.source_file_edits.try_conv_with(world)? {
             document_changes.push(DocumentChangeOperation::Edit(text_document_edit));
         }
+        for resource_op in self.file_system_edits.try_conv_with(world)? {
+            document_changes.push(DocumentChangeOperation::Op(resource_op));
+        }
         let workspace_edit = WorkspaceEdit {
             changes: None,
             document_changes: Some(DocumentChanges::Operations(document_changes)),
@@ -424,11 +435,45 @@ impl TryConvWith<&WorldSnapshot> for FileSystemEdit {
                 let new_uri = world.path_to_uri(dst_source_root, &dst_path)?;
                 ResourceOp::Rename(RenameFile { old_uri, new_uri, options: None })
             }
+            FileSystemEdit::MoveDir { source_root, src_path, dst_path } => {
+                let old_uri = world.path_to_uri(source_root, &src_path)?;
+                let new_uri = world.path_to_uri(source_root, &dst_path)?;
+                ResourceOp::Rename(RenameFile { old_uri, new_uri, options: None })
+            }
         };
         Ok(res)
     }
 }
 
+impl TryConvWith<(&WorldSnapshot, &Path)> for &RustcSpan {
+    type Output = Location;
+    fn try_conv_with(self, (world, workspace_root): (&WorldSnapshot, &Path)) -> Result<Location> {
+        let path = workspace_root.join(&self.file_name);
+        let uri = Url::from_file_path(&path)
+            .map_err(|_| format!("can't convert path to url: {}", path.display()))?;
+        let file_id = world.vfs.read().path2file(&path).map(|it| FileId(it.0));
+        let range = match file_id {
+            Some(file_id) => {
+                let text = world.analysis().file_text(file_id)?;
+                let line_index = world.analysis().file_line_index(file_id)?;
+                self.range_in(&text, &line_index).map(|range| range.conv_with(&line_index))
+            }
+            None => None,
+        };
+        // Fall back to the lines and columns reported by rustc.
+        let range = range.unwrap_or_else(|| {
+            Range::new(
+                Position::new(
+                    self.line_start.saturating_sub(1),
+                    self.column_start.saturating_sub(1),
+                ),
+                Position::new(self.line_end.saturating_sub(1), self.column_end.saturating_sub(1)),
+            )
+        });
+        Ok(Location::new(uri, range))
+    }
+}
+
 impl TryConvWith<&WorldSnapshot> for &NavigationTarget {
     type Output = Location;
     fn try_conv_with(self, world: &WorldSnapshot) -> Result<Location> {
@@ -0,0 +1,480 @@
+//! Runs a check command, like `cargo check --message-format=json`, when a file
+//! is saved, and turns the diagnostics reported by rustc into LSP diagnostics,
+//! which are published next to the ones computed by rust-analyzer.
+//!
+//! Machine applicable suggestions of rustc are offered as code actions.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, NumberOrString, TextEdit, Url,
+};
+use parking_lot::Mutex;
+use ra_ide_api::{LineCol, LineIndex};
+use ra_syntax::{TextRange, TextUnit};
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+
+use crate::{conv::TryConvWith, world::WorldSnapshot};
+
+/// A diagnostic, in the format of rustc's `--error-format=json`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RustcDiagnostic {
+    pub message: String,
+    pub code: Option<RustcDiagnosticCode>,
+    pub level: String,
+    #[serde(default)]
+    pub spans: Vec<RustcSpan>,
+    #[serde(default)]
+    pub children: Vec<RustcDiagnostic>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RustcDiagnosticCode {
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RustcSpan {
+    /// Relative to the workspace root for the files of the workspace.
+    pub file_name: String,
+    pub byte_start: u32,
+    pub byte_end: u32,
+    /// 1-based.
+    pub line_start: u64,
+    pub line_end: u64,
+    /// 1-based, in characters.
+    pub column_start: u64,
+    pub column_end: u64,
+    pub is_primary: bool,
+    pub label: Option<String>,
+    pub suggested_replacement: Option<String>,
+    pub suggestion_applicability: Option<Applicability>,
+    /// Set if the span is in the expansion of a macro.
+    pub expansion: Option<Box<RustcSpanExpansion>>,
+}
+
+impl RustcSpan {
+    /// Returns the range of the span in the current `text` of its file. The
+    /// byte offsets are only used if they are still at the lines and columns
+    /// reported by rustc, that is if the span was not moved by an edit.
+    pub fn range_in(&self, text: &str, line_index: &LineIndex) -> Option<TextRange> {
+        let (start, end) = (self.byte_start as usize, self.byte_end as usize);
+        if start > end
+            || end > text.len()
+            || !text.is_char_boundary(start)
+            || !text.is_char_boundary(end)
+        {
+            return None;
+        }
+        let range = TextRange::from_to(TextUnit::from_usize(start), TextUnit::from_usize(end));
+        // rustc counts columns in characters, like our line index.
+        let line_col = |line: u64, column: u64| LineCol {
+            line: line.saturating_sub(1) as u32,
+            col_utf16: column.saturating_sub(1) as u32,
+        };
+        if line_index.line_col(range.start()) != line_col(self.line_start, self.column_start)
+            || line_index.line_col(range.end()) != line_col(self.line_end, self.column_end)
+        {
+            return None;
+        }
+        Some(range)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RustcSpanExpansion {
+    /// The span of the macro call.
+    pub span: RustcSpan,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Applicability {
+    MachineApplicable,
+    HasPlaceholders,
+    MaybeIncorrect,
+    Unspecified,
+}
+
+/// A line of the output of cargo with `--message-format=json`. Only the
+/// compiler messages are of interest.
+#[derive(Deserialize)]
+struct CargoMessage {
+    reason: String,
+    message: Option<RustcDiagnostic>,
+}
+
+fn parse_message(line: &str) -> Option<RustcDiagnostic> {
+    let message: CargoMessage = serde_json::from_str(line).ok()?;
+    if message.reason != "compiler-message" {
+        return None;
+    }
+    message.message
+}
+
+pub enum CheckTask {
+    /// The run `generation` of the check command in the workspace root
+    /// `workspace` finished.
+    Finished { generation: u64, workspace: usize, diagnostics: Vec<RustcDiagnostic> },
+}
+
+impl fmt::Debug for CheckTask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckTask::Finished { generation, workspace, diagnostics } => f
+                .debug_struct("Finished")
+                .field("generation", generation)
+                .field("workspace", workspace)
+                .field("diagnostics", &diagnostics.len())
+                .finish(),
+        }
+    }
+}
+
+/// Runs the check command in the background, in each workspace root. Starting
+/// a new run cancels the current one.
+#[derive(Debug)]
+pub struct Flycheck {
+    command: Vec<String>,
+    pub workspace_roots: Vec<PathBuf>,
+    sender: Sender<CheckTask>,
+    pub task_receiver: Receiver<CheckTask>,
+    generation: u64,
+    /// Shared with the threads reading the output, which reap the processes.
+    /// The lock is never held while waiting for a process, so that a run can
+    /// always be cancelled without blocking.
+    children: Vec<Arc<Mutex<Child>>>,
+}
+
+impl Flycheck {
+    /// Nothing is run if `command` is empty.
+    pub fn new(command: Vec<String>, workspace_roots: Vec<PathBuf>) -> Flycheck {
+        let (sender, task_receiver) = unbounded();
+        Flycheck {
+            command,
+            workspace_roots,
+            sender,
+            task_receiver,
+            generation: 0,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn restart(&mut self) -> io::Result<()> {
+        if self.command.is_empty() {
+            return Ok(());
+        }
+        self.cancel();
+        self.generation += 1;
+        for workspace in 0..self.workspace_roots.len() {
+            let child = self.spawn(workspace)?;
+            self.children.push(child);
+        }
+        Ok(())
+    }
+
+    fn spawn(&self, workspace: usize) -> io::Result<Arc<Mutex<Child>>> {
+        let mut child = Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .current_dir(&self.workspace_roots[workspace])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdout = child.stdout.take().unwrap();
+        let child = Arc::new(Mutex::new(child));
+        let sender = self.sender.clone();
+        let generation = self.generation;
+        let thread_child = Arc::clone(&child);
+        thread::Builder::new().name("flycheck".to_string()).spawn(move || {
+            let diagnostics =
+                BufReader::new(stdout).lines().filter_map(|line| parse_message(&line.ok()?));
+            let diagnostics = diagnostics.collect();
+            // The command may keep running after closing its output. It is
+            // polled rather than waited for, to not hold the lock `cancel`
+            // needs to kill it.
+            loop {
+                match thread_child.lock().try_wait() {
+                    Ok(None) => (),
+                    Ok(Some(_)) | Err(_) => break,
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+            // The receiver is gone if the server has shut down in the meantime.
+            let _ = sender.send(CheckTask::Finished { generation, workspace, diagnostics });
+        })?;
+        Ok(child)
+    }
+
+    /// Whether `task` comes from the latest run, and not from a cancelled one.
+    pub fn is_current(&self, task: &CheckTask) -> bool {
+        match task {
+            CheckTask::Finished { generation, .. } => *generation == self.generation,
+        }
+    }
+
+    /// Kills the running commands. They are reaped by their reader threads.
+    fn cancel(&mut self) {
+        for child in self.children.drain(..) {
+            // Fails if the process has exited already.
+            let _ = child.lock().kill();
+        }
+    }
+}
+
+impl Drop for Flycheck {
+    fn drop(&mut self) {
+        self.cancel()
+    }
+}
+
+/// A machine applicable suggestion of rustc.
+#[derive(Debug, Clone)]
+pub struct CheckFix {
+    pub title: String,
+    /// The diagnostic the suggestion is attached to.
+    pub diagnostic: Diagnostic,
+    pub edits: HashMap<Url, Vec<TextEdit>>,
+}
+
+/// The diagnostics of the latest run of the check command, in each workspace
+/// root.
+#[derive(Debug, Default)]
+pub struct CheckDiagnostics {
+    workspaces: FxHashMap<usize, WorkspaceCheckDiagnostics>,
+}
+
+#[derive(Debug, Default)]
+struct WorkspaceCheckDiagnostics {
+    diagnostics: FxHashMap<Url, Vec<Diagnostic>>,
+    fixes: FxHashMap<Url, Vec<CheckFix>>,
+}
+
+impl CheckDiagnostics {
+    pub fn diagnostics_for<'a>(&'a self, uri: &'a Url) -> impl Iterator<Item = &'a Diagnostic> {
+        self.workspaces.values().filter_map(move |it| it.diagnostics.get(uri)).flatten()
+    }
+
+    pub fn fixes_for<'a>(&'a self, uri: &'a Url) -> impl Iterator<Item = &'a CheckFix> {
+        self.workspaces.values().filter_map(move |it| it.fixes.get(uri)).flatten()
+    }
+
+    /// Replaces the diagnostics of `workspace` with the ones of a new run.
+    /// Returns the files whose diagnostics have to be published again.
+    pub fn update(
+        &mut self,
+        world: &WorldSnapshot,
+        workspace: usize,
+        workspace_root: &Path,
+        diagnostics: &[RustcDiagnostic],
+    ) -> Vec<Url> {
+        let old = self.workspaces.remove(&workspace).unwrap_or_default();
+        let mut changed = old.diagnostics.keys().cloned().collect::<Vec<_>>();
+        let mut new = WorkspaceCheckDiagnostics::default();
+        for rd in diagnostics {
+            let (uri, diagnostic, fixes) = match map_diagnostic(world, workspace_root, rd) {
+                Some(it) => it,
+                None => continue,
+            };
+            if !changed.contains(&uri) {
+                changed.push(uri.clone());
+            }
+            new.diagnostics.entry(uri.clone()).or_default().push(diagnostic);
+            new.fixes.entry(uri).or_default().extend(fixes);
+        }
+        self.workspaces.insert(workspace, new);
+        changed
+    }
+
+    /// Drops the diagnostics of a file which has been edited: their ranges
+    /// and fixes don't apply to the new text.
+    pub fn clear_file(&mut self, uri: &Url) {
+        for workspace in self.workspaces.values_mut() {
+            workspace.diagnostics.remove(uri);
+            workspace.fixes.remove(uri);
+        }
+    }
+}
+
+fn map_diagnostic(
+    world: &WorldSnapshot,
+    workspace_root: &Path,
+    rd: &RustcDiagnostic,
+) -> Option<(Url, Diagnostic, Vec<CheckFix>)> {
+    let primary_span = rd.spans.iter().find(|span| span.is_primary)?;
+    let location = macro_call_span(primary_span).try_conv_with((world, workspace_root)).ok()?;
+
+    let mut message = rd.message.clone();
+    let mut related_information = Vec::new();
+    if let Some(label) = primary_span.label.as_ref().filter(|it| !it.is_empty()) {
+        message.push_str(&format!("\n{}", label));
+    }
+    for span in rd.spans.iter().filter(|span| !span.is_primary) {
+        if let (Some(label), Ok(location)) =
+            (&span.label, macro_call_span(span).try_conv_with((world, workspace_root)))
+        {
+            related_information
+                .push(DiagnosticRelatedInformation { location, message: label.clone() });
+        }
+    }
+
+    let mut suggestions = Vec::new();
+    for child in rd.children.iter() {
+        let child_location = child
+            .spans
+            .iter()
+            .find(|span| span.is_primary)
+            .and_then(|span| span.try_conv_with((world, workspace_root)).ok());
+        match child_location {
+            Some(location) => related_information
+                .push(DiagnosticRelatedInformation { location, message: child.message.clone() }),
+            None => message.push_str(&format!("\n{}: {}", child.level, child.message)),
+        }
+        if let Some(edits) = machine_applicable_edits(world, workspace_root, child) {
+            suggestions.push((child.message.clone(), edits));
+        }
+    }
+
+    let diagnostic = Diagnostic {
+        range: location.range,
+        severity: Some(severity(&rd.level)),
+        code: rd.code.as_ref().map(|it| NumberOrString::String(it.code.clone())),
+        source: Some("rustc".to_string()),
+        message,
+        related_information: if related_information.is_empty() {
+            None
+        } else {
+            Some(related_information)
+        },
+    };
+    let fixes = suggestions
+        .into_iter()
+        .map(|(title, edits)| CheckFix { title, diagnostic: diagnostic.clone(), edits })
+        .collect();
+    Some((location.uri, diagnostic, fixes))
+}
+
+/// Returns the edits of the suggestions of `child`, if they can all be applied
+/// automatically.
+fn machine_applicable_edits(
+    world: &WorldSnapshot,
+    workspace_root: &Path,
+    child: &RustcDiagnostic,
+) -> Option<HashMap<Url, Vec<TextEdit>>> {
+    let mut edits: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    for span in child.spans.iter() {
+        if span.suggestion_applicability != Some(Applicability::MachineApplicable) {
+            return None;
+        }
+        let replacement = span.suggested_replacement.clone()?;
+        let location = span.try_conv_with((world, workspace_root)).ok()?;
+        edits.entry(location.uri).or_default().push(TextEdit::new(location.range, replacement));
+    }
+    if edits.is_empty() {
+        None
+    } else {
+        Some(edits)
+    }
+}
+
+/// Spans in macros which are not defined in the workspace are reported at the
+/// outermost macro call.
+fn macro_call_span(mut span: &RustcSpan) -> &RustcSpan {
+    while let Some(expansion) = &span.expansion {
+        if !span.file_name.starts_with('<') && !Path::new(&span.file_name).is_absolute() {
+            break;
+        }
+        span = &expansion.span;
+    }
+    span
+}
+
+fn severity(level: &str) -> DiagnosticSeverity {
+    match level {
+        "warning" => DiagnosticSeverity::Warning,
+        "note" => DiagnosticSeverity::Information,
+        "help" => DiagnosticSeverity::Hint,
+        _ => DiagnosticSeverity::Error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_compiler_messages() {
+        let output = r#"{"reason":"compiler-artifact","package_id":"foo"}
+{"reason":"compiler-message","message":{"message":"unused variable: `x`","code":{"code":"unused_variables","explanation":null},"level":"warning","spans":[{"file_name":"src/lib.rs","byte_start":4,"byte_end":5,"line_start":1,"line_end":1,"column_start":5,"column_end":6,"is_primary":true,"text":[],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[{"message":"consider prefixing with an underscore","code":null,"level":"help","spans":[{"file_name":"src/lib.rs","byte_start":4,"byte_end":5,"line_start":1,"line_end":1,"column_start":5,"column_end":6,"is_primary":true,"text":[],"label":null,"suggested_replacement":"_x","suggestion_applicability":"MachineApplicable","expansion":null}],"children":[],"rendered":null}],"rendered":"warning: unused variable"}}
+not json
+{"reason":"build-finished","success":true}"#;
+        let diagnostics = output.lines().filter_map(parse_message).collect::<Vec<_>>();
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.message, "unused variable: `x`");
+        assert_eq!(diagnostic.code.as_ref().unwrap().code, "unused_variables");
+        let suggestion = &diagnostic.children[0].spans[0];
+        assert_eq!(suggestion.suggested_replacement.as_ref().unwrap(), "_x");
+        assert_eq!(suggestion.suggestion_applicability, Some(Applicability::MachineApplicable));
+    }
+
+    fn span(file_name: &str, expansion: Option<RustcSpan>) -> RustcSpan {
+        RustcSpan {
+            file_name: file_name.to_string(),
+            byte_start: 0,
+            byte_end: 1,
+            line_start: 1,
+            line_end: 1,
+            column_start: 1,
+            column_end: 2,
+            is_primary: true,
+            label: None,
+            suggested_replacement: None,
+            suggestion_applicability: None,
+            expansion: expansion.map(|span| Box::new(RustcSpanExpansion { span })),
+        }
+    }
+
+    #[test]
+    fn maps_spans_in_foreign_macros_to_calls() {
+        let call = span("src/main.rs", None);
+        let in_std = span("<::std::macros::panic macros>", Some(call));
+        assert_eq!(macro_call_span(&in_std).file_name, "src/main.rs");
+
+        let in_local_macro = span("src/macros.rs", Some(span("src/main.rs", None)));
+        assert_eq!(macro_call_span(&in_local_macro).file_name, "src/macros.rs");
+    }
+
+    #[test]
+    fn uses_byte_offsets_only_at_reported_positions() {
+        let span = RustcSpan {
+            byte_start: 22,
+            byte_end: 24,
+            line_start: 2,
+            line_end: 2,
+            column_start: 13,
+            column_end: 15,
+            ..span("src/lib.rs", None)
+        };
+
+        let text = "fn f() {\n    let é = 92;\n}\n";
+        let range = span.range_in(text, &LineIndex::new(text)).unwrap();
+        assert_eq!(&text[range.start().to_usize()..range.end().to_usize()], "92");
+
+        let edited = "fn f() {\n    let éé = 92;\n}\n";
+        assert_eq!(span.range_in(edited, &LineIndex::new(edited)), None);
+
+        let truncated = "fn f() {\n";
+        assert_eq!(span.range_in(truncated, &LineIndex::new(truncated)), None);
+    }
+}
@@ -24,9 +24,11 @@ macro_rules! print {
 mod caps;
 mod cargo_target_spec;
 mod conv;
+mod flycheck;
 mod main_loop;
 mod markdown;
 pub mod req;
+mod semantic_tokens;
 mod config;
 mod world;
 
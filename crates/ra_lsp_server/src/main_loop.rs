@@ -4,13 +4,21 @@ mod handlers;
 mod subscriptions;
 pub(crate) mod pending_requests;
 
-use std::{error::Error, fmt, panic, path::PathBuf, sync::Arc, time::Instant};
+use std::{
+    error::Error,
+    fmt, panic,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
 
 use crossbeam_channel::{select, unbounded, RecvError, Sender};
 use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
-use lsp_types::{ClientCapabilities, NumberOrString};
-use ra_ide_api::{Canceled, FeatureFlags, FileId, LibraryData, SourceRootId};
+use lsp_types::{ClientCapabilities, NumberOrString, TextDocumentContentChangeEvent};
+use ra_ide_api::{Canceled, FeatureFlags, FileId, LibraryData, LineIndex, SourceRootId};
 use ra_prof::profile;
+use ra_project_model::ProcMacroClient;
+use ra_text_edit::TextEdit;
 use ra_vfs::{VfsTask, Watch};
 use relative_path::RelativePathBuf;
 use rustc_hash::FxHashSet;
@@ -18,6 +26,8 @@ use serde::{de::DeserializeOwned, Serialize};
 use threadpool::ThreadPool;
 
 use crate::{
+    conv::ConvWith,
+    flycheck::CheckTask,
     main_loop::{
         pending_requests::{PendingRequest, PendingRequests},
         subscriptions::Subscriptions,
@@ -67,6 +77,7 @@ pub fn main_loop(
                 let workspace = ra_project_model::ProjectWorkspace::discover_with_sysroot(
                     ws_root.as_path(),
                     config.with_sysroot,
+                    &config.cargo_config,
                 );
                 match workspace {
                     Ok(workspace) => loaded_workspaces.push(workspace),
@@ -143,6 +154,22 @@ pub fn main_loop(
         };
         log::info!("feature_flags: {:#?}", feature_flags);
 
+        let proc_macro_client = match &config.proc_macro_srv {
+            None => ProcMacroClient::dummy(),
+            Some(path) => match ProcMacroClient::extern_process(Path::new(path)) {
+                Ok(it) => it,
+                Err(err) => {
+                    log::error!("failed to start proc macro server {:?}: {}", path, err);
+                    show_message(
+                        req::MessageType::Error,
+                        format!("rust-analyzer failed to start proc macro server: {}", err),
+                        &connection.sender,
+                    );
+                    ProcMacroClient::dummy()
+                }
+            },
+        };
+
         WorldState::new(
             ws_roots,
             workspaces,
@@ -151,6 +178,8 @@ pub fn main_loop(
             Watch(!config.use_client_watching),
             options,
             feature_flags,
+            &proc_macro_client,
+            config.check_command,
         )
     };
 
@@ -174,7 +203,8 @@ pub fn main_loop(
                     Ok(task) => Event::Vfs(task),
                     Err(RecvError) => Err("vfs died")?,
                 },
-                recv(libdata_receiver) -> data => Event::Lib(data.unwrap())
+                recv(libdata_receiver) -> data => Event::Lib(data.unwrap()),
+                recv(world_state.flycheck.task_receiver) -> task => Event::Check(task.unwrap()),
             };
             if let Event::Msg(Message::Request(req)) = &event {
                 if connection.handle_shutdown(&req)? {
@@ -220,6 +250,7 @@ enum Event {
     Task(Task),
     Vfs(VfsTask),
     Lib(LibraryData),
+    Check(CheckTask),
 }
 
 impl fmt::Debug for Event {
@@ -257,6 +288,7 @@ impl fmt::Debug for Event {
             Event::Task(it) => fmt::Debug::fmt(it, f),
             Event::Vfs(it) => fmt::Debug::fmt(it, f),
             Event::Lib(it) => fmt::Debug::fmt(it, f),
+            Event::Check(it) => fmt::Debug::fmt(it, f),
         }
     }
 }
@@ -318,6 +350,10 @@ fn loop_turn(
             world_state.maybe_collect_garbage();
             loop_state.in_flight_libraries -= 1;
         }
+        Event::Check(task) => {
+            on_check_task(task, world_state, &loop_state.subscriptions, &connection.sender);
+            state_changed = true;
+        }
         Event::Msg(msg) => match msg {
             Message::Request(req) => on_request(
                 world_state,
@@ -407,6 +443,41 @@ fn on_task(
     }
 }
 
+fn on_check_task(
+    task: CheckTask,
+    world_state: &mut WorldState,
+    subs: &Subscriptions,
+    msg_sender: &Sender<Message>,
+) {
+    if !world_state.flycheck.is_current(&task) {
+        return;
+    }
+    let CheckTask::Finished { workspace, diagnostics, .. } = task;
+    let world = world_state.snapshot();
+    let changed = world_state.check_diagnostics.write().update(
+        &world,
+        workspace,
+        &world_state.flycheck.workspace_roots[workspace],
+        &diagnostics,
+    );
+
+    // The diagnostics of the subscribed files are published with ours, after
+    // the state change.
+    let subscriptions = subs.subscriptions();
+    for uri in changed {
+        let is_subscribed =
+            world.uri_to_file_id(&uri).map_or(false, |file_id| subscriptions.contains(&file_id));
+        if is_subscribed {
+            continue;
+        }
+        let diagnostics =
+            world_state.check_diagnostics.read().diagnostics_for(&uri).cloned().collect();
+        let params = req::PublishDiagnosticsParams { uri, diagnostics };
+        let not = notification_new::<req::PublishDiagnostics>(params);
+        msg_sender.send(not.into()).unwrap();
+    }
+}
+
 fn on_request(
     world: &mut WorldState,
     pending_requests: &mut PendingRequests,
@@ -458,12 +529,39 @@ fn on_request(
         .on::<req::Rename>(handlers::handle_rename)?
         .on::<req::References>(handlers::handle_references)?
         .on::<req::Formatting>(handlers::handle_formatting)?
+        .on::<req::RangeFormatting>(handlers::handle_range_formatting)?
+        .on::<req::Ssr>(handlers::handle_ssr)?
+        .on::<req::ChangeSignature>(handlers::handle_change_signature)?
         .on::<req::DocumentHighlightRequest>(handlers::handle_document_highlight)?
         .on::<req::InlayHints>(handlers::handle_inlay_hints)?
+        .on::<req::CallHierarchyPrepare>(handlers::handle_call_hierarchy_prepare)?
+        .on::<req::CallHierarchyIncomingCalls>(handlers::handle_call_hierarchy_incoming)?
+        .on::<req::CallHierarchyOutgoingCalls>(handlers::handle_call_hierarchy_outgoing)?
+        .on::<req::SemanticTokensRequest>(handlers::handle_semantic_tokens)?
+        .on::<req::SemanticTokensEditsRequest>(handlers::handle_semantic_tokens_edits)?
+        .on::<req::SemanticTokensRangeRequest>(handlers::handle_semantic_tokens_range)?
         .finish();
     Ok(())
 }
 
+/// Applies the changes from a `textDocument/didChange` notification, in
+/// order, to the text of the document.
+fn apply_document_changes(text: &mut String, content_changes: Vec<TextDocumentContentChangeEvent>) {
+    // The index is only rebuilt if there's another range to convert.
+    let mut line_index = None;
+    for change in content_changes {
+        match change.range {
+            Some(range) => {
+                let line_index = line_index.get_or_insert_with(|| LineIndex::new(text.as_str()));
+                let range = range.conv_with(&*line_index);
+                *text = TextEdit::replace(range, change.text).apply(text.as_str());
+            }
+            None => *text = change.text,
+        }
+        line_index = None;
+    }
+}
+
 fn on_notification(
     msg_sender: &Sender<Message>,
     state: &mut WorldState,
@@ -503,12 +601,23 @@ fn on_notification(
         Err(not) => not,
     };
     let not = match notification_cast::<req::DidChangeTextDocument>(not) {
-        Ok(mut params) => {
+        Ok(params) => {
             let uri = params.text_document.uri;
             let path = uri.to_file_path().map_err(|()| format!("invalid uri: {}", uri))?;
-            let text =
-                params.content_changes.pop().ok_or_else(|| "empty changes".to_string())?.text;
-            state.vfs.write().change_file_overlay(path.as_path(), text);
+            if state.vfs.read().path2file(&path).is_none() {
+                // The changes are relative to a text we don't have, so the
+                // document stays out of sync until it's opened again.
+                log::error!("change in an unknown file, ignoring it: {}", path.display());
+                return Ok(());
+            }
+            // The overlay has the text the editor sent last, which the changes
+            // are relative to.
+            state.vfs.write().change_file_overlay(path.as_path(), |text| {
+                apply_document_changes(text, params.content_changes)
+            });
+            // The diagnostics of the check command don't match the new text.
+            // The file is open, so its diagnostics are published again anyway.
+            state.check_diagnostics.write().clear_file(&uri);
             return Ok(());
         }
         Err(not) => not,
@@ -520,6 +629,7 @@ fn on_notification(
             if let Some(file_id) = state.vfs.write().remove_file_overlay(path.as_path()) {
                 subs.remove_sub(FileId(file_id.0));
             }
+            state.semantic_tokens_cache.write().remove(&uri);
             let params = req::PublishDiagnosticsParams { uri, diagnostics: Vec::new() };
             let not = notification_new::<req::PublishDiagnostics>(params);
             msg_sender.send(not.into()).unwrap();
@@ -527,6 +637,20 @@ fn on_notification(
         }
         Err(not) => not,
     };
+    let not = match notification_cast::<req::DidSaveTextDocument>(not) {
+        Ok(_params) => {
+            if let Err(err) = state.flycheck.restart() {
+                log::error!("failed to run the check command: {}", err);
+                show_message(
+                    req::MessageType::Error,
+                    format!("rust-analyzer failed to run the check command: {}", err),
+                    msg_sender,
+                );
+            }
+            return Ok(());
+        }
+        Err(not) => not,
+    };
     let not = match notification_cast::<req::DidChangeConfiguration>(not) {
         Ok(_params) => {
             return Ok(());
@@ -1,6 +1,6 @@
 //! FIXME: write short doc here
 
-use std::{fmt::Write as _, io::Write as _};
+use std::{fmt::Write as _, io::Write as _, path::Path};
 
 use lsp_server::ErrorCode;
 use lsp_types::{
@@ -10,7 +10,8 @@ use lsp_types::{
     Range, RenameParams, SymbolInformation, TextDocumentIdentifier, TextEdit, WorkspaceEdit,
 };
 use ra_ide_api::{
-    AssistId, FileId, FilePosition, FileRange, Query, Runnable, RunnableKind, SearchScope,
+    AssistId, FileId, FilePosition, FileRange, FormatOptions, NavigationTarget, Query, Runnable,
+    RunnableKind, SearchScope,
 };
 use ra_prof::profile;
 use ra_syntax::{AstNode, SyntaxKind, TextRange, TextUnit};
@@ -22,6 +23,7 @@ use crate::{
     cargo_target_spec::{runnable_args, CargoTargetSpec},
     conv::{to_location, Conv, ConvWith, FoldConvCtx, MapConvWith, TryConvWith, TryConvWithToVec},
     req::{self, Decoration, InlayHint, InlayHintsParams, InlayKind},
+    semantic_tokens::{self, SemanticTokensBuilder},
     world::WorldSnapshot,
     LspError, Result,
 };
@@ -140,6 +142,37 @@ pub fn handle_join_lines(
     world.analysis().join_lines(frange)?.try_conv_with(&world)
 }
 
+pub fn handle_ssr(world: WorldSnapshot, params: req::SsrParams) -> Result<req::SourceChange> {
+    let _p = profile("handle_ssr");
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let line_index = world.analysis().file_line_index(file_id)?;
+    let offset = params.position.conv_with(&line_index);
+    world
+        .analysis()
+        .structural_search_replace(
+            FilePosition { file_id, offset },
+            &params.query,
+            params.parse_only,
+        )?
+        .map_err(|err| LspError::new(ErrorCode::InvalidRequest as i32, err.to_string()))?
+        .try_conv_with(&world)
+}
+
+pub fn handle_change_signature(
+    world: WorldSnapshot,
+    params: req::ChangeSignatureParams,
+) -> Result<req::SourceChange> {
+    let _p = profile("handle_change_signature");
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let line_index = world.analysis().file_line_index(file_id)?;
+    let offset = params.position.conv_with(&line_index);
+    world
+        .analysis()
+        .change_signature(FilePosition { file_id, offset }, &params.new_params)?
+        .map_err(|err| LspError::new(ErrorCode::InvalidRequest as i32, err.to_string()))?
+        .try_conv_with(&world)
+}
+
 pub fn handle_on_enter(
     world: WorldSnapshot,
     params: req::TextDocumentPositionParams,
@@ -463,10 +496,26 @@ pub fn handle_hover(
     };
     let line_index = world.analysis.file_line_index(position.file_id)?;
     let range = info.range.conv_with(&line_index);
+    // Intra-doc links point to the file of the definition. LSP has no way to
+    // point to a position in a file, so the `#L<line>,<column>` fragment which
+    // VS Code understands is only added if the client opts in.
+    let with_position = world.feature_flags().get("lsp.hover.link-positions");
+    let resolve_link = |path: &str| {
+        let link = info.info.doc_links().iter().find(|it| it.path == path)?;
+        let file_id = link.target.file_id();
+        let uri = world.file_id_to_uri(file_id).ok()?;
+        if !with_position {
+            return Some(uri.to_string());
+        }
+        let line_index = world.analysis().file_line_index(file_id).ok()?;
+        let offset = link.target.focus_range().unwrap_or(link.target.full_range()).start();
+        let position = offset.conv_with(&line_index);
+        Some(format!("{}#L{},{}", uri, position.line + 1, position.character + 1))
+    };
     let res = Hover {
         contents: HoverContents::Markup(MarkupContent {
             kind: MarkupKind::Markdown,
-            value: crate::markdown::format_docs(&info.info.to_markup()),
+            value: crate::markdown::format_docs_with_links(&info.info.to_markup(), resolve_link),
         }),
         range: Some(range),
     };
@@ -482,8 +531,8 @@ pub fn handle_prepare_rename(
 
     // We support renaming references like handle_rename does.
     // In the future we may want to reject the renaming of things like keywords here too.
-    let optional_change = world.analysis().rename(position, "dummy")?;
-    let range = match optional_change {
+    // References coming from macro definitions are only reported by the rename itself.
+    let range = match world.analysis().prepare_rename(position)? {
         None => return Ok(None),
         Some(it) => it.range,
     };
@@ -506,7 +555,10 @@ pub fn handle_rename(world: WorldSnapshot, params: RenameParams) -> Result<Optio
         .into());
     }
 
-    let optional_change = world.analysis().rename(position, &*params.new_name)?;
+    let optional_change = world
+        .analysis()
+        .rename(position, &*params.new_name)?
+        .map_err(|err| LspError::new(ErrorCode::InvalidRequest as i32, err.to_string()))?;
     let change = match optional_change {
         None => return Ok(None),
         Some(it) => it.info,
@@ -550,6 +602,91 @@ pub fn handle_references(
     Ok(Some(locations))
 }
 
+pub fn handle_call_hierarchy_prepare(
+    world: WorldSnapshot,
+    params: req::TextDocumentPositionParams,
+) -> Result<Option<Vec<req::CallHierarchyItem>>> {
+    let _p = profile("handle_call_hierarchy_prepare");
+    let position = params.try_conv_with(&world)?;
+    let navs = match world.analysis().call_hierarchy(position)? {
+        None => return Ok(None),
+        Some(it) => it.info,
+    };
+    let items =
+        navs.iter().map(|nav| to_call_hierarchy_item(&world, nav)).collect::<Result<_>>()?;
+    Ok(Some(items))
+}
+
+pub fn handle_call_hierarchy_incoming(
+    world: WorldSnapshot,
+    params: req::CallHierarchyCallsParams,
+) -> Result<Option<Vec<req::CallHierarchyIncomingCall>>> {
+    let _p = profile("handle_call_hierarchy_incoming");
+    let position = call_hierarchy_item_position(&world, params.item)?;
+    let calls = match world.analysis().incoming_calls(position)? {
+        None => return Ok(None),
+        Some(it) => it,
+    };
+    let mut res = Vec::new();
+    for call in calls {
+        let line_index = world.analysis().file_line_index(call.target.file_id())?;
+        res.push(req::CallHierarchyIncomingCall {
+            from: to_call_hierarchy_item(&world, &call.target)?,
+            from_ranges: call.ranges.iter().map(|it| it.conv_with(&line_index)).collect(),
+        });
+    }
+    Ok(Some(res))
+}
+
+pub fn handle_call_hierarchy_outgoing(
+    world: WorldSnapshot,
+    params: req::CallHierarchyCallsParams,
+) -> Result<Option<Vec<req::CallHierarchyOutgoingCall>>> {
+    let _p = profile("handle_call_hierarchy_outgoing");
+    let position = call_hierarchy_item_position(&world, params.item)?;
+    let calls = match world.analysis().outgoing_calls(position)? {
+        None => return Ok(None),
+        Some(it) => it,
+    };
+    let line_index = world.analysis().file_line_index(position.file_id)?;
+    let mut res = Vec::new();
+    for call in calls {
+        res.push(req::CallHierarchyOutgoingCall {
+            to: to_call_hierarchy_item(&world, &call.target)?,
+            from_ranges: call.ranges.iter().map(|it| it.conv_with(&line_index)).collect(),
+        });
+    }
+    Ok(Some(res))
+}
+
+fn to_call_hierarchy_item(
+    world: &WorldSnapshot,
+    nav: &NavigationTarget,
+) -> Result<req::CallHierarchyItem> {
+    let line_index = world.analysis().file_line_index(nav.file_id())?;
+    Ok(req::CallHierarchyItem {
+        name: nav.name().to_string(),
+        kind: nav.kind().conv(),
+        detail: nav.description().map(|it| it.to_string()),
+        uri: nav.file_id().try_conv_with(world)?,
+        range: nav.full_range().conv_with(&line_index),
+        selection_range: nav.range().conv_with(&line_index),
+    })
+}
+
+/// Items are sent back by the client unchanged, so the selection range is the
+/// name of the function.
+fn call_hierarchy_item_position(
+    world: &WorldSnapshot,
+    item: req::CallHierarchyItem,
+) -> Result<FilePosition> {
+    let params = req::TextDocumentPositionParams::new(
+        TextDocumentIdentifier::new(item.uri),
+        item.selection_range.start,
+    );
+    params.try_conv_with(world)
+}
+
 pub fn handle_formatting(
     world: WorldSnapshot,
     params: DocumentFormattingParams,
@@ -560,17 +697,39 @@ pub fn handle_formatting(
 
     let file_line_index = world.analysis().file_line_index(file_id)?;
     let end_position = TextUnit::of_str(&file).conv_with(&file_line_index);
+    let file_range =
+        FileRange { file_id, range: TextRange::offset_len(0.into(), TextUnit::of_str(&file)) };
+
+    let options = params.options.conv();
 
     use std::process;
     let mut rustfmt = process::Command::new("rustfmt");
     rustfmt.stdin(process::Stdio::piped()).stdout(process::Stdio::piped());
 
-    if let Ok(path) = params.text_document.uri.to_file_path() {
-        if let Some(parent) = path.parent() {
-            rustfmt.current_dir(parent);
-        }
+    let dir = params
+        .text_document
+        .uri
+        .to_file_path()
+        .ok()
+        .and_then(|path| path.parent().map(|it| it.to_path_buf()));
+    if let Some(dir) = &dir {
+        rustfmt.current_dir(dir);
     }
-    let mut rustfmt = rustfmt.spawn()?;
+    // `--config` takes precedence over the configuration of the project, so
+    // the editor settings are only used for projects without one.
+    if !dir.as_ref().map_or(false, |it| has_rustfmt_config(it)) {
+        rustfmt
+            .arg("--config")
+            .arg(format!("hard_tabs={},tab_spaces={}", !options.insert_spaces, options.tab_size));
+    }
+    let mut rustfmt = match rustfmt.spawn() {
+        Ok(it) => it,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            log::info!("rustfmt not found, using the built-in formatter");
+            return builtin_formatting(&world, file_range, options);
+        }
+        Err(e) => return Err(e.into()),
+    };
 
     rustfmt.stdin.as_mut().unwrap().write_all(file.as_bytes())?;
 
@@ -584,12 +743,13 @@ pub fn handle_formatting(
                 // likely cause exiting with 1. Most Language Servers swallow parse errors on
                 // formatting because otherwise an error is surfaced to the user on top of the
                 // syntax error diagnostics they're already receiving. This is especially jarring
-                // if they have format on save enabled.
-                log::info!("rustfmt exited with status 1, assuming parse error and ignoring");
-                return Ok(None);
+                // if they have format on save enabled. The built-in formatter copes with syntax
+                // errors, so we use it instead.
+                log::info!("rustfmt exited with status 1, using the built-in formatter");
+                return builtin_formatting(&world, file_range, options);
             }
             _ => {
-                // Something else happened - e.g. `rustfmt` is missing or caught a signal
+                // Something else happened - e.g. `rustfmt` caught a signal
                 return Err(LspError::new(
                     -32900,
                     format!(
@@ -610,6 +770,41 @@ pub fn handle_formatting(
     }]))
 }
 
+/// Checks if formatting of the files in `dir` is configured with a
+/// `rustfmt.toml` or a `.rustfmt.toml` in it or in one of its parents.
+fn has_rustfmt_config(dir: &Path) -> bool {
+    dir.ancestors()
+        .any(|it| it.join("rustfmt.toml").is_file() || it.join(".rustfmt.toml").is_file())
+}
+
+/// `rustfmt` can only format whole files, so ranges are always formatted with
+/// the built-in formatter.
+pub fn handle_range_formatting(
+    world: WorldSnapshot,
+    params: req::DocumentRangeFormattingParams,
+) -> Result<Option<Vec<TextEdit>>> {
+    let _p = profile("handle_range_formatting");
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let line_index = world.analysis().file_line_index(file_id)?;
+    let range = params.range.conv_with(&line_index);
+    builtin_formatting(&world, FileRange { file_id, range }, params.options.conv())
+}
+
+fn builtin_formatting(
+    world: &WorldSnapshot,
+    frange: FileRange,
+    options: FormatOptions,
+) -> Result<Option<Vec<TextEdit>>> {
+    let line_index = world.analysis().file_line_index(frange.file_id)?;
+    let line_endings = world.file_line_endings(frange.file_id);
+    let mut change = world.analysis().format_range(frange, options)?;
+    let edit = match change.source_file_edits.pop() {
+        Some(it) => it,
+        None => return Ok(None),
+    };
+    Ok(Some(edit.edit.conv_with((&line_index, line_endings))))
+}
+
 pub fn handle_code_action(
     world: WorldSnapshot,
     params: req::CodeActionParams,
@@ -648,6 +843,23 @@ pub fn handle_code_action(
         res.push(action.into());
     }
 
+    let check_diagnostics = world.check_diagnostics.read();
+    let fixes_from_check = check_diagnostics
+        .fixes_for(&params.text_document.uri)
+        .filter(|fix| ranges_intersect(fix.diagnostic.range, params.range));
+
+    for fix in fixes_from_check {
+        let edit = WorkspaceEdit { changes: Some(fix.edits.clone()), document_changes: None };
+        let action = CodeAction {
+            title: fix.title.clone(),
+            kind: Some("quickfix".to_string()),
+            diagnostics: Some(vec![fix.diagnostic.clone()]),
+            edit: Some(edit),
+            command: None,
+        };
+        res.push(action.into());
+    }
+
     for assist in assists {
         let title = assist.change.label.clone();
         let edit = assist.change.try_conv_with(&world)?;
@@ -673,6 +885,11 @@ pub fn handle_code_action(
     Ok(Some(res))
 }
 
+fn ranges_intersect(a: Range, b: Range) -> bool {
+    let pos = |it: Position| (it.line, it.character);
+    pos(a.start) <= pos(b.end) && pos(b.start) <= pos(a.end)
+}
+
 pub fn handle_code_lens(
     world: WorldSnapshot,
     params: req::CodeLensParams,
@@ -825,6 +1042,7 @@ pub fn publish_diagnostics(
             message: d.message,
             related_information: None,
         })
+        .chain(world.check_diagnostics.read().diagnostics_for(&uri).cloned())
         .collect();
     Ok(req::PublishDiagnosticsParams { uri, diagnostics })
 }
@@ -872,7 +1090,7 @@ fn highlight(world: &WorldSnapshot, file_id: FileId) -> Result<Vec<Decoration>>
         .into_iter()
         .map(|h| Decoration {
             range: h.range.conv_with(&line_index),
-            tag: h.tag,
+            tag: h.highlight.decoration_tag(),
             binding_hash: h.binding_hash.map(|x| x.to_string()),
         })
         .collect();
@@ -895,7 +1113,97 @@ pub fn handle_inlay_hints(
             range: api_type.range.conv_with(&line_index),
             kind: match api_type.kind {
                 ra_ide_api::InlayKind::TypeHint => InlayKind::TypeHint,
+                ra_ide_api::InlayKind::ParameterHint => InlayKind::ParameterHint,
+                ra_ide_api::InlayKind::ChainingHint => InlayKind::ChainingHint,
             },
         })
         .collect())
 }
+
+pub fn handle_semantic_tokens(
+    world: WorldSnapshot,
+    params: req::SemanticTokensParams,
+) -> Result<Option<req::SemanticTokens>> {
+    let _p = profile("handle_semantic_tokens");
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let mut tokens = semantic_tokens(&world, file_id, None)?;
+    tokens.result_id = Some(semantic_tokens::next_result_id());
+    world.semantic_tokens_cache.write().insert(params.text_document.uri, tokens.clone());
+    Ok(Some(tokens))
+}
+
+pub fn handle_semantic_tokens_edits(
+    world: WorldSnapshot,
+    params: req::SemanticTokensEditsParams,
+) -> Result<Option<req::SemanticTokensEditResult>> {
+    let _p = profile("handle_semantic_tokens_edits");
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let mut tokens = semantic_tokens(&world, file_id, None)?;
+    tokens.result_id = Some(semantic_tokens::next_result_id());
+
+    let mut cache = world.semantic_tokens_cache.write();
+    let previous = cache.insert(params.text_document.uri, tokens.clone());
+    let res = match previous {
+        Some(previous) if previous.result_id.as_ref() == Some(&params.previous_result_id) => {
+            let edits = semantic_tokens::diff_tokens(&previous.data, &tokens.data);
+            req::SemanticTokensEditResult::TokensEdits(req::SemanticTokensEdits {
+                result_id: tokens.result_id,
+                edits,
+            })
+        }
+        _ => req::SemanticTokensEditResult::Tokens(tokens),
+    };
+    Ok(Some(res))
+}
+
+pub fn handle_semantic_tokens_range(
+    world: WorldSnapshot,
+    params: req::SemanticTokensRangeParams,
+) -> Result<Option<req::SemanticTokens>> {
+    let _p = profile("handle_semantic_tokens_range");
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let line_index = world.analysis().file_line_index(file_id)?;
+    let range = params.range.conv_with(&line_index);
+    Ok(Some(semantic_tokens(&world, file_id, Some(range))?))
+}
+
+fn semantic_tokens(
+    world: &WorldSnapshot,
+    file_id: FileId,
+    range: Option<TextRange>,
+) -> Result<req::SemanticTokens> {
+    let analysis = world.analysis();
+    let line_index = analysis.file_line_index(file_id)?;
+    let text = analysis.file_text(file_id)?;
+
+    let mut highlights = analysis.highlight(file_id)?;
+    if let Some(range) = range {
+        highlights.retain(|it| it.range.intersection(&range).is_some());
+    }
+    highlights.sort_by_key(|it| it.range.start());
+
+    let mut builder = SemanticTokensBuilder::default();
+    let mut last_end = TextUnit::from(0);
+    for highlight in highlights {
+        // Tokens can't overlap, keep the outermost one.
+        if highlight.range.start() < last_end {
+            continue;
+        }
+        last_end = highlight.range.end();
+
+        let token_type = semantic_tokens::token_type_index(highlight.highlight.tag);
+        let token_modifiers = semantic_tokens::token_modifiers_bitset(highlight.highlight);
+        // Tokens can't span several lines either, so multiline comments and
+        // strings are split.
+        let mut start = highlight.range.start();
+        for line in text[highlight.range].split('\n') {
+            let len = TextUnit::of_str(line.trim_end_matches('\r'));
+            if len > TextUnit::from(0) {
+                let range = TextRange::offset_len(start, len).conv_with(&line_index);
+                builder.push(range, token_type, token_modifiers);
+            }
+            start += TextUnit::of_str(line) + TextUnit::from(1);
+        }
+    }
+    Ok(builder.build(None))
+}
@@ -1,28 +1,51 @@
-//! FIXME: write short doc here
+//! Post-processing of the markdown of doc comments, so that clients render
+//! it the way rustdoc would.
+
+/// The attributes of a code block which rustdoc still treats as Rust code.
+const RUSTDOC_FENCE_ATTRS: &[&str] =
+    &["rust", "ignore", "should_panic", "no_run", "compile_fail", "allow_fail", "test_harness"];
 
 pub(crate) fn format_docs(src: &str) -> String {
     let mut processed_lines = Vec::new();
     let mut in_code_block = false;
+    let mut is_rust = false;
     for line in src.lines() {
-        if in_code_block && code_line_ignored_by_rustdoc(line) {
+        if in_code_block && is_rust && code_line_ignored_by_rustdoc(line) {
             continue;
         }
 
         if line.starts_with("```") {
-            in_code_block ^= true
+            in_code_block ^= true;
+            if in_code_block {
+                is_rust = is_rust_fence(&line[3..]);
+                if is_rust {
+                    processed_lines.push("```rust");
+                    continue;
+                }
+            }
         }
 
-        let line = if in_code_block && line.starts_with("```") && !line.contains("rust") {
-            "```rust"
-        } else {
-            line
-        };
-
         processed_lines.push(line);
     }
     processed_lines.join("\n")
 }
 
+/// Like `format_docs`, but also replaces the destinations of the links for
+/// which `resolve_link` returns an URL.
+pub(crate) fn format_docs_with_links(
+    src: &str,
+    resolve_link: impl FnMut(&str) -> Option<String>,
+) -> String {
+    format_docs(&ra_ide_api::rewrite_links(src, resolve_link))
+}
+
+fn is_rust_fence(attrs: &str) -> bool {
+    attrs
+        .split(|c| c == ',' || c == ' ' || c == '\t')
+        .filter(|it| !it.is_empty())
+        .all(|attr| RUSTDOC_FENCE_ATTRS.contains(&attr) || attr.starts_with("edition"))
+}
+
 fn code_line_ignored_by_rustdoc(line: &str) -> bool {
     let trimmed = line.trim();
     trimmed == "#" || trimmed.starts_with("# ") || trimmed.starts_with("#\t")
@@ -72,4 +95,27 @@ let a = 1;
             "```rust\nfn main(){}\n```\nSome comment.\n```rust\nlet a = 1;\n```"
         );
     }
+
+    #[test]
+    fn test_format_docs_keeps_other_languages() {
+        let comment = "```text\n# stay\n```\n```ignore,edition2018\n# skip\nfoo();\n```";
+        assert_eq!(format_docs(comment), "```text\n# stay\n```\n```rust\nfoo();\n```");
+    }
+
+    #[test]
+    fn test_format_docs_resolves_links() {
+        let comment = "[`Foo`](Foo), [bar](bar) and [baz](https://baz.rs)\n```\n[x](Foo)\n```";
+        let resolve = |path: &str| {
+            if path == "Foo" {
+                Some("file:///foo.rs".to_string())
+            } else {
+                None
+            }
+        };
+        assert_eq!(
+            format_docs_with_links(comment, resolve),
+            "[`Foo`](file:///foo.rs), [bar](bar) and [baz](https://baz.rs)\n\
+             ```rust\n[x](Foo)\n```"
+        );
+    }
 }
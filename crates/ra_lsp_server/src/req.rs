@@ -1,6 +1,6 @@
 //! FIXME: write short doc here
 
-use lsp_types::{Location, Position, Range, TextDocumentIdentifier, Url};
+use lsp_types::{Location, Position, Range, SymbolKind, TextDocumentIdentifier, Url};
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 
@@ -8,10 +8,11 @@ pub use lsp_types::{
     notification::*, request::*, ApplyWorkspaceEditParams, CodeActionParams, CodeLens,
     CodeLensParams, CompletionParams, CompletionResponse, DidChangeConfigurationParams,
     DidChangeWatchedFilesParams, DidChangeWatchedFilesRegistrationOptions,
-    DocumentOnTypeFormattingParams, DocumentSymbolParams, DocumentSymbolResponse,
-    FileSystemWatcher, Hover, InitializeResult, MessageType, PublishDiagnosticsParams,
-    ReferenceParams, Registration, RegistrationParams, ShowMessageParams, SignatureHelp,
-    TextDocumentEdit, TextDocumentPositionParams, TextEdit, WorkspaceEdit, WorkspaceSymbolParams,
+    DocumentOnTypeFormattingParams, DocumentRangeFormattingParams, DocumentSymbolParams,
+    DocumentSymbolResponse, FileSystemWatcher, Hover, InitializeResult, MessageType,
+    PublishDiagnosticsParams, ReferenceParams, Registration, RegistrationParams, ShowMessageParams,
+    SignatureHelp, TextDocumentEdit, TextDocumentPositionParams, TextEdit, WorkspaceEdit,
+    WorkspaceSymbolParams,
 };
 
 pub enum AnalyzerStatus {}
@@ -38,7 +39,7 @@ impl Request for SyntaxTree {
     const METHOD: &'static str = "rust-analyzer/syntaxTree";
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct SyntaxTreeParams {
     pub text_document: TextDocumentIdentifier,
@@ -157,6 +158,39 @@ pub struct JoinLinesParams {
     pub range: Range,
 }
 
+pub enum Ssr {}
+
+impl Request for Ssr {
+    type Params = SsrParams;
+    type Result = SourceChange;
+    const METHOD: &'static str = "rust-analyzer/ssr";
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SsrParams {
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+    pub query: String,
+    pub parse_only: bool,
+}
+
+pub enum ChangeSignature {}
+
+impl Request for ChangeSignature {
+    type Params = ChangeSignatureParams;
+    type Result = SourceChange;
+    const METHOD: &'static str = "rust-analyzer/changeSignature";
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeSignatureParams {
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+    pub new_params: String,
+}
+
 pub enum OnEnter {}
 
 impl Request for OnEnter {
@@ -216,6 +250,8 @@ pub struct InlayHintsParams {
 #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub enum InlayKind {
     TypeHint,
+    ParameterHint,
+    ChainingHint,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -224,3 +260,164 @@ pub struct InlayHint {
     pub kind: InlayKind,
     pub label: String,
 }
+
+// Semantic tokens are not in `lsp_types` yet, so the protocol types are
+// defined here, following the proposed LSP 3.16 extension.
+
+pub enum SemanticTokensRequest {}
+
+impl Request for SemanticTokensRequest {
+    type Params = SemanticTokensParams;
+    type Result = Option<SemanticTokens>;
+    const METHOD: &'static str = "textDocument/semanticTokens";
+}
+
+pub enum SemanticTokensEditsRequest {}
+
+impl Request for SemanticTokensEditsRequest {
+    type Params = SemanticTokensEditsParams;
+    type Result = Option<SemanticTokensEditResult>;
+    const METHOD: &'static str = "textDocument/semanticTokens/edits";
+}
+
+pub enum SemanticTokensRangeRequest {}
+
+impl Request for SemanticTokensRangeRequest {
+    type Params = SemanticTokensRangeParams;
+    type Result = Option<SemanticTokens>;
+    const METHOD: &'static str = "textDocument/semanticTokens/range";
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensEditsParams {
+    pub text_document: TextDocumentIdentifier,
+    pub previous_result_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensRangeParams {
+    pub text_document: TextDocumentIdentifier,
+    pub range: Range,
+}
+
+/// Tokens are encoded as groups of five integers: delta line, delta start
+/// character, length, token type index and token modifiers bitset. Lines and
+/// characters are relative to the previous token.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokens {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_id: Option<String>,
+    pub data: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensEdits {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_id: Option<String>,
+    pub edits: Vec<SemanticTokensEdit>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensEdit {
+    pub start: u32,
+    pub delete_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Vec<u32>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum SemanticTokensEditResult {
+    Tokens(SemanticTokens),
+    TokensEdits(SemanticTokensEdits),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensLegend {
+    pub token_types: Vec<String>,
+    pub token_modifiers: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensOptions {
+    pub legend: SemanticTokensLegend,
+    pub range_provider: Option<bool>,
+    pub document_provider: Option<SemanticTokensDocumentProvider>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensDocumentProvider {
+    pub edits: Option<bool>,
+}
+
+// Call hierarchy is not in `lsp_types` yet either.
+
+pub enum CallHierarchyPrepare {}
+
+impl Request for CallHierarchyPrepare {
+    type Params = TextDocumentPositionParams;
+    type Result = Option<Vec<CallHierarchyItem>>;
+    const METHOD: &'static str = "textDocument/prepareCallHierarchy";
+}
+
+pub enum CallHierarchyIncomingCalls {}
+
+impl Request for CallHierarchyIncomingCalls {
+    type Params = CallHierarchyCallsParams;
+    type Result = Option<Vec<CallHierarchyIncomingCall>>;
+    const METHOD: &'static str = "callHierarchy/incomingCalls";
+}
+
+pub enum CallHierarchyOutgoingCalls {}
+
+impl Request for CallHierarchyOutgoingCalls {
+    type Params = CallHierarchyCallsParams;
+    type Result = Option<Vec<CallHierarchyOutgoingCall>>;
+    const METHOD: &'static str = "callHierarchy/outgoingCalls";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyItem {
+    pub name: String,
+    pub kind: SymbolKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub uri: Url,
+    pub range: Range,
+    pub selection_range: Range,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyCallsParams {
+    pub item: CallHierarchyItem,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyIncomingCall {
+    pub from: CallHierarchyItem,
+    pub from_ranges: Vec<Range>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyOutgoingCall {
+    pub to: CallHierarchyItem,
+    pub from_ranges: Vec<Range>,
+}
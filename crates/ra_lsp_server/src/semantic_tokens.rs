@@ -0,0 +1,187 @@
+//! Encoding of highlighted ranges as LSP semantic tokens.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use lsp_types::Range;
+use ra_ide_api::{Highlight, HighlightModifier, HighlightTag};
+
+use crate::req::{SemanticTokens, SemanticTokensEdit, SemanticTokensLegend};
+
+pub(crate) const SUPPORTED_TYPES: &[&str] = &[
+    "attribute",
+    "builtinType",
+    "comment",
+    "constant",
+    "enum",
+    "enumMember",
+    "function",
+    "interface",
+    "keyword",
+    "lifetime",
+    "macro",
+    "namespace",
+    "number",
+    "property",
+    "string",
+    "struct",
+    "type",
+    "typeAlias",
+    "typeParameter",
+    "union",
+    "unresolvedReference",
+    "variable",
+];
+
+/// Same order as `HighlightModifier::ALL`.
+pub(crate) const SUPPORTED_MODIFIERS: &[&str] =
+    &["control", "declaration", "library", "mutable", "static", "unsafe"];
+
+pub(crate) fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: SUPPORTED_TYPES.iter().map(|it| it.to_string()).collect(),
+        token_modifiers: SUPPORTED_MODIFIERS.iter().map(|it| it.to_string()).collect(),
+    }
+}
+
+pub(crate) fn token_type_index(tag: HighlightTag) -> u32 {
+    let token_type = match tag {
+        HighlightTag::Attribute => "attribute",
+        HighlightTag::BuiltinType => "builtinType",
+        HighlightTag::CharLiteral | HighlightTag::StringLiteral => "string",
+        HighlightTag::Comment => "comment",
+        HighlightTag::Constant => "constant",
+        HighlightTag::Enum => "enum",
+        HighlightTag::EnumVariant => "enumMember",
+        HighlightTag::Field => "property",
+        HighlightTag::Function => "function",
+        HighlightTag::Keyword => "keyword",
+        HighlightTag::Lifetime => "lifetime",
+        HighlightTag::Local | HighlightTag::Static => "variable",
+        HighlightTag::Macro => "macro",
+        HighlightTag::Module => "namespace",
+        HighlightTag::NumericLiteral => "number",
+        HighlightTag::SelfType => "type",
+        HighlightTag::Struct => "struct",
+        HighlightTag::Trait => "interface",
+        HighlightTag::TypeAlias => "typeAlias",
+        HighlightTag::TypeParam => "typeParameter",
+        HighlightTag::Union => "union",
+        HighlightTag::UnresolvedReference => "unresolvedReference",
+    };
+    SUPPORTED_TYPES.iter().position(|it| *it == token_type).unwrap() as u32
+}
+
+pub(crate) fn token_modifiers_bitset(highlight: Highlight) -> u32 {
+    highlight.modifiers.iter().fold(0, |acc, modifier| acc | 1 << modifier_index(modifier))
+}
+
+fn modifier_index(modifier: HighlightModifier) -> u32 {
+    HighlightModifier::ALL.iter().position(|it| *it == modifier).unwrap() as u32
+}
+
+/// Returns a fresh `result_id`, so that a later edits request can refer to
+/// the tokens which were sent to the client.
+pub(crate) fn next_result_id() -> String {
+    static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+    NEXT_ID.fetch_add(1, Ordering::SeqCst).to_string()
+}
+
+/// Encodes tokens relative to the previous one, as the protocol requires.
+#[derive(Default)]
+pub(crate) struct SemanticTokensBuilder {
+    prev_line: u32,
+    prev_char: u32,
+    data: Vec<u32>,
+}
+
+impl SemanticTokensBuilder {
+    /// Pushes a token, which must fit on a single line and must start after
+    /// the previously pushed one.
+    pub(crate) fn push(&mut self, range: Range, token_type: u32, token_modifiers: u32) {
+        let line = range.start.line as u32;
+        let start = range.start.character as u32;
+        let len = range.end.character as u32 - start;
+
+        let mut delta_line = line;
+        let mut delta_start = start;
+        if !self.data.is_empty() {
+            delta_line -= self.prev_line;
+            if delta_line == 0 {
+                delta_start -= self.prev_char;
+            }
+        }
+        self.data.extend_from_slice(&[delta_line, delta_start, len, token_type, token_modifiers]);
+
+        self.prev_line = line;
+        self.prev_char = start;
+    }
+
+    pub(crate) fn build(self, result_id: Option<String>) -> SemanticTokens {
+        SemanticTokens { result_id, data: self.data }
+    }
+}
+
+/// Computes the edit turning `old` into `new`, by stripping their common
+/// prefix and suffix.
+pub(crate) fn diff_tokens(old: &[u32], new: &[u32]) -> Vec<SemanticTokensEdit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old.iter().rev().zip(new.iter().rev()).take_while(|(a, b)| a == b).count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+
+    if old.is_empty() && new.is_empty() {
+        return Vec::new();
+    }
+    vec![SemanticTokensEdit {
+        start: prefix as u32,
+        delete_count: old.len() as u32,
+        data: Some(new.to_vec()),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Position;
+
+    use super::*;
+
+    fn range(line: u64, start: u64, end: u64) -> Range {
+        Range::new(Position::new(line, start), Position::new(line, end))
+    }
+
+    #[test]
+    fn encodes_tokens_relative_to_previous_one() {
+        let mut builder = SemanticTokensBuilder::default();
+        builder.push(range(1, 4, 7), 1, 0);
+        builder.push(range(1, 8, 11), 2, 3);
+        builder.push(range(3, 2, 4), 3, 0);
+        let tokens = builder.build(None);
+        assert_eq!(tokens.data, vec![1, 4, 3, 1, 0, 0, 4, 3, 2, 3, 2, 2, 2, 3, 0]);
+    }
+
+    #[test]
+    fn diffs_tokens() {
+        let old = [1, 4, 3, 1, 0, 0, 4, 3, 2, 3];
+        assert_eq!(diff_tokens(&old, &old), vec![]);
+
+        let new = [1, 4, 3, 1, 0, 0, 5, 3, 2, 3];
+        assert_eq!(
+            diff_tokens(&old, &new),
+            vec![SemanticTokensEdit { start: 6, delete_count: 1, data: Some(vec![5]) }]
+        );
+
+        let new = [1, 4, 3, 1, 0];
+        assert_eq!(
+            diff_tokens(&old, &new),
+            vec![SemanticTokensEdit { start: 5, delete_count: 5, data: Some(vec![]) }]
+        );
+    }
+
+    #[test]
+    fn modifiers_follow_highlight_modifier_order() {
+        let names: Vec<String> = HighlightModifier::ALL.iter().map(|it| it.to_string()).collect();
+        assert_eq!(names, SUPPORTED_MODIFIERS);
+        let highlight = HighlightTag::Local | HighlightModifier::Mutable;
+        assert_eq!(token_modifiers_bitset(highlight | HighlightModifier::Declaration), 0b1010);
+    }
+}
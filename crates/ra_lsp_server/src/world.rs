@@ -13,13 +13,16 @@ use ra_ide_api::{
     Analysis, AnalysisChange, AnalysisHost, CrateGraph, FeatureFlags, FileId, LibraryData,
     SourceRootId,
 };
-use ra_project_model::{get_rustc_cfg_options, ProjectWorkspace};
+use ra_project_model::{get_rustc_cfg_options, ProcMacroClient, ProjectWorkspace};
 use ra_vfs::{LineEndings, RootEntry, Vfs, VfsChange, VfsFile, VfsRoot, VfsTask, Watch};
 use ra_vfs_glob::{Glob, RustPackageFilterBuilder};
 use relative_path::RelativePathBuf;
+use rustc_hash::FxHashMap;
 
 use crate::{
+    flycheck::{CheckDiagnostics, Flycheck},
     main_loop::pending_requests::{CompletedRequest, LatestRequests},
+    req::SemanticTokens,
     LspError, Result,
 };
 
@@ -47,6 +50,10 @@ pub struct WorldState {
     pub vfs: Arc<RwLock<Vfs>>,
     pub task_receiver: Receiver<VfsTask>,
     pub latest_requests: Arc<RwLock<LatestRequests>>,
+    /// The last semantic tokens sent for each document, to compute edits.
+    pub semantic_tokens_cache: Arc<RwLock<FxHashMap<Url, SemanticTokens>>>,
+    pub flycheck: Flycheck,
+    pub check_diagnostics: Arc<RwLock<CheckDiagnostics>>,
 }
 
 /// An immutable snapshot of the world's state at a point in time.
@@ -56,6 +63,8 @@ pub struct WorldSnapshot {
     pub analysis: Analysis,
     pub vfs: Arc<RwLock<Vfs>>,
     pub latest_requests: Arc<RwLock<LatestRequests>>,
+    pub semantic_tokens_cache: Arc<RwLock<FxHashMap<Url, SemanticTokens>>>,
+    pub check_diagnostics: Arc<RwLock<CheckDiagnostics>>,
 }
 
 impl WorldState {
@@ -67,6 +76,8 @@ impl WorldState {
         watch: Watch,
         options: Options,
         feature_flags: FeatureFlags,
+        proc_macro_client: &ProcMacroClient,
+        check_command: Vec<String>,
     ) -> WorldState {
         let mut change = AnalysisChange::new();
 
@@ -92,11 +103,13 @@ impl WorldState {
         let task_sender = Box::new(move |t| task_sender.send(t).unwrap());
         let (mut vfs, vfs_roots) = Vfs::new(roots, task_sender, watch);
         let roots_to_scan = vfs_roots.len();
+        let mut extern_source_roots = FxHashMap::default();
         for r in vfs_roots {
             let vfs_root_path = vfs.root2path(r);
             let is_local = folder_roots.iter().any(|it| vfs_root_path.starts_with(it));
             change.add_root(SourceRootId(r.0), is_local);
             change.set_debug_root_path(SourceRootId(r.0), vfs_root_path.display().to_string());
+            extern_source_roots.insert(vfs_root_path, SourceRootId(r.0));
         }
 
         // FIXME: Read default cfgs from config
@@ -114,7 +127,12 @@ impl WorldState {
             vfs_file.map(|f| FileId(f.0))
         };
         for ws in workspaces.iter() {
-            let (graph, crate_names) = ws.to_crate_graph(&default_cfg_options, &mut load);
+            let (graph, crate_names) = ws.to_crate_graph(
+                &default_cfg_options,
+                &extern_source_roots,
+                proc_macro_client,
+                &mut load,
+            );
             let shift = crate_graph.extend(graph);
             for (crate_id, name) in crate_names {
                 change.set_debug_crate_name(crate_id.shift(shift), name)
@@ -124,6 +142,9 @@ impl WorldState {
 
         let mut analysis_host = AnalysisHost::new(lru_capacity, feature_flags);
         analysis_host.apply_change(change);
+
+        let flycheck = Flycheck::new(check_command, folder_roots.clone());
+
         WorldState {
             options,
             roots_to_scan,
@@ -133,6 +154,9 @@ impl WorldState {
             vfs: Arc::new(RwLock::new(vfs)),
             task_receiver,
             latest_requests: Default::default(),
+            semantic_tokens_cache: Default::default(),
+            flycheck,
+            check_diagnostics: Default::default(),
         }
     }
 
@@ -194,6 +218,8 @@ impl WorldState {
             analysis: self.analysis_host.analysis(),
             vfs: Arc::clone(&self.vfs),
             latest_requests: Arc::clone(&self.latest_requests),
+            semantic_tokens_cache: Arc::clone(&self.semantic_tokens_cache),
+            check_diagnostics: Arc::clone(&self.check_diagnostics),
         }
     }
 
@@ -3,13 +3,18 @@ mod support;
 use std::{collections::HashMap, time::Instant};
 
 use lsp_types::{
-    CodeActionContext, DidOpenTextDocumentParams, DocumentFormattingParams, FormattingOptions,
-    Position, Range, TextDocumentItem, TextDocumentPositionParams,
+    CodeActionContext, DidChangeTextDocumentParams, DidOpenTextDocumentParams,
+    DidSaveTextDocumentParams, DocumentFormattingParams, DocumentRangeFormattingParams,
+    FormattingOptions, Position, Range, RenameParams, TextDocumentContentChangeEvent,
+    TextDocumentItem, TextDocumentPositionParams, VersionedTextDocumentIdentifier,
 };
 use ra_lsp_server::req::{
-    CodeActionParams, CodeActionRequest, Completion, CompletionParams, DidOpenTextDocument,
-    Formatting, OnEnter, Runnables, RunnablesParams,
+    CallHierarchyCallsParams, CallHierarchyItem, CallHierarchyOutgoingCalls, CallHierarchyPrepare,
+    CodeActionParams, CodeActionRequest, Completion, CompletionParams, DidChangeTextDocument,
+    DidOpenTextDocument, DidSaveTextDocument, Formatting, OnEnter, RangeFormatting, Rename,
+    Runnables, RunnablesParams, SyntaxTree, SyntaxTreeParams,
 };
+use ra_syntax::{AstNode, SourceFile};
 use serde_json::json;
 use tempfile::TempDir;
 
@@ -220,6 +225,94 @@ pub use std::collections::HashMap;
     );
 }
 
+#[test]
+fn test_format_range() {
+    let server = project(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+fn main() {
+let x=1;
+let y=2;
+}
+"#,
+    );
+    server.wait_until_workspace_is_loaded();
+
+    server.request::<RangeFormatting>(
+        DocumentRangeFormattingParams {
+            text_document: server.doc_id("src/lib.rs"),
+            range: Range::new(Position::new(2, 0), Position::new(2, 8)),
+            options: FormattingOptions {
+                tab_size: 4,
+                insert_spaces: false,
+                properties: HashMap::new(),
+            },
+        },
+        json!([
+            {
+                "newText": "\n\t",
+                "range": { "start": { "line": 1, "character": 8 }, "end": { "line": 2, "character": 0 } }
+            },
+            {
+                "newText": " ",
+                "range": { "start": { "line": 2, "character": 5 }, "end": { "line": 2, "character": 5 } }
+            },
+            {
+                "newText": " ",
+                "range": { "start": { "line": 2, "character": 6 }, "end": { "line": 2, "character": 6 } }
+            }
+        ]),
+    );
+}
+
+#[test]
+fn test_call_hierarchy() {
+    let server = project(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+fn callee() {}
+fn caller() {
+    callee();
+}
+"#,
+    );
+    server.wait_until_workspace_is_loaded();
+
+    let items = server.send_request::<CallHierarchyPrepare>(TextDocumentPositionParams::new(
+        server.doc_id("src/lib.rs"),
+        Position::new(1, 4),
+    ));
+    let item: CallHierarchyItem = serde_json::from_value(items[0].clone()).unwrap();
+    server.request::<CallHierarchyOutgoingCalls>(
+        CallHierarchyCallsParams { item },
+        json!([
+            {
+                "to": {
+                    "name": "callee",
+                    "kind": 12,
+                    "detail": "fn callee()",
+                    "uri": "file:///[..]src/lib.rs",
+                    "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 14 } },
+                    "selectionRange": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 9 } }
+                },
+                "fromRanges": [
+                    { "start": { "line": 2, "character": 4 }, "end": { "line": 2, "character": 10 } }
+                ]
+            }
+        ]),
+    );
+}
+
 #[test]
 fn test_missing_module_code_action() {
     let server = project(
@@ -278,6 +371,82 @@ fn main() {}
     );
 }
 
+#[test]
+fn test_rename_module_moves_files() {
+    let server = project(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+mod bar;
+
+//- src/bar.rs
+mod baz;
+pub fn f() {}
+
+//- src/bar/baz.rs
+use crate::bar::f;
+"#,
+    );
+    server.wait_until_workspace_is_loaded();
+    server.request::<Rename>(
+        RenameParams {
+            text_document_position: TextDocumentPositionParams::new(
+                server.doc_id("src/lib.rs"),
+                Position::new(0, 5),
+            ),
+            new_name: "quux".to_string(),
+        },
+        json!({
+          "documentChanges": [
+            {
+              "textDocument": {
+                "uri": "file:///[..]/src/lib.rs",
+                "version": null
+              },
+              "edits": [
+                {
+                  "range": {
+                    "start": { "line": 0, "character": 4 },
+                    "end": { "line": 0, "character": 7 }
+                  },
+                  "newText": "quux"
+                }
+              ]
+            },
+            {
+              "textDocument": {
+                "uri": "file:///[..]/src/bar/baz.rs",
+                "version": null
+              },
+              "edits": [
+                {
+                  "range": {
+                    "start": { "line": 0, "character": 11 },
+                    "end": { "line": 0, "character": 14 }
+                  },
+                  "newText": "quux"
+                }
+              ]
+            },
+            {
+              "kind": "rename",
+              "oldUri": "file:///[..]/src/bar.rs",
+              "newUri": "file:///[..]/src/quux.rs"
+            },
+            {
+              "kind": "rename",
+              "oldUri": "file:///[..]/src/bar",
+              "newUri": "file:///[..]/src/quux"
+            }
+          ]
+        }),
+    );
+}
+
 #[test]
 fn test_missing_module_code_action_in_json_project() {
     let tmp_dir = TempDir::new().unwrap();
@@ -466,3 +635,136 @@ version = \"0.0.0\"
         }),
     );
 }
+
+#[test]
+fn applies_incremental_changes() {
+    let server = Project::with_fixture(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+fn foo() {}
+"#,
+    )
+    .server();
+    server.wait_until_workspace_is_loaded();
+
+    let uri = server.doc_id("src/lib.rs").uri;
+    server.notification::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+        text_document: TextDocumentItem {
+            uri: uri.clone(),
+            language_id: "rust".to_string(),
+            version: 0,
+            text: "fn foo() {}\n".to_string(),
+        },
+    });
+
+    let mut version = 0;
+    let mut change = |changes: Vec<(Option<((u64, u64), (u64, u64))>, &str)>| {
+        version += 1;
+        let content_changes = changes
+            .into_iter()
+            .map(|(range, text)| TextDocumentContentChangeEvent {
+                range: range.map(|(start, end)| {
+                    Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+                }),
+                range_length: None,
+                text: text.to_string(),
+            })
+            .collect();
+        server.notification::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier {
+                uri: uri.clone(),
+                version: Some(version),
+            },
+            content_changes,
+        });
+    };
+    let check_text = |expected: &str| {
+        let tree = format!("{:#?}", SourceFile::parse(expected).tree().syntax());
+        server.request::<SyntaxTree>(
+            SyntaxTreeParams { text_document: server.doc_id("src/lib.rs"), range: None },
+            json!(tree),
+        );
+    };
+
+    // Later changes in a notification apply to the result of the earlier ones.
+    change(vec![(Some(((0, 10), (0, 10))), " let s = \"é\"; "), (Some(((0, 3), (0, 6))), "bar")]);
+    check_text("fn bar() { let s = \"é\"; }\n");
+
+    // Columns are counted in UTF-16 code units.
+    change(vec![(Some(((0, 22), (0, 22))), ".len()")]);
+    check_text("fn bar() { let s = \"é\".len(); }\n");
+
+    change(vec![(Some(((1, 0), (1, 0))), "\nfn baz() {}\n"), (Some(((0, 3), (0, 6))), "quux")]);
+    check_text("fn quux() { let s = \"é\".len(); }\n\nfn baz() {}\n");
+
+    // A change without a range replaces the whole document.
+    change(vec![(None, "struct S;\n"), (Some(((0, 9), (0, 9))), " // comment")]);
+    check_text("struct S; // comment\n");
+}
+
+#[test]
+fn test_check_on_save() {
+    let server = Project::with_fixture(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+pub fn foo() {
+    let mut x = 92;
+    let _ = x;
+}
+"#,
+    )
+    .check_command(&["cargo", "check", "--message-format=json"])
+    .server();
+    server.wait_until_workspace_is_loaded();
+    server.notification::<DidSaveTextDocument>(DidSaveTextDocumentParams {
+        text_document: server.doc_id("src/lib.rs"),
+    });
+    let params = server.wait_until_diagnostics_from("rustc");
+    let diagnostic = params
+        .diagnostics
+        .iter()
+        .find(|it| it.source.as_ref().map(|it| it.as_str()) == Some("rustc"))
+        .unwrap();
+    assert_eq!(diagnostic.message, "variable does not need to be mutable");
+    assert_eq!(diagnostic.range.start, Position::new(1, 8));
+
+    let res = server.send_request::<CodeActionRequest>(CodeActionParams {
+        text_document: server.doc_id("src/lib.rs"),
+        range: diagnostic.range,
+        context: CodeActionContext { diagnostics: vec![diagnostic.clone()], only: None },
+    });
+    let fix = res
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|it| it["title"] == "remove this `mut`")
+        .expect("no fix from the check command");
+    assert_eq!(fix["kind"], "quickfix");
+    let edits = fix["edit"]["changes"].as_object().unwrap().values().next().unwrap();
+    assert_eq!(edits[0]["range"]["start"], json!({ "line": 1, "character": 8 }));
+    assert_eq!(edits[0]["newText"], "");
+
+    // The diagnostics of rustc are dropped once the file is edited.
+    server.notification::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+        text_document: VersionedTextDocumentIdentifier {
+            uri: server.doc_id("src/lib.rs").uri,
+            version: Some(1),
+        },
+        content_changes: vec![TextDocumentContentChangeEvent {
+            range: Some(Range::new(Position::new(1, 8), Position::new(1, 12))),
+            range_length: None,
+            text: String::new(),
+        }],
+    });
+    server.wait_until_diagnostics_without("rustc");
+}
@@ -27,11 +27,18 @@ pub struct Project<'a> {
     with_sysroot: bool,
     tmp_dir: Option<TempDir>,
     roots: Vec<PathBuf>,
+    check_command: Vec<String>,
 }
 
 impl<'a> Project<'a> {
     pub fn with_fixture(fixture: &str) -> Project {
-        Project { fixture, tmp_dir: None, roots: vec![], with_sysroot: false }
+        Project {
+            fixture,
+            tmp_dir: None,
+            roots: vec![],
+            with_sysroot: false,
+            check_command: vec![],
+        }
     }
 
     pub fn tmp_dir(mut self, tmp_dir: TempDir) -> Project<'a> {
@@ -49,6 +56,11 @@ impl<'a> Project<'a> {
         self
     }
 
+    pub fn check_command(mut self, command: &[&str]) -> Project<'a> {
+        self.check_command = command.iter().map(|it| it.to_string()).collect();
+        self
+    }
+
     pub fn server(self) -> Server {
         let tmp_dir = self.tmp_dir.unwrap_or_else(|| TempDir::new().unwrap());
         static INIT: Once = Once::new();
@@ -72,7 +84,12 @@ impl<'a> Project<'a> {
 
         let roots = self.roots.into_iter().map(|root| tmp_dir.path().join(root)).collect();
 
-        Server::new(tmp_dir, self.with_sysroot, roots, paths)
+        let config = ServerConfig {
+            with_sysroot: self.with_sysroot,
+            check_command: self.check_command,
+            ..ServerConfig::default()
+        };
+        Server::new(tmp_dir, config, roots, paths)
     }
 }
 
@@ -91,7 +108,7 @@ pub struct Server {
 impl Server {
     fn new(
         dir: TempDir,
-        with_sysroot: bool,
+        config: ServerConfig,
         roots: Vec<PathBuf>,
         files: Vec<(PathBuf, String)>,
     ) -> Server {
@@ -117,7 +134,7 @@ impl Server {
                         window: None,
                         experimental: None,
                     },
-                    ServerConfig { with_sysroot, ..ServerConfig::default() },
+                    config,
                     connection,
                 )
                 .unwrap()
@@ -210,6 +227,58 @@ impl Server {
             _ => false,
         })
     }
+    /// Waits for published diagnostics which contain a diagnostic of `source`.
+    pub fn wait_until_diagnostics_from(&self, source: &str) -> req::PublishDiagnosticsParams {
+        let extract = |msg: &Message| match msg {
+            Message::Notification(n) if n.method == "textDocument/publishDiagnostics" => {
+                let params = n
+                    .clone()
+                    .extract::<req::PublishDiagnosticsParams>("textDocument/publishDiagnostics")
+                    .unwrap();
+                let has_source = params
+                    .diagnostics
+                    .iter()
+                    .any(|it| it.source.as_ref().map(|it| it.as_str()) == Some(source));
+                if has_source {
+                    Some(params)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        if let Some(params) = self.messages.borrow().iter().find_map(extract) {
+            return params;
+        }
+        loop {
+            let msg = self.recv().expect("no response");
+            if let Some(params) = extract(&msg) {
+                return params;
+            }
+        }
+    }
+    /// Waits for newly published diagnostics without any diagnostic of
+    /// `source`.
+    pub fn wait_until_diagnostics_without(&self, source: &str) {
+        loop {
+            let msg = self.recv().expect("no response");
+            if let Message::Notification(n) = msg {
+                if n.method != "textDocument/publishDiagnostics" {
+                    continue;
+                }
+                let params = n
+                    .extract::<req::PublishDiagnosticsParams>("textDocument/publishDiagnostics")
+                    .unwrap();
+                let has_source = params
+                    .diagnostics
+                    .iter()
+                    .any(|it| it.source.as_ref().map(|it| it.as_str()) == Some(source));
+                if !has_source {
+                    return;
+                }
+            }
+        }
+    }
     fn wait_for_message_cond(&self, n: usize, cond: &dyn Fn(&Message) -> bool) {
         let mut total = 0;
         for msg in self.messages.borrow().iter() {
@@ -21,14 +21,24 @@ pub struct TokenMap {
 /// Convert the syntax tree (what user has written) to a `TokenTree` (what macro
 /// will consume).
 pub fn ast_to_token_tree(ast: &ast::TokenTree) -> Option<(tt::Subtree, TokenMap)> {
-    syntax_node_to_token_tree(ast.syntax())
+    convert(ast.syntax(), false)
 }
 
 /// Convert the syntax node to a `TokenTree` (what macro
 /// will consume).
+///
+/// Unlike `ast_to_token_tree`, this accepts arbitrary syntax like the items
+/// which attribute-like macros are applied to: composite puncts like `::` are split into
+/// joint single-char puncts, and nodes without delimiters (like paths) are
+/// flattened into their parent.
 pub fn syntax_node_to_token_tree(node: &SyntaxNode) -> Option<(tt::Subtree, TokenMap)> {
+    convert(node, true)
+}
+
+fn convert(node: &SyntaxNode, is_arbitrary_syntax: bool) -> Option<(tt::Subtree, TokenMap)> {
     let global_offset = node.text_range().start();
-    let mut c = Convertor { map: TokenMap::default(), global_offset, next_id: 0 };
+    let mut c =
+        Convertor { map: TokenMap::default(), global_offset, next_id: 0, is_arbitrary_syntax };
     let subtree = c.go(node)?;
     Some((subtree, c.map))
 }
@@ -150,6 +160,7 @@ struct Convertor {
     map: TokenMap,
     global_offset: TextUnit,
     next_id: u32,
+    is_arbitrary_syntax: bool,
 }
 
 impl Convertor {
@@ -208,13 +219,12 @@ impl Convertor {
                     } else if token.kind().is_trivia() {
                         continue;
                     } else if token.kind().is_punct() {
+                        // Composite puncts like `::` only appear outside of token trees.
                         assert!(
-                            token.text().len() == 1,
+                            self.is_arbitrary_syntax || token.text().len() == 1,
                             "Input ast::token punct must be single char."
                         );
-                        let char = token.text().chars().next().unwrap();
-
-                        let spacing = match child_iter.peek() {
+                        let last_spacing = match child_iter.peek() {
                             Some(NodeOrToken::Token(token)) => {
                                 if token.kind().is_punct() {
                                     tt::Spacing::Joint
@@ -224,8 +234,15 @@ impl Convertor {
                             }
                             _ => tt::Spacing::Alone,
                         };
-
-                        token_trees.push(tt::Leaf::from(tt::Punct { char, spacing }).into());
+                        let mut chars = token.text().chars().peekable();
+                        while let Some(char) = chars.next() {
+                            let spacing = if chars.peek().is_some() {
+                                tt::Spacing::Joint
+                            } else {
+                                last_spacing
+                            };
+                            token_trees.push(tt::Leaf::from(tt::Punct { char, spacing }).into());
+                        }
                     } else {
                         let child: tt::TokenTree =
                             if token.kind() == T![true] || token.kind() == T![false] {
@@ -246,8 +263,12 @@ impl Convertor {
                     }
                 }
                 NodeOrToken::Node(node) => {
-                    let child = self.go(&node)?.into();
-                    token_trees.push(child);
+                    let child = self.go(&node)?;
+                    if self.is_arbitrary_syntax && child.delimiter == tt::Delimiter::None {
+                        token_trees.extend(child.token_trees);
+                    } else {
+                        token_trees.push(child.into());
+                    }
                 }
             };
         }
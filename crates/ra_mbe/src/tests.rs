@@ -468,6 +468,26 @@ fn test_expand_literals_to_token_tree() {
     assert_eq!(to_literal(&stm_tokens[15 + 3]).text, "\"rust1\"");
 }
 
+#[test]
+fn test_item_to_token_tree() {
+    let source_file = ast::SourceFile::parse("fn foo(a: u32) -> core::Foo {}").ok().unwrap();
+    let (tt, _) = syntax_node_to_token_tree(source_file.syntax()).unwrap();
+
+    assert_eq!(tt.delimiter, tt::Delimiter::None);
+    assert_eq!(tt.to_string(), "fn foo (a : u32) -> core :: Foo {}");
+}
+
+#[test]
+fn test_token_tree_converts_the_same_as_syntax_node() {
+    let source_file = ast::SourceFile::parse("foo!(a::b => (c), [d] {e: &'a f}; #);").ok().unwrap();
+    let token_tree = source_file.syntax().descendants().find_map(ast::TokenTree::cast).unwrap();
+    let (from_token_tree, _) = ast_to_token_tree(&token_tree).unwrap();
+    let (from_syntax_node, _) = syntax_node_to_token_tree(token_tree.syntax()).unwrap();
+
+    assert_eq!(from_token_tree, from_syntax_node);
+    assert_eq!(from_token_tree.to_string(), "(a :: b => (c) , [d] {e : & 'a f} ; #)");
+}
+
 #[test]
 fn test_two_idents() {
     let rules = create_rules(
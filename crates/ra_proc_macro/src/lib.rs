@@ -0,0 +1,99 @@
+//! Client-side Proc-Macro crate
+//!
+//! We separate proc-macro expanding logic to an extern program to allow
+//! different implementations (e.g. wasm or dylib loading). And this crate
+//! is used to provide basic infrastructure for communication between two
+//! processes: Client (RA itself), Server (the external program)
+
+mod rpc;
+mod process;
+pub mod msg;
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use tt::{SmolStr, Subtree};
+
+use crate::process::ProcMacroProcessSrv;
+
+pub use rpc::{ExpansionResult, ExpansionTask, ListMacrosResult, ListMacrosTask, ProcMacroKind};
+
+/// A single macro exported by a proc-macro dylib, expanded by the external
+/// server process.
+#[derive(Debug, Clone)]
+pub struct ProcMacroProcessExpander {
+    process: Arc<ProcMacroProcessSrv>,
+    dylib_path: PathBuf,
+    name: SmolStr,
+}
+
+impl tt::TokenExpander for ProcMacroProcessExpander {
+    fn expand(
+        &self,
+        subtree: &Subtree,
+        attr: Option<&Subtree>,
+    ) -> Result<Subtree, tt::ExpansionError> {
+        self.process.expand(&self.dylib_path, subtree, attr, &self.name)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum ProcMacroClientKind {
+    Process { process: Arc<ProcMacroProcessSrv> },
+    Dummy,
+}
+
+/// Entry point for proc-macro expansion. When no server is available, the
+/// `Dummy` client reports no macros at all, so macro calls stay unexpanded.
+#[derive(Debug, Clone)]
+pub struct ProcMacroClient {
+    kind: ProcMacroClientKind,
+}
+
+impl ProcMacroClient {
+    pub fn extern_process(process_path: &Path) -> io::Result<ProcMacroClient> {
+        let process = ProcMacroProcessSrv::run(process_path)?;
+        Ok(ProcMacroClient { kind: ProcMacroClientKind::Process { process: Arc::new(process) } })
+    }
+
+    pub fn dummy() -> ProcMacroClient {
+        ProcMacroClient { kind: ProcMacroClientKind::Dummy }
+    }
+
+    /// Lists the macros of the proc-macro library at `dylib_path`, together with
+    /// expanders which forward expansion requests to the server.
+    pub fn by_dylib_path(
+        &self,
+        dylib_path: &Path,
+    ) -> Vec<(SmolStr, ProcMacroKind, Arc<dyn tt::TokenExpander>)> {
+        match &self.kind {
+            ProcMacroClientKind::Dummy => vec![],
+            ProcMacroClientKind::Process { process } => {
+                let macros = match process.find_proc_macros(dylib_path) {
+                    Err(err) => {
+                        log::error!("failed to list proc macros of {:?}: {:?}", dylib_path, err);
+                        return vec![];
+                    }
+                    Ok(macros) => macros,
+                };
+
+                macros
+                    .into_iter()
+                    .map(|(name, kind)| {
+                        let name = SmolStr::new(&name);
+                        let expander: Arc<dyn tt::TokenExpander> =
+                            Arc::new(ProcMacroProcessExpander {
+                                process: process.clone(),
+                                dylib_path: dylib_path.into(),
+                                name: name.clone(),
+                            });
+                        (name, kind, expander)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! Handle process life-time and message passing for proc-macro client
+
+use std::{
+    convert::{TryFrom, TryInto},
+    ffi::OsString,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use tt::Subtree;
+
+use crate::{
+    msg::{ErrorCode, Message, Request, Response, ResponseError},
+    rpc::{ExpansionResult, ExpansionTask, ListMacrosResult, ListMacrosTask, ProcMacroKind},
+};
+
+/// How long to wait for the server to answer a single request before it is
+/// considered stuck and restarted.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A running proc-macro server. Requests are serialized: the server handles
+/// one message at a time.
+#[derive(Debug)]
+pub(crate) struct ProcMacroProcessSrv {
+    inner: Mutex<Process>,
+    timeout: Duration,
+}
+
+#[derive(Debug)]
+struct Process {
+    program: PathBuf,
+    args: Vec<OsString>,
+    child: Child,
+    stdin: ChildStdin,
+    /// Responses read from the server's stdout by a dedicated thread, so that
+    /// waiting for them can time out.
+    responses: Receiver<io::Result<Option<Response>>>,
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Process {
+    fn run(program: &Path, args: &[OsString]) -> io::Result<Process> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+        let (sender, responses) = mpsc::channel();
+        thread::spawn(move || loop {
+            let res = Response::read(&mut stdout);
+            // A malformed message is reported, but the server can still
+            // answer the next request.
+            let is_done = match &res {
+                Ok(Some(_)) => false,
+                Ok(None) => true,
+                Err(err) => err.kind() != io::ErrorKind::InvalidData,
+            };
+            if sender.send(res).is_err() || is_done {
+                break;
+            }
+        });
+        Ok(Process { program: program.into(), args: args.to_vec(), child, stdin, responses })
+    }
+
+    fn restart(&mut self) -> io::Result<()> {
+        *self = Process::run(&self.program, &self.args)?;
+        Ok(())
+    }
+}
+
+impl ProcMacroProcessSrv {
+    pub(crate) fn run(process_path: &Path) -> io::Result<ProcMacroProcessSrv> {
+        ProcMacroProcessSrv::with_args(process_path, &[], REQUEST_TIMEOUT)
+    }
+
+    fn with_args(
+        program: &Path,
+        args: &[OsString],
+        timeout: Duration,
+    ) -> io::Result<ProcMacroProcessSrv> {
+        let process = Process::run(program, args)?;
+        Ok(ProcMacroProcessSrv { inner: Mutex::new(process), timeout })
+    }
+
+    pub(crate) fn find_proc_macros(
+        &self,
+        dylib_path: &Path,
+    ) -> Result<Vec<(String, ProcMacroKind)>, tt::ExpansionError> {
+        let task = ListMacrosTask { lib: dylib_path.to_path_buf() };
+
+        let result: ListMacrosResult = self.send_task(Request::ListMacro(task))?;
+        Ok(result.macros)
+    }
+
+    pub(crate) fn expand(
+        &self,
+        dylib_path: &Path,
+        subtree: &Subtree,
+        attr: Option<&Subtree>,
+        macro_name: &str,
+    ) -> Result<Subtree, tt::ExpansionError> {
+        let task = ExpansionTask {
+            macro_body: subtree.clone(),
+            macro_name: macro_name.to_string(),
+            attributes: attr.cloned(),
+            lib: dylib_path.to_path_buf(),
+        };
+
+        let result: ExpansionResult = self.send_task(Request::ExpansionMacro(task))?;
+        Ok(result.expansion)
+    }
+
+    fn send_task<R>(&self, req: Request) -> Result<R, tt::ExpansionError>
+    where
+        R: TryFrom<Response, Error = &'static str>,
+    {
+        let mut process = self.inner.lock().unwrap();
+        let res = match req.write(&mut process.stdin) {
+            Ok(()) => process.responses.recv_timeout(self.timeout),
+            Err(err) => Ok(Err(err)),
+        };
+        let err = match res {
+            Ok(Ok(Some(res))) => return response_to_result(res),
+            Ok(Err(err)) if err.kind() == io::ErrorKind::InvalidData => {
+                return Err(tt::ExpansionError::JsonError(err.to_string()));
+            }
+            Ok(Ok(None)) => "the server closed its output".to_string(),
+            Ok(Err(err)) => err.to_string(),
+            Err(RecvTimeoutError::Timeout) => {
+                format!("no response within {} ms", self.timeout.as_millis())
+            }
+            Err(RecvTimeoutError::Disconnected) => "the server stopped responding".to_string(),
+        };
+        // The server has most likely crashed or hangs, start a fresh one for
+        // the next request.
+        if let Err(restart_err) = process.restart() {
+            log::error!("failed to restart proc macro server: {}", restart_err);
+        }
+        Err(tt::ExpansionError::IOError(err))
+    }
+}
+
+fn response_to_result<R>(res: Response) -> Result<R, tt::ExpansionError>
+where
+    R: TryFrom<Response, Error = &'static str>,
+{
+    match res {
+        Response::Error(ResponseError { code: ErrorCode::ExpansionError, message }) => {
+            Err(tt::ExpansionError::ExpansionError(message))
+        }
+        Response::Error(ResponseError { code, message }) => {
+            Err(tt::ExpansionError::Unknown(format!("{:?}: {}", code, message)))
+        }
+        res => res.try_into().map_err(|err| tt::ExpansionError::Unknown(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn request_is_answered_by_a_single_line() {
+        let recorded = concat!(
+            r#"{"ListMacro":{"macros":[["identity","FuncLike"],["Serialize","CustomDerive"]]}}"#,
+            "\n",
+            r#"{"Error":{"code":"ExpansionError","message":"boom"}}"#,
+            "\n",
+        );
+        let mut reader = Cursor::new(recorded.as_bytes());
+        let mut writer = Vec::new();
+
+        let task = ListMacrosTask { lib: "libfoo.so".into() };
+        Request::ListMacro(task).write(&mut writer).unwrap();
+        let res: ListMacrosResult =
+            response_to_result(Response::read(&mut reader).unwrap().unwrap()).unwrap();
+        assert_eq!(
+            res.macros,
+            vec![
+                ("identity".to_string(), ProcMacroKind::FuncLike),
+                ("Serialize".to_string(), ProcMacroKind::CustomDerive)
+            ]
+        );
+        assert_eq!(String::from_utf8(writer).unwrap(), "{\"ListMacro\":{\"lib\":\"libfoo.so\"}}\n");
+
+        let res =
+            response_to_result::<ListMacrosResult>(Response::read(&mut reader).unwrap().unwrap());
+        assert_eq!(res.unwrap_err(), tt::ExpansionError::ExpansionError("boom".to_string()));
+
+        assert!(Response::read(&mut reader).unwrap().is_none());
+    }
+
+    /// Runs `script` with the shell of the platform: `unix` with `sh` and
+    /// `windows` with `cmd`.
+    fn shell_server(unix: &str, windows: &str, timeout: Duration) -> ProcMacroProcessSrv {
+        let (shell, flag, script) =
+            if cfg!(windows) { ("cmd", "/C", windows) } else { ("sh", "-c", unix) };
+        let args = vec![OsString::from(flag), OsString::from(script)];
+        ProcMacroProcessSrv::with_args(Path::new(shell), &args, timeout).unwrap()
+    }
+
+    #[test]
+    fn server_process_is_restarted_after_a_crash() {
+        // Answers a single request, then exits.
+        let srv = shell_server(
+            r#"read line; echo '{"ListMacro":{"macros":[["identity","FuncLike"]]}}'"#,
+            r#"set /p line= & echo {"ListMacro":{"macros":[["identity","FuncLike"]]}}"#,
+            REQUEST_TIMEOUT,
+        );
+        let lib = Path::new("libfoo.so");
+        let expected = vec![("identity".to_string(), ProcMacroKind::FuncLike)];
+        assert_eq!(srv.find_proc_macros(lib), Ok(expected.clone()));
+        match srv.find_proc_macros(lib) {
+            Err(tt::ExpansionError::IOError(_)) => (),
+            res => panic!("expected an IO error, got {:?}", res),
+        }
+        assert_eq!(srv.find_proc_macros(lib), Ok(expected));
+    }
+
+    #[test]
+    fn invalid_json_is_reported() {
+        let srv = shell_server(
+            "while read line; do echo 'not json'; done",
+            "for /L %i in (1,1,2) do @(set /p line= & echo not json)",
+            REQUEST_TIMEOUT,
+        );
+        for _ in 0..2 {
+            match srv.find_proc_macros(Path::new("libfoo.so")) {
+                Err(tt::ExpansionError::JsonError(_)) => (),
+                res => panic!("expected a JSON error, got {:?}", res),
+            }
+        }
+    }
+
+    #[test]
+    fn stuck_server_times_out() {
+        let srv = shell_server(
+            "read line; sleep 10",
+            "set /p line= & ping -n 11 127.0.0.1 > NUL",
+            Duration::from_millis(100),
+        );
+        match srv.find_proc_macros(Path::new("libfoo.so")) {
+            Err(tt::ExpansionError::IOError(message)) => assert!(message.contains("100 ms")),
+            res => panic!("expected a timeout, got {:?}", res),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Collects the output of build scripts (`build.rs`).
+//!
+//! Build scripts are never run by rust-analyzer itself. Instead, we either run
+//! `cargo check --message-format=json`, or read a file with previously recorded
+//! output of that command, and pick up `build-script-executed` messages. The
+//! `compiler-artifact` messages of `proc-macro` targets tell where their
+//! dynamic libraries are.
+
+use std::{
+    env::consts::DLL_SUFFIX,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+
+use crate::Result;
+
+/// Information about the code generated by a build script of a single package.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct BuildScriptOutput {
+    /// The `OUT_DIR` the build script was run with.
+    pub(crate) out_dir: Option<PathBuf>,
+    /// `cargo:rustc-cfg` directives, like `foo` or `feature="bar"`.
+    pub(crate) cfgs: Vec<String>,
+    /// `cargo:rustc-env` directives.
+    pub(crate) envs: Vec<(String, String)>,
+    /// The dynamic library compiled for the `proc-macro` target of the package.
+    pub(crate) proc_macro_dylib: Option<PathBuf>,
+}
+
+/// Build script outputs, keyed by the cargo package id.
+pub(crate) type BuildScriptOutputs = FxHashMap<String, BuildScriptOutput>;
+
+#[derive(Deserialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+enum Message {
+    BuildScriptExecuted(BuildScriptExecuted),
+    CompilerArtifact(CompilerArtifact),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct BuildScriptExecuted {
+    package_id: String,
+    #[serde(default)]
+    out_dir: Option<PathBuf>,
+    #[serde(default)]
+    cfgs: Vec<String>,
+    #[serde(default)]
+    env: Vec<(String, String)>,
+}
+
+#[derive(Deserialize)]
+struct CompilerArtifact {
+    package_id: String,
+    target: ArtifactTarget,
+    #[serde(default)]
+    filenames: Vec<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct ArtifactTarget {
+    kind: Vec<String>,
+}
+
+pub(crate) fn run_cargo_check(cargo_toml: &Path) -> Result<BuildScriptOutputs> {
+    let mut cmd = Command::new("cargo");
+    cmd.args(&["check", "--message-format=json", "--all-targets", "--all-features"])
+        .arg("--manifest-path")
+        .arg(cargo_toml)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    if let Some(parent) = cargo_toml.parent() {
+        cmd.current_dir(parent);
+    }
+    // Even if the check itself fails, build scripts that did run have already
+    // reported their output, so the exit status is ignored.
+    let output = cmd.output().map_err(|e| format!("failed to run cargo check: {}", e))?;
+    Ok(parse_messages(output.stdout.as_slice()))
+}
+
+pub(crate) fn load_from_file(path: &Path) -> Result<BuildScriptOutputs> {
+    let file = File::open(path)
+        .map_err(|e| format!("can't read build script output {}: {}", path.display(), e))?;
+    Ok(parse_messages(BufReader::new(file)))
+}
+
+fn parse_messages(reader: impl BufRead) -> BuildScriptOutputs {
+    let mut res = BuildScriptOutputs::default();
+    for line in reader.lines() {
+        let line = match line {
+            Ok(it) => it,
+            Err(_) => break,
+        };
+        let message = match serde_json::from_str::<Message>(&line) {
+            Ok(it) => it,
+            Err(e) => {
+                log::debug!("skipping cargo message {:?}: {}", line, e);
+                continue;
+            }
+        };
+        match message {
+            Message::BuildScriptExecuted(msg) => {
+                let output = res.entry(msg.package_id).or_default();
+                output.out_dir = msg.out_dir;
+                output.cfgs = msg.cfgs;
+                output.envs = msg.env;
+            }
+            Message::CompilerArtifact(msg) => {
+                if msg.target.kind.iter().any(|it| it == "proc-macro") {
+                    let dylib = msg.filenames.into_iter().find(|it| {
+                        it.file_name()
+                            .map_or(false, |it| it.to_string_lossy().ends_with(DLL_SUFFIX))
+                    });
+                    if dylib.is_some() {
+                        res.entry(msg.package_id).or_default().proc_macro_dylib = dylib;
+                    }
+                }
+            }
+            Message::Other => (),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_build_script_messages() {
+        let messages = r#"
+{"reason":"compiler-artifact","package_id":"dep 0.1.0 (path+file:///dep)","target":{"kind":["lib"],"name":"dep"},"filenames":[],"fresh":true}
+{"reason":"compiler-artifact","package_id":"foo 0.1.0 (path+file:///foo)","target":{"kind":["proc-macro"],"name":"foo"},"filenames":["/foo/target/debug/deps/PREFIXfoo-5678SUFFIX"],"fresh":false}
+{"reason":"build-script-executed","package_id":"foo 0.1.0 (path+file:///foo)","linked_libs":[],"linked_paths":[],"cfgs":["has_bar","feature=\"baz\""],"env":[["FOO_VERSION","1.2.3"]],"out_dir":"/foo/target/debug/build/foo-1234/out"}
+not json at all
+{"reason":"build-finished","success":true}
+"#;
+        let messages =
+            messages.replace("PREFIX", std::env::consts::DLL_PREFIX).replace("SUFFIX", DLL_SUFFIX);
+        let outputs = parse_messages(messages.as_bytes());
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(
+            outputs["foo 0.1.0 (path+file:///foo)"],
+            BuildScriptOutput {
+                out_dir: Some(PathBuf::from("/foo/target/debug/build/foo-1234/out")),
+                cfgs: vec!["has_bar".to_string(), "feature=\"baz\"".to_string()],
+                envs: vec![("FOO_VERSION".to_string(), "1.2.3".to_string())],
+                proc_macro_dylib: Some(PathBuf::from(format!(
+                    "/foo/target/debug/deps/{}foo-5678{}",
+                    std::env::consts::DLL_PREFIX,
+                    DLL_SUFFIX
+                ))),
+            }
+        );
+    }
+}
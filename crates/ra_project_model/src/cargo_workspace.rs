@@ -1,13 +1,21 @@
 //! FIXME: write short doc here
 
-use std::path::{Path, PathBuf};
+use std::{
+    env::consts::{DLL_PREFIX, DLL_SUFFIX},
+    fs,
+    path::{Path, PathBuf},
+};
 
-use cargo_metadata::{CargoOpt, MetadataCommand};
+use cargo_metadata::{CargoOpt, Metadata, MetadataCommand};
 use ra_arena::{impl_arena_id, Arena, RawId};
 use ra_db::Edition;
 use rustc_hash::FxHashMap;
+use serde::Deserialize;
 
-use crate::Result;
+use crate::{
+    build_scripts::{self, BuildScriptOutput, BuildScriptOutputs},
+    Result,
+};
 
 /// `CargoWorkspace` represents the logical structure of, well, a Cargo
 /// workspace. It pretty closely mirrors `cargo metadata` output.
@@ -21,6 +29,19 @@ pub struct CargoWorkspace {
     packages: Arena<Package, PackageData>,
     targets: Arena<Target, TargetData>,
     pub(crate) workspace_root: PathBuf,
+    target_directory: PathBuf,
+}
+
+/// Controls how much of the build is replicated when loading a cargo workspace.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CargoConfig {
+    /// Run `cargo check --message-format=json` to learn about `OUT_DIR`,
+    /// `rustc-cfg` and `rustc-env` set by build scripts.
+    pub load_out_dirs_from_check: bool,
+    /// Read the recorded output of `cargo check --message-format=json` from
+    /// this file instead of running cargo.
+    pub build_script_output: Option<PathBuf>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -40,6 +61,7 @@ struct PackageData {
     dependencies: Vec<PackageDependency>,
     edition: Edition,
     features: Vec<String>,
+    build_output: BuildScriptOutput,
 }
 
 #[derive(Debug, Clone)]
@@ -54,6 +76,7 @@ struct TargetData {
     name: String,
     root: PathBuf,
     kind: TargetKind,
+    is_proc_macro: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -74,6 +97,7 @@ impl TargetKind {
                 "test" => TargetKind::Test,
                 "bench" => TargetKind::Bench,
                 "example" => TargetKind::Example,
+                "proc-macro" => TargetKind::Lib,
                 _ if kind.contains("lib") => TargetKind::Lib,
                 _ => continue,
             };
@@ -95,6 +119,13 @@ impl Package {
     pub fn features(self, ws: &CargoWorkspace) -> &[String] {
         &ws.packages[self].features
     }
+    /// `OUT_DIR` of the build script, if build script output was loaded.
+    pub fn out_dir(self, ws: &CargoWorkspace) -> Option<&Path> {
+        ws.packages[self].build_output.out_dir.as_ref().map(PathBuf::as_path)
+    }
+    pub(crate) fn build_output(self, ws: &CargoWorkspace) -> &BuildScriptOutput {
+        &ws.packages[self].build_output
+    }
     pub fn targets<'a>(self, ws: &'a CargoWorkspace) -> impl Iterator<Item = Target> + 'a {
         ws.packages[self].targets.iter().cloned()
     }
@@ -123,16 +154,38 @@ impl Target {
     pub fn kind(self, ws: &CargoWorkspace) -> TargetKind {
         ws.targets[self].kind
     }
+    pub fn is_proc_macro(self, ws: &CargoWorkspace) -> bool {
+        ws.targets[self].is_proc_macro
+    }
 }
 
 impl CargoWorkspace {
-    pub fn from_cargo_metadata(cargo_toml: &Path) -> Result<CargoWorkspace> {
+    pub fn from_cargo_metadata(
+        cargo_toml: &Path,
+        cargo_config: &CargoConfig,
+    ) -> Result<CargoWorkspace> {
         let mut meta = MetadataCommand::new();
         meta.manifest_path(cargo_toml).features(CargoOpt::AllFeatures);
         if let Some(parent) = cargo_toml.parent() {
             meta.current_dir(parent);
         }
         let meta = meta.exec().map_err(|e| format!("cargo metadata failed: {}", e))?;
+        let build_outputs = match load_build_outputs(cargo_toml, cargo_config) {
+            Ok(it) => it,
+            Err(e) => {
+                log::error!("failed to load build script output: {}", e);
+                Default::default()
+            }
+        };
+        CargoWorkspace::from_metadata(meta, build_outputs)
+    }
+
+    /// Builds the workspace from the output of `cargo metadata`, with the
+    /// given build script outputs.
+    pub(crate) fn from_metadata(
+        meta: Metadata,
+        mut build_outputs: BuildScriptOutputs,
+    ) -> Result<CargoWorkspace> {
         let mut pkg_by_id = FxHashMap::default();
         let mut packages = Arena::default();
         let mut targets = Arena::default();
@@ -142,6 +195,7 @@ impl CargoWorkspace {
         for meta_pkg in meta.packages {
             let cargo_metadata::Package { id, edition, name, manifest_path, .. } = meta_pkg;
             let is_member = ws_members.contains(&id);
+            let build_output = build_outputs.remove(&id.repr).unwrap_or_default();
             let edition = edition.parse::<Edition>()?;
             let pkg = packages.alloc(PackageData {
                 name,
@@ -151,6 +205,7 @@ impl CargoWorkspace {
                 edition,
                 dependencies: Vec::new(),
                 features: Vec::new(),
+                build_output,
             });
             let pkg_data = &mut packages[pkg];
             pkg_by_id.insert(id, pkg);
@@ -160,6 +215,7 @@ impl CargoWorkspace {
                     name: meta_tgt.name,
                     root: meta_tgt.src_path.clone(),
                     kind: TargetKind::new(meta_tgt.kind.as_slice()),
+                    is_proc_macro: meta_tgt.kind.iter().any(|it| it == "proc-macro"),
                 });
                 pkg_data.targets.push(tgt);
             }
@@ -174,7 +230,12 @@ impl CargoWorkspace {
             packages[source].features.extend(node.features);
         }
 
-        Ok(CargoWorkspace { packages, targets, workspace_root: meta.workspace_root })
+        Ok(CargoWorkspace {
+            packages,
+            targets,
+            workspace_root: meta.workspace_root,
+            target_directory: meta.target_directory,
+        })
     }
 
     pub fn packages<'a>(&'a self) -> impl Iterator<Item = Package> + ExactSizeIterator + 'a {
@@ -184,4 +245,37 @@ impl CargoWorkspace {
     pub fn target_by_root(&self, root: &Path) -> Option<Target> {
         self.packages().filter_map(|pkg| pkg.targets(self).find(|it| it.root(self) == root)).next()
     }
+
+    /// Finds the dynamic library compiled for a `proc-macro` target. It exists
+    /// only after `cargo build` or `cargo check`. Unless cargo reported it,
+    /// the `deps` directories of every profile are searched, and if there are
+    /// several candidates, the most recently built one wins.
+    pub fn proc_macro_dylib_path(&self, tgt: Target) -> Option<PathBuf> {
+        if let Some(path) = &tgt.package(self).build_output(self).proc_macro_dylib {
+            return Some(path.clone());
+        }
+        let prefix = format!("{}{}-", DLL_PREFIX, tgt.name(self).replace('-', "_"));
+        fs::read_dir(&self.target_directory)
+            .ok()?
+            .filter_map(|profile| fs::read_dir(profile.ok()?.path().join("deps")).ok())
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let file_name = entry.file_name();
+                let file_name = file_name.to_string_lossy();
+                file_name.starts_with(&prefix) && file_name.ends_with(DLL_SUFFIX)
+            })
+            .max_by_key(|entry| entry.metadata().and_then(|it| it.modified()).ok())
+            .map(|entry| entry.path())
+    }
+}
+
+fn load_build_outputs(cargo_toml: &Path, cargo_config: &CargoConfig) -> Result<BuildScriptOutputs> {
+    if let Some(path) = &cargo_config.build_script_output {
+        return build_scripts::load_from_file(path);
+    }
+    if cargo_config.load_out_dirs_from_check {
+        return build_scripts::run_cargo_check(cargo_toml);
+    }
+    Ok(Default::default())
 }
@@ -1,5 +1,6 @@
 //! FIXME: write short doc here
 
+mod build_scripts;
 mod cargo_workspace;
 mod json_project;
 mod sysroot;
@@ -13,15 +14,18 @@ use std::{
 };
 
 use ra_cfg::CfgOptions;
-use ra_db::{CrateGraph, CrateId, Edition, Env, FileId};
+use ra_db::{
+    CrateGraph, CrateId, Edition, Env, ExternSource, FileId, ProcMacro, ProcMacroKind, SourceRootId,
+};
 use rustc_hash::FxHashMap;
 use serde_json::from_reader;
 
 pub use crate::{
-    cargo_workspace::{CargoWorkspace, Package, Target, TargetKind},
+    cargo_workspace::{CargoConfig, CargoWorkspace, Package, Target, TargetKind},
     json_project::JsonProject,
     sysroot::Sysroot,
 };
+pub use ra_proc_macro::ProcMacroClient;
 
 // FIXME use proper error enum
 pub type Result<T> = ::std::result::Result<T, Box<dyn Error + Send + Sync>>;
@@ -61,10 +65,14 @@ impl PackageRoot {
 
 impl ProjectWorkspace {
     pub fn discover(path: &Path) -> Result<ProjectWorkspace> {
-        ProjectWorkspace::discover_with_sysroot(path, true)
+        ProjectWorkspace::discover_with_sysroot(path, true, &CargoConfig::default())
     }
 
-    pub fn discover_with_sysroot(path: &Path, with_sysroot: bool) -> Result<ProjectWorkspace> {
+    pub fn discover_with_sysroot(
+        path: &Path,
+        with_sysroot: bool,
+        cargo_config: &CargoConfig,
+    ) -> Result<ProjectWorkspace> {
         match find_rust_project_json(path) {
             Some(json_path) => {
                 let file = File::open(json_path)?;
@@ -73,7 +81,7 @@ impl ProjectWorkspace {
             }
             None => {
                 let cargo_toml = find_cargo_toml(path)?;
-                let cargo = CargoWorkspace::from_cargo_metadata(&cargo_toml)?;
+                let cargo = CargoWorkspace::from_cargo_metadata(&cargo_toml, cargo_config)?;
                 let sysroot =
                     if with_sysroot { Sysroot::discover(&cargo_toml)? } else { Sysroot::default() };
                 Ok(ProjectWorkspace::Cargo { cargo, sysroot })
@@ -99,6 +107,10 @@ impl ProjectWorkspace {
                     let root = pkg.root(&cargo).to_path_buf();
                     let member = pkg.is_member(&cargo);
                     roots.push(PackageRoot::new(root, member));
+                    // Code generated by build scripts lives outside of the package
+                    if let Some(out_dir) = pkg.out_dir(&cargo) {
+                        roots.push(PackageRoot::new(out_dir.to_path_buf(), false));
+                    }
                 }
                 for krate in sysroot.crates() {
                     roots.push(PackageRoot::new(krate.root_dir(&sysroot).to_path_buf(), false))
@@ -120,6 +132,8 @@ impl ProjectWorkspace {
     pub fn to_crate_graph(
         &self,
         default_cfg_options: &CfgOptions,
+        extern_source_roots: &FxHashMap<PathBuf, SourceRootId>,
+        proc_macro_client: &ProcMacroClient,
         load: &mut dyn FnMut(&Path) -> Option<FileId>,
     ) -> (CrateGraph, FxHashMap<CrateId, String>) {
         let mut crate_graph = CrateGraph::default();
@@ -220,18 +234,47 @@ impl ProjectWorkspace {
                         let root = tgt.root(&cargo);
                         if let Some(file_id) = load(root) {
                             let edition = pkg.edition(&cargo);
+                            let build_output = pkg.build_output(&cargo);
                             let cfg_options = {
                                 let mut opts = default_cfg_options.clone();
                                 opts.insert_features(pkg.features(&cargo).iter().map(Into::into));
+                                for cfg in &build_output.cfgs {
+                                    insert_cfg(&mut opts, cfg);
+                                }
                                 opts
                             };
-                            let crate_id = crate_graph.add_crate_root(
-                                file_id,
-                                edition,
-                                cfg_options,
-                                Env::default(),
-                            );
+                            let mut env = Env::default();
+                            let mut extern_source = ExternSource::default();
+                            // FIXME: non-UTF8 paths can't be represented in `Env`
+                            if let Some(out_dir) =
+                                build_output.out_dir.as_ref().and_then(|it| it.to_str())
+                            {
+                                env.set("OUT_DIR", out_dir.to_string());
+                                if let Some(&root) = extern_source_roots.get(Path::new(out_dir)) {
+                                    extern_source.set_extern_path(out_dir, root);
+                                }
+                            }
+                            for (key, value) in &build_output.envs {
+                                env.set(key, value.clone());
+                            }
+                            let crate_id =
+                                crate_graph.add_crate_root(file_id, edition, cfg_options, env);
+                            crate_graph.set_extern_source(crate_id, extern_source);
                             names.insert(crate_id, pkg.name(&cargo).to_string());
+                            if tgt.is_proc_macro(&cargo) {
+                                if let Some(dylib_path) = cargo.proc_macro_dylib_path(tgt) {
+                                    let proc_macros = proc_macro_client
+                                        .by_dylib_path(&dylib_path)
+                                        .into_iter()
+                                        .map(|(name, kind, expander)| ProcMacro {
+                                            name,
+                                            kind: proc_macro_kind(kind),
+                                            expander,
+                                        })
+                                        .collect();
+                                    crate_graph.set_proc_macros(crate_id, proc_macros);
+                                }
+                            }
                             if tgt.kind(&cargo) == TargetKind::Lib {
                                 lib_tgt = Some(crate_id);
                                 pkg_to_lib_crate.insert(pkg, crate_id);
@@ -359,14 +402,7 @@ pub fn get_rustc_cfg_options() -> CfgOptions {
     })() {
         Ok(rustc_cfgs) => {
             for line in rustc_cfgs.lines() {
-                match line.find('=') {
-                    None => cfg_options.insert_atom(line.into()),
-                    Some(pos) => {
-                        let key = &line[..pos];
-                        let value = line[pos + 1..].trim_matches('"');
-                        cfg_options.insert_key_value(key.into(), value.into());
-                    }
-                }
+                insert_cfg(&mut cfg_options, line);
             }
         }
         Err(e) => log::error!("failed to get rustc cfgs: {}", e),
@@ -374,3 +410,117 @@ pub fn get_rustc_cfg_options() -> CfgOptions {
 
     cfg_options
 }
+
+/// Parses a cfg in the `rustc --print cfg` format, like `unix` or `feature="foo"`.
+fn insert_cfg(cfg_options: &mut CfgOptions, cfg: &str) {
+    match cfg.find('=') {
+        None => cfg_options.insert_atom(cfg.into()),
+        Some(pos) => {
+            let key = &cfg[..pos];
+            let value = cfg[pos + 1..].trim_matches('"');
+            cfg_options.insert_key_value(key.into(), value.into());
+        }
+    }
+}
+
+fn proc_macro_kind(kind: ra_proc_macro::ProcMacroKind) -> ProcMacroKind {
+    match kind {
+        ra_proc_macro::ProcMacroKind::CustomDerive => ProcMacroKind::CustomDerive,
+        ra_proc_macro::ProcMacroKind::FuncLike => ProcMacroKind::FuncLike,
+        ra_proc_macro::ProcMacroKind::Attr => ProcMacroKind::Attr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use cargo_metadata::Metadata;
+    use ra_cfg::CfgExpr;
+    use serde_json::json;
+
+    use super::*;
+
+    /// Builds the workspace from recorded `cargo metadata` and `cargo check`
+    /// output, so that no cargo command is run.
+    #[test]
+    fn build_script_output_reaches_crate_graph() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let root = tmp_dir.path().join("foo");
+        let lib_rs = root.join("src").join("lib.rs");
+        let out_dir = tmp_dir.path().join("out");
+        let package_id = format!("foo 0.1.0 (path+file://{})", root.display());
+
+        let meta = json!({
+            "packages": [{
+                "name": "foo",
+                "version": "0.1.0",
+                "authors": [],
+                "id": package_id,
+                "source": null,
+                "dependencies": [],
+                "targets": [{
+                    "name": "foo",
+                    "kind": ["lib"],
+                    "crate_types": ["lib"],
+                    "src_path": lib_rs,
+                    "edition": "2018",
+                }],
+                "features": {},
+                "manifest_path": root.join("Cargo.toml"),
+                "edition": "2018",
+            }],
+            "workspace_members": [package_id],
+            "resolve": {
+                "nodes": [{ "id": package_id, "dependencies": [], "deps": [], "features": [] }],
+                "root": package_id,
+            },
+            "workspace_root": root,
+            "target_directory": root.join("target"),
+            "version": 1,
+        });
+        let meta: Metadata = serde_json::from_value(meta).unwrap();
+
+        let messages = json!({
+            "reason": "build-script-executed",
+            "package_id": package_id,
+            "linked_libs": [],
+            "linked_paths": [],
+            "cfgs": ["has_foo", "feature=\"bar\""],
+            "env": [["FOO_VERSION", "1.2.3"]],
+            "out_dir": out_dir,
+        });
+        let messages_path = tmp_dir.path().join("check.json");
+        fs::write(&messages_path, messages.to_string()).unwrap();
+        let build_outputs = build_scripts::load_from_file(&messages_path).unwrap();
+
+        let cargo = CargoWorkspace::from_metadata(meta, build_outputs).unwrap();
+        let ws = ProjectWorkspace::Cargo { cargo, sysroot: Sysroot::default() };
+        assert!(ws.to_roots().iter().any(|root| root.path() == &out_dir && !root.is_member()));
+
+        let mut files = Vec::new();
+        let (crate_graph, _crate_names) = ws.to_crate_graph(
+            &CfgOptions::default(),
+            &FxHashMap::default(),
+            &ProcMacroClient::dummy(),
+            &mut |path: &Path| {
+                files.push(path.to_path_buf());
+                Some(FileId(files.len() as u32 - 1))
+            },
+        );
+        let lib_file = FileId(files.iter().position(|it| it == &lib_rs).unwrap() as u32);
+        let krate = crate_graph.crate_id_for_crate_root(lib_file).unwrap();
+
+        let env = crate_graph.env(krate);
+        assert_eq!(env.get("OUT_DIR").as_ref().map(String::as_str), out_dir.to_str());
+        assert_eq!(env.get("FOO_VERSION"), Some("1.2.3".to_string()));
+
+        let cfg_options = crate_graph.cfg_options(krate);
+        assert_eq!(cfg_options.check(&CfgExpr::Atom("has_foo".into())), Some(true));
+        assert_eq!(
+            cfg_options.check(&CfgExpr::KeyValue { key: "feature".into(), value: "bar".into() }),
+            Some(true)
+        );
+        assert_eq!(cfg_options.check(&CfgExpr::Atom("has_baz".into())), Some(false));
+    }
+}
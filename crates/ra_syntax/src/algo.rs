@@ -7,7 +7,8 @@ use ra_text_edit::TextEditBuilder;
 use rustc_hash::FxHashMap;
 
 use crate::{
-    AstNode, Direction, NodeOrToken, SyntaxElement, SyntaxNode, SyntaxNodePtr, TextRange, TextUnit,
+    AstNode, Direction, NodeOrToken, SourceFile, SyntaxElement, SyntaxNode, SyntaxNodePtr,
+    TextRange, TextUnit,
 };
 
 /// Returns ancestors of the node at the offset, sorted by length. This should
@@ -209,6 +210,30 @@ pub fn replace_descendants(
     }
 }
 
+/// Parses `text` and returns its first node of type `N`, if the text parses
+/// without errors. Useful to parse a fragment wrapped in some context, like an
+/// expression in `fn f() { let _ = $expr; }`.
+pub fn parse_wrapped<N: AstNode>(text: &str) -> Option<N> {
+    let parse = SourceFile::parse(text);
+    if !parse.errors().is_empty() {
+        return None;
+    }
+    parse.tree().syntax().descendants().find_map(N::cast)
+}
+
+/// Returns the text of `node` with the given, sorted and disjoint, ranges
+/// replaced.
+pub fn splice(node: &SyntaxNode, replacements: Vec<(TextRange, String)>) -> String {
+    let mut text = node.text().to_string();
+    let offset = node.text_range().start();
+    for (range, replacement) in replacements.into_iter().rev() {
+        let start = (range.start() - offset).to_usize();
+        let end = (range.end() - offset).to_usize();
+        text.replace_range(start..end, &replacement);
+    }
+    text
+}
+
 fn with_children(
     parent: &SyntaxNode,
     new_children: Box<[NodeOrToken<rowan::GreenNode, rowan::GreenToken>]>,
@@ -296,3 +296,15 @@ where
     assert_eq!("for<'a> F", pred.type_ref().unwrap().syntax().text().to_string());
     assert_bound("Fn(&'a str)", bounds.next());
 }
+
+#[test]
+fn test_path_parse() {
+    let path = Path::parse("crate::foo::Bar<T>").unwrap();
+    assert_eq!("Bar", path.segment().unwrap().name_ref().unwrap().syntax().text().to_string());
+    assert_eq!("crate::foo", path.qualifier().unwrap().syntax().text().to_string());
+
+    assert!(Path::parse("foo::match").is_none());
+    assert!(Path::parse("foo bar").is_none());
+    assert!(Path::parse("foo::").is_none());
+    assert!(Path::parse("").is_none());
+}
@@ -120,6 +120,11 @@ impl ast::PathSegment {
 }
 
 impl ast::Path {
+    /// Parses `text` as a path, which must be all of the text.
+    pub fn parse(text: &str) -> Option<ast::Path> {
+        crate::parsing::parse_text_fragment(text, ra_parser::FragmentKind::Path)
+    }
+
     pub fn parent_path(&self) -> Option<ast::Path> {
         self.syntax().parent().and_then(ast::Path::cast)
     }
@@ -6,7 +6,9 @@ mod text_token_source;
 mod text_tree_sink;
 mod reparsing;
 
-use crate::{syntax_node::GreenNode, SyntaxError};
+use ra_parser::{FragmentKind, TokenSource, TreeSink};
+
+use crate::{syntax_node::GreenNode, AstNode, SyntaxError, SyntaxKind, SyntaxNode};
 
 pub use self::lexer::{classify_literal, tokenize, Token};
 
@@ -19,3 +21,29 @@ pub(crate) fn parse_text(text: &str) -> (GreenNode, Vec<SyntaxError>) {
     ra_parser::parse(&mut token_source, &mut tree_sink);
     tree_sink.finish()
 }
+
+/// Parses all of `text` as a single fragment, like a path, if it has no
+/// errors.
+pub(crate) fn parse_text_fragment<T: AstNode>(
+    text: &str,
+    fragment_kind: FragmentKind,
+) -> Option<T> {
+    let tokens = tokenize(&text);
+    if tokens.iter().any(|it| it.kind == SyntaxKind::ERROR) {
+        return None;
+    }
+    let mut token_source = text_token_source::TextTokenSource::new(text, &tokens);
+    let mut tree_sink = text_tree_sink::TextTreeSink::new(text, &tokens);
+    // The sink needs a root to attach the trivia around the fragment to.
+    tree_sink.start_node(SyntaxKind::SOURCE_FILE);
+    ra_parser::parse_fragment(&mut token_source, &mut tree_sink, fragment_kind);
+    tree_sink.finish_node();
+    if token_source.current().kind != SyntaxKind::EOF {
+        return None;
+    }
+    let (green, errors) = tree_sink.finish();
+    if !errors.is_empty() {
+        return None;
+    }
+    SyntaxNode::new_root(green).first_child().and_then(T::cast)
+}
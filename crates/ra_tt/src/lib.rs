@@ -14,9 +14,12 @@ macro_rules! impl_froms {
     }
 }
 
-use std::fmt;
+use std::{
+    fmt::{self, Debug},
+    panic::RefUnwindSafe,
+};
 
-use smol_str::SmolStr;
+pub use smol_str::SmolStr;
 
 /// Represents identity of the token.
 ///
@@ -167,3 +170,18 @@ impl Subtree {
 }
 
 pub mod buffer;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExpansionError {
+    IOError(String),
+    JsonError(String),
+    Unknown(String),
+    ExpansionError(String),
+}
+
+/// An expander for macros which are not defined in the source code, like
+/// procedural macros. `attrs` holds the input of an attribute macro.
+pub trait TokenExpander: Debug + Send + Sync + RefUnwindSafe {
+    fn expand(&self, subtree: &Subtree, attrs: Option<&Subtree>)
+        -> Result<Subtree, ExpansionError>;
+}
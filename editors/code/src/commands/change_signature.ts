@@ -0,0 +1,48 @@
+import * as vscode from 'vscode';
+
+import * as lc from 'vscode-languageclient';
+import { Server } from '../server';
+import {
+    handle as applySourceChange,
+    SourceChange
+} from './apply_source_change';
+
+interface ChangeSignatureParams {
+    textDocument: lc.TextDocumentIdentifier;
+    position: lc.Position;
+    newParams: string;
+}
+
+export async function handle() {
+    const editor = vscode.window.activeTextEditor;
+    if (editor == null || editor.document.languageId !== 'rust') {
+        return;
+    }
+    const textDocument = { uri: editor.document.uri.toString() };
+    const position = Server.client.code2ProtocolConverter.asPosition(
+        editor.selection.active
+    );
+    const newParams = await vscode.window.showInputBox({
+        placeHolder: 'b, a, flag: bool = false',
+        prompt: 'Enter the new parameters'
+    });
+    if (newParams == null) {
+        return;
+    }
+    const request: ChangeSignatureParams = {
+        textDocument,
+        position,
+        newParams
+    };
+    let change: SourceChange;
+    try {
+        change = await Server.client.sendRequest<SourceChange>(
+            'rust-analyzer/changeSignature',
+            request
+        );
+    } catch (e) {
+        vscode.window.showErrorMessage(e.message);
+        return;
+    }
+    await applySourceChange(change);
+}
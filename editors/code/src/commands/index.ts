@@ -1,5 +1,6 @@
 import * as analyzerStatus from './analyzer_status';
 import * as applySourceChange from './apply_source_change';
+import * as changeSignature from './change_signature';
 import * as expandMacro from './expand_macro';
 import * as inlayHints from './inlay_hints';
 import * as joinLines from './join_lines';
@@ -7,16 +8,19 @@ import * as matchingBrace from './matching_brace';
 import * as onEnter from './on_enter';
 import * as parentModule from './parent_module';
 import * as runnables from './runnables';
+import * as ssr from './ssr';
 import * as syntaxTree from './syntaxTree';
 
 export {
     analyzerStatus,
     applySourceChange,
+    changeSignature,
     expandMacro,
     joinLines,
     matchingBrace,
     parentModule,
     runnables,
+    ssr,
     syntaxTree,
     onEnter,
     inlayHints
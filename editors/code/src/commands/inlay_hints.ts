@@ -19,6 +19,14 @@ const typeHintDecorationType = vscode.window.createTextEditorDecorationType({
     }
 });
 
+const parameterHintDecorationType = vscode.window.createTextEditorDecorationType(
+    {
+        before: {
+            color: new vscode.ThemeColor('ralsp.inlayHint')
+        }
+    }
+);
+
 export class HintsUpdater {
     private displayHints = true;
 
@@ -64,6 +72,14 @@ export class HintsUpdater {
                         )
                     )
                 );
+                promises.push(
+                    Promise.resolve(
+                        rustEditor.setDecorations(
+                            parameterHintDecorationType,
+                            newDecorations
+                        )
+                    )
+                );
             } else {
                 promises.push(this.updateDecorationsFromServer(rustEditor));
             }
@@ -83,17 +99,31 @@ export class HintsUpdater {
     ): Promise<void> {
         const newHints = await this.queryHints(editor.document.uri.toString());
         if (newHints !== null) {
-            const newDecorations = newHints.map(hint => ({
-                range: hint.range,
-                renderOptions: {
-                    after: {
-                        contentText: `: ${hint.label}`
+            const newTypeDecorations = newHints
+                .filter(hint => hint.kind !== 'ParameterHint')
+                .map(hint => ({
+                    range: hint.range,
+                    renderOptions: {
+                        after: {
+                            contentText: `: ${hint.label}`
+                        }
+                    }
+                }));
+            editor.setDecorations(typeHintDecorationType, newTypeDecorations);
+
+            const newParameterDecorations = newHints
+                .filter(hint => hint.kind === 'ParameterHint')
+                .map(hint => ({
+                    range: hint.range,
+                    renderOptions: {
+                        before: {
+                            contentText: `${hint.label}: `
+                        }
                     }
-                }
-            }));
-            return editor.setDecorations(
-                typeHintDecorationType,
-                newDecorations
+                }));
+            editor.setDecorations(
+                parameterHintDecorationType,
+                newParameterDecorations
             );
         }
     }
@@ -0,0 +1,60 @@
+import * as vscode from 'vscode';
+
+import * as lc from 'vscode-languageclient';
+import { Server } from '../server';
+import {
+    handle as applySourceChange,
+    SourceChange
+} from './apply_source_change';
+
+interface SsrParams {
+    textDocument: lc.TextDocumentIdentifier;
+    position: lc.Position;
+    query: string;
+    parseOnly: boolean;
+}
+
+export async function handle() {
+    const editor = vscode.window.activeTextEditor;
+    if (editor == null || editor.document.languageId !== 'rust') {
+        return;
+    }
+    // Paths of the pattern are resolved where the cursor is
+    const textDocument = { uri: editor.document.uri.toString() };
+    const position = Server.client.code2ProtocolConverter.asPosition(
+        editor.selection.active
+    );
+    const options: vscode.InputBoxOptions = {
+        placeHolder: 'foo($a, $b) ==>> bar($b, $a)',
+        prompt: 'Enter request',
+        validateInput: async (query: string) => {
+            const request: SsrParams = {
+                textDocument,
+                position,
+                query,
+                parseOnly: true
+            };
+            try {
+                await Server.client.sendRequest('rust-analyzer/ssr', request);
+            } catch (e) {
+                return e.toString();
+            }
+            return null;
+        }
+    };
+    const query = await vscode.window.showInputBox(options);
+    if (query == null) {
+        return;
+    }
+    const request: SsrParams = {
+        textDocument,
+        position,
+        query,
+        parseOnly: false
+    };
+    const change = await Server.client.sendRequest<SourceChange>(
+        'rust-analyzer/ssr',
+        request
+    );
+    await applySourceChange(change);
+}
@@ -25,6 +25,7 @@ export class Config {
     public maxInlayHintLength: null | number = null;
     public excludeGlobs = [];
     public useClientWatching = false;
+    public checkCommand: string[] = [];
     public featureFlags = {};
     public cargoWatchOptions: CargoWatchOptions = {
         enableOnStartup: 'ask',
@@ -127,6 +128,10 @@ export class Config {
             );
         }
 
+        if (config.has('checkCommand')) {
+            this.checkCommand = config.get<string[]>('checkCommand', []);
+        }
+
         if (config.has('lruCapacity')) {
             this.lruCapacity = config.get('lruCapacity') as number;
         }
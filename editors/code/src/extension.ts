@@ -65,6 +65,11 @@ export function activate(context: vscode.ExtensionContext) {
     );
     registerCommand('rust-analyzer.joinLines', commands.joinLines.handle);
     registerCommand('rust-analyzer.parentModule', commands.parentModule.handle);
+    registerCommand('rust-analyzer.ssr', commands.ssr.handle);
+    registerCommand(
+        'rust-analyzer.changeSignature',
+        commands.changeSignature.handle
+    );
     registerCommand('rust-analyzer.run', commands.runnables.handle);
     // Unlike the above this does not send requests to the language server
     registerCommand('rust-analyzer.runSingle', commands.runnables.handleSingle);
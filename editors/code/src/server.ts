@@ -46,7 +46,12 @@ export class Server {
                 maxInlayHintLength: Server.config.maxInlayHintLength,
                 excludeGlobs: Server.config.excludeGlobs,
                 useClientWatching: Server.config.useClientWatching,
-                featureFlags: Server.config.featureFlags
+                checkCommand: Server.config.checkCommand,
+                featureFlags: {
+                    // VS Code reveals the `#L<line>,<column>` of file links
+                    'lsp.hover.link-positions': true,
+                    ...Server.config.featureFlags
+                }
             },
             traceOutputChannel
         };